@@ -0,0 +1,71 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use std::io;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::os::unix::net::UnixStream;
+
+use wayland_client::protocol::{
+    wl_compositor, wl_data_device_manager, wl_output, wl_registry, wl_seat, wl_subcompositor,
+};
+use wayland_client::{Display, Main};
+use wayland_commons::socket::{BufferedSocket, Socket};
+use wayland_protocols::viewporter::client::wp_viewporter;
+use wayland_protocols::xdg_shell::client::xdg_wm_base;
+
+mod common;
+
+// Bind globals and create objects without waiting for the registry to advertise them,
+// so that the ids targeted by the fuzzed events are alive from the start
+fn create_objects(registry: &Main<wl_registry::WlRegistry>) {
+    let compositor = registry.bind::<wl_compositor::WlCompositor>(4, 1);
+    let subcompositor = registry.bind::<wl_subcompositor::WlSubcompositor>(1, 2);
+    let seat = registry.bind::<wl_seat::WlSeat>(6, 3);
+    registry.bind::<wl_output::WlOutput>(3, 4);
+    let data_device_manager = registry.bind::<wl_data_device_manager::WlDataDeviceManager>(3, 5);
+    let wm_base = registry.bind::<xdg_wm_base::XdgWmBase>(2, 6);
+    let viewporter = registry.bind::<wp_viewporter::WpViewporter>(1, 7);
+
+    let surface = compositor.create_surface();
+    let parent = compositor.create_surface();
+    surface.frame();
+    subcompositor.get_subsurface(&surface, &parent);
+    seat.get_pointer();
+    seat.get_keyboard();
+    seat.get_touch();
+    data_device_manager.get_data_device(&seat);
+    data_device_manager.create_data_source();
+    let xdg_surface = wm_base.get_xdg_surface(&parent);
+    xdg_surface.get_toplevel();
+    viewporter.get_viewport(&surface);
+}
+
+fuzz_target!(|actions: Vec<common::Action>| {
+    let (client_end, server_end) = UnixStream::pair().unwrap();
+    client_end.set_nonblocking(true).unwrap();
+    server_end.set_nonblocking(true).unwrap();
+    let display = unsafe { Display::from_fd(client_end.into_raw_fd()) }.unwrap();
+    let mut event_queue = display.create_event_queue();
+    let attached = (*display).clone().attach(event_queue.token());
+    create_objects(&attached.get_registry());
+
+    let mut socket = BufferedSocket::new(unsafe { Socket::from_raw_fd(server_end.into_raw_fd()) });
+
+    for action in actions {
+        if common::send(&mut socket, action) {
+            continue;
+        }
+        let _ = socket.flush();
+        let _ = display.flush();
+        common::drain(socket.get_socket());
+        if let Some(guard) = event_queue.prepare_read() {
+            match guard.read_events() {
+                Err(ref e) if e.kind() != io::ErrorKind::WouldBlock => break,
+                _ => {}
+            }
+        }
+        if event_queue.dispatch_pending(&mut (), |_, _, _| {}).is_err() {
+            break;
+        }
+    }
+});
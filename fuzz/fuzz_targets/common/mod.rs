@@ -0,0 +1,114 @@
+//! Structured input shared by the dispatch fuzz targets
+//!
+//! Rather than raw bytes, these targets are driven by a list of actions. Most of them are
+//! well-formed wire messages with arbitrary arguments, so that the fuzzer can reach the
+//! object management logic instead of being stopped at the parsing stage.
+
+use std::ffi::CString;
+use std::fs::File;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+use arbitrary::Arbitrary;
+
+use wayland_commons::socket::{BufferedSocket, Socket, MAX_FDS_OUT};
+use wayland_commons::wire::{Argument, Message, OwnedFd};
+
+/// An object id
+///
+/// Ids are kept small so that the fuzzer hits existing objects. Ids with their high bit
+/// set are mapped to the server-side range.
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub struct Id(u8);
+
+impl Id {
+    fn to_raw(self) -> u32 {
+        if self.0 & 0x80 != 0 {
+            0xFF00_0000 + u32::from(self.0 & 0x7F)
+        } else {
+            u32::from(self.0)
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Arg {
+    Int(i32),
+    Uint(u32),
+    Fixed(i32),
+    Str(Vec<u8>),
+    Object(Id),
+    NewId(Id),
+    Array(Vec<u8>),
+    // a file descriptor to /dev/null
+    Fd,
+}
+
+impl Arg {
+    fn into_argument(self) -> Argument {
+        match self {
+            Arg::Int(i) => Argument::Int(i),
+            Arg::Uint(u) => Argument::Uint(u),
+            Arg::Fixed(f) => Argument::Fixed(f),
+            Arg::Str(mut bytes) => {
+                bytes.retain(|&b| b != 0);
+                Argument::Str(Box::new(CString::new(bytes).unwrap()))
+            }
+            Arg::Object(id) => Argument::Object(id.to_raw()),
+            Arg::NewId(id) => Argument::NewId(id.to_raw()),
+            Arg::Array(bytes) => Argument::Array(Box::new(bytes)),
+            Arg::Fd => {
+                let fd = File::open("/dev/null").unwrap().into_raw_fd();
+                Argument::Fd(unsafe { OwnedFd::from_raw_fd(fd) })
+            }
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Action {
+    /// Send a well-formed message, whose arguments may not match its signature
+    Message { sender_id: Id, opcode: u8, args: Vec<Arg> },
+    /// Send arbitrary bytes, bypassing the message framing
+    Raw(Vec<u8>),
+    /// Let the tested side process what it received
+    Dispatch,
+}
+
+/// Send a message or raw bytes to the tested side
+///
+/// Returns `false` if the action is `Action::Dispatch`, which is left to the target.
+pub fn send(socket: &mut BufferedSocket, action: Action) -> bool {
+    match action {
+        Action::Message { sender_id, opcode, args } => {
+            let msg = Message {
+                sender_id: sender_id.to_raw(),
+                opcode: u16::from(opcode),
+                args: args.into_iter().map(Arg::into_argument).collect(),
+            };
+            // messages not fitting in the buffers are simply dropped
+            let _ = socket.write_message(&msg);
+            true
+        }
+        Action::Raw(bytes) => {
+            // keep the ordering with the previously written messages
+            let _ = socket.flush();
+            let _ = socket.get_socket().send_msg(&bytes, &[]);
+            true
+        }
+        Action::Dispatch => false,
+    }
+}
+
+/// Discard everything sent by the tested side, so that it never blocks on a full socket
+pub fn drain(socket: &mut Socket) {
+    let mut bytes = [0u8; 4096];
+    let mut fds = [0; MAX_FDS_OUT];
+    while let Ok((nbytes, nfds)) = socket.rcv_msg(&mut bytes, &mut fds) {
+        for &fd in &fds[..nfds] {
+            drop(unsafe { OwnedFd::from_raw_fd(fd) });
+        }
+        if nbytes == 0 {
+            break;
+        }
+    }
+}
@@ -5,7 +5,7 @@ extern crate wayland_commons;
 
 use std::os::unix::io::RawFd;
 use std::{mem, slice};
-use wayland_commons::wire::{ArgumentType, Message};
+use wayland_commons::wire::{ArgumentType, MessageRef};
 
 unsafe fn convert_slice<T: Sized>(data: &[u8]) -> &[T] {
     let n = mem::size_of::<T>();
@@ -42,5 +42,6 @@ fuzz_target!(|data: &[u8]| {
     // 16 `ArgumentType`s
     let args = get_arg_types(&data[16..32]);
     let data: &[u32] = unsafe { convert_slice(&data[32..]) };
-    let _res = Message::from_raw(data, &args, fds);
+    // the fds are arbitrary integers, so the message must not take ownership of them
+    let _res = MessageRef::from_raw(data, &args, fds).map(|(msg, _, _)| msg.args().count());
 });
@@ -0,0 +1,129 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use wayland_commons::socket::{BufferedSocket, Socket};
+use wayland_protocols::viewporter::server::wp_viewporter;
+use wayland_protocols::xdg_shell::server::{xdg_surface, xdg_wm_base};
+use wayland_server::protocol::{
+    wl_compositor, wl_data_device_manager, wl_output, wl_seat, wl_shm, wl_shm_pool,
+    wl_subcompositor, wl_surface,
+};
+use wayland_server::{Display, Filter, Interface, Main};
+
+mod common;
+
+use common::Action;
+
+// objects without requests creating new objects accept anything
+macro_rules! noop {
+    ($id: expr) => {
+        $id.quick_assign(|_, _, _| {})
+    };
+}
+
+fn global<I, F>(display: &mut Display, version: u32, implem: F)
+where
+    I: Interface + AsRef<wayland_server::Resource<I>> + From<wayland_server::Resource<I>>,
+    F: Fn(Main<I>) + 'static,
+{
+    display.create_global::<I, _>(
+        version,
+        Filter::new(move |(resource, _): (Main<I>, u32), _, _| implem(resource)),
+    );
+}
+
+fn setup_globals(display: &mut Display) {
+    global::<wl_compositor::WlCompositor, _>(display, 4, |compositor| {
+        compositor.quick_assign(|_, req, _| match req {
+            wl_compositor::Request::CreateSurface { id } => id.quick_assign(|_, req, _| {
+                if let wl_surface::Request::Frame { callback } = req {
+                    noop!(callback);
+                }
+            }),
+            wl_compositor::Request::CreateRegion { id } => noop!(id),
+            _ => {}
+        })
+    });
+    global::<wl_subcompositor::WlSubcompositor, _>(display, 1, |subcompositor| {
+        subcompositor.quick_assign(|_, req, _| {
+            if let wl_subcompositor::Request::GetSubsurface { id, .. } = req {
+                noop!(id);
+            }
+        })
+    });
+    global::<wl_shm::WlShm, _>(display, 1, |shm| {
+        shm.quick_assign(|_, req, _| {
+            if let wl_shm::Request::CreatePool { id, .. } = req {
+                id.quick_assign(|_, req, _| {
+                    if let wl_shm_pool::Request::CreateBuffer { id, .. } = req {
+                        noop!(id);
+                    }
+                });
+            }
+        })
+    });
+    global::<wl_seat::WlSeat, _>(display, 6, |seat| {
+        seat.quick_assign(|_, req, _| match req {
+            wl_seat::Request::GetPointer { id } => noop!(id),
+            wl_seat::Request::GetKeyboard { id } => noop!(id),
+            wl_seat::Request::GetTouch { id } => noop!(id),
+            _ => {}
+        })
+    });
+    global::<wl_output::WlOutput, _>(display, 3, |output| noop!(output));
+    global::<wl_data_device_manager::WlDataDeviceManager, _>(display, 3, |manager| {
+        manager.quick_assign(|_, req, _| match req {
+            wl_data_device_manager::Request::CreateDataSource { id } => noop!(id),
+            wl_data_device_manager::Request::GetDataDevice { id, .. } => noop!(id),
+            _ => {}
+        })
+    });
+    global::<xdg_wm_base::XdgWmBase, _>(display, 2, |wm_base| {
+        wm_base.quick_assign(|_, req, _| match req {
+            xdg_wm_base::Request::CreatePositioner { id } => noop!(id),
+            xdg_wm_base::Request::GetXdgSurface { id, .. } => {
+                id.quick_assign(|_, req, _| match req {
+                    xdg_surface::Request::GetToplevel { id } => noop!(id),
+                    xdg_surface::Request::GetPopup { id, .. } => noop!(id),
+                    _ => {}
+                })
+            }
+            _ => {}
+        })
+    });
+    global::<wp_viewporter::WpViewporter, _>(display, 1, |viewporter| {
+        viewporter.quick_assign(|_, req, _| {
+            if let wp_viewporter::Request::GetViewport { id, .. } = req {
+                noop!(id);
+            }
+        })
+    });
+}
+
+fuzz_target!(|actions: Vec<Action>| {
+    let mut display = Display::new();
+    setup_globals(&mut display);
+
+    let (client_end, server_end) = UnixStream::pair().unwrap();
+    client_end.set_nonblocking(true).unwrap();
+    let client = unsafe { display.create_client(server_end.into_raw_fd(), &mut ()) };
+    let mut socket = BufferedSocket::new(unsafe { Socket::from_raw_fd(client_end.into_raw_fd()) });
+
+    for action in actions {
+        if common::send(&mut socket, action) {
+            continue;
+        }
+        let _ = socket.flush();
+        display.dispatch(Duration::from_millis(0), &mut ()).unwrap();
+        display.flush_clients(&mut ());
+        common::drain(socket.get_socket());
+        if !client.alive() {
+            // the client was killed by a protocol error
+            break;
+        }
+    }
+});
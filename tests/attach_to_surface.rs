@@ -2,7 +2,7 @@ extern crate tempfile;
 
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::sync::{Arc, Mutex};
 
 mod helpers;
@@ -68,7 +68,7 @@ fn insert_shm(
         Reqs::Shm { request: wl_shm::Request::CreatePool { id, fd, size }, .. } => {
             assert!(size == 42);
             assert!(buffer.lock().unwrap().is_none());
-            *buffer.lock().unwrap() = Some((fd, None));
+            *buffer.lock().unwrap() = Some((fd.into_raw_fd(), None));
             id.assign(filter.clone());
         }
         Reqs::Pool { request: wl_shm_pool::Request::CreateBuffer { id, .. }, .. } => {
@@ -0,0 +1,105 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Longer than the default 4 KiB buffers
+fn long_string() -> String {
+    "Wayland".repeat(2000)
+}
+
+#[test]
+#[cfg_attr(feature = "server_native", ignore)]
+#[cfg_attr(feature = "client_native", ignore)]
+fn large_request() {
+    use ways::protocol::{wl_compositor, wl_shell, wl_shell_surface};
+
+    let title = Rc::new(RefCell::new(None));
+    let title2 = title.clone();
+
+    let mut server = TestServer::new();
+    server.display.create_global::<wl_compositor::WlCompositor, _>(
+        1,
+        ways::Filter::new(
+            |(compositor, _): (ways::Main<wl_compositor::WlCompositor>, u32), _, _| {
+                compositor.quick_assign(|_, _, _| {});
+            },
+        ),
+    );
+    server.display.create_global::<wl_shell::WlShell, _>(
+        1,
+        ways::Filter::new(move |(shell, _): (ways::Main<wl_shell::WlShell>, u32), _, _| {
+            let title = title.clone();
+            shell.quick_assign(move |_, req, _| {
+                if let wl_shell::Request::GetShellSurface { id, .. } = req {
+                    let title = title.clone();
+                    id.quick_assign(move |_, req, _| {
+                        if let wl_shell_surface::Request::SetTitle { title: t } = req {
+                            *title.borrow_mut() = Some(t);
+                        }
+                    });
+                }
+            });
+        }),
+    );
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor =
+        manager.instantiate_exact::<wayc::protocol::wl_compositor::WlCompositor>(1).unwrap();
+    let shell = manager.instantiate_exact::<wayc::protocol::wl_shell::WlShell>(1).unwrap();
+    let surface = compositor.create_surface();
+    let shell_surface = shell.get_shell_surface(&surface);
+
+    client.display.set_buffer_capacity(wayc::BufferCapacity::new(4096, 64 * 1024));
+    shell_surface.set_title(long_string());
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(title2.borrow().as_ref(), Some(&long_string()));
+}
+
+#[test]
+#[cfg_attr(feature = "server_native", ignore)]
+#[cfg_attr(feature = "client_native", ignore)]
+fn large_event() {
+    use ways::protocol::wl_output;
+
+    let mut server = TestServer::new();
+    server.display.set_default_buffer_capacity(ways::BufferCapacity::new(4096, 64 * 1024));
+    server.display.create_global::<wl_output::WlOutput, _>(
+        1,
+        ways::Filter::new(|(output, _): (ways::Main<wl_output::WlOutput>, u32), _, _| {
+            output.quick_assign(|_, _, _| {});
+            output.geometry(
+                0,
+                0,
+                100,
+                100,
+                wl_output::Subpixel::Unknown,
+                "Make".into(),
+                long_string(),
+                wl_output::Transform::Normal,
+            );
+        }),
+    );
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let model = Rc::new(RefCell::new(None));
+    let model2 = model.clone();
+    let output = manager.instantiate_exact::<wayc::protocol::wl_output::WlOutput>(1).unwrap();
+    output.quick_assign(move |_, evt, _| {
+        if let wayc::protocol::wl_output::Event::Geometry { model: m, .. } = evt {
+            *model2.borrow_mut() = Some(m);
+        }
+    });
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(model.borrow().as_ref(), Some(&long_string()));
+}
@@ -0,0 +1,93 @@
+mod helpers;
+
+use helpers::{wayc, ways, TestServer};
+
+use ways::protocol::wl_output;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+// shared data of the event loop, given to the filters of both sides
+#[derive(Default)]
+struct LoopData {
+    bound: u32,
+    synced: u32,
+    done: u32,
+}
+
+fn sync(display: &wayc::Attached<wayc::protocol::wl_display::WlDisplay>) {
+    display.sync().quick_assign(|_, _, mut ddata| {
+        ddata.get::<LoopData>().unwrap().synced += 1;
+    });
+}
+
+#[test]
+fn display_and_queue_sources() {
+    let mut event_loop = calloop::EventLoop::<LoopData>::new().unwrap();
+
+    let server = TestServer::new();
+    let socket_name = server.socket_name.clone();
+    let source = ways::DisplaySource::new(server.display);
+    let server_display = Rc::downgrade(&source.display());
+    let outputs = Rc::new(RefCell::new(Vec::new()));
+    let bound_outputs = outputs.clone();
+    source.display().borrow_mut().create_global::<wl_output::WlOutput, _>(
+        2,
+        ways::Filter::new(
+            move |(output, _): (ways::Main<wl_output::WlOutput>, u32), _, mut ddata| {
+                // the display is not mutably borrowed while dispatching its requests
+                server_display.upgrade().unwrap().borrow().get_poll_fd();
+                output.quick_assign(|_, _, _| {});
+                bound_outputs.borrow_mut().push(output);
+                ddata.get::<LoopData>().unwrap().bound += 1;
+            },
+        ),
+    );
+    source.insert(&event_loop.handle()).unwrap();
+
+    // events sent from another event source are flushed at the end of the loop iteration
+    let (ping, ping_source) = calloop::ping::make_ping().unwrap();
+    event_loop
+        .handle()
+        .insert_source(ping_source, move |(), &mut (), _| {
+            for output in outputs.borrow().iter() {
+                output.done();
+            }
+        })
+        .unwrap();
+
+    let display = wayc::Display::connect_to_name(socket_name).unwrap();
+    let queue = display.create_event_queue();
+    let attached = (*display).clone().attach(queue.token());
+    let manager = wayc::GlobalManager::new(&attached);
+    wayc::QueueSource::new(queue).insert(&event_loop.handle(), |_, _, _| unreachable!()).unwrap();
+
+    let mut data = LoopData::default();
+    // the requests sent here are flushed by hand, the events are flushed by the display source
+    let mut run_until = |data: &mut LoopData, done: &dyn Fn(&LoopData) -> bool| {
+        display.flush().unwrap();
+        for _ in 0..100 {
+            if done(data) {
+                return;
+            }
+            event_loop.dispatch(Some(Duration::from_millis(10)), data).unwrap();
+        }
+        panic!("Event loop did not make progress.");
+    };
+
+    sync(&attached);
+    run_until(&mut data, &|data| data.synced == 1);
+    let output = manager.instantiate_exact::<wayc::protocol::wl_output::WlOutput>(2).unwrap();
+    output.quick_assign(|_, event, mut ddata| {
+        if let wayc::protocol::wl_output::Event::Done = event {
+            ddata.get::<LoopData>().unwrap().done += 1;
+        }
+    });
+    sync(&attached);
+    run_until(&mut data, &|data| data.synced == 2);
+    assert_eq!(data.bound, 1);
+
+    ping.ping();
+    run_until(&mut data, &|data| data.done == 1);
+}
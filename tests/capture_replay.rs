@@ -0,0 +1,136 @@
+mod helpers;
+
+use helpers::{roundtrip, ways, TestClient, TestServer};
+
+use ways::protocol::wl_compositor::WlCompositor as ServerCompositor;
+
+use wayland_commons::capture::{replay, CaptureReader, CaptureWriter};
+use wayland_commons::debug::MessageDirection;
+use wayland_commons::smallvec;
+use wayland_commons::socket::{BufferedSocket, Socket};
+use wayland_commons::wire::{Argument, Message};
+
+use std::ffi::CString;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn server_with_compositor() -> (ways::Display, Arc<Mutex<u32>>) {
+    let mut display = ways::Display::new();
+    let binds = Arc::new(Mutex::new(0));
+    let binds2 = binds.clone();
+    display.create_global::<ServerCompositor, _>(
+        1,
+        ways::Filter::new(move |_: (ways::Main<ServerCompositor>, u32), _, _| {
+            *binds2.lock().unwrap() += 1;
+        }),
+    );
+    (display, binds)
+}
+
+fn connect(display: &mut ways::Display) -> BufferedSocket {
+    let (client, server) = UnixStream::pair().unwrap();
+    unsafe {
+        display.create_client(server.into_raw_fd(), &mut ());
+        BufferedSocket::new(Socket::from_raw_fd(client.into_raw_fd()))
+    }
+}
+
+fn dispatch(display: &mut ways::Display) {
+    display.dispatch(Duration::from_millis(10), &mut ()).unwrap();
+    display.flush_clients(&mut ());
+}
+
+#[test]
+fn replay_client_session() {
+    // record a session binding the compositor
+    let (mut display, binds) = server_with_compositor();
+    let mut socket = connect(&mut display);
+    let capture = Arc::new(Mutex::new(CaptureWriter::new(Vec::new()).unwrap()));
+    socket.set_recorder(Some(Box::new(capture.clone())));
+
+    // wl_display.get_registry
+    socket
+        .write_message(&Message { sender_id: 1, opcode: 1, args: smallvec![Argument::NewId(2)] })
+        .unwrap();
+    // wl_registry.bind
+    socket
+        .write_message(&Message {
+            sender_id: 2,
+            opcode: 0,
+            args: smallvec![
+                Argument::Uint(1),
+                Argument::Str(Box::new(CString::new("wl_compositor").unwrap())),
+                Argument::Uint(1),
+                Argument::NewId(3),
+            ],
+        })
+        .unwrap();
+    socket.flush().unwrap();
+    dispatch(&mut display);
+    assert_eq!(*binds.lock().unwrap(), 1);
+
+    socket.set_recorder(None);
+    let data = Arc::try_unwrap(capture).unwrap().into_inner().unwrap().finish().unwrap();
+
+    // replay it against a fresh server
+    let (mut display, binds) = server_with_compositor();
+    let mut socket = connect(&mut display);
+    let reader = CaptureReader::new(&data[..]).unwrap();
+    let replayed = replay(reader, MessageDirection::Sent, &mut socket, |_, _| -1).unwrap();
+    assert_eq!(replayed, 2);
+    dispatch(&mut display);
+    assert_eq!(*binds.lock().unwrap(), 1);
+}
+
+#[test]
+#[cfg_attr(any(feature = "client_native", feature = "server_native"), ignore)]
+fn record_live_session() {
+    let mut server = TestServer::new();
+    let (server_cx, client_cx) = UnixStream::pair().unwrap();
+    let server_client = unsafe { server.display.create_client(server_cx.into_raw_fd(), &mut ()) };
+    let mut client = unsafe { TestClient::from_fd(client_cx.into_raw_fd()) };
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let client_capture = Arc::new(Mutex::new(CaptureWriter::new(Vec::new()).unwrap()));
+    let server_capture = Arc::new(Mutex::new(CaptureWriter::new(Vec::new()).unwrap()));
+    assert!(client.display.set_recorder(Some(Box::new(client_capture.clone()))).is_none());
+    assert!(server_client.set_recorder(Some(Box::new(server_capture.clone()))).is_none());
+
+    let _registry = client.display_proxy.get_registry();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert!(client.display.set_recorder(None).is_some());
+    assert!(server_client.set_recorder(None).is_some());
+    let read = |capture: Arc<Mutex<CaptureWriter<Vec<u8>>>>| {
+        let data = Arc::try_unwrap(capture).unwrap().into_inner().unwrap().finish().unwrap();
+        CaptureReader::new(&data[..])
+            .unwrap()
+            .map(|captured| {
+                let captured = captured.unwrap();
+                (captured.direction, captured.sender_id, captured.opcode)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // wl_display.get_registry then wl_display.sync for the roundtrip
+    let requests = vec![(1, 1), (1, 0)];
+    let client_messages = read(client_capture);
+    let server_messages = read(server_capture);
+    let of_direction = |messages: &[(MessageDirection, u32, u16)], direction| {
+        messages
+            .iter()
+            .filter(|&&(d, _, _)| d == direction)
+            .map(|&(_, id, opcode)| (id, opcode))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(of_direction(&client_messages, MessageDirection::Sent), requests);
+    assert_eq!(of_direction(&server_messages, MessageDirection::Received), requests);
+    // the events of the registry and the callback went the other way
+    assert_eq!(
+        of_direction(&client_messages, MessageDirection::Received),
+        of_direction(&server_messages, MessageDirection::Sent)
+    );
+    assert!(!of_direction(&server_messages, MessageDirection::Sent).is_empty());
+}
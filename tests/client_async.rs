@@ -0,0 +1,151 @@
+mod helpers;
+
+use helpers::{wayc, ways, ServerThread, TestClient};
+
+use ways::protocol::{wl_compositor, wl_output, wl_surface};
+
+use futures_lite::{future, StreamExt};
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+// run a server in its own thread and connect a client to it
+fn spawn_server() -> (ServerThread, TestClient) {
+    ServerThread::spawn(|display| {
+        display.create_global::<wl_compositor::WlCompositor, _>(
+            1,
+            ways::Filter::new(
+                |(compositor, _): (ways::Main<wl_compositor::WlCompositor>, u32), _, _| {
+                    compositor.quick_assign(|_, req, _| {
+                        if let wl_compositor::Request::CreateSurface { id } = req {
+                            id.quick_assign(|_, req, _| {
+                                if let wl_surface::Request::Frame { callback } = req {
+                                    callback.done(42);
+                                }
+                            });
+                        }
+                    });
+                },
+            ),
+        );
+        display.create_global::<wl_output::WlOutput, _>(
+            2,
+            ways::Filter::new(|(output, _): (ways::Main<wl_output::WlOutput>, u32), _, _| {
+                output.quick_assign(|_, _, _| {});
+                output.scale(2);
+                output.done();
+            }),
+        );
+    })
+}
+
+#[test]
+fn async_sync_roundtrip() {
+    let (server, client) = spawn_server();
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    let mut queue = wayc::AsyncEventQueue::new(client.event_queue).unwrap();
+
+    async_io::block_on(queue.sync_roundtrip(&mut (), |_, _, _| unreachable!())).unwrap();
+
+    let globals = manager.list();
+    assert!(globals.iter().any(|&(_, ref iface, _)| iface == "wl_compositor"));
+    assert!(globals.iter().any(|&(_, ref iface, _)| iface == "wl_output"));
+
+    server.stop();
+}
+
+#[test]
+fn async_concurrent_queues() {
+    let (server, client) = spawn_server();
+    let mut queue = wayc::AsyncEventQueue::new(client.event_queue).unwrap();
+
+    // bind the output on a second queue
+    let mut output_queue = wayc::AsyncEventQueue::new(client.display.create_event_queue()).unwrap();
+    let display = (**client.display).clone().attach(output_queue.get_ref().token());
+    let manager = wayc::GlobalManager::new(&display);
+    async_io::block_on(output_queue.sync_roundtrip(&mut (), |_, _, _| unreachable!())).unwrap();
+
+    let scale = Rc::new(Cell::new(0));
+    let scale2 = scale.clone();
+    let output = manager.instantiate_exact::<wayc::protocol::wl_output::WlOutput>(2).unwrap();
+    output.quick_assign(move |_, evt, _| {
+        if let wayc::protocol::wl_output::Event::Scale { factor } = evt {
+            scale2.set(factor);
+        }
+    });
+
+    // the events for each queue may be read by the other one
+    let (ret1, ret2) = async_io::block_on(future::zip(
+        queue.sync_roundtrip(&mut (), |_, _, _| unreachable!()),
+        output_queue.sync_roundtrip(&mut (), |_, _, _| unreachable!()),
+    ));
+    ret1.unwrap();
+    ret2.unwrap();
+
+    assert_eq!(scale.get(), 2);
+
+    server.stop();
+}
+
+#[test]
+fn async_readiness() {
+    let (server, client) = spawn_server();
+    let mut queue = wayc::AsyncEventQueue::new(client.event_queue).unwrap();
+    let mut readiness = queue.readiness();
+
+    let done = Rc::new(Cell::new(false));
+    let done2 = done.clone();
+    client.display_proxy.sync().quick_assign(move |_, _, _| done2.set(true));
+
+    async_io::block_on(async {
+        queue.flush().await.unwrap();
+        while !done.get() {
+            readiness.next().await.unwrap().unwrap();
+            queue.dispatch_pending(&mut (), |_, _, _| unreachable!()).unwrap();
+        }
+    });
+
+    server.stop();
+}
+
+#[test]
+fn async_callback_futures() {
+    let (server, client) = spawn_server();
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    let mut queue = wayc::AsyncEventQueue::new(client.event_queue).unwrap();
+    let display = client.display.clone();
+
+    async_io::block_on(async {
+        let sync = display.sync(queue.get_ref().token());
+        queue.dispatch_until(sync, &mut (), |_, _, _| unreachable!()).await.unwrap();
+
+        let compositor =
+            manager.instantiate_exact::<wayc::protocol::wl_compositor::WlCompositor>(1).unwrap();
+        let surface = compositor.create_surface();
+        let frame = surface.frame_future();
+        surface.commit();
+        let time = queue.dispatch_until(frame, &mut (), |_, _, _| unreachable!()).await.unwrap();
+        assert_eq!(time, 42);
+
+        let output = manager.instantiate_exact::<wayc::protocol::wl_output::WlOutput>(2).unwrap();
+        let output_done = Rc::new(Cell::new(false));
+        let output_done2 = output_done.clone();
+        output.quick_assign(move |_, event, _| match event {
+            wayc::protocol::wl_output::Event::Done => output_done2.set(true),
+            _ => panic!("Unexpected event: {:?}", event),
+        });
+        let event = queue
+            .dispatch_until(output.next_event(), &mut (), |_, _, _| unreachable!())
+            .await
+            .unwrap();
+        match event {
+            wayc::protocol::wl_output::Event::Scale { factor } => assert_eq!(factor, 2),
+            _ => panic!("Unexpected event: {:?}", event),
+        }
+        // the following events are still given to the filter of the object
+        queue.sync_roundtrip(&mut (), |_, _, _| unreachable!()).await.unwrap();
+        assert!(output_done.get());
+    });
+
+    server.stop();
+}
@@ -0,0 +1,84 @@
+mod helpers;
+
+use helpers::{wayc, ways, TestClient};
+
+use std::cell::Cell;
+use std::io;
+use std::os::unix::io::IntoRawFd;
+use std::os::unix::net::UnixStream;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[test]
+fn dispatch_timeout() {
+    let (server_cx, client_cx) = UnixStream::pair().unwrap();
+    let mut display = ways::Display::new();
+    let _client = unsafe { display.create_client(server_cx.into_raw_fd(), &mut ()) };
+    let mut client = unsafe { TestClient::from_fd(client_cx.into_raw_fd()) };
+
+    // the server is not dispatched, so no events can arrive
+    let ret = client.event_queue.dispatch_timeout(
+        Duration::from_millis(50),
+        &mut (),
+        |_, _, _| unreachable!(),
+    );
+    assert_eq!(ret.unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+    // the queue can still be used normally afterwards, its read intent was cancelled
+    let done = Rc::new(Cell::new(false));
+    let done2 = done.clone();
+    client.display_proxy.sync().quick_assign(move |_, _, _| done2.set(true));
+    client.display.flush().unwrap();
+    for _ in 0..2 {
+        display.dispatch(Duration::from_millis(10), &mut ()).unwrap();
+        display.flush_clients(&mut ());
+    }
+    let dispatched = client
+        .event_queue
+        .dispatch_timeout(Duration::from_secs(30), &mut (), |_, _, _| unreachable!())
+        .unwrap();
+    assert!(dispatched > 0);
+    assert!(done.get());
+}
+
+#[test]
+fn sync_roundtrip_timeout() {
+    let (server_cx, client_cx) = UnixStream::pair().unwrap();
+
+    let (start_server, server_started) = mpsc::channel::<()>();
+    let (stop_server, server_stopped) = mpsc::channel::<()>();
+
+    let server_thread = ::std::thread::spawn(move || {
+        let mut display = ways::Display::new();
+        let _client = unsafe { display.create_client(server_cx.into_raw_fd(), &mut ()) };
+
+        // don't process any request until asked to
+        server_started.recv().unwrap();
+        while let Err(mpsc::TryRecvError::Empty) = server_stopped.try_recv() {
+            display.dispatch(Duration::from_millis(10), &mut ()).unwrap();
+            display.flush_clients(&mut ());
+        }
+    });
+
+    let mut client = unsafe { TestClient::from_fd(client_cx.into_raw_fd()) };
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+
+    let ret = client.event_queue.sync_roundtrip_timeout(
+        Duration::from_millis(50),
+        &mut (),
+        |_, _, _| unreachable!(),
+    );
+    assert_eq!(ret.unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+    // once the server processes the requests, the roundtrip can complete
+    start_server.send(()).unwrap();
+    client
+        .event_queue
+        .sync_roundtrip_timeout(Duration::from_secs(30), &mut (), |_, _, _| unreachable!())
+        .unwrap();
+    assert!(manager.list().is_empty());
+
+    stop_server.send(()).unwrap();
+    server_thread.join().unwrap();
+}
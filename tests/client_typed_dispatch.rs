@@ -0,0 +1,104 @@
+mod helpers;
+
+use helpers::{wayc, ways, ServerThread};
+
+use wayc::protocol::{wl_display, wl_output, wl_registry};
+use wayc::Interface;
+use ways::protocol::wl_output as server_output;
+
+#[derive(Default)]
+struct State {
+    globals: Vec<String>,
+    scales: Vec<(u32, i32)>,
+    done: u32,
+}
+
+impl wayc::Dispatch<wl_registry::WlRegistry> for State {
+    type UserData = ();
+
+    fn event(
+        &mut self,
+        registry: &wayc::Main<wl_registry::WlRegistry>,
+        event: wl_registry::Event,
+        _: &(),
+        qhandle: &wayc::QueueHandle<State>,
+    ) {
+        if let wl_registry::Event::Global { name, interface, .. } = event {
+            if interface == "wl_output" {
+                let request =
+                    wl_registry::Request::Bind { name, id: (wl_output::WlOutput::NAME.into(), 2) };
+                qhandle
+                    .send_constructor::<_, wl_output::WlOutput>(
+                        registry.as_ref(),
+                        request,
+                        Some(2),
+                        name,
+                    )
+                    .unwrap();
+            }
+            self.globals.push(interface);
+        }
+    }
+}
+
+impl wayc::Dispatch<wl_output::WlOutput> for State {
+    // the name of the global the output was bound from
+    type UserData = u32;
+
+    fn event(
+        &mut self,
+        _: &wayc::Main<wl_output::WlOutput>,
+        event: wl_output::Event,
+        name: &u32,
+        _: &wayc::QueueHandle<State>,
+    ) {
+        match event {
+            wl_output::Event::Scale { factor } => self.scales.push((*name, factor)),
+            wl_output::Event::Done => self.done += 1,
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn typed_dispatch() {
+    let (server, client) = ServerThread::spawn(|display| {
+        for scale in 1..3 {
+            display.create_global::<server_output::WlOutput, _>(
+                2,
+                ways::Filter::new(
+                    move |(output, _): (ways::Main<server_output::WlOutput>, u32), _, _| {
+                        output.quick_assign(|_, _, _| {});
+                        output.scale(scale);
+                        output.done();
+                    },
+                ),
+            );
+        }
+    });
+    let mut queue = wayc::TypedEventQueue::<State>::new(client.event_queue);
+    let qhandle = queue.handle();
+    qhandle
+        .send_constructor::<_, wl_registry::WlRegistry>(
+            client.display_proxy.as_ref(),
+            wl_display::Request::GetRegistry {},
+            None,
+            (),
+        )
+        .unwrap();
+
+    let mut state = State::default();
+    // first roundtrip receives the globals, second one the events of the outputs
+    queue.sync_roundtrip(&mut state).unwrap();
+    queue.sync_roundtrip(&mut state).unwrap();
+
+    assert_eq!(state.globals, vec!["wl_output", "wl_output"]);
+    let mut scales = state.scales.clone();
+    scales.sort_by_key(|&(_, scale)| scale);
+    assert_eq!(scales.iter().map(|&(_, scale)| scale).collect::<Vec<_>>(), vec![1, 2]);
+    // each output received the name of its own global as user data
+    assert!(scales[0].0 != scales[1].0);
+    assert_eq!(state.done, 2);
+
+    server.stop();
+}
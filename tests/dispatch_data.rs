@@ -147,3 +147,77 @@ fn server_dispatch_data_resource_destructor() {
     server.display.flush_clients(&mut done);
     assert!(done);
 }
+
+#[test]
+fn client_dispatch_data_multiple() {
+    let mut server = TestServer::new();
+
+    let mut client = TestClient::new(&server.socket_name);
+
+    // two independent states, each accessed by its own callback
+    let mut done = false;
+    let mut count = 0u32;
+    client.display_proxy.sync().quick_assign(move |_, _, mut data| {
+        *data.get::<bool>().unwrap() = true;
+    });
+    client.display_proxy.sync().quick_assign(move |_, _, mut data| {
+        *data.get::<u32>().unwrap() += 1;
+    });
+    client.display.flush().unwrap();
+    server.answer();
+    let data = wayc::DispatchData::wrap(&mut done).with(&mut count);
+    client.event_queue.dispatch_with_data(data, |_, _, _| unreachable!()).unwrap();
+    assert!(done);
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn server_dispatch_data_multiple() {
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerCompositor, _>(
+        1,
+        ways::Filter::new(|_: (_, _), _, mut data| {
+            *data.get::<bool>().unwrap() = true;
+            *data.get::<u32>().unwrap() += 1;
+            assert!(!data.contains::<String>());
+        }),
+    );
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    manager.instantiate_exact::<wl_compositor::WlCompositor>(1).unwrap();
+    client.display.flush().unwrap();
+
+    let mut done = false;
+    let mut count = 0u32;
+    let data = ways::DispatchData::new().with(&mut done).with(&mut count);
+    server.display.dispatch_with_data(Duration::from_millis(10), data).unwrap();
+    assert!(done);
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn dispatch_data_reborrow() {
+    let mut done = false;
+    let mut count = 0u32;
+    let mut name = String::from("original");
+    let mut shadow = String::from("shadow");
+    let mut data = wayc::DispatchData::wrap(&mut done).with(&mut count).with(&mut name);
+    {
+        let mut reborrowed = data.reborrow();
+        *reborrowed.get::<bool>().unwrap() = true;
+        // data inserted in a reborrow hides the original data of the same type
+        reborrowed.insert(&mut shadow);
+        assert_eq!(reborrowed.get::<String>().unwrap(), "shadow");
+        let mut nested = reborrowed.reborrow();
+        *nested.get::<u32>().unwrap() += 1;
+        assert_eq!(nested.get::<String>().unwrap(), "shadow");
+        assert!(!nested.contains::<i32>());
+    }
+    assert_eq!(data.get::<String>().unwrap(), "original");
+    drop(data);
+    assert!(done);
+    assert_eq!(count, 1);
+}
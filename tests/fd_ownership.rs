@@ -0,0 +1,94 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::os::unix::io::RawFd;
+
+use nix::fcntl::OFlag;
+use nix::unistd::{close, pipe2, read};
+
+// A non-blocking pipe, to check whether all the copies of its write end
+// have been closed: reading then returns EOF rather than EAGAIN.
+fn pipe() -> (RawFd, RawFd) {
+    pipe2(OFlag::O_NONBLOCK | OFlag::O_CLOEXEC).unwrap()
+}
+
+fn write_end_closed(read_end: RawFd) -> bool {
+    let mut buf = [0u8; 1];
+    match read(read_end, &mut buf) {
+        Ok(0) => true,
+        Err(nix::Error::Sys(nix::errno::Errno::EAGAIN)) => false,
+        other => panic!("Unexpected read result: {:?}", other),
+    }
+}
+
+#[test]
+fn ignored_request_fd_is_closed() {
+    use ways::protocol::wl_shm;
+
+    let mut server = TestServer::new();
+    server.display.create_global::<wl_shm::WlShm, _>(
+        1,
+        ways::Filter::new(|(shm, _): (ways::Main<wl_shm::WlShm>, u32), _, _| {
+            shm.quick_assign(|_, req, _| {
+                if let wl_shm::Request::CreatePool { id, .. } = req {
+                    // the fd is ignored
+                    id.quick_assign(|_, _, _| {});
+                }
+            });
+        }),
+    );
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let shm = manager.instantiate_exact::<wayc::protocol::wl_shm::WlShm>(1).unwrap();
+    let (read_end, write_end) = pipe();
+    shm.create_pool(write_end, 42);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the client still owns the fd it provided
+    assert!(!write_end_closed(read_end));
+    close(write_end).unwrap();
+    // all the copies sent to the server have been closed
+    assert!(write_end_closed(read_end));
+    close(read_end).unwrap();
+}
+
+#[test]
+fn ignored_event_fd_is_closed() {
+    use ways::protocol::{wl_keyboard, wl_seat};
+
+    let (read_end, write_end) = pipe();
+
+    let mut server = TestServer::new();
+    server.display.create_global::<wl_seat::WlSeat, _>(
+        1,
+        ways::Filter::new(move |(seat, _): (ways::Main<wl_seat::WlSeat>, u32), _, _| {
+            seat.quick_assign(move |_, req, _| {
+                if let wl_seat::Request::GetKeyboard { id } = req {
+                    id.quick_assign(|_, _, _| {});
+                    id.keymap(wl_keyboard::KeymapFormat::NoKeymap, write_end, 0);
+                }
+            });
+        }),
+    );
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let seat = manager.instantiate_exact::<wayc::protocol::wl_seat::WlSeat>(1).unwrap();
+    // the keymap event is ignored
+    seat.get_keyboard().quick_assign(|_, _, _| {});
+    roundtrip(&mut client, &mut server).unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    // the server still owns the fd it provided
+    assert!(!write_end_closed(read_end));
+    close(write_end).unwrap();
+    // all the copies sent to the client have been closed
+    assert!(write_end_closed(read_end));
+    close(read_end).unwrap();
+}
@@ -0,0 +1,78 @@
+#[macro_use]
+extern crate wayland_client;
+
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::protocol::wl_output::{Subpixel, Transform, WlOutput as ServerOutput};
+
+use wayc::protocol::wl_output;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+type OutputEvent = (wayc::Main<wl_output::WlOutput>, wl_output::Event);
+
+fn insert_output(server: &mut TestServer, make: &'static str) {
+    server.display.create_global::<ServerOutput, _>(
+        1,
+        ways::Filter::new(move |(output, _): (ways::Main<ServerOutput>, u32), _, _| {
+            output.geometry(
+                0,
+                0,
+                0,
+                0,
+                Subpixel::Unknown,
+                make.into(),
+                "Model".into(),
+                Transform::Normal,
+            );
+        }),
+    );
+}
+
+#[test]
+fn route_by_output() {
+    let mut server = TestServer::new();
+    insert_output(&mut server, "First");
+    insert_output(&mut server, "Second");
+
+    let router = wayc::Router::new(|(output, _): &OutputEvent| (**output).clone());
+    let makes = Rc::new(RefCell::new(Vec::new()));
+
+    // each output gets its own route, all routes feed the same filter
+    let all_makes = {
+        let makes = makes.clone();
+        wayc::Filter::new(move |(index, make): (usize, String), _, _| {
+            makes.borrow_mut().push((index, make))
+        })
+    };
+    let router2 = router.clone();
+    let mut count = 0;
+    let mut client = TestClient::new(&server.socket_name);
+    let _manager = wayc::GlobalManager::new_with_cb(
+        &client.display_proxy,
+        global_filter!([
+            wl_output::WlOutput,
+            1,
+            move |output: wayc::Main<wl_output::WlOutput>, _: wayc::DispatchData| {
+                let index = count;
+                count += 1;
+                let route = all_makes.filter_map(move |(_, event): OutputEvent| match event {
+                    wl_output::Event::Geometry { make, .. } => Some((index, make)),
+                    _ => None,
+                });
+                router2.add_route((*output).clone(), route);
+                output.assign(router2.filter());
+            }
+        ]),
+    );
+
+    roundtrip(&mut client, &mut server).unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let mut makes = makes.borrow().clone();
+    makes.sort();
+    assert_eq!(makes, vec![(0, "First".to_string()), (1, "Second".to_string())]);
+}
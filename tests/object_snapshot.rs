@@ -0,0 +1,174 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::protocol::{wl_compositor, wl_surface};
+
+use wayc::protocol::wl_compositor::WlCompositor as ClientCompositor;
+
+use std::sync::{Arc, Mutex};
+
+fn insert_compositor(server: &mut TestServer) -> Arc<Mutex<Option<ways::Client>>> {
+    let client = Arc::new(Mutex::new(None));
+    let client2 = client.clone();
+    server.display.create_global::<wl_compositor::WlCompositor, _>(
+        3,
+        ways::Filter::new(
+            move |(compositor, _): (ways::Main<wl_compositor::WlCompositor>, u32), _, _| {
+                *client.lock().unwrap() = compositor.as_ref().client();
+                compositor.quick_assign(|_, req, _| {
+                    if let wl_compositor::Request::CreateSurface { id } = req {
+                        id.quick_assign(|_, _, _| {});
+                        id.as_ref().user_data().set(|| "surface");
+                    }
+                });
+            },
+        ),
+    );
+    client2
+}
+
+fn summary<'a, T>(
+    objects: &'a [T],
+    f: impl Fn(&'a T) -> (u32, &'a str, u32),
+) -> Vec<(u32, &'a str, u32)> {
+    objects.iter().map(f).collect()
+}
+
+#[test]
+fn client_objects() {
+    let mut server = TestServer::new();
+    insert_compositor(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager.instantiate_exact::<ClientCompositor>(2).unwrap();
+    let surface = compositor.create_surface();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let objects = client.display.objects();
+    assert_eq!(
+        summary(&objects, |o| (o.id, o.interface, o.version)),
+        vec![
+            (1, "wl_display", 1),
+            (2, "wl_registry", 1),
+            (3, "wl_compositor", 2),
+            (4, "wl_surface", 2)
+        ]
+    );
+    assert!(objects.iter().all(|o| o.alive && !o.client_destroyed && !o.server_destroyed));
+
+    surface.destroy();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let objects = client.display.objects();
+    assert_eq!(objects.len(), 3);
+    assert!(objects.iter().all(|o| o.interface != "wl_surface"));
+}
+
+#[test]
+#[cfg_attr(feature = "client_native", ignore)]
+fn client_objects_queues() {
+    let mut server = TestServer::new();
+    insert_compositor(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let event_queue = client.display.create_event_queue();
+    let compositor = manager.instantiate_exact::<ClientCompositor>(1).unwrap();
+    let surface = compositor.as_ref().clone().attach(event_queue.token()).create_surface();
+
+    let objects = client.display.objects();
+    let queue_of = |id| objects.iter().find(|o| o.id == id).unwrap().queue;
+    assert_eq!(queue_of(3), Some(client.event_queue.id()));
+    assert_eq!(queue_of(4), Some(event_queue.id()));
+
+    // destroyed objects are listed until the server acknowledges it
+    surface.destroy();
+    let objects = client.display.objects();
+    let surface = objects.iter().find(|o| o.id == 4).unwrap();
+    assert!(!surface.alive);
+    assert!(surface.client_destroyed);
+    assert!(!surface.server_destroyed);
+}
+
+#[test]
+fn server_objects() {
+    let mut server = TestServer::new();
+    let server_client = insert_compositor(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager.instantiate_exact::<ClientCompositor>(3).unwrap();
+    let surface = compositor.create_surface();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let server_client = server_client.lock().unwrap().clone().unwrap();
+    let objects = server_client.objects();
+    assert_eq!(
+        summary(&objects, |o| (o.id, &o.interface, o.version)),
+        vec![
+            (1, "wl_display", 1),
+            (2, "wl_registry", 1),
+            (3, "wl_compositor", 3),
+            (4, "wl_surface", 3)
+        ]
+    );
+    assert!(objects.iter().all(|o| o.alive));
+
+    surface.destroy();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let objects = server_client.objects();
+    assert_eq!(objects.len(), 3);
+    assert!(objects.iter().all(|o| o.interface != "wl_surface"));
+
+    server_client.kill();
+    assert!(server_client.objects().is_empty());
+}
+
+#[test]
+fn server_resource_lookup() {
+    let mut server = TestServer::new();
+    let server_client = insert_compositor(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager.instantiate_exact::<ClientCompositor>(3).unwrap();
+    let surface = compositor.create_surface();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let server_client = server_client.lock().unwrap().clone().unwrap();
+    let server_surface = server_client.get_resource::<wl_surface::WlSurface>(4).unwrap();
+    assert_eq!(server_surface.as_ref().id(), 4);
+    assert_eq!(server_surface.as_ref().version(), 3);
+    assert_eq!(server_surface.as_ref().user_data().get::<&str>(), Some(&"surface"));
+    // the interface must match
+    assert!(server_client.get_resource::<wl_compositor::WlCompositor>(4).is_none());
+    assert!(server_client.get_resource::<wl_surface::WlSurface>(5).is_none());
+
+    let object = server_client.get_object(4).unwrap();
+    assert_eq!(object.as_ref().id(), 4);
+    assert!(object.as_ref().is_alive());
+    assert_eq!(object.as_ref().user_data().get::<&str>(), Some(&"surface"));
+    assert!(server_client.get_object(5).is_none());
+
+    surface.destroy();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert!(!server_surface.as_ref().is_alive());
+    assert!(server_client.get_resource::<wl_surface::WlSurface>(4).is_none());
+    assert!(server_client.get_object(4).is_none());
+    assert!(server_client.get_object(3).is_some());
+
+    server_client.kill();
+    assert!(server_client.get_object(1).is_none());
+}
@@ -7,7 +7,7 @@ extern crate wayland_commons as wc;
 
 use wc::smallvec;
 use wc::socket::{BufferedSocket, Socket};
-use wc::wire::{Argument, Message};
+use wc::wire::{Argument, ArgumentType, Message};
 
 use std::cell::RefCell;
 use std::env;
@@ -90,6 +90,91 @@ fn client_wrong_sender() {
     assert_eq!(socket.flush(), Err(nix::Error::Sys(nix::errno::Errno::EPIPE)));
 }
 
+#[test]
+fn client_invalid_enum() {
+    use ways::protocol::wl_compositor;
+
+    let mut server = TestServer::new();
+    server.display.create_global::<wl_compositor::WlCompositor, _>(
+        3,
+        ways::Filter::new(
+            |(compositor, _): (ways::Main<wl_compositor::WlCompositor>, u32), _, _| {
+                compositor.quick_assign(|_, req, _| {
+                    if let wl_compositor::Request::CreateSurface { id } = req {
+                        id.quick_assign(|_, _, _| {});
+                    }
+                });
+            },
+        ),
+    );
+
+    let mut socket: PathBuf = env::var_os("XDG_RUNTIME_DIR").unwrap().into();
+    socket.push(&server.socket_name);
+    let socket = UnixStream::connect(socket).unwrap();
+
+    let mut socket = BufferedSocket::new(unsafe { Socket::from_raw_fd(socket.into_raw_fd()) });
+    let messages = [
+        Message {
+            sender_id: 1, // wl_display
+            opcode: 1,    // get_registry
+            args: smallvec![Argument::NewId(2)],
+        },
+        Message {
+            sender_id: 2, // wl_registry
+            opcode: 0,    // bind
+            args: smallvec![
+                Argument::Uint(1),
+                Argument::Str(Box::new(std::ffi::CString::new("wl_compositor").unwrap())),
+                Argument::Uint(3),
+                Argument::NewId(3),
+            ],
+        },
+        Message {
+            sender_id: 3, // wl_compositor
+            opcode: 0,    // create_surface
+            args: smallvec![Argument::NewId(4)],
+        },
+        Message {
+            sender_id: 4, // wl_surface
+            opcode: 7,    // set_buffer_transform
+            args: smallvec![Argument::Int(0xdead)],
+        },
+    ];
+    for msg in &messages {
+        socket.write_message(msg).unwrap();
+    }
+    socket.flush().unwrap();
+
+    server.answer();
+
+    // the server should have told us what was wrong before killing us
+    socket.fill_incoming_buffers().unwrap();
+    let error = loop {
+        let msg = socket
+            .read_one_message(|id, opcode| match (id, opcode) {
+                (1, 0) => Some(&[ArgumentType::Object, ArgumentType::Uint, ArgumentType::Str]),
+                (2, 0) => Some(&[ArgumentType::Uint, ArgumentType::Str, ArgumentType::Uint]),
+                _ => None,
+            })
+            .unwrap();
+        if msg.sender_id == 1 {
+            break msg;
+        }
+    };
+    assert_eq!(error.args[0], Argument::Object(1));
+    assert_eq!(error.args[1], Argument::Uint(1)); // invalid_method
+    assert_eq!(
+        error.args[2],
+        Argument::Str(Box::new(
+            std::ffi::CString::new(
+                "invalid arguments for wl_surface@4.set_buffer_transform: \
+                 argument 0 (transform): unknown enum value 57005"
+            )
+            .unwrap()
+        ))
+    );
+}
+
 #[test]
 fn client_receive_error() {
     let mut server = TestServer::new();
@@ -0,0 +1,74 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::protocol::wl_compositor::WlCompositor as ServerCompositor;
+
+use std::sync::{Arc, Mutex};
+
+type Log = Arc<Mutex<Vec<(ways::MessageDirection, String)>>>;
+
+fn recording_logger(log: &Log) -> Arc<dyn ways::ProtocolLogger> {
+    let log = log.clone();
+    Arc::new(move |msg: &ways::LoggedMessage| {
+        log.lock()
+            .unwrap()
+            .push((msg.direction, format!("{}@{}.{}", msg.interface, msg.id, msg.name)))
+    })
+}
+
+#[test]
+fn server_logger() {
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerCompositor, _>(1, ways::Filter::new(|_: (_, _), _, _| {}));
+    let log = Log::default();
+    server.display.set_protocol_logger(Some(recording_logger(&log)));
+
+    let mut client = TestClient::new(&server.socket_name);
+    let _manager = wayc::GlobalManager::new(&client.display_proxy);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let log = log.lock().unwrap();
+    assert!(log.contains(&(ways::MessageDirection::Received, "wl_display@1.get_registry".into())));
+    assert!(log.contains(&(ways::MessageDirection::Received, "wl_display@1.sync".into())));
+    assert!(log.contains(&(ways::MessageDirection::Sent, "wl_registry@2.global".into())));
+    assert!(log
+        .iter()
+        .any(|&(dir, ref msg)| dir == ways::MessageDirection::Sent && msg.ends_with(".done")));
+}
+
+#[test]
+fn client_logger() {
+    let mut server = TestServer::new();
+    server.display.create_global::<ServerCompositor, _>(1, ways::Filter::new(|_: (_, _), _, _| {}));
+
+    let mut client = TestClient::new(&server.socket_name);
+    let log = Log::default();
+    client.display.set_protocol_logger(Some(recording_logger(&log)));
+    let _manager = wayc::GlobalManager::new(&client.display_proxy);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let log = log.lock().unwrap();
+    assert!(log.contains(&(wayc::MessageDirection::Sent, "wl_display@1.get_registry".into())));
+    assert!(log.contains(&(wayc::MessageDirection::Received, "wl_registry@2.global".into())));
+    assert!(log
+        .iter()
+        .any(|&(dir, ref msg)| dir == wayc::MessageDirection::Received && msg.ends_with(".done")));
+}
+
+#[test]
+fn remove_logger() {
+    let mut server = TestServer::new();
+    let log = Log::default();
+    server.display.set_protocol_logger(Some(recording_logger(&log)));
+    server.display.set_protocol_logger(None);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let _manager = wayc::GlobalManager::new(&client.display_proxy);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert!(log.lock().unwrap().is_empty());
+}
@@ -15,8 +15,9 @@ pub mod wl_foo {
     use super::sys::client::*;
     use super::sys::common::{wl_argument, wl_array, wl_interface, wl_message};
     use super::{
-        smallvec, types_null, AnonymousObject, Argument, ArgumentType, Interface, Main, Message,
-        MessageDesc, MessageGroup, Object, ObjectMetadata, Proxy, NULLPTR,
+        smallvec, types_null, AnonymousObject, Argument, ArgumentError, ArgumentRef, ArgumentType,
+        FromRawError, Interface, Main, Message, MessageDesc, MessageGroup, MessageRef,
+        MessageWriteError, Object, ObjectMetadata, OwnedFd, Proxy, NULLPTR,
     };
     use std::os::raw::c_char;
     #[doc = "Possible cake kinds\n\nList of the possible kind of cake supported by the protocol."]
@@ -116,11 +117,14 @@ pub mod wl_foo {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             panic!("Request::from_raw can not be used Client-side.")
         }
-        fn into_raw(self, sender_id: u32) -> Message {
-            match self {
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            panic!("Request::from_raw_ref can not be used Client-side.")
+        }
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
+            Ok(match self {
                 Request::FooIt { number, unumber, text, float, file } => Message {
                     sender_id,
                     opcode: 0,
@@ -131,19 +135,19 @@ pub mod wl_foo {
                             ::std::ffi::CString::from_vec_unchecked(text.into())
                         })),
                         Argument::Fixed((float * 256.) as i32),
-                        Argument::Fd(file),
+                        Argument::Fd(OwnedFd::dup(file).map_err(MessageWriteError::DupFdFailed)?),
                     ],
                 },
                 Request::CreateBar {} => {
                     Message { sender_id, opcode: 1, args: smallvec![Argument::NewId(0),] }
                 }
-            }
+            })
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Request, ()> {
+        ) -> Result<Request, FromRawError> {
             panic!("Request::from_raw_c can not be used Client-side.")
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -207,47 +211,101 @@ pub mod wl_foo {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             match msg.opcode {
                 0 => {
                     let mut args = msg.args.into_iter();
                     Ok(Event::Cake {
                         kind: {
                             if let Some(Argument::Uint(val)) = args.next() {
-                                CakeKind::from_raw(val).ok_or(())?
+                                CakeKind::from_raw(val).ok_or(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "kind",
+                                    reason: ArgumentError::UnknownEnumValue(val),
+                                })?
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "kind",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         amount: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 1,
+                                    name: "amount",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                     })
                 }
-                _ => Err(()),
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
             }
         }
-        fn into_raw(self, sender_id: u32) -> Message {
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            match msg.opcode {
+                0 => {
+                    let mut args = msg.args();
+                    Ok(Event::Cake {
+                        kind: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                CakeKind::from_raw(val).ok_or(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "kind",
+                                    reason: ArgumentError::UnknownEnumValue(val),
+                                })?
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "kind",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        amount: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 1,
+                                    name: "amount",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                    })
+                }
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
+            }
+        }
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
             panic!("Event::into_raw can not be used Client-side.")
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Event, ()> {
+        ) -> Result<Event, FromRawError> {
             match opcode {
                 0 => {
                     let _args = ::std::slice::from_raw_parts(args, 2);
                     Ok(Event::Cake {
-                        kind: CakeKind::from_raw(_args[0].u).ok_or(())?,
+                        kind: CakeKind::from_raw(_args[0].u).ok_or(
+                            FromRawError::InvalidArgument {
+                                index: 0,
+                                name: "kind",
+                                reason: ArgumentError::UnknownEnumValue(_args[0].u),
+                            },
+                        )?,
                         amount: _args[1].u,
                     })
                 }
-                _ => return Err(()),
+                _ => return Err(FromRawError::UnknownOpcode(opcode as u16)),
             }
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -352,8 +410,9 @@ pub mod wl_bar {
     use super::sys::client::*;
     use super::sys::common::{wl_argument, wl_array, wl_interface, wl_message};
     use super::{
-        smallvec, types_null, AnonymousObject, Argument, ArgumentType, Interface, Main, Message,
-        MessageDesc, MessageGroup, Object, ObjectMetadata, Proxy, NULLPTR,
+        smallvec, types_null, AnonymousObject, Argument, ArgumentError, ArgumentRef, ArgumentType,
+        FromRawError, Interface, Main, Message, MessageDesc, MessageGroup, MessageRef,
+        MessageWriteError, Object, ObjectMetadata, OwnedFd, Proxy, NULLPTR,
     };
     use std::os::raw::c_char;
     #[derive(Debug)]
@@ -440,11 +499,14 @@ pub mod wl_bar {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             panic!("Request::from_raw can not be used Client-side.")
         }
-        fn into_raw(self, sender_id: u32) -> Message {
-            match self {
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            panic!("Request::from_raw_ref can not be used Client-side.")
+        }
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
+            Ok(match self {
                 Request::BarDelivery { kind, target, metadata, metametadata } => Message {
                     sender_id,
                     opcode: 0,
@@ -479,13 +541,13 @@ pub mod wl_bar {
                         Argument::Uint(event),
                     ],
                 },
-            }
+            })
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Request, ()> {
+        ) -> Result<Request, FromRawError> {
             panic!("Request::from_raw_c can not be used Client-side.")
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -596,7 +658,7 @@ pub mod wl_bar {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             match msg.opcode {
                 0 => {
                     let mut args = msg.args.into_iter();
@@ -605,71 +667,201 @@ pub mod wl_bar {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "self",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         _mut: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 1,
+                                    name: "mut",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         object: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 2,
+                                    name: "object",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         ___object: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 3,
+                                    name: "__object",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         handler: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 4,
+                                    name: "handler",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         ___handler: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 5,
+                                    name: "__handler",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         request: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 6,
+                                    name: "request",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         event: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 7,
+                                    name: "event",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                    })
+                }
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
+            }
+        }
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            match msg.opcode {
+                0 => {
+                    let mut args = msg.args();
+                    Ok(Event::_Self {
+                        _self: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "self",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        _mut: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 1,
+                                    name: "mut",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        object: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 2,
+                                    name: "object",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        ___object: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 3,
+                                    name: "__object",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        handler: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 4,
+                                    name: "handler",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        ___handler: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 5,
+                                    name: "__handler",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        request: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 6,
+                                    name: "request",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        event: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 7,
+                                    name: "event",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                     })
                 }
-                _ => Err(()),
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
             }
         }
-        fn into_raw(self, sender_id: u32) -> Message {
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
             panic!("Event::into_raw can not be used Client-side.")
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Event, ()> {
+        ) -> Result<Event, FromRawError> {
             match opcode {
                 0 => {
                     let _args = ::std::slice::from_raw_parts(args, 8);
@@ -684,7 +876,7 @@ pub mod wl_bar {
                         event: _args[7].u,
                     })
                 }
-                _ => return Err(()),
+                _ => return Err(FromRawError::UnknownOpcode(opcode as u16)),
             }
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -823,8 +1015,9 @@ pub mod wl_display {
     use super::sys::client::*;
     use super::sys::common::{wl_argument, wl_array, wl_interface, wl_message};
     use super::{
-        smallvec, types_null, AnonymousObject, Argument, ArgumentType, Interface, Main, Message,
-        MessageDesc, MessageGroup, Object, ObjectMetadata, Proxy, NULLPTR,
+        smallvec, types_null, AnonymousObject, Argument, ArgumentError, ArgumentRef, ArgumentType,
+        FromRawError, Interface, Main, Message, MessageDesc, MessageGroup, MessageRef,
+        MessageWriteError, Object, ObjectMetadata, OwnedFd, Proxy, NULLPTR,
     };
     use std::os::raw::c_char;
     #[derive(Debug)]
@@ -851,17 +1044,20 @@ pub mod wl_display {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             panic!("Request::from_raw can not be used Client-side.")
         }
-        fn into_raw(self, sender_id: u32) -> Message {
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            panic!("Request::from_raw_ref can not be used Client-side.")
+        }
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
             match self {}
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Request, ()> {
+        ) -> Result<Request, FromRawError> {
             panic!("Request::from_raw_c can not be used Client-side.")
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -895,21 +1091,26 @@ pub mod wl_display {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            match msg.opcode {
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
+            }
+        }
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
             match msg.opcode {
-                _ => Err(()),
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
             }
         }
-        fn into_raw(self, sender_id: u32) -> Message {
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
             panic!("Event::into_raw can not be used Client-side.")
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Event, ()> {
+        ) -> Result<Event, FromRawError> {
             match opcode {
-                _ => return Err(()),
+                _ => return Err(FromRawError::UnknownOpcode(opcode as u16)),
             }
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -969,8 +1170,9 @@ pub mod wl_registry {
     use super::sys::client::*;
     use super::sys::common::{wl_argument, wl_array, wl_interface, wl_message};
     use super::{
-        smallvec, types_null, AnonymousObject, Argument, ArgumentType, Interface, Main, Message,
-        MessageDesc, MessageGroup, Object, ObjectMetadata, Proxy, NULLPTR,
+        smallvec, types_null, AnonymousObject, Argument, ArgumentError, ArgumentRef, ArgumentType,
+        FromRawError, Interface, Main, Message, MessageDesc, MessageGroup, MessageRef,
+        MessageWriteError, Object, ObjectMetadata, OwnedFd, Proxy, NULLPTR,
     };
     use std::os::raw::c_char;
     #[derive(Debug)]
@@ -1011,11 +1213,14 @@ pub mod wl_registry {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             panic!("Request::from_raw can not be used Client-side.")
         }
-        fn into_raw(self, sender_id: u32) -> Message {
-            match self {
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            panic!("Request::from_raw_ref can not be used Client-side.")
+        }
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
+            Ok(match self {
                 Request::Bind { name, id } => Message {
                     sender_id,
                     opcode: 0,
@@ -1028,13 +1233,13 @@ pub mod wl_registry {
                         Argument::NewId(0),
                     ],
                 },
-            }
+            })
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Request, ()> {
+        ) -> Result<Request, FromRawError> {
             panic!("Request::from_raw_c can not be used Client-side.")
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -1078,21 +1283,26 @@ pub mod wl_registry {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            match msg.opcode {
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
+            }
+        }
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
             match msg.opcode {
-                _ => Err(()),
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
             }
         }
-        fn into_raw(self, sender_id: u32) -> Message {
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
             panic!("Event::into_raw can not be used Client-side.")
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Event, ()> {
+        ) -> Result<Event, FromRawError> {
             match opcode {
-                _ => return Err(()),
+                _ => return Err(FromRawError::UnknownOpcode(opcode as u16)),
             }
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -1170,8 +1380,9 @@ pub mod wl_callback {
     use super::sys::client::*;
     use super::sys::common::{wl_argument, wl_array, wl_interface, wl_message};
     use super::{
-        smallvec, types_null, AnonymousObject, Argument, ArgumentType, Interface, Main, Message,
-        MessageDesc, MessageGroup, Object, ObjectMetadata, Proxy, NULLPTR,
+        smallvec, types_null, AnonymousObject, Argument, ArgumentError, ArgumentRef, ArgumentType,
+        FromRawError, Interface, Main, Message, MessageDesc, MessageGroup, MessageRef,
+        MessageWriteError, Object, ObjectMetadata, OwnedFd, Proxy, NULLPTR,
     };
     use std::os::raw::c_char;
     #[derive(Debug)]
@@ -1198,17 +1409,20 @@ pub mod wl_callback {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             panic!("Request::from_raw can not be used Client-side.")
         }
-        fn into_raw(self, sender_id: u32) -> Message {
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            panic!("Request::from_raw_ref can not be used Client-side.")
+        }
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
             match self {}
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Request, ()> {
+        ) -> Result<Request, FromRawError> {
             panic!("Request::from_raw_c can not be used Client-side.")
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -1256,7 +1470,7 @@ pub mod wl_callback {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             match msg.opcode {
                 0 => {
                     let mut args = msg.args.into_iter();
@@ -1265,28 +1479,53 @@ pub mod wl_callback {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "callback_data",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                    })
+                }
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
+            }
+        }
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            match msg.opcode {
+                0 => {
+                    let mut args = msg.args();
+                    Ok(Event::Done {
+                        callback_data: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "callback_data",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                     })
                 }
-                _ => Err(()),
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
             }
         }
-        fn into_raw(self, sender_id: u32) -> Message {
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
             panic!("Event::into_raw can not be used Client-side.")
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Event, ()> {
+        ) -> Result<Event, FromRawError> {
             match opcode {
                 0 => {
                     let _args = ::std::slice::from_raw_parts(args, 1);
                     Ok(Event::Done { callback_data: _args[0].u })
                 }
-                _ => return Err(()),
+                _ => return Err(FromRawError::UnknownOpcode(opcode as u16)),
             }
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
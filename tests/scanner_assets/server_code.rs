@@ -15,8 +15,9 @@ pub mod wl_foo {
     use super::sys::common::{wl_argument, wl_array, wl_interface, wl_message};
     use super::sys::server::*;
     use super::{
-        smallvec, types_null, AnonymousObject, Argument, ArgumentType, Interface, Main, Message,
-        MessageDesc, MessageGroup, Object, ObjectMetadata, Resource, NULLPTR,
+        smallvec, types_null, AnonymousObject, Argument, ArgumentError, ArgumentRef, ArgumentType,
+        FromRawError, Interface, Main, Message, MessageDesc, MessageGroup, MessageRef,
+        MessageWriteError, Object, ObjectMetadata, OwnedFd, Resource, NULLPTR,
     };
     use std::os::raw::c_char;
     #[doc = "Possible cake kinds\n\nList of the possible kind of cake supported by the protocol."]
@@ -57,13 +58,7 @@ pub mod wl_foo {
     #[non_exhaustive]
     pub enum Request {
         #[doc = "do some foo\n\nThis will do some foo with its args."]
-        FooIt {
-            number: i32,
-            unumber: u32,
-            text: String,
-            float: f64,
-            file: ::std::os::unix::io::RawFd,
-        },
+        FooIt { number: i32, unumber: u32, text: String, float: f64, file: OwnedFd },
         #[doc = "create a bar\n\nCreate a bar which will do its bar job."]
         CreateBar { id: Main<super::wl_bar::WlBar> },
     }
@@ -116,7 +111,7 @@ pub mod wl_foo {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             match msg.opcode {
                 0 => {
                     let mut args = msg.args.into_iter();
@@ -125,14 +120,22 @@ pub mod wl_foo {
                             if let Some(Argument::Int(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "number",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         unumber: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 1,
+                                    name: "unumber",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         text: {
@@ -142,21 +145,33 @@ pub mod wl_foo {
                                 });
                                 s
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 2,
+                                    name: "text",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         float: {
                             if let Some(Argument::Fixed(val)) = args.next() {
                                 (val as f64) / 256.
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 3,
+                                    name: "float",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         file: {
                             if let Some(Argument::Fd(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 4,
+                                    name: "file",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                     })
@@ -166,24 +181,118 @@ pub mod wl_foo {
                     Ok(Request::CreateBar {
                         id: {
                             if let Some(Argument::NewId(val)) = args.next() {
-                                map.get_new(val).ok_or(())?
+                                map.get_new(val).ok_or(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "id",
+                                    reason: ArgumentError::InvalidNewId(val),
+                                })?
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "id",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                    })
+                }
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
+            }
+        }
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            match msg.opcode {
+                0 => {
+                    let mut args = msg.args();
+                    Ok(Request::FooIt {
+                        number: {
+                            if let Some(ArgumentRef::Int(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "number",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        unumber: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 1,
+                                    name: "unumber",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        text: {
+                            if let Some(ArgumentRef::Str(val)) = args.next() {
+                                let s = String::from_utf8_lossy(val.to_bytes()).into_owned();
+                                s
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 2,
+                                    name: "text",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        float: {
+                            if let Some(ArgumentRef::Fixed(val)) = args.next() {
+                                (val as f64) / 256.
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 3,
+                                    name: "float",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        file: {
+                            if let Some(ArgumentRef::Fd(val)) = args.next() {
+                                OwnedFd::dup(val).map_err(FromRawError::DupFdFailed)?
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 4,
+                                    name: "file",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                    })
+                }
+                1 => {
+                    let mut args = msg.args();
+                    Ok(Request::CreateBar {
+                        id: {
+                            if let Some(ArgumentRef::NewId(val)) = args.next() {
+                                map.get_new(val).ok_or(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "id",
+                                    reason: ArgumentError::InvalidNewId(val),
+                                })?
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "id",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                     })
                 }
-                _ => Err(()),
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
             }
         }
-        fn into_raw(self, sender_id: u32) -> Message {
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
             panic!("Request::into_raw can not be used Server-side.")
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Request, ()> {
+        ) -> Result<Request, FromRawError> {
             match opcode {
                 0 => {
                     let _args = ::std::slice::from_raw_parts(args, 5);
@@ -192,7 +301,7 @@ pub mod wl_foo {
                         unumber: _args[1].u,
                         text: ::std::ffi::CStr::from_ptr(_args[2].s).to_string_lossy().into_owned(),
                         float: (_args[3].f as f64) / 256.,
-                        file: _args[4].h,
+                        file: ::std::os::unix::io::FromRawFd::from_raw_fd(_args[4].h),
                     })
                 }
                 1 => {
@@ -204,7 +313,7 @@ pub mod wl_foo {
                         },
                     })
                 }
-                _ => return Err(()),
+                _ => return Err(FromRawError::UnknownOpcode(opcode as u16)),
             }
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -252,23 +361,26 @@ pub mod wl_foo {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             panic!("Event::from_raw can not be used Server-side.")
         }
-        fn into_raw(self, sender_id: u32) -> Message {
-            match self {
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            panic!("Event::from_raw_ref can not be used Server-side.")
+        }
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
+            Ok(match self {
                 Event::Cake { kind, amount } => Message {
                     sender_id,
                     opcode: 0,
                     args: smallvec![Argument::Uint(kind.to_raw()), Argument::Uint(amount),],
                 },
-            }
+            })
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Event, ()> {
+        ) -> Result<Event, FromRawError> {
             panic!("Event::from_raw_c can not be used Server-side.")
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -368,8 +480,9 @@ pub mod wl_bar {
     use super::sys::common::{wl_argument, wl_array, wl_interface, wl_message};
     use super::sys::server::*;
     use super::{
-        smallvec, types_null, AnonymousObject, Argument, ArgumentType, Interface, Main, Message,
-        MessageDesc, MessageGroup, Object, ObjectMetadata, Resource, NULLPTR,
+        smallvec, types_null, AnonymousObject, Argument, ArgumentError, ArgumentRef, ArgumentType,
+        FromRawError, Interface, Main, Message, MessageDesc, MessageGroup, MessageRef,
+        MessageWriteError, Object, ObjectMetadata, OwnedFd, Resource, NULLPTR,
     };
     use std::os::raw::c_char;
     #[derive(Debug)]
@@ -456,30 +569,62 @@ pub mod wl_bar {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             match msg.opcode {
                 0 => {
                     let mut args = msg.args.into_iter();
                     Ok(Request::BarDelivery {
                         kind: {
                             if let Some(Argument::Uint(val)) = args.next() {
-                                super::wl_foo::DeliveryKind::from_raw(val).ok_or(())?
+                                super::wl_foo::DeliveryKind::from_raw(val).ok_or(
+                                    FromRawError::InvalidArgument {
+                                        index: 0,
+                                        name: "kind",
+                                        reason: ArgumentError::UnknownEnumValue(val),
+                                    },
+                                )?
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "kind",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         target: {
                             if let Some(Argument::Object(val)) = args.next() {
-                                map.get(val).ok_or(())?.into()
+                                map.get(val)
+                                    .ok_or(if val == 0 {
+                                        FromRawError::InvalidArgument {
+                                            index: 1,
+                                            name: "target",
+                                            reason: ArgumentError::NullObject,
+                                        }
+                                    } else {
+                                        FromRawError::InvalidArgument {
+                                            index: 1,
+                                            name: "target",
+                                            reason: ArgumentError::InvalidObject(val),
+                                        }
+                                    })?
+                                    .into()
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 1,
+                                    name: "target",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         metadata: {
                             if let Some(Argument::Array(val)) = args.next() {
                                 *val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 2,
+                                    name: "metadata",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         metametadata: {
@@ -490,7 +635,11 @@ pub mod wl_bar {
                                     Some(*val)
                                 }
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 3,
+                                    name: "metametadata",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                     })
@@ -503,76 +652,286 @@ pub mod wl_bar {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "self",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         _mut: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 1,
+                                    name: "mut",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         object: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 2,
+                                    name: "object",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         ___object: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 3,
+                                    name: "__object",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         handler: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 4,
+                                    name: "handler",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         ___handler: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 5,
+                                    name: "__handler",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         request: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 6,
+                                    name: "request",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                         event: {
                             if let Some(Argument::Uint(val)) = args.next() {
                                 val
                             } else {
-                                return Err(());
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 7,
+                                    name: "event",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                    })
+                }
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
+            }
+        }
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            match msg.opcode {
+                0 => {
+                    let mut args = msg.args();
+                    Ok(Request::BarDelivery {
+                        kind: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                super::wl_foo::DeliveryKind::from_raw(val).ok_or(
+                                    FromRawError::InvalidArgument {
+                                        index: 0,
+                                        name: "kind",
+                                        reason: ArgumentError::UnknownEnumValue(val),
+                                    },
+                                )?
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "kind",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        target: {
+                            if let Some(ArgumentRef::Object(val)) = args.next() {
+                                map.get(val)
+                                    .ok_or(if val == 0 {
+                                        FromRawError::InvalidArgument {
+                                            index: 1,
+                                            name: "target",
+                                            reason: ArgumentError::NullObject,
+                                        }
+                                    } else {
+                                        FromRawError::InvalidArgument {
+                                            index: 1,
+                                            name: "target",
+                                            reason: ArgumentError::InvalidObject(val),
+                                        }
+                                    })?
+                                    .into()
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 1,
+                                    name: "target",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        metadata: {
+                            if let Some(ArgumentRef::Array(val)) = args.next() {
+                                val.to_vec()
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 2,
+                                    name: "metadata",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        metametadata: {
+                            if let Some(ArgumentRef::Array(val)) = args.next() {
+                                if val.len() == 0 {
+                                    None
+                                } else {
+                                    Some(val.to_vec())
+                                }
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 3,
+                                    name: "metametadata",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                    })
+                }
+                1 => Ok(Request::Release),
+                2 => {
+                    let mut args = msg.args();
+                    Ok(Request::_Self {
+                        _self: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 0,
+                                    name: "self",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        _mut: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 1,
+                                    name: "mut",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        object: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 2,
+                                    name: "object",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        ___object: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 3,
+                                    name: "__object",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        handler: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 4,
+                                    name: "handler",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        ___handler: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 5,
+                                    name: "__handler",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        request: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 6,
+                                    name: "request",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
+                            }
+                        },
+                        event: {
+                            if let Some(ArgumentRef::Uint(val)) = args.next() {
+                                val
+                            } else {
+                                return Err(FromRawError::InvalidArgument {
+                                    index: 7,
+                                    name: "event",
+                                    reason: ArgumentError::UnexpectedType,
+                                });
                             }
                         },
                     })
                 }
-                _ => Err(()),
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
             }
         }
-        fn into_raw(self, sender_id: u32) -> Message {
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
             panic!("Request::into_raw can not be used Server-side.")
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Request, ()> {
+        ) -> Result<Request, FromRawError> {
             match opcode {
                 0 => {
                     let _args = ::std::slice::from_raw_parts(args, 4);
                     Ok(Request::BarDelivery {
-                        kind: super::wl_foo::DeliveryKind::from_raw(_args[0].u).ok_or(())?,
+                        kind: super::wl_foo::DeliveryKind::from_raw(_args[0].u).ok_or(
+                            FromRawError::InvalidArgument {
+                                index: 0,
+                                name: "kind",
+                                reason: ArgumentError::UnknownEnumValue(_args[0].u),
+                            },
+                        )?,
                         target: Resource::<super::wl_foo::WlFoo>::from_c_ptr(_args[1].o as *mut _)
                             .into(),
                         metadata: {
@@ -605,7 +964,7 @@ pub mod wl_bar {
                         event: _args[7].u,
                     })
                 }
-                _ => return Err(()),
+                _ => return Err(FromRawError::UnknownOpcode(opcode as u16)),
             }
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -671,11 +1030,14 @@ pub mod wl_bar {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             panic!("Event::from_raw can not be used Server-side.")
         }
-        fn into_raw(self, sender_id: u32) -> Message {
-            match self {
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            panic!("Event::from_raw_ref can not be used Server-side.")
+        }
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
+            Ok(match self {
                 Event::_Self {
                     _self,
                     _mut,
@@ -699,13 +1061,13 @@ pub mod wl_bar {
                         Argument::Uint(event),
                     ],
                 },
-            }
+            })
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Event, ()> {
+        ) -> Result<Event, FromRawError> {
             panic!("Event::from_raw_c can not be used Server-side.")
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -850,8 +1212,9 @@ pub mod wl_callback {
     use super::sys::common::{wl_argument, wl_array, wl_interface, wl_message};
     use super::sys::server::*;
     use super::{
-        smallvec, types_null, AnonymousObject, Argument, ArgumentType, Interface, Main, Message,
-        MessageDesc, MessageGroup, Object, ObjectMetadata, Resource, NULLPTR,
+        smallvec, types_null, AnonymousObject, Argument, ArgumentError, ArgumentRef, ArgumentType,
+        FromRawError, Interface, Main, Message, MessageDesc, MessageGroup, MessageRef,
+        MessageWriteError, Object, ObjectMetadata, OwnedFd, Resource, NULLPTR,
     };
     use std::os::raw::c_char;
     #[derive(Debug)]
@@ -878,21 +1241,26 @@ pub mod wl_callback {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            match msg.opcode {
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
+            }
+        }
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
             match msg.opcode {
-                _ => Err(()),
+                _ => Err(FromRawError::UnknownOpcode(msg.opcode)),
             }
         }
-        fn into_raw(self, sender_id: u32) -> Message {
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
             panic!("Request::into_raw can not be used Server-side.")
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Request, ()> {
+        ) -> Result<Request, FromRawError> {
             match opcode {
-                _ => return Err(()),
+                _ => return Err(FromRawError::UnknownOpcode(opcode as u16)),
             }
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -940,23 +1308,26 @@ pub mod wl_callback {
                 _ => None,
             }
         }
-        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+        fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
             panic!("Event::from_raw can not be used Server-side.")
         }
-        fn into_raw(self, sender_id: u32) -> Message {
-            match self {
+        fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+            panic!("Event::from_raw_ref can not be used Server-side.")
+        }
+        fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
+            Ok(match self {
                 Event::Done { callback_data } => Message {
                     sender_id,
                     opcode: 0,
                     args: smallvec![Argument::Uint(callback_data),],
                 },
-            }
+            })
         }
         unsafe fn from_raw_c(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<Event, ()> {
+        ) -> Result<Event, FromRawError> {
             panic!("Event::from_raw_c can not be used Server-side.")
         }
         fn as_raw_c_in<F, T>(self, f: F) -> T
@@ -0,0 +1,230 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use ways::protocol::{wl_compositor, wl_output};
+use ways::{ClientLimits, Limit, LimitPolicy};
+
+struct Setup {
+    server: TestServer,
+    client: TestClient,
+    manager: wayc::GlobalManager,
+    compositor: wayc::Main<wayc::protocol::wl_compositor::WlCompositor>,
+    server_client: ways::Client,
+    surfaces: Rc<Cell<usize>>,
+    outputs: Rc<RefCell<Vec<ways::Main<wl_output::WlOutput>>>>,
+}
+
+// a client bound to a compositor counting the surfaces it creates, and an output global
+fn setup(limits: ClientLimits) -> Setup {
+    let mut server = TestServer::new();
+    server.display.set_default_client_limits(limits).unwrap();
+
+    let surfaces = Rc::new(Cell::new(0));
+    let server_client = Rc::new(RefCell::new(None));
+    server.display.create_global::<wl_compositor::WlCompositor, _>(1, {
+        let surfaces = surfaces.clone();
+        let server_client = server_client.clone();
+        ways::Filter::new(
+            move |(compositor, _): (ways::Main<wl_compositor::WlCompositor>, u32), _, _| {
+                *server_client.borrow_mut() = compositor.as_ref().client();
+                let surfaces = surfaces.clone();
+                compositor.quick_assign(move |_, request, _| {
+                    if let wl_compositor::Request::CreateSurface { id } = request {
+                        id.quick_assign(|_, _, _| {});
+                        surfaces.set(surfaces.get() + 1);
+                    }
+                });
+            },
+        )
+    });
+
+    let outputs = Rc::new(RefCell::new(Vec::new()));
+    server.display.create_global::<wl_output::WlOutput, _>(1, {
+        let outputs = outputs.clone();
+        ways::Filter::new(move |(output, _): (ways::Main<wl_output::WlOutput>, u32), _, _| {
+            output.quick_assign(|_, _, _| {});
+            outputs.borrow_mut().push(output);
+        })
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor =
+        manager.instantiate_exact::<wayc::protocol::wl_compositor::WlCompositor>(1).unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let server_client = server_client.borrow_mut().take().unwrap();
+    Setup { server, client, manager, compositor, server_client, surfaces, outputs }
+}
+
+fn dispatch_once(server: &mut TestServer) {
+    server.display.dispatch(Duration::from_millis(100), &mut ()).unwrap();
+    server.display.flush_clients(&mut ());
+}
+
+#[test]
+#[cfg_attr(feature = "server_native", ignore)]
+fn requests_per_dispatch_defer() {
+    let mut s = setup(ClientLimits::default());
+    s.server_client
+        .set_limits(ClientLimits {
+            requests_per_dispatch: Some(Limit::new(2, LimitPolicy::Defer)),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let _surfaces: Vec<_> = (0..5).map(|_| s.compositor.create_surface()).collect();
+    s.client.display.flush().unwrap();
+
+    // the requests are spread over several dispatches
+    dispatch_once(&mut s.server);
+    assert_eq!(s.surfaces.get(), 2);
+    dispatch_once(&mut s.server);
+    assert_eq!(s.surfaces.get(), 4);
+    dispatch_once(&mut s.server);
+    assert_eq!(s.surfaces.get(), 5);
+
+    roundtrip(&mut s.client, &mut s.server).unwrap();
+    assert!(s.server_client.alive());
+}
+
+#[test]
+#[cfg_attr(feature = "server_native", ignore)]
+fn requests_per_dispatch_no_memory() {
+    let mut s = setup(ClientLimits::default());
+    s.server_client
+        .set_limits(ClientLimits {
+            requests_per_dispatch: Some(Limit::new(2, LimitPolicy::NoMemory)),
+            ..Default::default()
+        })
+        .unwrap();
+
+    for _ in 0..5 {
+        s.compositor.create_surface();
+    }
+
+    assert!(roundtrip(&mut s.client, &mut s.server).is_err());
+    assert_eq!(s.surfaces.get(), 2);
+    let error = s.client.display.protocol_error().unwrap();
+    assert_eq!(error.code, wayc::protocol::wl_display::Error::NoMemory as u32);
+    assert_eq!(error.object_interface, "wl_display");
+    assert!(!s.server_client.alive());
+}
+
+#[test]
+#[cfg_attr(feature = "server_native", ignore)]
+fn objects_disconnect() {
+    let mut s = setup(ClientLimits {
+        objects: Some(Limit::new(5, LimitPolicy::Disconnect)),
+        ..Default::default()
+    });
+
+    // the display, the registry, the compositor, a surface and the callback of the roundtrip
+    s.compositor.create_surface();
+    roundtrip(&mut s.client, &mut s.server).unwrap();
+    assert_eq!(s.surfaces.get(), 1);
+
+    // the second surface reaches the limit, the third exceeds it
+    s.compositor.create_surface();
+    s.compositor.create_surface();
+    assert!(roundtrip(&mut s.client, &mut s.server).is_err());
+    assert_eq!(s.surfaces.get(), 2);
+    // disconnected without any error
+    assert!(s.client.display.protocol_error().is_none());
+    assert!(!s.server_client.alive());
+}
+
+#[test]
+#[cfg_attr(feature = "server_native", ignore)]
+fn queued_bytes_defer() {
+    let mut s = setup(ClientLimits::default());
+    s.server_client.set_buffer_capacity(ways::BufferCapacity::new(4096, 4 * 1024 * 1024));
+    s.server_client
+        .set_limits(ClientLimits {
+            queued_bytes: Some(Limit::new(64 * 1024, LimitPolicy::Defer)),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let client_output =
+        s.manager.instantiate_exact::<wayc::protocol::wl_output::WlOutput>(1).unwrap();
+    client_output.quick_assign(|_, _, _| {});
+    roundtrip(&mut s.client, &mut s.server).unwrap();
+    let output = s.outputs.borrow_mut().pop().unwrap();
+
+    // queue more events than the socket can hold, each of them fitting in the 4096 bytes
+    // libwayland-client accepts for a message
+    for _ in 0..200 {
+        output.geometry(
+            0,
+            0,
+            100,
+            100,
+            wl_output::Subpixel::Unknown,
+            "Make".into(),
+            "Model".repeat(600),
+            wl_output::Transform::Normal,
+        );
+    }
+    s.server.display.flush_clients(&mut ());
+
+    s.compositor.create_surface();
+    s.client.display.flush().unwrap();
+
+    // the request is not processed while the client does not read its events
+    dispatch_once(&mut s.server);
+    dispatch_once(&mut s.server);
+    assert_eq!(s.surfaces.get(), 0);
+    assert!(s.server_client.alive());
+
+    // nor does it wake up the event loop in the meantime
+    let start = Instant::now();
+    s.server.display.dispatch(Duration::from_millis(100), &mut ()).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(90));
+    assert_eq!(s.surfaces.get(), 0);
+
+    // it is once the client catches up, which can take one read per event with the system
+    // library, so the events are read without the delays of `roundtrip()`
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while s.surfaces.get() == 0 && Instant::now() < deadline {
+        let _ =
+            s.client.event_queue.dispatch_timeout(Duration::from_millis(10), &mut (), |_, _, _| {});
+        s.server.display.dispatch(Duration::from_millis(0), &mut ()).unwrap();
+        s.server.display.flush_clients(&mut ());
+    }
+    assert_eq!(s.surfaces.get(), 1);
+}
+
+#[test]
+#[cfg_attr(feature = "server_native", ignore)]
+fn objects_defer_rejected() {
+    let mut server = TestServer::new();
+    let limits =
+        ClientLimits { objects: Some(Limit::new(10, LimitPolicy::Defer)), ..Default::default() };
+    match server.display.set_default_client_limits(limits) {
+        Err(ways::LimitsError::DeferredObjects) => {}
+        ret => panic!("Unexpected result: {:?}", ret),
+    }
+}
+
+#[test]
+#[cfg(feature = "server_native")]
+fn limits_unsupported() {
+    let mut server = TestServer::new();
+    server.display.set_default_client_limits(ClientLimits::default()).unwrap();
+    let limits = ClientLimits {
+        objects: Some(Limit::new(10, LimitPolicy::Disconnect)),
+        ..Default::default()
+    };
+    match server.display.set_default_client_limits(limits) {
+        Err(ways::LimitsError::Unsupported) => {}
+        ret => panic!("Unexpected result: {:?}", ret),
+    }
+}
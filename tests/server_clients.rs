@@ -71,3 +71,58 @@ fn client_user_data() {
     assert!(clients[1].data_map().get::<HasCompositor>().is_some());
     assert!(clients[1].data_map().get::<HasOutput>().is_some());
 }
+
+#[test]
+fn client_credentials() {
+    let mut server = TestServer::new();
+    let clients = Arc::new(Mutex::new(Vec::new()));
+
+    server.display.create_global::<wl_output::WlOutput, _>(1, {
+        let clients = clients.clone();
+        ways::Filter::new(move |(output, _): (ways::Main<wl_output::WlOutput>, u32), _, _| {
+            clients.lock().unwrap().push(output.as_ref().client().unwrap());
+        })
+    });
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    manager.instantiate_exact::<ClientOutput>(1).unwrap();
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let client_handle = clients.lock().unwrap().pop().unwrap();
+    // the client runs in this process
+    let credentials = client_handle.credentials().unwrap();
+    assert_eq!(credentials.pid, nix::unistd::getpid().as_raw());
+    assert_eq!(credentials.uid, nix::unistd::getuid().as_raw());
+    assert_eq!(credentials.gid, nix::unistd::getgid().as_raw());
+
+    client_handle.kill();
+    assert!(client_handle.credentials().is_none());
+}
+
+#[test]
+fn display_clients() {
+    let mut server = TestServer::new();
+    assert!(server.display.clients().is_empty());
+
+    let mut client_1 = TestClient::new(&server.socket_name);
+    let mut client_2 = TestClient::new(&server.socket_name);
+    roundtrip(&mut client_1, &mut server).unwrap();
+    roundtrip(&mut client_2, &mut server).unwrap();
+
+    let clients = server.display.clients();
+    assert_eq!(clients.len(), 2);
+    assert!(!clients[0].equals(&clients[1]));
+    assert!(clients.iter().all(|client| client.alive()));
+
+    // dead clients are not listed
+    clients[0].kill();
+    server.display.flush_clients(&mut ());
+    let remaining = server.display.clients();
+    assert_eq!(remaining.len(), 1);
+    assert!(remaining[0].equals(&clients[1]));
+}
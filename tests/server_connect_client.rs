@@ -0,0 +1,41 @@
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::protocol::wl_output;
+
+use wayc::protocol::wl_output::WlOutput as ClientOutput;
+
+use std::cell::Cell;
+use std::ffi::OsString;
+use std::rc::Rc;
+
+#[test]
+fn connect_client() {
+    // no listening socket is needed
+    let mut server = TestServer { display: ways::Display::new(), socket_name: OsString::new() };
+    let bound = Rc::new(Cell::new(false));
+    let bound2 = bound.clone();
+    server.display.create_global::<wl_output::WlOutput, _>(
+        1,
+        ways::Filter::new(move |(output, _): (ways::Main<wl_output::WlOutput>, u32), _, _| {
+            output.quick_assign(|_, _, _| {});
+            bound2.set(true);
+        }),
+    );
+
+    let (server_client, display) = server.display.connect_client(&mut ()).unwrap();
+    let mut client = TestClient::from_display(display);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+
+    roundtrip(&mut client, &mut server).unwrap();
+    manager.instantiate_exact::<ClientOutput>(1).unwrap();
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert!(bound.get());
+    assert!(server_client.alive());
+
+    server_client.kill();
+    server.display.flush_clients(&mut ());
+    assert!(!server_client.alive());
+}
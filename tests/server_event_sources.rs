@@ -0,0 +1,196 @@
+extern crate nix;
+extern crate wayland_server as ways;
+
+use std::cell::{Cell, RefCell};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use ways::{Display, Interest, Readiness};
+
+#[test]
+fn fd_source() {
+    let mut display = Display::new();
+    let (mut tx, rx) = UnixStream::pair().unwrap();
+    rx.set_nonblocking(true).unwrap();
+
+    let received = Rc::new(RefCell::new(Vec::new()));
+    let source = {
+        let received = received.clone();
+        let mut rx = rx.try_clone().unwrap();
+        display
+            .add_fd_source(rx.as_raw_fd(), Interest::READ, move |readiness, mut ddata| {
+                assert!(readiness.contains(Readiness::READABLE));
+                *ddata.get::<u32>().unwrap() += 1;
+                let mut buf = [0u8; 16];
+                let n = rx.read(&mut buf).unwrap();
+                received.borrow_mut().extend_from_slice(&buf[..n]);
+            })
+            .unwrap()
+    };
+
+    let mut count = 0u32;
+    display.dispatch(Duration::from_millis(0), &mut count).unwrap();
+    assert_eq!(count, 0);
+
+    tx.write_all(b"hello").unwrap();
+    display.dispatch(Duration::from_millis(100), &mut count).unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(&*received.borrow(), b"hello");
+
+    source.remove();
+    tx.write_all(b"world").unwrap();
+    display.dispatch(Duration::from_millis(10), &mut count).unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn fd_source_update() {
+    let mut display = Display::new();
+    let (tx, _rx) = UnixStream::pair().unwrap();
+
+    let writable = Rc::new(Cell::new(0));
+    let source = {
+        let writable = writable.clone();
+        display
+            .add_fd_source(tx.as_raw_fd(), Interest::empty(), move |readiness, _| {
+                if readiness.contains(Readiness::WRITABLE) {
+                    writable.set(writable.get() + 1);
+                }
+            })
+            .unwrap()
+    };
+
+    display.dispatch(Duration::from_millis(0), &mut ()).unwrap();
+    assert_eq!(writable.get(), 0);
+
+    source.update(Interest::WRITE).unwrap();
+    display.dispatch(Duration::from_millis(100), &mut ()).unwrap();
+    assert_eq!(writable.get(), 1);
+    source.remove();
+}
+
+#[test]
+fn timer_source() {
+    let mut display = Display::new();
+
+    let fired = Rc::new(Cell::new(0));
+    let timer = {
+        let fired = fired.clone();
+        display.add_timer(move |_| fired.set(fired.get() + 1)).unwrap()
+    };
+
+    // the timer is initially disarmed
+    display.dispatch(Duration::from_millis(10), &mut ()).unwrap();
+    assert_eq!(fired.get(), 0);
+
+    let start = Instant::now();
+    timer.set_timeout(Some(Duration::from_millis(20))).unwrap();
+    while fired.get() == 0 && start.elapsed() < Duration::from_secs(5) {
+        display.dispatch(Duration::from_millis(100), &mut ()).unwrap();
+    }
+    assert_eq!(fired.get(), 1);
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    // the timer only fires once
+    display.dispatch(Duration::from_millis(40), &mut ()).unwrap();
+    assert_eq!(fired.get(), 1);
+
+    timer.set_timeout(Some(Duration::from_millis(10))).unwrap();
+    timer.set_timeout(None).unwrap();
+    display.dispatch(Duration::from_millis(40), &mut ()).unwrap();
+    assert_eq!(fired.get(), 1);
+    timer.remove();
+}
+
+#[test]
+fn signal_source() {
+    let mut display = Display::new();
+
+    let received = Rc::new(Cell::new(0));
+    let source = {
+        let received = received.clone();
+        display.add_signal(nix::libc::SIGUSR1, move |_| received.set(received.get() + 1)).unwrap()
+    };
+
+    // the signal is blocked for this thread, send it to this thread only
+    unsafe {
+        nix::libc::pthread_kill(nix::libc::pthread_self(), nix::libc::SIGUSR1);
+    }
+    display.dispatch(Duration::from_millis(100), &mut ()).unwrap();
+    assert_eq!(received.get(), 1);
+    source.remove();
+}
+
+#[test]
+fn idle_source() {
+    let mut display = Display::new();
+
+    let order = Rc::new(RefCell::new(Vec::new()));
+    {
+        let order = order.clone();
+        display
+            .add_idle(move |mut ddata| {
+                *ddata.get::<u32>().unwrap() += 1;
+                order.borrow_mut().push("first");
+            })
+            .unwrap();
+    }
+    let cancelled = {
+        let order = order.clone();
+        display.add_idle(move |_| order.borrow_mut().push("cancelled")).unwrap()
+    };
+    {
+        let order = order.clone();
+        display.add_idle(move |_| order.borrow_mut().push("second")).unwrap();
+    }
+    cancelled.remove();
+
+    let mut count = 0u32;
+    display.dispatch(Duration::from_millis(0), &mut count).unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(&*order.borrow(), &["first", "second"]);
+
+    // idle callbacks only run once
+    display.dispatch(Duration::from_millis(0), &mut count).unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn sources_outliving_display() {
+    let mut display = Display::new();
+    let (tx, _rx) = UnixStream::pair().unwrap();
+
+    let fired = Rc::new(Cell::new(0));
+    let fd_source = {
+        let fired = fired.clone();
+        display
+            .add_fd_source(tx.as_raw_fd(), Interest::READ, move |_, _| fired.set(fired.get() + 1))
+            .unwrap()
+    };
+    let timer = {
+        let fired = fired.clone();
+        display.add_timer(move |_| fired.set(fired.get() + 1)).unwrap()
+    };
+    let idle = {
+        let fired = fired.clone();
+        display.add_idle(move |_| fired.set(fired.get() + 1)).unwrap()
+    };
+    let kept_timer = {
+        let fired = fired.clone();
+        display.add_timer(move |_| fired.set(fired.get() + 1)).unwrap()
+    };
+    drop(display);
+
+    // the handles can still be used once the display is destroyed, and free the callbacks
+    fd_source.update(Interest::WRITE).unwrap();
+    timer.set_timeout(Some(Duration::from_millis(10))).unwrap();
+    fd_source.remove();
+    timer.remove();
+    idle.remove();
+    drop(kept_timer);
+    assert_eq!(fired.get(), 0);
+    assert_eq!(Rc::strong_count(&fired), 1);
+}
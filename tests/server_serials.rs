@@ -0,0 +1,83 @@
+mod helpers;
+
+use helpers::{roundtrip, ways, TestClient, TestServer};
+
+use std::cell::Cell;
+use std::os::unix::net::UnixStream;
+use std::rc::Rc;
+
+use ways::SerialTracker;
+
+#[derive(Debug, PartialEq)]
+enum SeatEvent {
+    ButtonPress,
+    ButtonRelease,
+}
+
+#[test]
+fn next_serial() {
+    let mut server = TestServer::new();
+    let mut client = TestClient::new(&server.socket_name);
+
+    let first = server.display.next_serial();
+    let second = server.display.next_serial();
+    assert_eq!(second, first.wrapping_add(1));
+
+    // the callbacks of wl_display.sync carry the current serial of the display
+    let callback_data = Rc::new(Cell::new(None));
+    let callback_data2 = callback_data.clone();
+    client.display_proxy.sync().quick_assign(move |_, event, _| {
+        if let helpers::wayc::protocol::wl_callback::Event::Done { callback_data } = event {
+            callback_data2.set(Some(callback_data));
+        }
+    });
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    assert_eq!(callback_data.get(), Some(second));
+}
+
+#[test]
+fn serial_tracker() {
+    let mut server = TestServer::new();
+
+    let (server_end_1, _client_end_1) = UnixStream::pair().unwrap();
+    let (server_end_2, _client_end_2) = UnixStream::pair().unwrap();
+    let client_1 = server.display.create_client_from_stream(server_end_1, &mut ());
+    let client_2 = server.display.create_client_from_stream(server_end_2, &mut ());
+
+    let mut tracker = SerialTracker::new(3);
+
+    let press = server.display.next_serial();
+    tracker.record(press, &client_1, SeatEvent::ButtonPress);
+    let release = server.display.next_serial();
+    tracker.record(release, &client_1, SeatEvent::ButtonRelease);
+    let other_press = server.display.next_serial();
+    tracker.record(other_press, &client_2, SeatEvent::ButtonPress);
+
+    assert_eq!(tracker.get(press, &client_1), Some(&SeatEvent::ButtonPress));
+    assert!(tracker.check(press, &client_1, |event| *event == SeatEvent::ButtonPress));
+    assert!(!tracker.check(release, &client_1, |event| *event == SeatEvent::ButtonPress));
+    // serials are only valid for the client they were sent to
+    assert!(!tracker.check(press, &client_2, |event| *event == SeatEvent::ButtonPress));
+    assert_eq!(tracker.get(other_press, &client_1), None);
+
+    assert_eq!(tracker.last(&client_1, |event| *event == SeatEvent::ButtonPress), Some(press));
+    assert_eq!(tracker.last(&client_2, |event| *event == SeatEvent::ButtonRelease), None);
+
+    // the oldest serial is forgotten
+    let new_press = server.display.next_serial();
+    tracker.record(new_press, &client_1, SeatEvent::ButtonPress);
+    assert_eq!(tracker.get(press, &client_1), None);
+    assert_eq!(tracker.last(&client_1, |event| *event == SeatEvent::ButtonPress), Some(new_press));
+
+    // as well as the serials of dead clients
+    client_2.kill();
+    let last_release = server.display.next_serial();
+    tracker.record(last_release, &client_1, SeatEvent::ButtonRelease);
+    assert_eq!(tracker.get(other_press, &client_2), None);
+    assert_eq!(tracker.get(release, &client_1), Some(&SeatEvent::ButtonRelease));
+
+    tracker.clear();
+    assert_eq!(tracker.get(last_release, &client_1), None);
+}
@@ -0,0 +1,252 @@
+extern crate tempfile;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::rc::Rc;
+
+mod helpers;
+
+use helpers::{roundtrip, wayc, ways, TestClient, TestServer};
+
+use wayc::protocol::wl_shm::Format;
+
+use ways::protocol::wl_buffer::WlBuffer as ServerBuffer;
+use ways::shm::{with_buffer_contents, BufferAccessError, BufferData};
+
+fn insert_compositor(server: &mut TestServer) -> Rc<RefCell<Option<ServerBuffer>>> {
+    use ways::protocol::{wl_compositor, wl_surface};
+
+    let attached = Rc::new(RefCell::new(None));
+    let attached2 = attached.clone();
+
+    server.display.create_global::<wl_compositor::WlCompositor, _>(
+        1,
+        ways::Filter::new(
+            move |(compositor, _): (ways::Main<wl_compositor::WlCompositor>, u32), _, _| {
+                let attached = attached.clone();
+                compositor.quick_assign(move |_, request, _| {
+                    if let wl_compositor::Request::CreateSurface { id } = request {
+                        let attached = attached.clone();
+                        id.quick_assign(move |_, request, _| {
+                            if let wl_surface::Request::Attach { buffer, .. } = request {
+                                *attached.borrow_mut() = buffer;
+                            }
+                        });
+                    }
+                });
+            },
+        ),
+    );
+
+    attached2
+}
+
+struct Setup {
+    server: TestServer,
+    client: TestClient,
+    shm: wayc::Main<wayc::protocol::wl_shm::WlShm>,
+    surface: wayc::Main<wayc::protocol::wl_surface::WlSurface>,
+    attached: Rc<RefCell<Option<ServerBuffer>>>,
+}
+
+fn setup(formats: &[ways::protocol::wl_shm::Format]) -> Setup {
+    let mut server = TestServer::new();
+    server.display.create_shm_global(formats).unwrap();
+    let attached = insert_compositor(&mut server);
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let shm = manager.instantiate_exact::<wayc::protocol::wl_shm::WlShm>(1).unwrap();
+    let compositor =
+        manager.instantiate_exact::<wayc::protocol::wl_compositor::WlCompositor>(1).unwrap();
+    let surface = compositor.create_surface();
+
+    Setup { server, client, shm, surface, attached }
+}
+
+fn pool_file(contents: &[u8]) -> File {
+    let mut file = tempfile::tempfile().unwrap();
+    file.write_all(contents).unwrap();
+    file.flush().unwrap();
+    file
+}
+
+#[test]
+fn shm_advertised_formats() {
+    let mut s = setup(&[ways::protocol::wl_shm::Format::Rgb565]);
+
+    let formats = Rc::new(RefCell::new(Vec::new()));
+    let formats2 = formats.clone();
+    s.shm.quick_assign(move |_, event, _| {
+        if let wayc::protocol::wl_shm::Event::Format { format } = event {
+            formats2.borrow_mut().push(format);
+        }
+    });
+
+    roundtrip(&mut s.client, &mut s.server).unwrap();
+
+    assert_eq!(*formats.borrow(), vec![Format::Argb8888, Format::Xrgb8888, Format::Rgb565]);
+}
+
+#[test]
+fn shm_buffer_contents() {
+    let mut s = setup(&[]);
+
+    let contents: Vec<u8> = (0..64).collect();
+    let file = pool_file(&contents);
+    let pool = s.shm.create_pool(file.as_raw_fd(), 64);
+    let buffer = pool.create_buffer(16, 2, 3, 8, Format::Xrgb8888);
+    s.surface.attach(Some(&buffer), 0, 0);
+
+    roundtrip(&mut s.client, &mut s.server).unwrap();
+
+    let server_buffer = s.attached.borrow_mut().take().unwrap();
+    let (pixels, data) =
+        with_buffer_contents(&server_buffer, |pixels, data| (pixels.to_vec(), data)).unwrap();
+    assert_eq!(
+        data,
+        BufferData {
+            offset: 16,
+            width: 2,
+            height: 3,
+            stride: 8,
+            format: ways::protocol::wl_shm::Format::Xrgb8888
+        }
+    );
+    assert_eq!(pixels, &contents[16..40]);
+}
+
+#[test]
+fn shm_truncated_pool() {
+    let mut s = setup(&[]);
+
+    let file = pool_file(&[0xFF; 64]);
+    let pool = s.shm.create_pool(file.as_raw_fd(), 64);
+    let buffer = pool.create_buffer(0, 4, 4, 16, Format::Argb8888);
+    s.surface.attach(Some(&buffer), 0, 0);
+
+    roundtrip(&mut s.client, &mut s.server).unwrap();
+
+    let server_buffer = s.attached.borrow_mut().take().unwrap();
+    assert_eq!(with_buffer_contents(&server_buffer, |pixels, _| pixels[63]), Ok(0xFF));
+
+    // the client truncates the file, accessing the pool now raises a SIGBUS
+    file.set_len(0).unwrap();
+
+    assert_eq!(
+        with_buffer_contents(&server_buffer, |pixels, _| pixels[0]),
+        Err(BufferAccessError::BadMap)
+    );
+    // the pool stays unusable
+    assert_eq!(
+        with_buffer_contents(&server_buffer, |pixels, _| pixels.len()),
+        Err(BufferAccessError::BadMap)
+    );
+}
+
+#[test]
+fn shm_pool_resize() {
+    let mut s = setup(&[]);
+
+    let mut file = pool_file(&[1; 16]);
+    let pool = s.shm.create_pool(file.as_raw_fd(), 16);
+
+    file.write_all(&[2; 16]).unwrap();
+    file.flush().unwrap();
+    pool.resize(32);
+    let buffer = pool.create_buffer(16, 4, 1, 16, Format::Argb8888);
+    s.surface.attach(Some(&buffer), 0, 0);
+
+    roundtrip(&mut s.client, &mut s.server).unwrap();
+
+    let server_buffer = s.attached.borrow_mut().take().unwrap();
+    assert_eq!(with_buffer_contents(&server_buffer, |pixels, _| pixels.to_vec()), Ok(vec![2; 16]));
+
+    // shrinking the pool is a protocol error
+    pool.resize(16);
+    assert!(roundtrip(&mut s.client, &mut s.server).is_err());
+    let error = s.client.display.protocol_error().unwrap();
+    assert_eq!(error.code, wayc::protocol::wl_shm::Error::InvalidStride as u32);
+    assert_eq!(error.object_interface, "wl_shm_pool");
+}
+
+#[test]
+fn shm_pool_resize_during_access() {
+    let mut s = setup(&[]);
+
+    let mut file = pool_file(&[1; 16]);
+    let pool = s.shm.create_pool(file.as_raw_fd(), 16);
+    let buffer = pool.create_buffer(0, 4, 1, 16, Format::Argb8888);
+    s.surface.attach(Some(&buffer), 0, 0);
+
+    roundtrip(&mut s.client, &mut s.server).unwrap();
+    let server_buffer = s.attached.borrow_mut().take().unwrap();
+
+    file.write_all(&[2; 16]).unwrap();
+    file.flush().unwrap();
+    pool.resize(32);
+    s.client.display.flush().unwrap();
+
+    // the resize is processed while the contents of the pool are accessed
+    let server = &mut s.server;
+    let pixels = with_buffer_contents(&server_buffer, |pixels, _| {
+        server.answer();
+        pixels.to_vec()
+    });
+    assert_eq!(pixels, Ok(vec![1; 16]));
+
+    let buffer = pool.create_buffer(16, 4, 1, 16, Format::Argb8888);
+    s.surface.attach(Some(&buffer), 0, 0);
+
+    roundtrip(&mut s.client, &mut s.server).unwrap();
+    let server_buffer = s.attached.borrow_mut().take().unwrap();
+    assert_eq!(with_buffer_contents(&server_buffer, |pixels, _| pixels.to_vec()), Ok(vec![2; 16]));
+}
+
+#[test]
+fn shm_invalid_stride() {
+    let mut s = setup(&[]);
+
+    let file = pool_file(&[0; 64]);
+    let pool = s.shm.create_pool(file.as_raw_fd(), 64);
+    // the buffer does not fit in the pool
+    pool.create_buffer(16, 4, 4, 16, Format::Argb8888);
+
+    assert!(roundtrip(&mut s.client, &mut s.server).is_err());
+    let error = s.client.display.protocol_error().unwrap();
+    assert_eq!(error.code, wayc::protocol::wl_shm::Error::InvalidStride as u32);
+    assert_eq!(error.object_interface, "wl_shm_pool");
+}
+
+#[test]
+fn shm_invalid_format() {
+    let mut s = setup(&[]);
+
+    let file = pool_file(&[0; 64]);
+    let pool = s.shm.create_pool(file.as_raw_fd(), 64);
+    // not advertised by the global
+    pool.create_buffer(0, 4, 4, 16, Format::Rgb565);
+
+    assert!(roundtrip(&mut s.client, &mut s.server).is_err());
+    let error = s.client.display.protocol_error().unwrap();
+    assert_eq!(error.code, wayc::protocol::wl_shm::Error::InvalidFormat as u32);
+    assert_eq!(error.object_interface, "wl_shm_pool");
+}
+
+#[test]
+fn shm_invalid_pool_size() {
+    let mut s = setup(&[]);
+
+    let file = pool_file(&[0; 64]);
+    s.shm.create_pool(file.as_raw_fd(), 0);
+
+    assert!(roundtrip(&mut s.client, &mut s.server).is_err());
+    let error = s.client.display.protocol_error().unwrap();
+    assert_eq!(error.code, wayc::protocol::wl_shm::Error::InvalidStride as u32);
+    assert_eq!(error.object_interface, "wl_shm");
+}
@@ -0,0 +1,96 @@
+mod helpers;
+
+use helpers::{roundtrip_with_ddata, wayc, ways, TestClient, TestServer};
+
+use ways::protocol::{wl_compositor, wl_surface};
+
+use wayc::protocol::wl_compositor::WlCompositor as ClientCompositor;
+
+#[derive(Default)]
+struct State {
+    bound: Vec<&'static str>,
+    surfaces: u32,
+    commits: Vec<(&'static str, u32)>,
+}
+
+impl ways::GlobalDispatch<wl_compositor::WlCompositor> for State {
+    type GlobalData = &'static str;
+
+    fn bind(&mut self, compositor: ways::Main<wl_compositor::WlCompositor>, name: &&'static str) {
+        self.bound.push(name);
+        compositor.assign_dispatch::<State>(*name);
+    }
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor> for State {
+    // the name of the global the compositor was bound from
+    type UserData = &'static str;
+
+    fn request(
+        &mut self,
+        _: &ways::Main<wl_compositor::WlCompositor>,
+        request: wl_compositor::Request,
+        name: &&'static str,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            self.surfaces += 1;
+            id.assign_dispatch::<State>((*name, self.surfaces));
+        }
+    }
+}
+
+impl ways::Dispatch<wl_surface::WlSurface> for State {
+    type UserData = (&'static str, u32);
+
+    fn request(
+        &mut self,
+        _: &ways::Main<wl_surface::WlSurface>,
+        request: wl_surface::Request,
+        data: &(&'static str, u32),
+    ) {
+        if let wl_surface::Request::Commit = request {
+            self.commits.push(*data);
+        }
+    }
+}
+
+#[test]
+fn typed_dispatch() {
+    let mut server = TestServer::new();
+    server.display.create_global_dispatch::<wl_compositor::WlCompositor, State>(1, "first");
+    server.display.create_global_dispatch::<wl_compositor::WlCompositor, State>(1, "second");
+    let mut state = State::default();
+
+    let mut client = TestClient::new(&server.socket_name);
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+
+    roundtrip_with_ddata(&mut client, &mut server, &mut (), &mut state).unwrap();
+
+    // bind both globals, the manager only instantiates the first one of an interface
+    let registry = client.display_proxy.get_registry();
+    registry.quick_assign(|_, _, _| {});
+    let compositors = manager
+        .list()
+        .into_iter()
+        .map(|(name, _, _)| registry.bind::<ClientCompositor>(1, name))
+        .collect::<Vec<_>>();
+    assert_eq!(compositors.len(), 2);
+
+    roundtrip_with_ddata(&mut client, &mut server, &mut (), &mut state).unwrap();
+
+    let mut bound = state.bound.clone();
+    bound.sort();
+    assert_eq!(bound, vec!["first", "second"]);
+
+    let surface1 = compositors[1].create_surface();
+    let surface2 = compositors[1].create_surface();
+    surface2.commit();
+    surface1.commit();
+
+    roundtrip_with_ddata(&mut client, &mut server, &mut (), &mut state).unwrap();
+
+    // the surfaces received the name of the global of their compositor
+    let name = state.bound[1];
+    assert_eq!(state.surfaces, 2);
+    assert_eq!(state.commits, vec![(name, 2), (name, 1)]);
+}
@@ -0,0 +1,71 @@
+extern crate wayland_testing;
+
+mod helpers;
+
+use helpers::assert_protocol_error_message;
+
+use wayland_testing::{client as wayc, server as ways};
+use wayland_testing::{expect_protocol_error, roundtrip, EventRecorder, TestServer};
+
+use ways::protocol::{wl_compositor, wl_output};
+
+use wayc::protocol::wl_compositor::WlCompositor as ClientCompositor;
+use wayc::protocol::wl_output::{self as client_output, WlOutput as ClientOutput};
+
+#[test]
+fn roundtrip_without_socket() {
+    let mut server = TestServer::new();
+    server.display.create_global::<wl_output::WlOutput, _>(
+        2,
+        ways::Filter::new(|(output, _): (ways::Main<wl_output::WlOutput>, u32), _, _| {
+            output.quick_assign(|_, _, _| {});
+            output.scale(3);
+            output.done();
+        }),
+    );
+
+    let (_client, mut client) = server.add_client();
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let output = manager.instantiate_exact::<ClientOutput>(2).unwrap();
+    let recorder = EventRecorder::new(&output);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    recorder.assert_received(|event| match *event {
+        client_output::Event::Scale { factor } => factor == 3,
+        _ => false,
+    });
+    let events = recorder.take();
+    assert_eq!(events.len(), 1);
+    match events[0] {
+        client_output::Event::Done => {}
+        ref event => panic!("Unexpected event {:?}.", event),
+    }
+}
+
+#[test]
+fn protocol_error() {
+    let mut server = TestServer::new();
+    server.display.create_global::<wl_compositor::WlCompositor, _>(
+        1,
+        ways::Filter::new(
+            |(compositor, _): (ways::Main<wl_compositor::WlCompositor>, u32), _, _| {
+                compositor.quick_assign(|compositor, _, _| {
+                    compositor.as_ref().post_error(42, "I don't want surfaces.".into());
+                });
+            },
+        ),
+    );
+
+    let (client_handle, mut client) = server.add_client();
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut server).unwrap();
+
+    let compositor = manager.instantiate_exact::<ClientCompositor>(1).unwrap();
+    compositor.create_surface();
+
+    let error = expect_protocol_error(&mut client, &mut server, "wl_compositor", 42);
+    assert_protocol_error_message(&error, "I don't want surfaces.");
+    assert!(!client_handle.alive());
+}
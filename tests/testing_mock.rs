@@ -0,0 +1,132 @@
+extern crate wayland_testing;
+
+mod helpers;
+
+use helpers::assert_protocol_error_message;
+
+use std::cell::{Cell, RefCell};
+use std::ffi::CString;
+use std::rc::Rc;
+
+use wayland_testing::{client as wayc, server as ways};
+use wayland_testing::{expect_protocol_error, roundtrip, Argument, MockServer};
+
+use ways::protocol::{wl_compositor, wl_output, wl_seat, wl_surface};
+
+use wayc::protocol::wl_compositor::WlCompositor as ClientCompositor;
+use wayc::protocol::wl_seat::{self as client_seat, WlSeat as ClientSeat};
+
+#[test]
+fn scripted_replies() {
+    let mut mock = MockServer::new();
+    mock.add_global::<wl_compositor::WlCompositor>(4);
+    mock.add_global_with_bind::<wl_seat::WlSeat, _>(5, |seat| {
+        seat.capabilities(wl_seat::Capability::Keyboard);
+        seat.name("mock-seat".into());
+    });
+    let first_frame = mock.on::<wl_surface::WlSurface>().opcode(3).times(1).reply(|_, request| {
+        if let wl_surface::Request::Frame { ref callback } = *request {
+            callback.done(42);
+        }
+    });
+    let frames = mock.on::<wl_surface::WlSurface>().opcode(3).reply(|_, request| {
+        if let wl_surface::Request::Frame { ref callback } = *request {
+            callback.done(7);
+        }
+    });
+
+    let (_client, mut client) = mock.add_client();
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut mock.server).unwrap();
+
+    let capabilities = Rc::new(Cell::new(client_seat::Capability::empty()));
+    let seat = manager.instantiate_exact::<ClientSeat>(5).unwrap();
+    let seat_capabilities = capabilities.clone();
+    seat.quick_assign(move |_, event, _| {
+        if let client_seat::Event::Capabilities { capabilities } = event {
+            seat_capabilities.set(capabilities);
+        }
+    });
+    let compositor = manager.instantiate_exact::<ClientCompositor>(4).unwrap();
+    let surface = compositor.create_surface();
+    let times = Rc::new(RefCell::new(Vec::new()));
+    for _ in 0..2 {
+        let times = times.clone();
+        surface.frame().quick_assign(move |_, event, _| {
+            if let wayc::protocol::wl_callback::Event::Done { callback_data } = event {
+                times.borrow_mut().push(callback_data);
+            }
+        });
+    }
+    roundtrip(&mut client, &mut mock.server).unwrap();
+
+    assert_eq!(capabilities.get(), client_seat::Capability::Keyboard);
+    assert_eq!(*times.borrow(), vec![42, 7]);
+    assert_eq!(first_frame.count(), 1);
+    assert_eq!(frames.count(), 1);
+
+    let bind = mock.assert_received(|req| {
+        req.interface == "wl_registry"
+            && req.name == "bind"
+            && req.args.get(1) == Some(&Argument::Str(Box::new(CString::new("wl_seat").unwrap())))
+    });
+    assert_eq!(bind.object_id, 2);
+    mock.assert_received(|req| req.interface == "wl_compositor" && req.name == "create_surface");
+    let requests = mock.take_requests();
+    assert_eq!(requests.iter().filter(|req| req.name == "frame").count(), 2);
+}
+
+#[test]
+fn scripted_global_removal() {
+    let mut mock = MockServer::new();
+    let output = mock.add_global::<wl_output::WlOutput>(2);
+    mock.add_global::<wl_compositor::WlCompositor>(4);
+    mock.on::<wl_compositor::WlCompositor>().opcode(1).remove_global(output);
+
+    let (_client, mut client) = mock.add_client();
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut mock.server).unwrap();
+    assert_eq!(manager.list().len(), 2);
+
+    let compositor = manager.instantiate_exact::<ClientCompositor>(4).unwrap();
+    compositor.create_surface();
+    roundtrip(&mut client, &mut mock.server).unwrap();
+    assert_eq!(manager.list().len(), 2);
+
+    compositor.create_region();
+    roundtrip(&mut client, &mut mock.server).unwrap();
+    let globals = manager.list();
+    assert_eq!(globals.len(), 1);
+    assert_eq!(globals[0].1, "wl_compositor");
+}
+
+#[test]
+fn scripted_protocol_error() {
+    let mut mock = MockServer::new();
+    mock.add_global::<wl_compositor::WlCompositor>(4);
+    mock.on::<wl_surface::WlSurface>()
+        .matching(|request| match *request {
+            wl_surface::Request::SetBufferScale { scale } => scale < 1,
+            _ => false,
+        })
+        .post_error(wl_surface::Error::InvalidScale as u32, "Invalid scale.".into());
+
+    let (client_handle, mut client) = mock.add_client();
+    let manager = wayc::GlobalManager::new(&client.display_proxy);
+    roundtrip(&mut client, &mut mock.server).unwrap();
+
+    let compositor = manager.instantiate_exact::<ClientCompositor>(4).unwrap();
+    let surface = compositor.create_surface();
+    surface.set_buffer_scale(2);
+    roundtrip(&mut client, &mut mock.server).unwrap();
+    surface.set_buffer_scale(0);
+
+    let error = expect_protocol_error(
+        &mut client,
+        &mut mock.server,
+        "wl_surface",
+        wl_surface::Error::InvalidScale as u32,
+    );
+    assert_protocol_error_message(&error, "Invalid scale.");
+    assert!(!client_handle.alive());
+}
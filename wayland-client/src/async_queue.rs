@@ -0,0 +1,405 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+
+use async_io::{Async, ReadableOwned};
+use futures_core::Stream;
+
+use crate::protocol::wl_display::WlDisplay;
+use crate::{AnonymousObject, Attached, DispatchData, Display, EventQueue, Main, RawEvent};
+
+/// Storage for the reactor source of a connection, shared by all clones of a `Display`
+///
+/// The connection FD can only be registered once in the reactor, so all async event
+/// queues of a connection share the same source while at least one of them is alive.
+pub(crate) type SourceSlot = Arc<Mutex<Weak<AsyncSource>>>;
+
+struct ConnectionFd(RawFd);
+
+impl AsRawFd for ConnectionFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+struct WakeState {
+    generation: u64,
+    wakers: Vec<Waker>,
+}
+
+pub(crate) struct AsyncSource {
+    fd: Arc<Async<ConnectionFd>>,
+    state: Mutex<WakeState>,
+}
+
+impl AsyncSource {
+    fn get(display: &Display) -> io::Result<Arc<AsyncSource>> {
+        let mut slot = display.async_source.lock().unwrap();
+        if let Some(source) = slot.upgrade() {
+            return Ok(source);
+        }
+        let source = Arc::new(AsyncSource {
+            fd: Arc::new(Async::new(ConnectionFd(display.get_connection_fd()))?),
+            state: Mutex::new(WakeState { generation: 0, wakers: Vec::new() }),
+        });
+        *slot = Arc::downgrade(&source);
+        Ok(source)
+    }
+
+    fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    // Ready if events were read from the socket since `generation`, otherwise
+    // the task will be woken the next time it happens
+    fn poll_notified(&self, generation: &mut u64, cx: &mut Context) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != *generation {
+            *generation = state.generation;
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    // Signal that events were read from the socket and may have been inserted
+    // in the buffers of any queue, returns the new generation
+    fn notify_read(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.generation = state.generation.wrapping_add(1);
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+        state.generation
+    }
+}
+
+/// A stream of readiness of a wayland connection
+///
+/// This stream yields every time the wayland socket becomes readable, or events are read
+/// from it by an `AsyncEventQueue` of the same connection. When it yields, some events may be
+/// ready to be dispatched, and you should call `AsyncEventQueue::dispatch_pending()`.
+///
+/// It is obtained with the `AsyncEventQueue::readiness()` method, and never ends.
+pub struct Readiness {
+    source: Arc<AsyncSource>,
+    generation: u64,
+    readable: Option<ReadableOwned<ConnectionFd>>,
+}
+
+impl Readiness {
+    fn new(source: Arc<AsyncSource>) -> Readiness {
+        let generation = source.generation();
+        Readiness { source, generation, readable: None }
+    }
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        if self.source.poll_notified(&mut self.generation, cx).is_ready() {
+            self.readable = None;
+            return Poll::Ready(Ok(()));
+        }
+        let source = &self.source;
+        let readable = self.readable.get_or_insert_with(|| source.fd.clone().readable_owned());
+        match Pin::new(readable).poll(cx) {
+            Poll::Ready(ret) => {
+                self.readable = None;
+                Poll::Ready(ret)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn next_ready(&mut self) -> NextReady<'_> {
+        NextReady(self)
+    }
+}
+
+impl Stream for Readiness {
+    type Item = io::Result<()>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<io::Result<()>>> {
+        self.get_mut().poll_ready(cx).map(Some)
+    }
+}
+
+struct NextReady<'a>(&'a mut Readiness);
+
+impl<'a> Future for NextReady<'a> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_ready(cx)
+    }
+}
+
+// Resolves with the output of `fut`, or with `None` if `dispatch` completes first
+struct Race<'a, A, B> {
+    fut: Pin<&'a mut A>,
+    dispatch: Pin<&'a mut B>,
+}
+
+impl<'a, A, B> Future for Race<'a, A, B>
+where
+    A: Future,
+    B: Future<Output = io::Result<u32>>,
+{
+    type Output = io::Result<Option<A::Output>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let me = self.get_mut();
+        if let Poll::Ready(output) = me.fut.as_mut().poll(cx) {
+            return Poll::Ready(Ok(Some(output)));
+        }
+        me.dispatch.as_mut().poll(cx).map(|ret| ret.map(|_| None))
+    }
+}
+
+/// An event queue driven by an async executor
+///
+/// This is a wrapper around an `EventQueue`, which waits for events using the
+/// [`async-io`](https://docs.rs/async-io) reactor instead of blocking the thread. It can
+/// be used with any executor, but as the `EventQueue` it wraps it is not `Send`, so its
+/// futures must be run on a local executor (for example a `LocalSet` with tokio, or
+/// `spawn_local` with async-std).
+///
+/// Several async event queues of the same connection can be driven concurrently. The reading
+/// of events is never kept pending across an `.await`, and when a queue reads events from the
+/// socket the other async queues of the connection are woken up to dispatch the events that
+/// were inserted in their buffers. Queues driven with the blocking methods of `EventQueue`
+/// (for example on an other thread) do not wake the async queues when reading events.
+///
+/// This type is only available with the `async` cargo feature.
+pub struct AsyncEventQueue {
+    queue: EventQueue,
+    readiness: Readiness,
+}
+
+impl AsyncEventQueue {
+    /// Wrap an event queue
+    ///
+    /// This puts the wayland socket in non-blocking mode and registers it in the reactor,
+    /// which can fail.
+    pub fn new(queue: EventQueue) -> io::Result<AsyncEventQueue> {
+        let source = AsyncSource::get(queue.display())?;
+        Ok(AsyncEventQueue { queue, readiness: Readiness::new(source) })
+    }
+
+    /// Access the wrapped event queue
+    pub fn get_ref(&self) -> &EventQueue {
+        &self.queue
+    }
+
+    /// Mutably access the wrapped event queue
+    ///
+    /// Events read using this reference do not wake the other async queues of the connection.
+    pub fn get_mut(&mut self) -> &mut EventQueue {
+        &mut self.queue
+    }
+
+    /// Retrieve the wrapped event queue
+    pub fn into_inner(self) -> EventQueue {
+        self.queue
+    }
+
+    /// Get a stream of readiness of the connection
+    ///
+    /// See the [`Readiness`](struct.Readiness.html) documentation for details.
+    pub fn readiness(&self) -> Readiness {
+        Readiness::new(self.readiness.source.clone())
+    }
+
+    /// Dispatches pending events, reading new ones from the socket if necessary
+    ///
+    /// Dispatches the events from the internal buffer, and if there were none, reads the
+    /// events available on the socket and dispatches the ones for this queue. Never blocks,
+    /// if no events were available simply returns `Ok(0)`.
+    ///
+    /// The `data` and `fallback` arguments are the same as for `EventQueue::dispatch_pending()`.
+    ///
+    /// If an error is returned, your connection with the wayland compositor is probably lost.
+    /// You may want to check `Display::protocol_error()` to see if it was caused by a protocol error.
+    pub fn dispatch_pending<T: std::any::Any, F>(
+        &mut self,
+        data: &mut T,
+        fallback: F,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        self.dispatch_pending_with_data(DispatchData::wrap(data), fallback)
+    }
+
+    /// Same as `dispatch_pending()`, with the callbacks accessing the content of a `DispatchData`
+    pub fn dispatch_pending_with_data<F>(
+        &mut self,
+        mut data: DispatchData,
+        mut fallback: F,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        let dispatched = self.queue.dispatch_pending_with_data(data.reborrow(), &mut fallback)?;
+        if dispatched > 0 {
+            return Ok(dispatched);
+        }
+
+        if let Some(guard) = self.queue.prepare_read() {
+            match guard.read_events() {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(0),
+                Err(e) => return Err(e),
+            }
+        }
+        // we just read the events, no need to wake up for them
+        self.readiness.generation = self.readiness.source.notify_read();
+
+        self.queue.dispatch_pending_with_data(data, fallback)
+    }
+
+    /// Flush pending requests to the server
+    ///
+    /// If the socket is full, waits until it becomes writable again.
+    pub async fn flush(&self) -> io::Result<()> {
+        loop {
+            match self.queue.display().flush() {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.readiness.source.fd.writable().await?
+                }
+                ret => return ret,
+            }
+        }
+    }
+
+    /// Dispatches events, waiting for some if necessary
+    ///
+    /// Flushes the pending requests and dispatches the events of this queue. If there were
+    /// none, waits until some are received.
+    ///
+    /// The `data` and `fallback` arguments are the same as for `EventQueue::dispatch()`.
+    ///
+    /// If an error is returned, your connection with the wayland compositor is probably lost.
+    /// You may want to check `Display::protocol_error()` to see if it was caused by a protocol error.
+    pub async fn dispatch<T: std::any::Any, F>(
+        &mut self,
+        data: &mut T,
+        fallback: F,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        self.dispatch_with_data(DispatchData::wrap(data), fallback).await
+    }
+
+    /// Same as `dispatch()`, with the callbacks accessing the content of a `DispatchData`
+    pub async fn dispatch_with_data<F>(
+        &mut self,
+        mut data: DispatchData<'_>,
+        mut fallback: F,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        loop {
+            let dispatched = self.dispatch_pending_with_data(data.reborrow(), &mut fallback)?;
+            if dispatched > 0 {
+                return Ok(dispatched);
+            }
+            self.flush().await?;
+            self.readiness.next_ready().await?;
+        }
+    }
+
+    /// Dispatches events until a future resolves
+    ///
+    /// This is meant to await the futures resolved by the events of this queue, such as
+    /// `NextEvent` or `CallbackFuture`, which need the queue to be dispatched to make progress.
+    ///
+    /// The `data` and `fallback` arguments are the same as for `EventQueue::dispatch()`.
+    ///
+    /// If an error is returned, your connection with the wayland compositor is probably lost.
+    /// You may want to check `Display::protocol_error()` to see if it was caused by a protocol error.
+    pub async fn dispatch_until<Fut, T, F>(
+        &mut self,
+        fut: Fut,
+        data: &mut T,
+        fallback: F,
+    ) -> io::Result<Fut::Output>
+    where
+        Fut: Future,
+        T: std::any::Any,
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        self.dispatch_until_with_data(fut, DispatchData::wrap(data), fallback).await
+    }
+
+    /// Same as `dispatch_until()`, with the callbacks accessing the content of a `DispatchData`
+    pub async fn dispatch_until_with_data<Fut, F>(
+        &mut self,
+        fut: Fut,
+        mut data: DispatchData<'_>,
+        mut fallback: F,
+    ) -> io::Result<Fut::Output>
+    where
+        Fut: Future,
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        let mut fut = Box::pin(fut);
+        loop {
+            let mut dispatch = Box::pin(self.dispatch_with_data(data.reborrow(), &mut fallback));
+            let race = Race { fut: fut.as_mut(), dispatch: dispatch.as_mut() };
+            if let Some(output) = race.await? {
+                return Ok(output);
+            }
+        }
+    }
+
+    /// Asynchronous roundtrip
+    ///
+    /// Sends a `wl_display.sync` request on this queue and dispatches events until the server
+    /// has answered it, meaning that it has processed all the requests previously sent.
+    ///
+    /// The `data` and `fallback` arguments are the same as for `EventQueue::sync_roundtrip()`.
+    ///
+    /// On success returns the number of dispatched events.
+    /// If an error is returned, your connection with the wayland compositor is probably lost.
+    /// You may want to check `Display::protocol_error()` to see if it was caused by a protocol error.
+    pub async fn sync_roundtrip<T: std::any::Any, F>(
+        &mut self,
+        data: &mut T,
+        fallback: F,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        self.sync_roundtrip_with_data(DispatchData::wrap(data), fallback).await
+    }
+
+    /// Same as `sync_roundtrip()`, with the callbacks accessing the content of a `DispatchData`
+    pub async fn sync_roundtrip_with_data<F>(
+        &mut self,
+        mut data: DispatchData<'_>,
+        mut fallback: F,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        let display: Attached<WlDisplay> =
+            (**self.queue.display()).clone().attach(self.queue.token());
+        let done = Rc::new(Cell::new(false));
+        let done2 = done.clone();
+        display.sync().quick_assign(move |_, _, _| done2.set(true));
+
+        let mut dispatched = 0;
+        while !done.get() {
+            dispatched += self.dispatch_with_data(data.reborrow(), &mut fallback).await?;
+        }
+        Ok(dispatched)
+    }
+}
@@ -9,7 +9,7 @@ use std::sync::Arc;
 
 use nix::fcntl;
 
-use crate::{EventQueue, Proxy};
+use crate::{BufferCapacity, EventQueue, MessageRecorder, ProtocolLogger, Proxy, QueueId};
 
 use crate::imp::DisplayInner;
 
@@ -88,6 +88,32 @@ impl ::std::fmt::Display for ProtocolError {
     }
 }
 
+/// Information about a protocol object of a connection
+///
+/// See `Display::objects()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectInfo {
+    /// The protocol id of the object
+    pub id: u32,
+    /// The interface of the object
+    pub interface: &'static str,
+    /// The version of the object
+    pub version: u32,
+    /// Whether the object is still alive, and can be used to send requests
+    pub alive: bool,
+    /// Whether the object was destroyed by a request of the client
+    ///
+    /// Such objects remain known until the server acknowledges their destruction.
+    pub client_destroyed: bool,
+    /// Whether the object was destroyed by an event of the server
+    pub server_destroyed: bool,
+    /// The event queue this object is attached to
+    ///
+    /// This can be compared with `EventQueue::id()`. It is always `None` with
+    /// the `use_system_lib` feature, as `libwayland-client` does not expose it.
+    pub queue: Option<QueueId>,
+}
+
 /// A connection to a wayland server
 ///
 /// This object both represent the connection to the server and contains the
@@ -102,6 +128,8 @@ impl ::std::fmt::Display for ProtocolError {
 #[derive(Clone)]
 pub struct Display {
     pub(crate) inner: Arc<DisplayInner>,
+    #[cfg(feature = "async")]
+    pub(crate) async_source: crate::async_queue::SourceSlot,
 }
 
 impl Display {
@@ -164,6 +192,15 @@ impl Display {
         unsafe { Display::from_fd(socket.into_raw_fd()) }
     }
 
+    /// Start a wayland connection on an already connected unix socket
+    ///
+    /// On success, you are given the `Display` object. This is the safe counterpart of
+    /// `from_fd()`, for example to connect to a server running in the same process through one
+    /// end of a `UnixStream::pair()`.
+    pub fn from_stream(stream: UnixStream) -> Result<Display, ConnectError> {
+        unsafe { Display::from_fd(stream.into_raw_fd()) }
+    }
+
     /// Attempt to use an already connected unix socket on given FD to start a wayland connection
     ///
     /// On success, you are given the `Display` object.
@@ -174,7 +211,11 @@ impl Display {
     ///
     /// The file descriptor must be associated to a connected unix socket.
     pub unsafe fn from_fd(fd: RawFd) -> Result<Display, ConnectError> {
-        Ok(Display { inner: DisplayInner::from_fd(fd)? })
+        Ok(Display {
+            inner: DisplayInner::from_fd(fd)?,
+            #[cfg(feature = "async")]
+            async_source: Default::default(),
+        })
     }
 
     /// Non-blocking write to the server
@@ -218,6 +259,65 @@ impl Display {
         self.inner.get_connection_fd()
     }
 
+    /// Set the protocol logger of this connection
+    ///
+    /// The logger will be invoked for every request sent and every event dispatched
+    /// on this connection, replacing any previously set logger. Pass `None` to remove
+    /// the current logger.
+    ///
+    /// If the `WAYLAND_DEBUG` env variable is set to `1` or `client`, a logger printing
+    /// the messages to stderr is initially installed.
+    ///
+    /// With the `use_system_lib` feature, `libwayland-client` has no hook for protocol
+    /// messages, so the logger only sees the messages of the objects managed by this crate,
+    /// not those of objects created by other C libraries sharing the connection. The
+    /// `WAYLAND_DEBUG` output is then printed by `libwayland-client` in its own format, and is
+    /// not affected by this method.
+    pub fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.inner.set_protocol_logger(logger)
+    }
+
+    /// Set the recorder of this connection
+    ///
+    /// Once set, the recorder is notified of every message sent or received on this
+    /// connection, for example to store the session with a `CaptureWriter` from the
+    /// `wayland_commons::capture` module. Pass `None` to remove the current recorder.
+    ///
+    /// Returns the previous recorder, if any.
+    ///
+    /// With the `use_system_lib` feature, the connection is managed by `libwayland-client`
+    /// and cannot be recorded: this method does nothing and returns `None`.
+    pub fn set_recorder(
+        &self,
+        recorder: Option<Box<dyn MessageRecorder>>,
+    ) -> Option<Box<dyn MessageRecorder>> {
+        self.inner.set_recorder(recorder)
+    }
+
+    /// Get a snapshot of the protocol objects of this connection
+    ///
+    /// This is meant for debugging purposes, the returned list is sorted by id and
+    /// is not updated as objects are created or destroyed.
+    ///
+    /// With the `use_system_lib` feature, only the objects created through this
+    /// library are listed, alongside the display itself.
+    pub fn objects(&self) -> Vec<ObjectInfo> {
+        self.inner.objects()
+    }
+
+    /// Set the buffer size policy of this connection
+    ///
+    /// By default, the outgoing buffer holds 4 KiB of requests, and sending more
+    /// requests while the server is not reading them fails. Allowing the buffer to
+    /// grow lets the client keep sending requests (or send larger ones) until the
+    /// server catches up.
+    ///
+    /// With the `use_system_lib` feature, the buffers are managed by `libwayland-client`
+    /// and this method does nothing.
+    pub fn set_buffer_capacity(&self, capacity: BufferCapacity) {
+        self.inner.set_buffer_capacity(capacity)
+    }
+
     #[cfg(feature = "use_system_lib")]
     /// Create a Display and from an external display
     ///
@@ -231,7 +331,11 @@ impl Display {
     ///
     /// The provided pointer must point to a valid `wl_display` from `libwayland-client`
     pub unsafe fn from_external_display(display_ptr: *mut wl_display) -> Display {
-        Display { inner: DisplayInner::from_external(display_ptr) }
+        Display {
+            inner: DisplayInner::from_external(display_ptr),
+            #[cfg(feature = "async")]
+            async_source: Default::default(),
+        }
     }
 
     #[cfg(feature = "use_system_lib")]
@@ -0,0 +1,112 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use crate::protocol::wl_callback::{self, WlCallback};
+use crate::protocol::wl_surface::WlSurface;
+use crate::{Display, Interface, Main, MessageGroup, Proxy, ProxyMap, QueueToken};
+
+struct Slot<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// A future resolving to the next event received by an object
+///
+/// It is obtained with the `Main::next_event()` method. The event is received when the
+/// event queue of the object is dispatched, so this future can only resolve if this queue is
+/// dispatched while it is pending (for example by `AsyncEventQueue::dispatch_until()`).
+///
+/// Only the next event is given to this future, the following ones are dispatched to the
+/// filter the object was assigned to before. If the object is destroyed before receiving
+/// any event, this future will never resolve.
+pub struct NextEvent<I: Interface> {
+    slot: Rc<RefCell<Slot<I::Event>>>,
+}
+
+impl<I: Interface> NextEvent<I> {
+    pub(crate) fn new(main: &Main<I>) -> NextEvent<I>
+    where
+        I: AsRef<Proxy<I>> + From<Proxy<I>> + Sync,
+        I::Event: MessageGroup<Map = ProxyMap>,
+    {
+        let slot = Rc::new(RefCell::new(Slot { value: None, waker: None }));
+        let filter_slot = slot.clone();
+        main.as_ref().inner.assign_next_event::<I, _>(move |event| {
+            let mut slot = filter_slot.borrow_mut();
+            slot.value = Some(event);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        });
+        NextEvent { slot }
+    }
+}
+
+impl<I: Interface> Future for NextEvent<I> {
+    type Output = I::Event;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<I::Event> {
+        let mut slot = self.slot.borrow_mut();
+        match slot.value.take() {
+            Some(event) => Poll::Ready(event),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A future resolving when a `wl_callback` is done
+///
+/// It resolves to the `callback_data` of the `done` event, whose meaning depends on the
+/// request that created the callback: the serial of the event for `wl_display.sync`, the
+/// current time in milliseconds for `wl_surface.frame`.
+///
+/// As for `NextEvent`, the event queue of the callback must be dispatched for this future
+/// to resolve.
+pub struct CallbackFuture {
+    inner: NextEvent<WlCallback>,
+}
+
+impl CallbackFuture {
+    /// Wait for the `done` event of a callback
+    pub fn new(callback: &Main<WlCallback>) -> CallbackFuture {
+        CallbackFuture { inner: NextEvent::new(callback) }
+    }
+}
+
+impl Future for CallbackFuture {
+    type Output = u32;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<u32> {
+        Pin::new(&mut self.inner).poll(cx).map(|event| match event {
+            wl_callback::Event::Done { callback_data } => callback_data,
+        })
+    }
+}
+
+impl Display {
+    /// Send a `wl_display.sync` request and get a future resolving when it is done
+    ///
+    /// The callback is created on the event queue associated with `token`. Once the future
+    /// resolves, the server has processed all the requests previously sent, and the events
+    /// they generated have been dispatched.
+    pub fn sync(&self, token: QueueToken) -> CallbackFuture {
+        CallbackFuture::new(&(**self).clone().attach(token).sync())
+    }
+}
+
+impl WlSurface {
+    /// Send a `wl_surface.frame` request and get a future resolving when it is done
+    ///
+    /// The future resolves to the time of the frame callback, in milliseconds. As for the
+    /// `frame()` request, this must be called on an attached surface and the frame must then
+    /// be committed with `commit()`.
+    pub fn frame_future(&self) -> CallbackFuture {
+        CallbackFuture::new(&self.frame())
+    }
+}
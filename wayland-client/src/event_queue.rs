@@ -1,5 +1,9 @@
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
 use std::{io, rc::Rc};
 
+use nix::poll::{poll, PollFd, PollFlags};
+
 use crate::imp::EventQueueInner;
 use crate::{AnonymousObject, DispatchData, Display, Main, RawEvent};
 
@@ -103,6 +107,12 @@ pub struct EventQueue {
     display: Display,
 }
 
+/// An identifier of an event queue
+///
+/// See `EventQueue::id()` and `ObjectInfo::queue`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct QueueId(pub(crate) usize);
+
 /// A token representing this event queue
 ///
 /// This token can be cloned and is meant to allow easier
@@ -136,7 +146,16 @@ impl EventQueue {
     where
         F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
     {
-        let mut data = DispatchData::wrap(data);
+        self.dispatch_with_data(DispatchData::wrap(data), fallback)
+    }
+
+    /// Same as `dispatch()`, with the callbacks accessing the content of a `DispatchData`
+    ///
+    /// This allows giving several references of different types to the callbacks.
+    pub fn dispatch_with_data<F>(&mut self, mut data: DispatchData, fallback: F) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
         self.inner.dispatch(data.reborrow(), fallback)
     }
 
@@ -160,7 +179,20 @@ impl EventQueue {
     where
         F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
     {
-        let mut data = DispatchData::wrap(data);
+        self.dispatch_pending_with_data(DispatchData::wrap(data), fallback)
+    }
+
+    /// Same as `dispatch_pending()`, with the callbacks accessing the content of a `DispatchData`
+    ///
+    /// This allows giving several references of different types to the callbacks.
+    pub fn dispatch_pending_with_data<F>(
+        &mut self,
+        mut data: DispatchData,
+        fallback: F,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
         self.inner.dispatch_pending(data.reborrow(), fallback)
     }
 
@@ -187,10 +219,76 @@ impl EventQueue {
     where
         F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
     {
-        let mut data = DispatchData::wrap(data);
+        self.sync_roundtrip_with_data(DispatchData::wrap(data), fallback)
+    }
+
+    /// Same as `sync_roundtrip()`, with the callbacks accessing the content of a `DispatchData`
+    ///
+    /// This allows giving several references of different types to the callbacks.
+    pub fn sync_roundtrip_with_data<F>(
+        &mut self,
+        mut data: DispatchData,
+        fallback: F,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
         self.inner.sync_roundtrip(data.reborrow(), fallback)
     }
 
+    /// Dispatches events from the internal buffer, with a timeout
+    ///
+    /// Behaves like `dispatch()`, but if no events were in the internal buffer, will block
+    /// at most for the duration of `timeout` waiting for some events to be read. If none
+    /// arrived by then, returns an error of kind `TimedOut`, and the read intention of this
+    /// queue is cancelled.
+    ///
+    /// The provided `data` will be mutably accessible from all the callbacks, via the
+    /// [`DispatchData`](struct.DispatchData.html) mechanism. If you don't need global data, you
+    /// can just provide a `&mut ()` there.
+    ///
+    /// If any other error is returned, your connection with the wayland compositor is probably lost.
+    /// You may want to check `Display::protocol_error()` to see if it was caused by a protocol error.
+    pub fn dispatch_timeout<T: std::any::Any, F>(
+        &mut self,
+        timeout: Duration,
+        data: &mut T,
+        fallback: F,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        let mut data = DispatchData::wrap(data);
+        let deadline = Instant::now().checked_add(timeout);
+        self.inner.dispatch_timeout(data.reborrow(), fallback, deadline)
+    }
+
+    /// Synchronous roundtrip, with a timeout
+    ///
+    /// Behaves like `sync_roundtrip()`, but if the server has not answered after the duration
+    /// of `timeout`, returns an error of kind `TimedOut`. The events received until then have
+    /// been dispatched, and the read intention of this queue is cancelled.
+    ///
+    /// The provided `data` will be mutably accessible from all the callbacks, via the
+    /// [`DispatchData`](struct.DispatchData.html) mechanism. If you don't need global data, you
+    /// can just provide a `&mut ()` there.
+    ///
+    /// If any other error is returned, your connection with the wayland compositor is probably lost.
+    /// You may want to check `Display::protocol_error()` to see if it was caused by a protocol error.
+    pub fn sync_roundtrip_timeout<T: std::any::Any, F>(
+        &mut self,
+        timeout: Duration,
+        data: &mut T,
+        fallback: F,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        let mut data = DispatchData::wrap(data);
+        let deadline = Instant::now().checked_add(timeout);
+        self.inner.sync_roundtrip_timeout(data.reborrow(), fallback, deadline)
+    }
+
     /// Create a new token associated with this event queue
     ///
     /// See `QueueToken` documentation for its use.
@@ -222,6 +320,11 @@ impl EventQueue {
         }
     }
 
+    /// Get the identifier of this event queue
+    pub fn id(&self) -> QueueId {
+        self.inner.id()
+    }
+
     /// Access the `Display` of the connection
     pub fn display(&self) -> &Display {
         &self.display
@@ -263,3 +366,35 @@ impl Drop for ReadEventsGuard {
         }
     }
 }
+
+// Wait until the socket is ready for `flags`, returning false if `deadline` is reached first
+//
+// The poll is restarted if it is interrupted by a signal.
+pub(crate) fn poll_socket(
+    fd: RawFd,
+    flags: PollFlags,
+    deadline: Option<Instant>,
+) -> io::Result<bool> {
+    loop {
+        match poll(&mut [PollFd::new(fd, flags)], poll_timeout(deadline)) {
+            Ok(ready) => return Ok(ready > 0),
+            Err(::nix::Error::Sys(::nix::errno::Errno::EINTR)) => continue,
+            Err(::nix::Error::Sys(e)) => return Err(e.into()),
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+// Timeout argument of poll() to wait until `deadline`, -1 if there is none
+fn poll_timeout(deadline: Option<Instant>) -> i32 {
+    match deadline {
+        Some(deadline) => {
+            // round up, to not wake up right before the deadline
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let ms = remaining.as_millis() + u128::from(remaining.subsec_nanos() % 1_000_000 != 0);
+            // clamp to the maximum value of an i32
+            ms.min(0x7fff_ffff) as i32
+        }
+        None => -1,
+    }
+}
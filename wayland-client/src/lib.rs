@@ -78,6 +78,26 @@
 //! At the beginning you'll need to create an event queue and assign the initial `Proxy<WlDisplay>`
 //! to it.
 //!
+//! ## Typed dispatching
+//!
+//! As an alternative to assigning a `Filter` to each object, an event queue can be wrapped in a
+//! `TypedEventQueue<State>`. The events of its objects are then given to the `Dispatch<I>`
+//! implementations of the `State` type, with a mutable reference to the state given to the
+//! dispatching methods and the user data given to the object when it was created.
+//!
+//! ## Async integration
+//!
+//! If you activate the `async` cargo feature, an event queue can be wrapped in an
+//! `AsyncEventQueue`, which provides `async` versions of the dispatching methods. They wait
+//! for events using the [`async-io`](https://docs.rs/async-io) reactor, and can thus be used
+//! from any executor.
+//!
+//! ## Calloop integration
+//!
+//! If you activate the `calloop` cargo feature, an event queue can be wrapped in a `QueueSource`,
+//! which can be inserted in a [`calloop`](https://docs.rs/calloop) event loop to dispatch it
+//! whenever events are received.
+//!
 //! ## Dynamic linking with `libwayland-client.so`
 //!
 //! If you need to gracefully handle the case of a system on which Wayland is not installed (by
@@ -98,20 +118,36 @@ extern crate downcast_rs as downcast;
 #[cfg_attr(feature = "use_system_lib", macro_use)]
 extern crate wayland_sys;
 
+#[cfg(feature = "async")]
+mod async_queue;
 mod display;
+mod event_future;
 mod event_queue;
 mod globals;
+#[cfg(feature = "calloop")]
+mod loop_source;
 mod proxy;
+mod typed_queue;
 
 pub use anonymous_object::AnonymousObject;
-pub use display::{ConnectError, Display, ProtocolError};
-pub use event_queue::{EventQueue, QueueToken, ReadEventsGuard};
+#[cfg(feature = "async")]
+pub use async_queue::{AsyncEventQueue, Readiness};
+pub use display::{ConnectError, Display, ObjectInfo, ProtocolError};
+pub use event_future::{CallbackFuture, NextEvent};
+pub use event_queue::{EventQueue, QueueId, QueueToken, ReadEventsGuard};
 pub use globals::{GlobalError, GlobalEvent, GlobalImplementor, GlobalManager};
 pub use imp::ProxyMap;
+#[cfg(feature = "calloop")]
+pub use loop_source::QueueSource;
 pub use proxy::{Attached, Main, Proxy};
+pub use typed_queue::{Dispatch, QueueHandle, TypedEventQueue};
 pub use wayland_commons::{
-    filter::{DispatchData, Filter},
+    capture::MessageRecorder,
+    debug::{LoggedMessage, MessageDirection, ProtocolLogger, StderrLogger},
+    filter::{DispatchData, Filter, Router},
+    socket::BufferCapacity,
     user_data::UserData,
+    wire::OwnedFd,
     Interface, MessageGroup, NoMessage,
 };
 
@@ -140,7 +176,10 @@ pub mod protocol {
     pub(crate) use crate::{AnonymousObject, Attached, Main, Proxy, ProxyMap};
     pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
     pub(crate) use wayland_commons::smallvec;
-    pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+    pub(crate) use wayland_commons::wire::{
+        Argument, ArgumentError, ArgumentRef, ArgumentType, FromRawError, Message, MessageDesc,
+        MessageRef, MessageWriteError, OwnedFd,
+    };
     pub(crate) use wayland_commons::{Interface, MessageGroup};
     pub(crate) use wayland_sys as sys;
     include!(concat!(env!("OUT_DIR"), "/wayland_api.rs"));
@@ -209,8 +248,8 @@ pub enum Argument {
     NewId(Option<Main<AnonymousObject>>),
     /// Vec<u8>
     Array(Option<Vec<u8>>),
-    /// RawFd
-    Fd(std::os::unix::io::RawFd),
+    /// An owned file descriptor
+    Fd(OwnedFd),
 }
 
 /// An generic event
@@ -0,0 +1,110 @@
+use std::io;
+
+use calloop::generic::{Fd, Generic};
+use calloop::{
+    EventSource, InsertError, Interest, LoopHandle, Mode, Poll, Readiness, Source, Token,
+};
+
+use crate::{AnonymousObject, DispatchData, EventQueue, Main, RawEvent};
+
+/// A `calloop` event source for an `EventQueue`
+///
+/// This event source becomes ready when the wayland socket is readable. It then reads the
+/// available events into the buffers of the event queues, and provides its event queue to its
+/// callback, which can dispatch the events. The simplest way to use it is to insert it using
+/// the `insert()` method, whose callback dispatches the queue with the shared data of the event
+/// loop, and then flushes the requests sent by the dispatched callbacks to the server.
+///
+/// Only the events read by this source wake up the event loop: if other event queues of the
+/// same connection are dispatched from other threads, they may read the events of this queue,
+/// which will then only be dispatched the next time this source is ready.
+///
+/// The requests sent outside of the callbacks of this source, for example from other event
+/// sources, are not flushed by it. If you insert it with `LoopHandle::insert_source()`
+/// instead, nothing is flushed for you. In both cases, you need to flush the connection
+/// yourself, for example in the callback given to `EventLoop::run()`.
+///
+/// This type is only available with the `calloop` cargo feature.
+pub struct QueueSource {
+    queue: EventQueue,
+    fd: Generic<Fd>,
+}
+
+impl QueueSource {
+    /// Create an event source for this event queue
+    pub fn new(queue: EventQueue) -> QueueSource {
+        let fd =
+            Generic::from_fd(queue.display().get_connection_fd(), Interest::Readable, Mode::Level);
+        QueueSource { queue, fd }
+    }
+
+    /// Access the event queue of this source
+    pub fn queue(&mut self) -> &mut EventQueue {
+        &mut self.queue
+    }
+
+    /// Insert this source in an event loop
+    ///
+    /// Every time the wayland socket is readable, the events of the queue are dispatched with
+    /// the shared data of the event loop, and the requests they sent are then flushed to the
+    /// server. The `fallback` closure is the same as for `EventQueue::dispatch()`.
+    ///
+    /// Flushing errors are ignored, as a broken connection is reported by the next dispatch of
+    /// this source.
+    pub fn insert<Data: 'static, F>(
+        self,
+        handle: &LoopHandle<Data>,
+        mut fallback: F,
+    ) -> Result<Source<QueueSource>, InsertError<QueueSource>>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>) + 'static,
+    {
+        handle.insert_source(self, move |(), queue, data| {
+            let ret = queue.dispatch_pending(data, &mut fallback).map(|_| ());
+            let _ = queue.display().flush();
+            ret
+        })
+    }
+}
+
+impl EventSource for QueueSource {
+    type Event = ();
+    type Metadata = EventQueue;
+    type Ret = io::Result<()>;
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> io::Result<()>
+    where
+        F: FnMut((), &mut EventQueue) -> io::Result<()>,
+    {
+        let queue = &mut self.queue;
+        self.fd.process_events(readiness, token, |_, _| {
+            // if the queue still has pending events, they are dispatched first
+            // and the remaining ones will be read on the next wakeup
+            if let Some(guard) = queue.prepare_read() {
+                match guard.read_events() {
+                    Ok(()) => {}
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            callback((), queue)
+        })
+    }
+
+    fn register(&mut self, poll: &mut Poll, token: Token) -> io::Result<()> {
+        self.fd.register(poll, token)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token: Token) -> io::Result<()> {
+        self.fd.reregister(poll, token)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> io::Result<()> {
+        self.fd.unregister(poll)
+    }
+}
@@ -1,14 +1,29 @@
 use std::io;
 use std::os::unix::io::RawFd;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use crate::protocol::wl_display::WlDisplay;
 use wayland_sys::client::*;
 
-use crate::{ConnectError, Proxy};
+use crate::{
+    BufferCapacity, ConnectError, Interface, MessageRecorder, ObjectInfo, ProtocolLogger, Proxy,
+};
 
+use super::proxy::ProxyInternal;
 use super::{EventQueueInner, ProxyInner};
 
+unsafe fn object_info(ptr: *mut wl_proxy, interface: &'static str) -> ObjectInfo {
+    ObjectInfo {
+        id: ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_id, ptr),
+        interface,
+        version: ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_version, ptr),
+        alive: true,
+        client_destroyed: false,
+        server_destroyed: false,
+        queue: None,
+    }
+}
+
 pub(crate) struct DisplayInner {
     proxy: Proxy<WlDisplay>,
     display: Arc<DisplayGuard>,
@@ -17,6 +32,36 @@ pub(crate) struct DisplayInner {
 pub(crate) struct DisplayGuard {
     ptr: *mut wl_display,
     external: bool,
+    logger: Mutex<Option<Arc<dyn ProtocolLogger>>>,
+    // libwayland-client does not allow listing the proxies of a connection,
+    // so we keep track of the ones created through this library
+    proxies: Mutex<Vec<(ObjectInfo, Arc<ProxyInternal>)>>,
+}
+
+impl DisplayGuard {
+    fn new(ptr: *mut wl_display, external: bool) -> DisplayGuard {
+        DisplayGuard { ptr, external, logger: Mutex::new(None), proxies: Mutex::new(Vec::new()) }
+    }
+
+    pub(crate) fn logger(&self) -> Option<Arc<dyn ProtocolLogger>> {
+        self.logger.lock().unwrap().clone()
+    }
+
+    pub(crate) unsafe fn track_proxy(
+        &self,
+        ptr: *mut wl_proxy,
+        interface: &'static str,
+        internal: Arc<ProxyInternal>,
+    ) {
+        let mut proxies = self.proxies.lock().unwrap();
+        // the proxies destroyed without knowing their display are only pruned here
+        proxies.retain(|(_, internal)| internal.is_alive());
+        proxies.push((object_info(ptr, interface), internal));
+    }
+
+    pub(crate) fn untrack_proxy(&self, internal: &Arc<ProxyInternal>) {
+        self.proxies.lock().unwrap().retain(|(_, tracked)| !Arc::ptr_eq(tracked, internal));
+    }
 }
 
 unsafe impl Send for DisplayInner {}
@@ -29,7 +74,7 @@ unsafe fn make_display(ptr: *mut wl_display) -> Result<Arc<DisplayInner>, Connec
 
     let mut inner = DisplayInner {
         proxy: Proxy::from_c_ptr(ptr as *mut _),
-        display: Arc::new(DisplayGuard { ptr, external: false }),
+        display: Arc::new(DisplayGuard::new(ptr, false)),
     };
 
     inner.proxy.inner.display = Some(Arc::downgrade(&inner.display));
@@ -56,6 +101,51 @@ impl DisplayInner {
         self.display.ptr
     }
 
+    pub(crate) fn guard(&self) -> &Arc<DisplayGuard> {
+        &self.display
+    }
+
+    pub(crate) fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        // libwayland-client does not provide a logging hook for protocol messages,
+        // they are reported from our dispatching and sending logic instead
+        *self.display.logger.lock().unwrap() = logger;
+    }
+
+    pub(crate) fn set_recorder(
+        &self,
+        _recorder: Option<Box<dyn MessageRecorder>>,
+    ) -> Option<Box<dyn MessageRecorder>> {
+        // libwayland-client does not give access to the messages of the connection
+        None
+    }
+
+    pub(crate) fn objects(&self) -> Vec<ObjectInfo> {
+        let proxies = self.display.proxies.lock().unwrap();
+        let mut objects = Vec::with_capacity(proxies.len() + 1);
+        objects.extend(
+            proxies
+                .iter()
+                .filter(|(_, internal)| internal.is_alive())
+                .map(|(info, _)| info.clone()),
+        );
+        // wl_proxy_get_version() returns 0 for the display, which always has version 1
+        objects.push(ObjectInfo {
+            id: 1,
+            interface: WlDisplay::NAME,
+            version: 1,
+            alive: true,
+            client_destroyed: false,
+            server_destroyed: false,
+            queue: None,
+        });
+        objects.sort_by_key(|object| object.id);
+        objects
+    }
+
+    pub(crate) fn set_buffer_capacity(&self, _capacity: BufferCapacity) {
+        // libwayland-client manages the size of its buffers itself
+    }
+
     pub(crate) fn flush(&self) -> io::Result<()> {
         let ret = unsafe { ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_flush, self.ptr()) };
         if ret >= 0 {
@@ -78,34 +168,39 @@ impl DisplayInner {
 
     pub(crate) fn protocol_error(&self) -> Option<crate::ProtocolError> {
         let ret = unsafe { ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_get_error, self.ptr()) };
-        if ret == ::nix::errno::Errno::EPROTO as i32 {
-            let mut interface = ::std::ptr::null_mut();
-            let mut id = 0;
-            let code = unsafe {
-                ffi_dispatch!(
-                    WAYLAND_CLIENT_HANDLE,
-                    wl_display_get_protocol_error,
-                    self.ptr(),
-                    &mut interface,
-                    &mut id
-                )
-            };
-            let interface_name = unsafe { ::std::ffi::CStr::from_ptr((*interface).name) };
-            Some(crate::ProtocolError {
-                code,
-                object_id: id,
-                object_interface: interface_name.to_str().unwrap_or("<unknown>"),
-                message: String::new(),
-            })
-        } else {
-            None
+        if ret == 0 {
+            return None;
+        }
+        // The errors of wl_display itself are reported with their own errno rather than
+        // EPROTO (ENOMEM for no_memory for example), the interface of the object is only set
+        // for protocol errors.
+        let mut interface = ::std::ptr::null_mut();
+        let mut id = 0;
+        let code = unsafe {
+            ffi_dispatch!(
+                WAYLAND_CLIENT_HANDLE,
+                wl_display_get_protocol_error,
+                self.ptr(),
+                &mut interface,
+                &mut id
+            )
+        };
+        if interface.is_null() {
+            return None;
         }
+        let interface_name = unsafe { ::std::ffi::CStr::from_ptr((*interface).name) };
+        Some(crate::ProtocolError {
+            code,
+            object_id: id,
+            object_interface: interface_name.to_str().unwrap_or("<unknown>"),
+            message: String::new(),
+        })
     }
 
     pub(crate) unsafe fn from_external(display_ptr: *mut wl_display) -> Arc<DisplayInner> {
         Arc::new(DisplayInner {
             proxy: Proxy::wrap(ProxyInner::from_external_display(display_ptr as *mut _)),
-            display: Arc::new(DisplayGuard { ptr: display_ptr, external: true }),
+            display: Arc::new(DisplayGuard::new(display_ptr, true)),
         })
     }
 }
@@ -1,18 +1,33 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::io;
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Instant;
 
-use crate::{AnonymousObject, DispatchData, Main, RawEvent};
+use nix::poll::PollFlags;
+
+use crate::event_queue::poll_socket;
+use crate::{AnonymousObject, DispatchData, Filter, Main, QueueId, RawEvent};
 use wayland_sys::client::*;
 
+use super::display::DisplayGuard;
 use super::DisplayInner;
 
 scoped_tls::scoped_thread_local! {
     pub(crate) static DISPATCH_METADATA: RefCell<(&mut dyn FnMut(RawEvent, Main<AnonymousObject>, DispatchData), DispatchData)>
 }
 
+scoped_tls::scoped_thread_local! {
+    pub(crate) static DISPATCH_DISPLAY: Arc<DisplayGuard>
+}
+
 #[allow(clippy::transmute_ptr_to_ptr)]
-fn with_dispatch_meta<T, FB, F>(mut fb: FB, data: DispatchData, f: F) -> T
+fn with_dispatch_meta<T, FB, F>(
+    mut fb: FB,
+    data: DispatchData,
+    display: &Arc<DisplayGuard>,
+    f: F,
+) -> T
 where
     FB: FnMut(RawEvent, Main<AnonymousObject>, DispatchData),
     F: FnOnce() -> T,
@@ -21,7 +36,7 @@ where
     // it's safe as it'll only last until the end of this function call anyway
     let fb = unsafe { std::mem::transmute(&mut fb as &mut dyn FnMut(_, _, _)) };
     let data = unsafe { std::mem::transmute(data) };
-    DISPATCH_METADATA.set(&RefCell::new((fb, data)), || f())
+    DISPATCH_DISPLAY.set(display, || DISPATCH_METADATA.set(&RefCell::new((fb, data)), || f()))
 }
 
 pub(crate) struct EventQueueInner {
@@ -38,7 +53,7 @@ impl EventQueueInner {
     where
         F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
     {
-        with_dispatch_meta(fallback, data, || {
+        with_dispatch_meta(fallback, data, self.inner.guard(), || {
             let ret = unsafe {
                 ffi_dispatch!(
                     WAYLAND_CLIENT_HANDLE,
@@ -59,7 +74,7 @@ impl EventQueueInner {
     where
         F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
     {
-        with_dispatch_meta(fallback, data, || {
+        with_dispatch_meta(fallback, data, self.inner.guard(), || {
             let ret = unsafe {
                 ffi_dispatch!(
                     WAYLAND_CLIENT_HANDLE,
@@ -76,11 +91,70 @@ impl EventQueueInner {
         })
     }
 
+    pub(crate) fn dispatch_timeout<F>(
+        &self,
+        mut data: DispatchData,
+        mut fallback: F,
+        deadline: Option<Instant>,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        // this mirrors wl_display_dispatch_queue(), with a timeout on the polls
+
+        // don't read events if there are some pending
+        if let Err(()) = self.prepare_read() {
+            return self.dispatch_pending(data, fallback);
+        }
+
+        let socket_fd = self.inner.get_connection_fd();
+        loop {
+            match self.inner.flush() {
+                Ok(()) => break,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    match poll_socket(socket_fd, PollFlags::POLLOUT, deadline) {
+                        Ok(true) => continue,
+                        Ok(false) => {
+                            self.cancel_read();
+                            return Err(io::ErrorKind::TimedOut.into());
+                        }
+                        Err(e) => {
+                            self.cancel_read();
+                            return Err(e);
+                        }
+                    }
+                }
+                // don't abort on EPIPE, so we can continue reading
+                // to get the protocol error
+                Err(ref e) if e.raw_os_error() == Some(libc::EPIPE) => break,
+                Err(e) => {
+                    self.cancel_read();
+                    return Err(e);
+                }
+            }
+        }
+
+        match poll_socket(socket_fd, PollFlags::POLLIN, deadline) {
+            Ok(true) => (),
+            Ok(false) => {
+                self.cancel_read();
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            Err(e) => {
+                self.cancel_read();
+                return Err(e);
+            }
+        }
+        self.read_events()?;
+
+        self.dispatch_pending(data.reborrow(), &mut fallback)
+    }
+
     pub(crate) fn sync_roundtrip<F>(&self, data: DispatchData, fallback: F) -> io::Result<u32>
     where
         F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
     {
-        with_dispatch_meta(fallback, data, || {
+        with_dispatch_meta(fallback, data, self.inner.guard(), || {
             let ret = unsafe {
                 ffi_dispatch!(
                     WAYLAND_CLIENT_HANDLE,
@@ -97,6 +171,38 @@ impl EventQueueInner {
         })
     }
 
+    pub(crate) fn sync_roundtrip_timeout<F>(
+        &self,
+        mut data: DispatchData,
+        mut fallback: F,
+        deadline: Option<Instant>,
+    ) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        use crate::protocol::wl_callback::{Event as CbEvent, WlCallback};
+        use crate::protocol::wl_display::{Request as DRequest, WlDisplay};
+        // first retrieve the display and make a wrapper for it in this event queue
+        let mut display = self.inner.get_proxy().inner.clone();
+        display.attach(self);
+
+        let done = Rc::new(Cell::new(false));
+        let cb = display.send::<WlDisplay, WlCallback>(DRequest::Sync {}, Some(1)).unwrap();
+        let done2 = done.clone();
+        cb.assign::<WlCallback, _>(Filter::new(move |(_, CbEvent::Done { .. }), _, _| {
+            done2.set(true);
+        }));
+
+        let mut dispatched = 0;
+
+        loop {
+            dispatched += self.dispatch_timeout(data.reborrow(), &mut fallback, deadline)?;
+            if done.get() {
+                return Ok(dispatched);
+            }
+        }
+    }
+
     pub(crate) fn prepare_read(&self) -> Result<(), ()> {
         let ret = unsafe {
             ffi_dispatch!(
@@ -128,6 +234,10 @@ impl EventQueueInner {
         unsafe { ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_cancel_read, self.inner.ptr()) }
     }
 
+    pub(crate) fn id(&self) -> QueueId {
+        QueueId(self.wlevq as usize)
+    }
+
     pub(crate) unsafe fn assign_proxy(&self, proxy: *mut wl_proxy) {
         ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_set_queue, proxy, self.wlevq)
     }
@@ -1,12 +1,16 @@
 use std::cell::RefCell;
+use std::ffi::CStr;
 use std::os::raw::{c_int, c_void};
+use std::os::unix::io::FromRawFd;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
 
-use crate::{Interface, Main, Proxy, RawEvent};
+use crate::{Interface, Main, ProtocolLogger, Proxy, RawEvent};
+use wayland_commons::debug::{LoggedMessage, MessageDirection};
 use wayland_commons::filter::Filter;
 use wayland_commons::user_data::UserData;
-use wayland_commons::wire::ArgumentType;
+use wayland_commons::wire::{ArgumentRef, ArgumentType, FromRawError, OwnedFd};
 use wayland_commons::MessageGroup;
 
 use super::EventQueueInner;
@@ -23,6 +27,10 @@ impl ProxyInternal {
     pub fn new(user_data: UserData) -> ProxyInternal {
         ProxyInternal { alive: AtomicBool::new(true), user_data }
     }
+
+    pub(crate) fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::Acquire)
+    }
 }
 
 pub(crate) struct ProxyInner {
@@ -110,6 +118,9 @@ impl ProxyInner {
 
         let alive = self.is_alive();
 
+        let logger = self.display.as_ref().and_then(Weak::upgrade).and_then(|d| d.logger());
+        let c_message = unsafe { &*(*I::c_interface()).requests.add(opcode as usize) };
+
         let ret = if let Some(mut nid_idx) = nid_idx {
             if let Some(o) = I::Request::child(opcode, 1, &()) {
                 if !o.is_interface::<J>() {
@@ -133,7 +144,7 @@ impl ProxyInner {
                             args[nid_idx].o.is_null(),
                             "Trying to use 'send_constructor' with a non-placeholder object."
                         );
-                        ffi_dispatch!(
+                        let ptr = ffi_dispatch!(
                             WAYLAND_CLIENT_HANDLE,
                             wl_proxy_marshal_array_constructor_versioned,
                             self.wrapping.unwrap_or(self.ptr),
@@ -141,10 +152,32 @@ impl ProxyInner {
                             args.as_mut_ptr(),
                             J::c_interface(),
                             version
-                        )
+                        );
+                        if let Some(ref logger) = logger {
+                            // report the newly created object rather than the placeholder
+                            args[nid_idx].o = ptr as *const _;
+                            log_c_message(
+                                &**logger,
+                                MessageDirection::Sent,
+                                I::NAME,
+                                self.ptr,
+                                c_message,
+                                args.as_ptr(),
+                            );
+                        }
+                        ptr
                     });
                     let mut new_proxy = ProxyInner::init_from_c_ptr::<J>(ptr);
                     new_proxy.display = self.display.clone();
+                    if let Some(display) = self.display.as_ref().and_then(Weak::upgrade) {
+                        // proxies created while dispatching are already tracked
+                        let tracked = super::event_queue::DISPATCH_DISPLAY.is_set()
+                            && super::event_queue::DISPATCH_DISPLAY
+                                .with(|dispatching| Arc::ptr_eq(dispatching, &display));
+                        if !tracked {
+                            display.track_proxy(ptr, J::NAME, new_proxy.internal.clone().unwrap());
+                        }
+                    }
                     Some(new_proxy)
                 }
             } else {
@@ -162,6 +195,16 @@ impl ProxyInner {
                         opcode,
                         args.as_ptr() as *mut _
                     );
+                    if let Some(ref logger) = logger {
+                        log_c_message(
+                            &**logger,
+                            MessageDirection::Sent,
+                            I::NAME,
+                            self.ptr,
+                            c_message,
+                            args.as_ptr(),
+                        );
+                    }
                 });
             }
             None
@@ -171,6 +214,9 @@ impl ProxyInner {
             // we need to destroy the proxy now
             if let Some(ref internal) = self.internal {
                 internal.alive.store(false, Ordering::Release);
+                if let Some(display) = self.display.as_ref().and_then(Weak::upgrade) {
+                    display.untrack_proxy(internal);
+                }
             }
             unsafe {
                 ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, self.ptr);
@@ -242,14 +288,47 @@ impl ProxyInner {
             let user_data = ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_user_data, self.ptr)
                 as *mut ProxyUserData<I>;
             if let Ok(ref mut guard) = (*user_data).implem.try_borrow_mut() {
-                **guard =
-                    Some(Box::new(move |evt, obj, data| filter.send((obj, evt).into(), data)));
+                **guard = Some(Rc::new(move |evt, obj, data| filter.send((obj, evt).into(), data)));
             } else {
                 panic!("Re-assigning an object from within its own callback is not supported.");
             }
         }
     }
 
+    pub(crate) fn assign_next_event<I, F>(&self, f: F)
+    where
+        I: Interface + AsRef<Proxy<I>> + From<Proxy<I>> + Sync,
+        F: FnOnce(I::Event) + 'static,
+        I::Event: MessageGroup<Map = super::ProxyMap>,
+    {
+        if self.is_external() {
+            panic!("Cannot assign an external proxy to a filter.");
+        }
+
+        if !self.is_alive() {
+            return;
+        }
+
+        unsafe {
+            let user_data = ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_user_data, self.ptr)
+                as *mut ProxyUserData<I>;
+            let mut implem = (*user_data).implem.borrow_mut();
+            let next = RefCell::new(Some((f, implem.take())));
+            *implem = Some(Rc::new(move |evt, proxy: Main<I>, _| {
+                if let Some((f, previous)) = next.borrow_mut().take() {
+                    // the following events are dispatched to the previous filter
+                    let user_data = ffi_dispatch!(
+                        WAYLAND_CLIENT_HANDLE,
+                        wl_proxy_get_user_data,
+                        proxy.as_ref().c_ptr()
+                    ) as *mut ProxyUserData<I>;
+                    *(*user_data).implem.borrow_mut() = previous;
+                    f(evt);
+                }
+            }));
+        }
+    }
+
     pub(crate) unsafe fn init_from_c_ptr<I: Interface + From<Proxy<I>> + AsRef<Proxy<I>>>(
         ptr: *mut wl_proxy,
     ) -> Self {
@@ -265,8 +344,18 @@ impl ProxyInner {
             Box::into_raw(new_user_data) as *mut _
         );
 
+        let display = if super::event_queue::DISPATCH_DISPLAY.is_set() {
+            // the proxy is created while dispatching events of this display
+            super::event_queue::DISPATCH_DISPLAY.with(|display| {
+                display.track_proxy(ptr, I::NAME, internal.clone());
+                Some(Arc::downgrade(display))
+            })
+        } else {
+            None
+        };
+
         // We are a Main<_>, so ptr == wrapping
-        ProxyInner { internal: Some(internal), ptr, wrapping: Some(ptr), display: None }
+        ProxyInner { internal: Some(internal), ptr, wrapping: Some(ptr), display }
     }
 
     fn dead() -> Self {
@@ -343,7 +432,7 @@ impl Drop for ProxyInner {
     }
 }
 
-type BoxedCallback<I> = Box<dyn Fn(<I as Interface>::Event, Main<I>, crate::DispatchData<'_>)>;
+type BoxedCallback<I> = Rc<dyn Fn(<I as Interface>::Event, Main<I>, crate::DispatchData<'_>)>;
 
 struct ProxyUserData<I: Interface + From<Proxy<I>> + AsRef<Proxy<I>>> {
     internal: Arc<ProxyInternal>,
@@ -363,7 +452,7 @@ unsafe extern "C" fn proxy_dispatcher<I: Interface>(
     _implem: *const c_void,
     proxy: *mut c_void,
     opcode: u32,
-    _msg: *const wl_message,
+    msg: *const wl_message,
     args: *const wl_argument,
 ) -> c_int
 where
@@ -375,14 +464,27 @@ where
     // we'll abort the process, so no access to corrupted data is possible.
     let ret = ::std::panic::catch_unwind(move || {
         let must_destroy = I::Event::MESSAGES[opcode as usize].destructor;
+        // the display this event is dispatched from
+        let display = if super::event_queue::DISPATCH_DISPLAY.is_set() {
+            super::event_queue::DISPATCH_DISPLAY.with(|display| Some(display.clone()))
+        } else {
+            None
+        };
+        if let Some(logger) = display.as_ref().and_then(|d| d.logger()) {
+            log_c_message(&*logger, MessageDirection::Received, I::NAME, proxy, msg, args);
+        }
         // retrieve the impl
         let user_data = ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_user_data, proxy);
         {
             let user_data = &mut *(user_data as *mut ProxyUserData<I>);
-            let implem = user_data.implem.borrow();
+            // the implementation is not borrowed while it runs, as it can replace itself
+            let implem = user_data.implem.borrow().clone();
 
             if must_destroy {
                 user_data.internal.alive.store(false, Ordering::Release);
+                if let Some(ref display) = display {
+                    display.untrack_proxy(&user_data.internal);
+                }
                 ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_destroy, proxy);
             }
             // if there is an implem, call it, otherwise call the fallback
@@ -394,6 +496,7 @@ where
                     let mut proxy_inner = ProxyInner::from_c_ptr::<I>(proxy);
                     // This proxy must be a Main, so it as attached wrapping itself
                     proxy_inner.wrapping = Some(proxy_inner.ptr);
+                    proxy_inner.display = display.as_ref().map(Arc::downgrade);
                     let proxy_obj = crate::Main::wrap(proxy_inner);
                     super::event_queue::DISPATCH_METADATA.with(|meta| {
                         let mut meta = meta.borrow_mut();
@@ -405,7 +508,9 @@ where
                     // parse the message:
                     let msg = parse_raw_event::<I>(opcode, args);
                     // create the proxy object
-                    let proxy_obj = crate::Main::wrap(ProxyInner::from_c_ptr::<I>(proxy));
+                    let mut proxy_inner = ProxyInner::from_c_ptr::<I>(proxy);
+                    proxy_inner.display = display.as_ref().map(Arc::downgrade);
+                    let proxy_obj = crate::Main::wrap(proxy_inner);
                     super::event_queue::DISPATCH_METADATA.with(|meta| {
                         let mut meta = meta.borrow_mut();
                         let (ref mut fallback, ref mut dispatch_data) = *meta;
@@ -418,16 +523,17 @@ where
             // final cleanup
             let _ = Box::from_raw(user_data as *mut ProxyUserData<I>);
         }
-        Ok(())
+        Ok::<(), FromRawError>(())
     });
     // check the return status
     match ret {
         Ok(Ok(())) => 0,
-        Ok(Err(())) => {
+        Ok(Err(e)) => {
             eprintln!(
-                "[wayland-client error] Attempted to dispatch unknown opcode {} for {}, aborting.",
+                "[wayland-client error] Attempted to dispatch invalid event {} for {}: {}, aborting.",
                 opcode,
-                I::NAME
+                I::NAME,
+                e
             );
             libc::abort();
         }
@@ -438,6 +544,55 @@ where
     }
 }
 
+/// Report a message to a protocol logger, parsing its arguments from their C representation
+unsafe fn log_c_message(
+    logger: &dyn ProtocolLogger,
+    direction: MessageDirection,
+    interface: &str,
+    proxy: *mut wl_proxy,
+    message: *const wl_message,
+    args: *const wl_argument,
+) {
+    let id = ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_id, proxy);
+    let name = CStr::from_ptr((*message).name).to_string_lossy();
+    let signature = CStr::from_ptr((*message).signature).to_bytes();
+    let proxy_id = |ptr: *const c_void| {
+        if ptr.is_null() {
+            0
+        } else {
+            ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_id, ptr as *mut wl_proxy)
+        }
+    };
+    // skip the version and nullability markers of the signature
+    let types = signature.iter().filter(|&&c| c != b'?' && !c.is_ascii_digit());
+    let args = types
+        .enumerate()
+        .map(|(i, &c)| {
+            let arg = &*args.add(i);
+            match c {
+                b'i' => ArgumentRef::Int(arg.i),
+                b'u' => ArgumentRef::Uint(arg.u),
+                b'f' => ArgumentRef::Fixed(arg.f),
+                b's' if arg.s.is_null() => ArgumentRef::Str(Default::default()),
+                b's' => ArgumentRef::Str(CStr::from_ptr(arg.s)),
+                b'o' => ArgumentRef::Object(proxy_id(arg.o)),
+                b'n' => ArgumentRef::NewId(proxy_id(arg.o)),
+                b'a' if arg.a.is_null() => ArgumentRef::Array(&[]),
+                b'a' => {
+                    let array = &*arg.a;
+                    ArgumentRef::Array(std::slice::from_raw_parts(
+                        array.data as *const u8,
+                        array.size,
+                    ))
+                }
+                // the fds remain owned by libwayland, they are only borrowed for logging
+                _ => ArgumentRef::Fd(arg.h),
+            }
+        })
+        .collect::<Vec<_>>();
+    logger.log(&LoggedMessage::now(direction, interface, id, true, &name, &args));
+}
+
 unsafe fn parse_raw_event<I: Interface>(opcode: u32, args: *const wl_argument) -> RawEvent {
     let desc = &I::Event::MESSAGES[opcode as usize];
 
@@ -470,7 +625,7 @@ unsafe fn parse_raw_event<I: Interface>(opcode: u32, args: *const wl_argument) -
                     ))
                 }
             }
-            ArgumentType::Fd => crate::Argument::Fd(a.h),
+            ArgumentType::Fd => crate::Argument::Fd(OwnedFd::from_raw_fd(a.h)),
             ArgumentType::Object => {
                 if a.o.is_null() {
                     crate::Argument::Object(None)
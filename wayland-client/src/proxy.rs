@@ -7,6 +7,7 @@ use wayland_commons::Interface;
 
 use wayland_sys::client::*;
 
+use crate::event_future::NextEvent;
 use crate::event_queue::QueueToken;
 
 use crate::imp::ProxyInner;
@@ -272,6 +273,20 @@ where
     {
         self.assign(Filter::new(move |(proxy, event), _, data| f(proxy, event, data)))
     }
+
+    /// Get a future resolving to the next event received by this object
+    ///
+    /// The next event received by this object is given to the future rather than to the filter
+    /// of the object, which receives the following events again.
+    ///
+    /// See [`NextEvent`](struct.NextEvent.html) for details.
+    pub fn next_event(&self) -> NextEvent<I>
+    where
+        I: Sync,
+        I::Event: MessageGroup<Map = crate::ProxyMap>,
+    {
+        NextEvent::new(self)
+    }
 }
 
 impl Main<AnonymousObject> {
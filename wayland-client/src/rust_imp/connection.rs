@@ -1,12 +1,13 @@
 use std::cell::RefCell;
 use std::os::unix::io::{FromRawFd, RawFd};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
 
 use nix::Result as NixResult;
 
+use wayland_commons::debug::{self, ProtocolLogger, StderrLogger};
 use wayland_commons::map::{Object, ObjectMap, SERVER_ID_LIMIT};
 use wayland_commons::socket::{BufferedSocket, Socket};
-use wayland_commons::wire::{Argument, ArgumentType, Message, MessageParseError};
+use wayland_commons::wire::{ArgumentRef, ArgumentType, Message, MessageParseError};
 
 use super::proxy::ObjectMeta;
 use super::queues::QueueBuffer;
@@ -25,6 +26,13 @@ pub(crate) struct Connection {
     pub(crate) map: Arc<Mutex<ObjectMap<ObjectMeta>>>,
     pub(crate) last_error: Arc<Mutex<Option<Error>>>,
     pub(crate) display_buffer: QueueBuffer,
+    pub(crate) logger: Option<Arc<dyn ProtocolLogger>>,
+    // number of threads intending to read events, the last of them does the actual read
+    pub(crate) readers: usize,
+    // incremented when the readers are done, either by reading or by cancelling
+    pub(crate) read_serial: u32,
+    // notified with the increments of `read_serial`
+    pub(crate) read_cond: Arc<Condvar>,
 }
 
 impl Connection {
@@ -36,11 +44,22 @@ impl Connection {
         let display_buffer = display_object.meta.buffer.clone();
         map.insert_at(1, display_object).unwrap();
 
+        // Follow libwayland-client and enable debug log only on `1` and `client` values.
+        let logger = if debug::debug_enabled("client") {
+            Some(Arc::new(StderrLogger) as Arc<dyn ProtocolLogger>)
+        } else {
+            None
+        };
+
         Connection {
             socket,
             map: Arc::new(Mutex::new(map)),
             last_error: Arc::new(Mutex::new(None)),
             display_buffer,
+            logger,
+            readers: 0,
+            read_serial: 0,
+            read_cond: Arc::new(Condvar::new()),
         }
     }
 
@@ -49,6 +68,10 @@ impl Connection {
     }
 
     pub(crate) fn flush(&mut self) -> NixResult<()> {
+        if let Some(Error::Nix(e)) = *self.last_error.lock().unwrap() {
+            // a message could not be written, the connection is unusable
+            return Err(e);
+        }
         self.socket.flush()
     }
 
@@ -62,8 +85,8 @@ impl Connection {
         // wrap it in a RefCell for cheap sharing in the two closures below
         let map = RefCell::new(&mut *map);
         let mut last_error = self.last_error.lock().unwrap();
-        // read messages
-        let ret = self.socket.read_messages(
+        // read messages, they are only copied once they are known to be delivered
+        let ret = self.socket.read_messages_ref(
             |id, opcode| {
                 let object = match map.borrow().find(id) {
                     Some(result) => result,
@@ -94,9 +117,10 @@ impl Connection {
                     .and_then(|o| o.event_child(msg.opcode).map(|c| (c, o.meta.client_destroyed)))
                 {
                     let new_id = msg
-                        .args
-                        .iter()
-                        .flat_map(|a| if let Argument::NewId(nid) = *a { Some(nid) } else { None })
+                        .args()
+                        .flat_map(
+                            |a| if let ArgumentRef::NewId(nid) = a { Some(nid) } else { None },
+                        )
                         .next()
                         .unwrap();
                     let child_interface = child.interface;
@@ -133,24 +157,24 @@ impl Connection {
                 } else {
                     // debug assert: if this opcode does not define a child, then there should be no
                     // NewId argument
-                    debug_assert!(!msg.args.iter().any(|a| a.get_type() == ArgumentType::NewId));
+                    debug_assert!(!msg.args().any(|a| a.get_type() == ArgumentType::NewId));
                 }
 
                 // send the message to the appropriate pending queue
                 match object {
                     Some(Object { meta: ObjectMeta { client_destroyed: true, .. }, .. }) | None => {
                         // this is a message sent to a destroyed object
-                        // to avoid dying because of races, we just consume it into void
-                        // closing any associated FDs
-                        for a in msg.args {
-                            if let Argument::Fd(fd) = a {
-                                let _ = ::nix::unistd::close(fd);
-                            }
-                        }
-                    }
-                    Some(obj) => {
-                        obj.meta.buffer.lock().unwrap().push_back(msg);
+                        // to avoid dying because of races, we just consume it into void,
+                        // the socket closes any associated FDs
                     }
+                    Some(obj) => match msg.to_owned() {
+                        Ok(msg) => obj.meta.buffer.lock().unwrap().push_back(msg),
+                        Err(e) => {
+                            // the FDs of the message could not be duplicated, abort parsing
+                            *last_error = Some(Error::Nix(e));
+                            return false;
+                        }
+                    },
                 };
 
                 // continue parsing
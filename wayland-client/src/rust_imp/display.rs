@@ -3,18 +3,20 @@ use std::os::unix::io::{AsRawFd, RawFd};
 use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 
-use wayland_commons::debug;
+use wayland_commons::capture::MessageRecorder;
+use wayland_commons::debug::ProtocolLogger;
 use wayland_commons::map::{Object, ObjectMap};
 use wayland_commons::wire::Message;
 use wayland_commons::MessageGroup;
 
 use crate::protocol::wl_display::{self, WlDisplay};
 
-use crate::{ConnectError, ProtocolError, Proxy};
+use crate::{BufferCapacity, ConnectError, ObjectInfo, ProtocolError, Proxy};
 
 use super::connection::{Connection, Error as CxError};
 use super::proxy::{ObjectMeta, ProxyInner};
-use super::{Dispatched, EventQueueInner, ProxyMap, WAYLAND_DEBUG};
+use super::queues::queue_id;
+use super::{Dispatched, EventQueueInner, ProxyMap};
 
 pub(crate) struct DisplayInner {
     connection: Arc<Mutex<Connection>>,
@@ -23,14 +25,6 @@ pub(crate) struct DisplayInner {
 
 impl DisplayInner {
     pub unsafe fn from_fd(fd: RawFd) -> Result<Arc<DisplayInner>, ConnectError> {
-        if let Some(value) = std::env::var_os("WAYLAND_DEBUG") {
-            // Follow libwayland-client and enable debug log only on `1` and `client` values.
-            if value == "1" || value == "client" {
-                // Toggle debug log.
-                WAYLAND_DEBUG.store(true, Ordering::Relaxed);
-            }
-        }
-
         // The special buffer for display events
         let buffer = super::queues::create_queue_buffer();
         let display_object = Object::from_interface::<WlDisplay>(1, ObjectMeta::new(buffer));
@@ -87,6 +81,43 @@ impl DisplayInner {
     pub(crate) fn get_connection_fd(&self) -> ::std::os::unix::io::RawFd {
         self.connection.lock().unwrap().socket.get_socket().as_raw_fd()
     }
+
+    pub(crate) fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.connection.lock().unwrap().logger = logger;
+    }
+
+    pub(crate) fn set_recorder(
+        &self,
+        recorder: Option<Box<dyn MessageRecorder>>,
+    ) -> Option<Box<dyn MessageRecorder>> {
+        self.connection.lock().unwrap().socket.set_recorder(recorder)
+    }
+
+    pub(crate) fn objects(&self) -> Vec<ObjectInfo> {
+        let cx = self.connection.lock().unwrap();
+        let mut map = cx.map.lock().unwrap();
+        let mut objects = Vec::new();
+        map.with_all(|id, object| {
+            if object.interface.is_empty() {
+                // placeholder, not yet a real object
+                return;
+            }
+            objects.push(ObjectInfo {
+                id,
+                interface: object.interface,
+                version: object.version,
+                alive: object.meta.alive.load(Ordering::Acquire),
+                client_destroyed: object.meta.client_destroyed,
+                server_destroyed: object.meta.server_destroyed,
+                queue: Some(queue_id(&object.meta.buffer)),
+            });
+        });
+        objects
+    }
+
+    pub(crate) fn set_buffer_capacity(&self, capacity: BufferCapacity) {
+        self.connection.lock().unwrap().socket.set_capacity(capacity);
+    }
 }
 
 // WlDisplay needs its own dispatcher, as it can be dispatched from multiple threads
@@ -99,22 +130,13 @@ impl super::Dispatcher for DisplayDispatcher {
     fn dispatch(
         &mut self,
         msg: Message,
-        proxy: ProxyInner,
+        _proxy: ProxyInner,
         map: &mut ProxyMap,
         _data: crate::DispatchData,
     ) -> Dispatched {
-        if WAYLAND_DEBUG.load(Ordering::Relaxed) {
-            debug::print_dispatched_message(
-                proxy.object.interface,
-                proxy.id,
-                proxy.object.events[msg.opcode as usize].name,
-                &msg.args,
-            );
-        }
-
         let event = match wl_display::Event::from_raw(msg, map) {
             Ok(v) => v,
-            Err(()) => return Dispatched::BadMsg,
+            Err(e) => return Dispatched::BadMsg(Some(e)),
         };
 
         match event {
@@ -1,12 +1,11 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 
 use downcast::Downcast;
 
-use wayland_commons::debug;
 use wayland_commons::filter::Filter;
 use wayland_commons::map::ObjectMap;
-use wayland_commons::wire::Message;
+use wayland_commons::wire::{FromRawError, Message};
 use wayland_commons::{MessageGroup, ThreadGuard};
 
 use crate::{Interface, Main, Proxy};
@@ -20,9 +19,6 @@ pub(crate) use self::display::DisplayInner;
 pub(crate) use self::proxy::ProxyInner;
 pub(crate) use self::queues::EventQueueInner;
 
-/// Flag to toggle debug output.
-static WAYLAND_DEBUG: AtomicBool = AtomicBool::new(false);
-
 /// A handle to the object map internal to the library state.
 ///
 /// This type is only used by code generated by `wayland-scanner`, and can not
@@ -84,7 +80,7 @@ impl ProxyMap {
 pub(crate) enum Dispatched {
     Yes,
     NoDispatch(Message, ProxyInner),
-    BadMsg,
+    BadMsg(Option<FromRawError>),
 }
 
 pub(crate) trait Dispatcher: Downcast + Send {
@@ -127,18 +123,9 @@ where
     ) -> Dispatched {
         let opcode = msg.opcode as usize;
 
-        if WAYLAND_DEBUG.load(Ordering::Relaxed) {
-            debug::print_dispatched_message(
-                proxy.object.interface,
-                proxy.id,
-                proxy.object.events[opcode].name,
-                &msg.args,
-            );
-        }
-
         let message = match I::Event::from_raw(msg, map) {
             Ok(v) => v,
-            Err(()) => return Dispatched::BadMsg,
+            Err(e) => return Dispatched::BadMsg(Some(e)),
         };
 
         if message.since() > proxy.version() {
@@ -150,7 +137,7 @@ where
                 proxy.id,
                 proxy.version()
             );
-            return Dispatched::BadMsg;
+            return Dispatched::BadMsg(None);
         }
 
         if message.is_destructor() {
@@ -1,16 +1,17 @@
+use std::cell::RefCell;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
-use wayland_commons::debug;
+use wayland_commons::debug::{LoggedMessage, MessageDirection};
 use wayland_commons::filter::Filter;
 use wayland_commons::map::{Object, ObjectMap, ObjectMetadata};
 use wayland_commons::user_data::UserData;
-use wayland_commons::wire::{Argument, ArgumentType};
-use wayland_commons::MessageGroup;
+use wayland_commons::wire::{Argument, ArgumentType, MessageWriteError};
+use wayland_commons::{MessageGroup, ThreadGuard};
 
-use super::connection::Connection;
+use super::connection::{Connection, Error};
 use super::queues::QueueBuffer;
-use super::{Dispatcher, EventQueueInner, WAYLAND_DEBUG};
+use super::{Dispatcher, EventQueueInner, ImplDispatcher};
 use crate::{Interface, Main, Proxy};
 
 #[derive(Clone)]
@@ -140,9 +141,21 @@ impl ProxyInner {
         // this avoids the risk or races during object creation
         let mut conn_lock = self.connection.lock().unwrap();
         let destructor = msg.is_destructor();
-        let mut msg = msg.into_raw(self.id);
-
-        let opcode = msg.opcode;
+        let opcode = msg.opcode();
+        let mut msg = match msg.into_raw(self.id) {
+            Ok(msg) => Some(msg),
+            Err(e) => {
+                // the fds of the message could not be duplicated, the connection is now in
+                // an error state like it would be if the message could not be written
+                if let MessageWriteError::DupFdFailed(e) = e {
+                    let mut last_error = conn_lock.last_error.lock().unwrap();
+                    if last_error.is_none() {
+                        *last_error = Some(Error::Nix(e));
+                    }
+                }
+                None
+            }
+        };
 
         // figure out if the call creates an object
         let nid_idx = I::Request::MESSAGES[opcode as usize]
@@ -150,7 +163,8 @@ impl ProxyInner {
             .iter()
             .position(|&t| t == ArgumentType::NewId);
 
-        let alive = self.is_alive();
+        // a message that could not be built is not sent, as if the object was dead
+        let alive = self.is_alive() && msg.is_some();
 
         let ret = if let Some(mut nid_idx) = nid_idx {
             let target_queue = self
@@ -180,7 +194,9 @@ impl ProxyInner {
             let mut new_id = 0;
             if alive {
                 new_id = self.map.lock().unwrap().client_insert_new(new_object.clone());
-                msg.args[nid_idx] = Argument::NewId(new_id);
+                if let Some(ref mut msg) = msg {
+                    msg.args[nid_idx] = Argument::NewId(new_id);
+                }
             }
             Some(ProxyInner {
                 map: self.map.clone(),
@@ -193,14 +209,21 @@ impl ProxyInner {
             None
         };
 
-        if WAYLAND_DEBUG.load(Ordering::Relaxed) {
-            debug::print_send_message(
+        let msg = match msg {
+            Some(msg) => msg,
+            None => return ret,
+        };
+
+        if let Some(ref logger) = conn_lock.logger {
+            let args = msg.args.iter().map(Argument::as_ref).collect::<Vec<_>>();
+            logger.log(&LoggedMessage::now(
+                MessageDirection::Sent,
                 I::NAME,
                 self.id,
                 alive,
                 self.object.requests[msg.opcode as usize].name,
-                &msg.args,
-            );
+                &args,
+            ));
         }
 
         // Only actually send the message (& process destructor) if the object is alive.
@@ -208,7 +231,14 @@ impl ProxyInner {
             return ret;
         }
 
-        conn_lock.write_message(&msg).expect("Sending a message failed.");
+        if let Err(e) = conn_lock.write_message(&msg) {
+            // the message could not be sent, the connection is now in an error state
+            // like it would be if the server had closed it
+            let mut last_error = conn_lock.last_error.lock().unwrap();
+            if last_error.is_none() {
+                *last_error = Some(Error::Nix(e));
+            }
+        }
 
         if destructor {
             self.object.meta.alive.store(false, Ordering::Release);
@@ -245,4 +275,28 @@ impl ProxyInner {
             obj.meta.dispatcher = super::make_dispatcher(filter);
         });
     }
+
+    pub(crate) fn assign_next_event<I, F>(&self, f: F)
+    where
+        I: Interface + AsRef<Proxy<I>> + From<Proxy<I>> + Sync,
+        F: FnOnce(I::Event) + 'static,
+        I::Event: MessageGroup<Map = super::ProxyMap>,
+    {
+        let map = self.map.clone();
+        let id = self.id;
+        // ignore failure if target object is dead
+        let _ = self.map.lock().unwrap().with(self.id, |obj| {
+            let next = ThreadGuard::new(RefCell::new(Some((f, obj.meta.dispatcher.clone()))));
+            obj.meta.dispatcher = Arc::new(Mutex::new(ImplDispatcher {
+                _i: ::std::marker::PhantomData,
+                implementation: move |evt, _: Main<I>, _| {
+                    if let Some((f, previous)) = next.get().borrow_mut().take() {
+                        // the following events are dispatched to the previous filter
+                        let _ = map.lock().unwrap().with(id, |obj| obj.meta.dispatcher = previous);
+                        f(evt);
+                    }
+                },
+            }));
+        });
+    }
 }
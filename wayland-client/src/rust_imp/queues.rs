@@ -3,10 +3,12 @@ use std::collections::VecDeque;
 use std::io;
 use std::os::unix::io::AsRawFd;
 use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
 
-use nix::poll::{poll, PollFd, PollFlags};
+use nix::poll::PollFlags;
 
+use wayland_commons::debug::{LoggedMessage, MessageDirection};
 use wayland_commons::map::ObjectMap;
 use wayland_commons::wire::{Argument, Message};
 
@@ -14,7 +16,8 @@ use super::connection::{Connection, Error as CError};
 use super::proxy::{ObjectMeta, ProxyInner};
 use super::Dispatched;
 
-use crate::{AnonymousObject, DispatchData, Filter, Main, RawEvent};
+use crate::event_queue::poll_socket;
+use crate::{AnonymousObject, DispatchData, Filter, Main, QueueId, RawEvent};
 
 pub(crate) type QueueBuffer = Arc<Mutex<VecDeque<Message>>>;
 
@@ -22,11 +25,16 @@ pub(crate) fn create_queue_buffer() -> QueueBuffer {
     Arc::new(Mutex::new(VecDeque::new()))
 }
 
+pub(crate) fn queue_id(buffer: &QueueBuffer) -> QueueId {
+    QueueId(Arc::as_ptr(buffer) as *const () as usize)
+}
+
 pub(crate) struct EventQueueInner {
     pub(crate) connection: Arc<Mutex<Connection>>,
     pub(crate) map: Arc<Mutex<ObjectMap<ObjectMeta>>>,
     pub(crate) buffer: QueueBuffer,
     display_buffer: QueueBuffer,
+    read_cond: Arc<Condvar>,
 }
 
 impl EventQueueInner {
@@ -34,19 +42,32 @@ impl EventQueueInner {
         connection: Arc<Mutex<Connection>>,
         buffer: Option<QueueBuffer>,
     ) -> EventQueueInner {
-        let (map, display_buffer) = {
+        let (map, display_buffer, read_cond) = {
             let cx = connection.lock().unwrap();
-            (cx.map.clone(), cx.display_buffer.clone())
+            (cx.map.clone(), cx.display_buffer.clone(), cx.read_cond.clone())
         };
         EventQueueInner {
             connection,
             map,
             buffer: buffer.unwrap_or_else(create_queue_buffer),
             display_buffer,
+            read_cond,
         }
     }
 
-    pub(crate) fn dispatch<F>(&self, mut data: DispatchData, mut fallback: F) -> io::Result<u32>
+    pub(crate) fn dispatch<F>(&self, data: DispatchData, fallback: F) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        self.dispatch_timeout(data, fallback, None)
+    }
+
+    pub(crate) fn dispatch_timeout<F>(
+        &self,
+        mut data: DispatchData,
+        mut fallback: F,
+        deadline: Option<Instant>,
+    ) -> io::Result<u32>
     where
         F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
     {
@@ -66,14 +87,16 @@ impl EventQueueInner {
                     Ok(_) => break,
                     Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => {
                         // EAGAIN, we need to wait before writing, so we poll the socket
-                        let poll_ret = poll(&mut [PollFd::new(socket_fd, PollFlags::POLLOUT)], -1);
-                        match poll_ret {
-                            Ok(_) => continue,
-                            Err(::nix::Error::Sys(e)) => {
+                        match poll_socket(socket_fd, PollFlags::POLLOUT, deadline) {
+                            Ok(true) => continue,
+                            Ok(false) => {
                                 self.cancel_read();
-                                return Err(e.into());
+                                return Err(io::ErrorKind::TimedOut.into());
+                            }
+                            Err(e) => {
+                                self.cancel_read();
+                                return Err(e);
                             }
-                            Err(_) => unreachable!(),
                         }
                     }
                     Err(::nix::Error::Sys(e)) => {
@@ -90,15 +113,18 @@ impl EventQueueInner {
         }
 
         // wait for incoming messages to arrive
-        match poll(&mut [PollFd::new(socket_fd, PollFlags::POLLIN)], -1) {
-            Ok(_) => (),
-            Err(::nix::Error::Sys(e)) => {
+        match poll_socket(socket_fd, PollFlags::POLLIN, deadline) {
+            Ok(true) => (),
+            Ok(false) => {
                 self.cancel_read();
-                return Err(e.into());
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            Err(e) => {
+                self.cancel_read();
+                return Err(e);
             }
-            Err(_) => unreachable!(),
         }
-        let read_ret = self.read_events();
+        let read_ret = self.read_events_until(deadline);
 
         // even if read_events returned an error, it may have queued messages the need dispatching
         // so we dispatch them
@@ -129,6 +155,7 @@ impl EventQueueInner {
     {
         let mut count = 0;
         let mut proxymap = super::ProxyMap::make(self.map.clone(), self.connection.clone());
+        let logger = self.connection.lock().unwrap().logger.clone();
         loop {
             let msg = { buffer.lock().unwrap().pop_front() };
             let msg = match msg {
@@ -136,32 +163,38 @@ impl EventQueueInner {
                 None => break,
             };
             let id = msg.sender_id;
+            let opcode = msg.opcode as usize;
             if let Some(proxy) = ProxyInner::from_id(id, self.map.clone(), self.connection.clone())
             {
                 let object = proxy.object.clone();
                 if object.meta.client_destroyed {
                     // This is a potential race, if we reach here it means that the proxy was
                     // destroyed by the user between this message was queued and now. To handle it
-                    // correctly, we must close any FDs it contains, mark any child object as
-                    // destroyed (but the server will never know about it, so the ids will be
-                    // leaked) and discard the event.
+                    // correctly, we must mark any child object as destroyed (but the server will
+                    // never know about it, so the ids will be leaked) and discard the event,
+                    // dropping it closes any FDs it contains.
                     for arg in msg.args {
-                        match arg {
-                            Argument::Fd(fd) => {
-                                let _ = ::nix::unistd::close(fd);
-                            }
-                            Argument::NewId(id) => {
-                                let mut map = self.map.lock().unwrap();
-                                map.with(id, |obj| {
-                                    obj.meta.client_destroyed = true;
-                                })
-                                .unwrap();
-                            }
-                            _ => {}
+                        if let Argument::NewId(id) = arg {
+                            let mut map = self.map.lock().unwrap();
+                            map.with(id, |obj| {
+                                obj.meta.client_destroyed = true;
+                            })
+                            .unwrap();
                         }
                     }
                     continue;
                 }
+                if let Some(ref logger) = logger {
+                    let args = msg.args.iter().map(Argument::as_ref).collect::<Vec<_>>();
+                    logger.log(&LoggedMessage::now(
+                        MessageDirection::Received,
+                        object.interface,
+                        id,
+                        true,
+                        object.events[msg.opcode as usize].name,
+                        &args,
+                    ));
+                }
                 let mut dispatcher = object.meta.dispatcher.lock().unwrap();
                 match dispatcher.dispatch(msg, proxy, &mut proxymap, data.reborrow()) {
                     Dispatched::Yes => {
@@ -172,11 +205,17 @@ impl EventQueueInner {
                         fallback(raw_event, Main::wrap(proxy), data.reborrow());
                         count += 1;
                     }
-                    Dispatched::BadMsg => {
-                        return Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!("Dispatch for object {}@{} errored.", object.interface, id),
-                        ))
+                    Dispatched::BadMsg(error) => {
+                        let message = match error {
+                            Some(e) => format!(
+                                "Dispatch for object {}@{} errored: invalid event {}: {}",
+                                object.interface, id, object.events[opcode].name, e
+                            ),
+                            None => {
+                                format!("Dispatch for object {}@{} errored.", object.interface, id)
+                            }
+                        };
+                        return Err(io::Error::new(io::ErrorKind::Other, message));
                     }
                 }
             } else {
@@ -203,10 +242,18 @@ impl EventQueueInner {
         Ok(display_dispatched + self_dispatched)
     }
 
-    pub(crate) fn sync_roundtrip<F>(
+    pub(crate) fn sync_roundtrip<F>(&self, data: DispatchData, fallback: F) -> io::Result<u32>
+    where
+        F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
+    {
+        self.sync_roundtrip_timeout(data, fallback, None)
+    }
+
+    pub(crate) fn sync_roundtrip_timeout<F>(
         &self,
         mut data: DispatchData,
         mut fallback: F,
+        deadline: Option<Instant>,
     ) -> io::Result<u32>
     where
         F: FnMut(RawEvent, Main<AnonymousObject>, DispatchData<'_>),
@@ -228,7 +275,7 @@ impl EventQueueInner {
         let mut dispatched = 0;
 
         loop {
-            dispatched += self.dispatch(data.reborrow(), &mut fallback)?;
+            dispatched += self.dispatch_timeout(data.reborrow(), &mut fallback, deadline)?;
             if done.get() {
                 return Ok(dispatched);
             }
@@ -236,17 +283,49 @@ impl EventQueueInner {
     }
 
     pub(crate) fn prepare_read(&self) -> Result<(), ()> {
+        // the events are queued with the connection locked, so none can be queued between
+        // this check and the registration of the read intent
+        let mut cx = self.connection.lock().unwrap();
         if !self.buffer.lock().unwrap().is_empty() {
             return Err(());
         }
-
-        // TODO: un-mock
+        cx.readers += 1;
         Ok(())
     }
 
     pub(crate) fn read_events(&self) -> io::Result<()> {
-        // TODO: integrate more properly with prepare read with a fence
-        match self.connection.lock().unwrap().read_events() {
+        self.read_events_until(None)
+    }
+
+    fn read_events_until(&self, deadline: Option<Instant>) -> io::Result<()> {
+        let mut cx = self.connection.lock().unwrap();
+        cx.readers -= 1;
+        if cx.readers > 0 {
+            // the last reader will read the events for everyone, wait for it
+            let serial = cx.read_serial;
+            while cx.read_serial == serial {
+                cx = match deadline {
+                    Some(deadline) => {
+                        let timeout = deadline.saturating_duration_since(Instant::now());
+                        let (cx, result) = self.read_cond.wait_timeout(cx, timeout).unwrap();
+                        if result.timed_out() && cx.read_serial == serial {
+                            return Err(io::ErrorKind::TimedOut.into());
+                        }
+                        cx
+                    }
+                    None => self.read_cond.wait(cx).unwrap(),
+                };
+            }
+            return match *cx.last_error.lock().unwrap() {
+                Some(_) => Err(::nix::errno::Errno::EPROTO.into()),
+                None => Ok(()),
+            };
+        }
+
+        let ret = cx.read_events();
+        cx.read_serial = cx.read_serial.wrapping_add(1);
+        self.read_cond.notify_all();
+        match ret {
             Ok(_) => Ok(()),
             Err(CError::Protocol(e)) => {
                 eprintln!("[wayland-client] Protocol error while reading events: {}", e);
@@ -262,7 +341,17 @@ impl EventQueueInner {
     }
 
     pub(crate) fn cancel_read(&self) {
-        // TODO: un-mock
+        let mut cx = self.connection.lock().unwrap();
+        cx.readers -= 1;
+        if cx.readers == 0 {
+            // wake up the other readers, which were waiting for this one
+            cx.read_serial = cx.read_serial.wrapping_add(1);
+            self.read_cond.notify_all();
+        }
+    }
+
+    pub(crate) fn id(&self) -> QueueId {
+        queue_id(&self.buffer)
     }
 }
 
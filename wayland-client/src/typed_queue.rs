@@ -0,0 +1,179 @@
+use std::io;
+use std::marker::PhantomData;
+
+use crate::{DispatchData, EventQueue, Interface, Main, MessageGroup, Proxy, ProxyMap, QueueToken};
+
+/// A trait for state types handling the events of objects of interface `I`
+///
+/// This is an alternative to assigning a `Filter` to each object: the events of the objects
+/// of a `TypedEventQueue` are given to the `event()` method of its state type, along with the
+/// user data the object was given when it was created. As a single mutable reference to the
+/// state is used for the whole dispatching, the handlers of all objects can access it without
+/// sharing it behind `Rc<RefCell<_>>`.
+///
+/// A state type implements this trait once for each interface it handles.
+pub trait Dispatch<I>: Sized
+where
+    I: Interface + AsRef<Proxy<I>> + From<Proxy<I>>,
+{
+    /// The user data associated with each object of this interface
+    type UserData: 'static;
+
+    /// Handle an event of an object of this interface
+    ///
+    /// The queue handle can be used to create objects of the same queue, for example with
+    /// `wl_registry.bind`.
+    fn event(
+        &mut self,
+        proxy: &Main<I>,
+        event: I::Event,
+        data: &Self::UserData,
+        qhandle: &QueueHandle<Self>,
+    );
+}
+
+/// A handle to a `TypedEventQueue`
+///
+/// It is used to create objects dispatched to the state type of the queue, and can be turned
+/// into a `QueueToken` to attach proxies to the queue.
+pub struct QueueHandle<State> {
+    token: QueueToken,
+    _state: PhantomData<fn(&mut State)>,
+}
+
+impl<State> Clone for QueueHandle<State> {
+    fn clone(&self) -> QueueHandle<State> {
+        QueueHandle { token: self.token.clone(), _state: PhantomData }
+    }
+}
+
+impl<State: 'static> QueueHandle<State> {
+    /// Get a token to the underlying event queue
+    pub fn token(&self) -> QueueToken {
+        self.token.clone()
+    }
+
+    /// Send a request creating an object, dispatching the events of the new object to the state
+    ///
+    /// This behaves like `Proxy::send()`, and gives its user data to the new object before
+    /// any of its events can be dispatched. The events of the new object are then given to
+    /// `<State as Dispatch<J>>::event()` along with `data`.
+    ///
+    /// The new object belongs to the queue of `proxy`, which must be this queue.
+    pub fn send_constructor<I, J>(
+        &self,
+        proxy: &Proxy<I>,
+        request: I::Request,
+        version: Option<u32>,
+        data: <State as Dispatch<J>>::UserData,
+    ) -> Option<Main<J>>
+    where
+        I: Interface + AsRef<Proxy<I>> + From<Proxy<I>>,
+        State: Dispatch<J>,
+        J: Interface + AsRef<Proxy<J>> + From<Proxy<J>> + Sync,
+        J::Event: MessageGroup<Map = ProxyMap>,
+    {
+        let proxy = proxy.send::<J>(request, version)?;
+        self.assign(&proxy, data);
+        Some(proxy)
+    }
+
+    /// Dispatch the events of an object created by the server to the state
+    ///
+    /// This is meant for the objects created by an event, which must be given their user data
+    /// while handling the event creating them. The objects created by a request should be
+    /// created with `send_constructor()` instead.
+    ///
+    /// The events of this object will be given to `<State as Dispatch<I>>::event()` along
+    /// with `data`. This replaces any filter previously assigned to the object. The object must
+    /// belong to this queue.
+    pub fn assign<I>(&self, proxy: &Main<I>, data: <State as Dispatch<I>>::UserData)
+    where
+        State: Dispatch<I>,
+        I: Interface + AsRef<Proxy<I>> + From<Proxy<I>> + Sync,
+        I::Event: MessageGroup<Map = ProxyMap>,
+    {
+        let qhandle = self.clone();
+        proxy.quick_assign(move |proxy, event, mut ddata| match ddata.get::<State>() {
+            Some(state) => state.event(&proxy, event, &data, &qhandle),
+            // the queue was dispatched without the state, by the underlying `EventQueue`
+            None => eprintln!(
+                "[wayland-client] Event {} for {}@{} ignored, as there is no state to handle it.",
+                I::Event::MESSAGES[event.opcode() as usize].name,
+                I::NAME,
+                proxy.as_ref().id()
+            ),
+        });
+    }
+}
+
+/// An event queue dispatching its events to a state type
+///
+/// This wraps an `EventQueue`, and dispatches the events of its objects with a mutable
+/// reference to a value of type `State`, which must implement `Dispatch<I>` for the
+/// interfaces of all the objects of this queue. Objects are created with
+/// `QueueHandle::send_constructor()`, or given their user data with `QueueHandle::assign()`
+/// if they were created by an event.
+///
+/// The events received by objects of this queue that were not given their user data are
+/// ignored.
+pub struct TypedEventQueue<State> {
+    queue: EventQueue,
+    handle: QueueHandle<State>,
+}
+
+impl<State: 'static> TypedEventQueue<State> {
+    /// Create a typed event queue from an event queue
+    pub fn new(queue: EventQueue) -> TypedEventQueue<State> {
+        let handle = QueueHandle { token: queue.token(), _state: PhantomData };
+        TypedEventQueue { queue, handle }
+    }
+
+    /// Get a handle to this queue
+    pub fn handle(&self) -> QueueHandle<State> {
+        self.handle.clone()
+    }
+
+    /// Access the underlying event queue
+    pub fn get_ref(&self) -> &EventQueue {
+        &self.queue
+    }
+
+    /// Retrieve the underlying event queue
+    ///
+    /// The events of the objects of this queue are then only given to the state if it is
+    /// provided as the dispatch data of the queue, and ignored otherwise.
+    pub fn into_inner(self) -> EventQueue {
+        self.queue
+    }
+
+    /// Dispatch events, blocking if there are none
+    ///
+    /// This behaves like `EventQueue::dispatch()`, with the events handled by the state.
+    pub fn dispatch(&mut self, state: &mut State) -> io::Result<u32> {
+        self.queue.dispatch(state, unassigned_event)
+    }
+
+    /// Dispatch the pending events without blocking
+    ///
+    /// This behaves like `EventQueue::dispatch_pending()`, with the events handled by the state.
+    pub fn dispatch_pending(&mut self, state: &mut State) -> io::Result<u32> {
+        self.queue.dispatch_pending(state, unassigned_event)
+    }
+
+    /// Synchronous roundtrip
+    ///
+    /// This behaves like `EventQueue::sync_roundtrip()`, with the events handled by the state.
+    pub fn sync_roundtrip(&mut self, state: &mut State) -> io::Result<u32> {
+        self.queue.sync_roundtrip(state, unassigned_event)
+    }
+}
+
+fn unassigned_event(event: crate::RawEvent, object: Main<crate::AnonymousObject>, _: DispatchData) {
+    eprintln!(
+        "[wayland-client] Event {} for {}@{} ignored, as the object was not given its user data.",
+        event.name,
+        event.interface,
+        object.as_ref().id()
+    );
+}
@@ -0,0 +1,643 @@
+//! Recording and replaying of wayland connections
+//!
+//! This module defines a versioned on-disk format to store the messages exchanged
+//! on a wayland connection, along with the tooling to record them and to replay
+//! them.
+//!
+//! A capture is recorded by installing a `MessageRecorder` on a `BufferedSocket`
+//! (see `BufferedSocket::set_recorder()`), typically a `CaptureWriter`. Every
+//! message written to or parsed from the socket is then stored, with its
+//! timestamp and direction.
+//!
+//! File descriptors cannot be meaningfully stored, so they are recorded as
+//! placeholders: when reading a capture back, they are given as
+//! `CapturedArgument::Fd`, and `replay()` substitutes them with actual file
+//! descriptors provided by the caller.
+//!
+//! ## Format
+//!
+//! All integers are stored in little-endian. A capture starts with the 8 bytes
+//! `CAPTURE_MAGIC` followed by the format version as a `u32`. Each message then
+//! contains:
+//!
+//! - the timestamp as a `u64` of seconds and a `u32` of nanoseconds since the UNIX epoch
+//! - the direction as a `u8`: `0` for sent and `1` for received messages
+//! - the sender id as a `u32`, the opcode as a `u16` and the number of arguments as a `u16`
+//! - each argument as a `u8` tag followed by its contents: a `u32` for `int`, `uint`,
+//!   `fixed`, `object` and `new_id`; a `u32` length followed by the bytes for `string`
+//!   (without the trailing nul byte) and `array`; nothing for `fd`.
+
+use std::ffi::CString;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::debug::MessageDirection;
+use crate::socket::BufferedSocket;
+use crate::wire::{Argument, ArgumentRef, Message, MessageRef, OwnedFd};
+
+/// Magic bytes at the start of every capture
+pub const CAPTURE_MAGIC: [u8; 8] = *b"WLRSCAP\0";
+
+/// Current version of the capture format
+pub const CAPTURE_VERSION: u32 = 1;
+
+/// An argument of a message stored in a capture
+#[derive(Debug, PartialEq)]
+pub enum CapturedArgument {
+    /// Any argument but a file descriptor, with its recorded value
+    Value(Argument),
+    /// A file descriptor, whose value is not stored in the capture
+    Fd,
+}
+
+/// A message stored in a capture
+#[derive(Debug, PartialEq)]
+pub struct CapturedMessage {
+    /// Time at which the message was recorded, since the UNIX epoch
+    pub timestamp: Duration,
+    /// Whether the message was sent or received by the recorded socket
+    pub direction: MessageDirection,
+    /// ID of the object sending the message
+    pub sender_id: u32,
+    /// Opcode of the message
+    pub opcode: u16,
+    /// Arguments of the message
+    pub args: Vec<CapturedArgument>,
+}
+
+/// Error generated when reading or replaying a capture
+#[derive(Debug)]
+pub enum CaptureError {
+    /// An I/O error occurred accessing the capture
+    Io(io::Error),
+    /// The data does not start with `CAPTURE_MAGIC`
+    BadMagic,
+    /// The capture was created with an unsupported version of the format
+    UnsupportedVersion(u32),
+    /// The capture contains invalid data
+    Malformed,
+    /// An error occurred writing a message to the socket during a replay
+    Socket(nix::Error),
+}
+
+impl std::error::Error for CaptureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            CaptureError::Io(ref e) => Some(e),
+            CaptureError::Socket(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        match *self {
+            CaptureError::Io(ref e) => write!(f, "I/O error reading the capture: {}", e),
+            CaptureError::BadMagic => f.write_str("The data is not a wayland capture"),
+            CaptureError::UnsupportedVersion(v) => {
+                write!(f, "Unsupported capture format version: {}", v)
+            }
+            CaptureError::Malformed => f.write_str("The capture is malformed"),
+            CaptureError::Socket(ref e) => write!(f, "Error writing to the socket: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(e: io::Error) -> CaptureError {
+        CaptureError::Io(e)
+    }
+}
+
+/// A recorder for the messages going through a `BufferedSocket`
+pub trait MessageRecorder: Send {
+    /// Record a message
+    ///
+    /// The message borrows the buffers of the socket, and its file descriptors remain
+    /// owned by the socket: the recorder must not close them nor take ownership of them.
+    fn record(&mut self, direction: MessageDirection, msg: MessageRef<'_>);
+}
+
+impl<R: MessageRecorder> MessageRecorder for Arc<Mutex<R>> {
+    fn record(&mut self, direction: MessageDirection, msg: MessageRef<'_>) {
+        if let Ok(mut guard) = self.lock() {
+            guard.record(direction, msg);
+        }
+    }
+}
+
+/*
+ * Writing
+ */
+
+const TAG_INT: u8 = 0;
+const TAG_UINT: u8 = 1;
+const TAG_FIXED: u8 = 2;
+const TAG_STR: u8 = 3;
+const TAG_OBJECT: u8 = 4;
+const TAG_NEWID: u8 = 5;
+const TAG_ARRAY: u8 = 6;
+const TAG_FD: u8 = 7;
+
+/// A writer storing messages in the capture format
+///
+/// As a `MessageRecorder` cannot report errors, the first I/O error encountered
+/// while recording is stored and further messages are ignored. It is returned
+/// by `finish()`.
+///
+/// To retrieve the writer once the recording is done, install it on the socket
+/// wrapped in an `Arc<Mutex<_>>` and keep a clone of it.
+#[derive(Debug)]
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Create a new capture, writing its header to `writer`
+    pub fn new(mut writer: W) -> io::Result<CaptureWriter<W>> {
+        writer.write_all(&CAPTURE_MAGIC)?;
+        writer.write_all(&CAPTURE_VERSION.to_le_bytes())?;
+        Ok(CaptureWriter { writer, error: None })
+    }
+
+    /// Append a message to the capture
+    pub fn write_message(
+        &mut self,
+        timestamp: Duration,
+        direction: MessageDirection,
+        msg: &Message,
+    ) -> io::Result<()> {
+        let args = msg.args.iter().map(argument_ref);
+        self.write_entry(timestamp, direction, msg.sender_id, msg.opcode, args)
+    }
+
+    fn write_entry<'a, I>(
+        &mut self,
+        timestamp: Duration,
+        direction: MessageDirection,
+        sender_id: u32,
+        opcode: u16,
+        args: I,
+    ) -> io::Result<()>
+    where
+        I: ExactSizeIterator<Item = ArgumentRef<'a>>,
+    {
+        let mut buf = Vec::with_capacity(32);
+        buf.extend_from_slice(&timestamp.as_secs().to_le_bytes());
+        buf.extend_from_slice(&timestamp.subsec_nanos().to_le_bytes());
+        buf.push(match direction {
+            MessageDirection::Sent => 0,
+            MessageDirection::Received => 1,
+        });
+        buf.extend_from_slice(&sender_id.to_le_bytes());
+        buf.extend_from_slice(&opcode.to_le_bytes());
+        buf.extend_from_slice(&(args.len() as u16).to_le_bytes());
+        for arg in args {
+            let (tag, value) = match arg {
+                ArgumentRef::Int(i) => (TAG_INT, i as u32),
+                ArgumentRef::Uint(u) => (TAG_UINT, u),
+                ArgumentRef::Fixed(f) => (TAG_FIXED, f as u32),
+                ArgumentRef::Object(o) => (TAG_OBJECT, o),
+                ArgumentRef::NewId(n) => (TAG_NEWID, n),
+                ArgumentRef::Str(s) => {
+                    write_bytes(&mut buf, TAG_STR, s.to_bytes());
+                    continue;
+                }
+                ArgumentRef::Array(a) => {
+                    write_bytes(&mut buf, TAG_ARRAY, a);
+                    continue;
+                }
+                ArgumentRef::Fd(_) => {
+                    buf.push(TAG_FD);
+                    continue;
+                }
+            };
+            buf.push(tag);
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+        self.writer.write_all(&buf)
+    }
+
+    /// Finish the recording, flushing and returning the underlying writer
+    ///
+    /// If an error occurred while recording messages, it is returned instead.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write + Send> MessageRecorder for CaptureWriter<W> {
+    fn record(&mut self, direction: MessageDirection, msg: MessageRef<'_>) {
+        if self.error.is_some() {
+            return;
+        }
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        if let Err(e) =
+            self.write_entry(timestamp, direction, msg.sender_id, msg.opcode, msg.args())
+        {
+            self.error = Some(e);
+        }
+    }
+}
+
+fn argument_ref(arg: &Argument) -> ArgumentRef<'_> {
+    match *arg {
+        Argument::Int(i) => ArgumentRef::Int(i),
+        Argument::Uint(u) => ArgumentRef::Uint(u),
+        Argument::Fixed(f) => ArgumentRef::Fixed(f),
+        Argument::Str(ref s) => ArgumentRef::Str(s),
+        Argument::Object(o) => ArgumentRef::Object(o),
+        Argument::NewId(n) => ArgumentRef::NewId(n),
+        Argument::Array(ref a) => ArgumentRef::Array(a),
+        Argument::Fd(ref fd) => ArgumentRef::Fd(fd.as_raw_fd()),
+    }
+}
+
+fn write_bytes(buf: &mut Vec<u8>, tag: u8, bytes: &[u8]) {
+    buf.push(tag);
+    buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+/*
+ * Reading
+ */
+
+/// A reader parsing messages from a capture
+///
+/// It can be used as an iterator over the messages of the capture.
+#[derive(Debug)]
+pub struct CaptureReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Open a capture, checking its header
+    pub fn new(mut reader: R) -> Result<CaptureReader<R>, CaptureError> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic).map_err(eof_as_bad_magic)?;
+        if magic != CAPTURE_MAGIC {
+            return Err(CaptureError::BadMagic);
+        }
+        let mut version = [0u8; 4];
+        reader.read_exact(&mut version).map_err(eof_as_bad_magic)?;
+        let version = u32::from_le_bytes(version);
+        if version != CAPTURE_VERSION {
+            return Err(CaptureError::UnsupportedVersion(version));
+        }
+        Ok(CaptureReader { reader })
+    }
+
+    /// Read the next message of the capture
+    ///
+    /// Returns `Ok(None)` if the end of the capture was reached.
+    pub fn read_message(&mut self) -> Result<Option<CapturedMessage>, CaptureError> {
+        // detect a clean end of file before the start of a message
+        let mut secs = [0u8; 8];
+        let mut read = 0;
+        while read < secs.len() {
+            match self.reader.read(&mut secs[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(CaptureError::Malformed),
+                Ok(n) => read += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let secs = u64::from_le_bytes(secs);
+        let nanos = self.read_u32()?;
+        if nanos >= 1_000_000_000 {
+            return Err(CaptureError::Malformed);
+        }
+        let direction = match self.read_u8()? {
+            0 => MessageDirection::Sent,
+            1 => MessageDirection::Received,
+            _ => return Err(CaptureError::Malformed),
+        };
+        let sender_id = self.read_u32()?;
+        let opcode = self.read_u16()?;
+        let arg_count = self.read_u16()?;
+        let mut args = Vec::new();
+        for _ in 0..arg_count {
+            let arg = match self.read_u8()? {
+                TAG_INT => Argument::Int(self.read_u32()? as i32),
+                TAG_UINT => Argument::Uint(self.read_u32()?),
+                TAG_FIXED => Argument::Fixed(self.read_u32()? as i32),
+                TAG_OBJECT => Argument::Object(self.read_u32()?),
+                TAG_NEWID => Argument::NewId(self.read_u32()?),
+                TAG_STR => {
+                    let bytes = self.read_bytes()?;
+                    let s = CString::new(bytes).map_err(|_| CaptureError::Malformed)?;
+                    Argument::Str(Box::new(s))
+                }
+                TAG_ARRAY => Argument::Array(Box::new(self.read_bytes()?)),
+                TAG_FD => {
+                    args.push(CapturedArgument::Fd);
+                    continue;
+                }
+                _ => return Err(CaptureError::Malformed),
+            };
+            args.push(CapturedArgument::Value(arg));
+        }
+        Ok(Some(CapturedMessage {
+            timestamp: Duration::new(secs, nanos),
+            direction,
+            sender_id,
+            opcode,
+            args,
+        }))
+    }
+
+    /// Retrieve the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), CaptureError> {
+        self.reader.read_exact(buf).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                CaptureError::Malformed
+            } else {
+                CaptureError::Io(e)
+            }
+        })
+    }
+
+    fn read_u8(&mut self) -> Result<u8, CaptureError> {
+        let mut buf = [0u8; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, CaptureError> {
+        let mut buf = [0u8; 2];
+        self.read_exact(&mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, CaptureError> {
+        let mut buf = [0u8; 4];
+        self.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>, CaptureError> {
+        let len = self.read_u32()? as usize;
+        // don't trust the length for the allocation, a corrupted capture
+        // could otherwise trigger a huge one
+        let mut bytes = Vec::new();
+        (&mut self.reader).take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(CaptureError::Malformed);
+        }
+        Ok(bytes)
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<CapturedMessage, CaptureError>;
+
+    fn next(&mut self) -> Option<Result<CapturedMessage, CaptureError>> {
+        self.read_message().transpose()
+    }
+}
+
+fn eof_as_bad_magic(e: io::Error) -> CaptureError {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        CaptureError::BadMagic
+    } else {
+        CaptureError::Io(e)
+    }
+}
+
+/*
+ * Replay
+ */
+
+/// Replay the messages of a capture into a socket
+///
+/// All messages of the capture with given `direction` are written into `socket`,
+/// which is flushed at the end. The other messages are ignored. For example,
+/// to replay a client session recorded client-side against a server, replay the
+/// `Sent` messages into a socket connected to the server.
+///
+/// Each `Fd` argument is substituted by the return value of `fds`, which is
+/// given the sender id and opcode of the message. The returned fd is `dup()`-ed,
+/// so it remains owned by the caller.
+///
+/// Returns the number of messages written.
+pub fn replay<R, F>(
+    capture: CaptureReader<R>,
+    direction: MessageDirection,
+    socket: &mut BufferedSocket,
+    mut fds: F,
+) -> Result<usize, CaptureError>
+where
+    R: Read,
+    F: FnMut(u32, u16) -> RawFd,
+{
+    let mut count = 0;
+    for captured in capture {
+        let captured = captured?;
+        if captured.direction != direction {
+            continue;
+        }
+        let (sender_id, opcode) = (captured.sender_id, captured.opcode);
+        let mut args = smallvec::SmallVec::with_capacity(captured.args.len());
+        for arg in captured.args {
+            args.push(match arg {
+                CapturedArgument::Value(arg) => arg,
+                CapturedArgument::Fd => Argument::Fd(
+                    OwnedFd::dup(fds(sender_id, opcode)).map_err(CaptureError::Socket)?,
+                ),
+            });
+        }
+        let message = Message { sender_id, opcode, args };
+        socket.write_message(&message).map_err(CaptureError::Socket)?;
+        count += 1;
+    }
+    socket.flush().map_err(CaptureError::Socket)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::Socket;
+    use crate::wire::ArgumentType;
+
+    use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
+
+    use smallvec::smallvec;
+
+    fn sample_message() -> Message {
+        Message {
+            sender_id: 42,
+            opcode: 7,
+            args: smallvec![
+                Argument::Uint(3),
+                Argument::Fixed(-89),
+                Argument::Str(Box::new(CString::new(&b"I like trains!"[..]).unwrap())),
+                Argument::Array(vec![1, 2, 3, 4, 5, 6, 7, 8, 9].into()),
+                Argument::Object(88),
+                Argument::NewId(56),
+                Argument::Int(-25),
+                Argument::Fd(OwnedFd::dup(1).unwrap()),
+            ],
+        }
+    }
+
+    static SIGNATURE: &[ArgumentType] = &[
+        ArgumentType::Uint,
+        ArgumentType::Fixed,
+        ArgumentType::Str,
+        ArgumentType::Array,
+        ArgumentType::Object,
+        ArgumentType::NewId,
+        ArgumentType::Int,
+        ArgumentType::Fd,
+    ];
+
+    fn socket_pair() -> (BufferedSocket, BufferedSocket) {
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let client = BufferedSocket::new(unsafe { Socket::from_raw_fd(client.into_raw_fd()) });
+        let server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+        (client, server)
+    }
+
+    #[test]
+    fn write_read_cycle() {
+        let msg = sample_message();
+        let timestamp = Duration::new(1234, 5678);
+
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer.write_message(timestamp, MessageDirection::Received, &msg).unwrap();
+        let data = writer.finish().unwrap();
+
+        let mut reader = CaptureReader::new(&data[..]).unwrap();
+        let captured = reader.read_message().unwrap().unwrap();
+        assert_eq!(captured.timestamp, timestamp);
+        assert_eq!(captured.direction, MessageDirection::Received);
+        assert_eq!(captured.sender_id, msg.sender_id);
+        assert_eq!(captured.opcode, msg.opcode);
+        let mut expected = msg.args.into_iter().map(CapturedArgument::Value).collect::<Vec<_>>();
+        expected[7] = CapturedArgument::Fd;
+        assert_eq!(captured.args, expected);
+        assert!(reader.read_message().unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_captures() {
+        match CaptureReader::new(&b"not a capture"[..]) {
+            Err(CaptureError::BadMagic) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        match CaptureReader::new(&b"WLR"[..]) {
+            Err(CaptureError::BadMagic) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let mut data = CAPTURE_MAGIC.to_vec();
+        data.extend_from_slice(&42u32.to_le_bytes());
+        match CaptureReader::new(&data[..]) {
+            Err(CaptureError::UnsupportedVersion(42)) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer
+            .write_message(Duration::new(0, 0), MessageDirection::Sent, &sample_message())
+            .unwrap();
+        let data = writer.finish().unwrap();
+        let truncated = CaptureReader::new(&data[..data.len() - 3]).unwrap();
+        match truncated.last() {
+            Some(Err(CaptureError::Malformed)) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let (mut client, mut server) = socket_pair();
+        let capture = Arc::new(Mutex::new(CaptureWriter::new(Vec::new()).unwrap()));
+        client.set_recorder(Some(Box::new(capture.clone())));
+
+        let msg = sample_message();
+        client.write_message(&msg).unwrap();
+        client.flush().unwrap();
+        let received = server.read_messages(|_, _| Some(SIGNATURE), |_| true).unwrap().unwrap();
+        assert_eq!(received, 1);
+
+        client.set_recorder(None);
+        let data = Arc::try_unwrap(capture).unwrap().into_inner().unwrap().finish().unwrap();
+
+        // replay the recorded session into a new connection
+        let (mut client, mut server) = socket_pair();
+        let reader = CaptureReader::new(&data[..]).unwrap();
+        let written = replay(reader, MessageDirection::Sent, &mut client, |_, _| 1).unwrap();
+        assert_eq!(written, 1);
+
+        let mut replayed = Vec::new();
+        server
+            .read_messages(
+                |_, _| Some(SIGNATURE),
+                |msg| {
+                    replayed.push(msg);
+                    true
+                },
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].args[..7], msg.args[..7]);
+        match replayed[0].args[7] {
+            Argument::Fd(ref fd) => assert!(fd.as_raw_fd() >= 0),
+            ref other => panic!("Expected an fd, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn record_received_messages() {
+        let (mut client, mut server) = socket_pair();
+        let capture = Arc::new(Mutex::new(CaptureWriter::new(Vec::new()).unwrap()));
+        server.set_recorder(Some(Box::new(capture.clone())));
+
+        client.write_message(&sample_message()).unwrap();
+        client.flush().unwrap();
+        let mut received = Vec::new();
+        server
+            .read_messages(
+                |_, _| Some(SIGNATURE),
+                |msg| {
+                    received.push(msg);
+                    true
+                },
+            )
+            .unwrap()
+            .unwrap();
+
+        // recording the message must not have closed its fd
+        match received[0].args[7] {
+            Argument::Fd(ref fd) => {
+                assert!(::nix::fcntl::fcntl(fd.as_raw_fd(), ::nix::fcntl::F_GETFD).is_ok())
+            }
+            ref other => panic!("Expected an fd, got {:?}", other),
+        }
+
+        drop(server);
+        let data = Arc::try_unwrap(capture).unwrap().into_inner().unwrap().finish().unwrap();
+        let messages =
+            CaptureReader::new(&data[..]).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].direction, MessageDirection::Received);
+        assert_eq!(messages[0].sender_id, 42);
+    }
+}
@@ -1,22 +1,140 @@
-//! Debugging helpers to handle `WAYLAND_DEBUG` env variable.
+//! Debugging helpers to handle `WAYLAND_DEBUG` env variable and protocol logging.
+//!
+//! The `ProtocolLogger` trait allows to receive a notification for each message
+//! that is sent or received on a connection. A logger can be installed on both
+//! client-side and server-side displays. When the `WAYLAND_DEBUG` env variable
+//! is set, a `StderrLogger` is installed by default, printing the messages in
+//! the same format as the C libraries.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::wire::{Argument, ArgumentRef};
+
+/// The direction of a logged message
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MessageDirection {
+    /// The message was sent to the other end of the connection
+    Sent,
+    /// The message was received from the other end of the connection
+    Received,
+}
 
-use std::time::{SystemTime, UNIX_EPOCH};
+/// A protocol message, as seen by a `ProtocolLogger`
+#[derive(Debug)]
+pub struct LoggedMessage<'a> {
+    /// Time at which the message was sent or received, since the UNIX epoch
+    pub timestamp: Duration,
+    /// Whether the message was sent or received
+    pub direction: MessageDirection,
+    /// Interface of the object the message is attached to
+    pub interface: &'a str,
+    /// Id of the object the message is attached to
+    pub id: u32,
+    /// Whether the object was still alive when the message was processed
+    ///
+    /// Messages sent to a dead object are not actually written on the wire.
+    pub alive: bool,
+    /// Name of the message
+    pub name: &'a str,
+    /// Arguments of the message
+    ///
+    /// Their file descriptors are only borrowed for the duration of the logging.
+    pub args: &'a [ArgumentRef<'a>],
+}
 
-use crate::wire::Argument;
+impl<'a> LoggedMessage<'a> {
+    /// Create a new logged message, timestamped with the current time
+    pub fn now(
+        direction: MessageDirection,
+        interface: &'a str,
+        id: u32,
+        alive: bool,
+        name: &'a str,
+        args: &'a [ArgumentRef<'a>],
+    ) -> LoggedMessage<'a> {
+        LoggedMessage {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+            direction,
+            interface,
+            id,
+            alive,
+            name,
+            args,
+        }
+    }
+}
 
-/// Print the dispatched message to stderr in a following format:
+/// A logger for the protocol messages of a connection
 ///
+/// It is invoked for every message sent or received by the display it
+/// is installed on.
+pub trait ProtocolLogger: Send + Sync {
+    /// Log a message
+    fn log(&self, message: &LoggedMessage<'_>);
+}
+
+impl<F> ProtocolLogger for F
+where
+    F: Fn(&LoggedMessage<'_>) + Send + Sync,
+{
+    fn log(&self, message: &LoggedMessage<'_>) {
+        self(message)
+    }
+}
+
+/// A `ProtocolLogger` printing messages to stderr
+///
+/// This is the logger used when the `WAYLAND_DEBUG` env variable is set,
+/// the format is the following:
+///
+/// ```text
+/// [timestamp] -> interface@id.msg_name(args)
 /// [timestamp] <- interface@id.msg_name(args)
-pub fn print_dispatched_message(interface: &str, id: u32, msg_name: &str, args: &[Argument]) {
-    // Add timestamp to output.
-    print_timestamp();
+/// ```
+///
+/// for sent and received messages respectively. If the message was sent to
+/// a dead object, `[ZOMBIE]` is added after `id`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct StderrLogger;
+
+impl ProtocolLogger for StderrLogger {
+    fn log(&self, message: &LoggedMessage<'_>) {
+        // Add timestamp to output.
+        print_timestamp(message.timestamp);
+
+        match message.direction {
+            MessageDirection::Sent => eprint!(
+                " -> {}@{}{}.{}",
+                message.interface,
+                message.id,
+                if message.alive { "" } else { "[ZOMBIE]" },
+                message.name
+            ),
+            MessageDirection::Received => {
+                eprint!(" <- {}@{}.{}", message.interface, message.id, message.name)
+            }
+        }
 
-    eprint!(" <- {}@{}.{}", interface, id, msg_name);
+        print_args(message.args);
 
-    print_args(args);
+        // Add a new line.
+        eprintln!();
+    }
+}
 
-    // Add a new line.
-    eprintln!();
+/// Print the dispatched message to stderr in a following format:
+///
+/// [timestamp] <- interface@id.msg_name(args)
+pub fn print_dispatched_message(interface: &str, id: u32, msg_name: &str, args: &[Argument]) {
+    let args = args.iter().map(Argument::as_ref).collect::<Vec<_>>();
+    StderrLogger.log(&LoggedMessage::now(
+        MessageDirection::Received,
+        interface,
+        id,
+        true,
+        msg_name,
+        &args,
+    ));
 }
 
 /// Print the send message to stderr in a following format:
@@ -31,19 +149,19 @@ pub fn print_send_message(
     msg_name: &str,
     args: &[Argument],
 ) {
-    // Add timestamp to output.
-    print_timestamp();
-
-    eprint!(" -> {}@{}{}.{}", interface, id, if is_alive { "" } else { "[ZOMBIE]" }, msg_name);
-
-    print_args(args);
-
-    // Add a new line.
-    eprintln!();
+    let args = args.iter().map(Argument::as_ref).collect::<Vec<_>>();
+    StderrLogger.log(&LoggedMessage::now(
+        MessageDirection::Sent,
+        interface,
+        id,
+        is_alive,
+        msg_name,
+        &args,
+    ));
 }
 
 /// Print arguments with opening/closing bracket.
-fn print_args(args: &[Argument]) {
+fn print_args(args: &[ArgumentRef<'_>]) {
     let num_args = args.len();
 
     eprint!("(");
@@ -62,10 +180,19 @@ fn print_args(args: &[Argument]) {
 }
 
 /// Print timestamp in seconds.microseconds format.
-fn print_timestamp() {
-    if let Ok(timestamp) = SystemTime::now().duration_since(UNIX_EPOCH) {
-        let sc = timestamp.as_secs();
-        let ms = timestamp.subsec_micros();
-        eprint!("[{}.{:06}]", sc, ms);
+fn print_timestamp(timestamp: Duration) {
+    let sc = timestamp.as_secs();
+    let ms = timestamp.subsec_micros();
+    eprint!("[{}.{:06}]", sc, ms);
+}
+
+/// Check whether the `WAYLAND_DEBUG` env variable enables debug output for `side`
+///
+/// Following the C libraries, the debug output is enabled if the variable is
+/// set to `1` or to the name of the side (`client` or `server`).
+pub fn debug_enabled(side: &str) -> bool {
+    match std::env::var_os("WAYLAND_DEBUG") {
+        Some(value) => value == "1" || value == side,
+        None => false,
     }
 }
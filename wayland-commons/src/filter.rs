@@ -1,6 +1,13 @@
 //! Filter
 
-use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::VecDeque,
+    rc::Rc,
+};
+
+use smallvec::SmallVec;
 
 /// Holder of global dispatch-related data
 ///
@@ -9,27 +16,106 @@ use std::{cell::RefCell, collections::VecDeque, rc::Rc};
 /// input to all your callbacks. It allows you to share global state
 /// between your filters.
 ///
+/// It can hold several mutable references at once, as long as they are
+/// of different types. This allows independent components (for example
+/// a toolkit and the application using it) to each carry their own state
+/// through the same dispatch:
+///
+/// ```
+/// # use wayland_commons::filter::DispatchData;
+/// struct ToolkitState { frames: u32 }
+/// struct AppState { running: bool }
+///
+/// let mut toolkit = ToolkitState { frames: 0 };
+/// let mut app = AppState { running: true };
+/// let mut data = DispatchData::wrap(&mut toolkit).with(&mut app);
+///
+/// data.get::<ToolkitState>().unwrap().frames += 1;
+/// data.get::<AppState>().unwrap().running = false;
+/// assert!(data.get::<u32>().is_none());
+/// ```
+///
 /// The main method of interest is the `get` method, which allows you to
-/// access a `&mut _` reference to the global data itself. The other methods
-/// are mostly used internally by the crate.
+/// access a `&mut _` reference to the global data of a given type. The other
+/// methods are mostly used internally by the crate.
 pub struct DispatchData<'a> {
-    data: &'a mut dyn std::any::Any,
+    data: SmallVec<[&'a mut dyn Any; 2]>,
+    // the `DispatchData` this one was reborrowed from
+    parent: Option<&'a mut (dyn DataSet + 'a)>,
+}
+
+// A set of mutable references indexed by their type
+trait DataSet {
+    fn get_any(&mut self, id: TypeId) -> Option<&mut dyn Any>;
+    fn contains_any(&self, id: TypeId) -> bool;
+}
+
+impl<'a> DataSet for DispatchData<'a> {
+    fn get_any(&mut self, id: TypeId) -> Option<&mut dyn Any> {
+        match self.data.iter().position(|data| (**data).type_id() == id) {
+            Some(i) => Some(&mut *self.data[i]),
+            None => self.parent.as_mut().and_then(|parent| parent.get_any(id)),
+        }
+    }
+
+    fn contains_any(&self, id: TypeId) -> bool {
+        if self.data.iter().any(|data| (**data).type_id() == id) {
+            return true;
+        }
+        match self.parent {
+            Some(ref parent) => parent.contains_any(id),
+            None => false,
+        }
+    }
 }
 
 impl<'a> DispatchData<'a> {
+    /// Create an empty `DispatchData`
+    pub fn new() -> DispatchData<'a> {
+        DispatchData { data: SmallVec::new(), parent: None }
+    }
+
     /// Access the dispatch data knowing its type
     ///
-    /// Will return `None` if the provided type is not the correct
-    /// inner type.
-    pub fn get<T: std::any::Any>(&mut self) -> Option<&mut T> {
-        self.data.downcast_mut()
+    /// Will return `None` if no data of the provided type is stored
+    /// in this `DispatchData`.
+    pub fn get<T: Any>(&mut self) -> Option<&mut T> {
+        self.get_any(TypeId::of::<T>()).and_then(|data| data.downcast_mut())
+    }
+
+    /// Check whether data of the provided type is stored in this `DispatchData`
+    pub fn contains<T: Any>(&self) -> bool {
+        self.contains_any(TypeId::of::<T>())
     }
 
     /// Wrap a mutable reference
     ///
     /// This creates a new `DispatchData` from a mutable reference
-    pub fn wrap<T: std::any::Any>(data: &'a mut T) -> DispatchData<'a> {
-        DispatchData { data }
+    pub fn wrap<T: Any>(data: &'a mut T) -> DispatchData<'a> {
+        DispatchData::new().with(data)
+    }
+
+    /// Add a mutable reference to this `DispatchData`
+    ///
+    /// If a reference to data of the same type was already stored, it is replaced
+    /// and returned. If this `DispatchData` was reborrowed, the data of the same type
+    /// stored in the original one is hidden until this one is dropped.
+    pub fn insert<T: Any>(&mut self, data: &'a mut T) -> Option<&'a mut T> {
+        match self.data.iter().position(|old| old.is::<T>()) {
+            Some(i) => std::mem::replace(&mut self.data[i], data).downcast_mut(),
+            None => {
+                self.data.push(data);
+                None
+            }
+        }
+    }
+
+    /// Add a mutable reference to this `DispatchData`, builder-style
+    ///
+    /// See `insert` for details.
+    pub fn with<T: Any>(mut self, data: &'a mut T) -> DispatchData<'a> {
+        self.insert(data);
+        self
     }
 
     /// Reborrows this `DispatchData` to create a new one with the same content
@@ -38,7 +124,28 @@ impl<'a> DispatchData<'a> {
     /// callback stack by value. It is basically a noop only there to ease
     /// work with the borrow checker.
     pub fn reborrow(&mut self) -> DispatchData {
-        DispatchData { data: &mut *self.data }
+        // a reborrow of a reborrow points directly to the original data
+        let parent: &mut dyn DataSet = if self.data.is_empty() && self.parent.is_some() {
+            match self.parent {
+                Some(ref mut parent) => &mut **parent,
+                None => unreachable!(),
+            }
+        } else {
+            self
+        };
+        DispatchData { data: SmallVec::new(), parent: Some(parent) }
+    }
+}
+
+impl<'a> Default for DispatchData<'a> {
+    fn default() -> DispatchData<'a> {
+        DispatchData::new()
+    }
+}
+
+impl<'a, T: Any> From<&'a mut T> for DispatchData<'a> {
+    fn from(data: &'a mut T) -> DispatchData<'a> {
+        DispatchData::wrap(data)
     }
 }
 
@@ -95,3 +202,231 @@ impl<E> Filter<E> {
         }
     }
 }
+
+impl<E: 'static> Filter<E> {
+    /// Create a filter forwarding its messages to this one, after transforming them
+    ///
+    /// This allows you to aggregate messages of different types into a single filter,
+    /// typically by wrapping them into the variants of an enum:
+    ///
+    /// ```
+    /// # use wayland_commons::filter::{DispatchData, Filter};
+    /// enum Msg {
+    ///     Number(u32),
+    ///     Text(String),
+    /// }
+    ///
+    /// let filter = Filter::new(|msg: Msg, _, _| match msg {
+    ///     Msg::Number(n) => println!("Number: {}", n),
+    ///     Msg::Text(t) => println!("Text: {}", t),
+    /// });
+    /// let numbers: Filter<u32> = filter.map(Msg::Number);
+    /// let texts: Filter<String> = filter.map(Msg::Text);
+    ///
+    /// numbers.send(42, DispatchData::wrap(&mut ()));
+    /// texts.send("Hello".into(), DispatchData::wrap(&mut ()));
+    /// ```
+    pub fn map<E2, F>(&self, mut f: F) -> Filter<E2>
+    where
+        F: FnMut(E2) -> E + 'static,
+    {
+        let target = self.clone();
+        Filter::new(move |evt, _, data| target.send(f(evt), data))
+    }
+
+    /// Create a filter forwarding its messages to this one, if the transformation succeeds
+    ///
+    /// Messages for which the closure returns `None` are discarded.
+    pub fn filter_map<E2, F>(&self, mut f: F) -> Filter<E2>
+    where
+        F: FnMut(E2) -> Option<E> + 'static,
+    {
+        let target = self.clone();
+        Filter::new(move |evt, _, data| {
+            if let Some(evt) = f(evt) {
+                target.send(evt, data)
+            }
+        })
+    }
+
+    /// Create a filter sending a copy of each message to this filter and to `other`
+    ///
+    /// Messages are sent to this filter first.
+    pub fn tee(&self, other: &Filter<E>) -> Filter<E>
+    where
+        E: Clone,
+    {
+        let first = self.clone();
+        let second = other.clone();
+        Filter::new(move |evt: E, _, mut data| {
+            first.send(evt.clone(), data.reborrow());
+            second.send(evt, data);
+        })
+    }
+}
+
+struct RouterInner<K, E> {
+    key: Box<dyn Fn(&E) -> K>,
+    routes: RefCell<Vec<(K, Filter<E>)>>,
+    fallback: RefCell<Option<Filter<E>>>,
+}
+
+/// A router dispatching messages to different filters
+///
+/// The router extracts a key from each message, typically identifying the object it
+/// originates from, and sends the message to the filter registered for this key. Messages
+/// for which no route exists are sent to the fallback filter if any, and discarded otherwise.
+///
+/// This allows you to handle the messages of several instances of the same interface (like
+/// several seats or outputs) with a different filter for each, while assigning them a single
+/// filter obtained from `Router::filter()`.
+///
+/// Routes are not removed automatically, you need to call `remove_route()` when the
+/// corresponding object is destroyed.
+///
+/// The `Router` can be cloned, and all clones share the same routes. However it is not
+/// threadsafe.
+pub struct Router<K, E> {
+    inner: Rc<RouterInner<K, E>>,
+}
+
+impl<K, E> Clone for Router<K, E> {
+    fn clone(&self) -> Router<K, E> {
+        Router { inner: self.inner.clone() }
+    }
+}
+
+impl<K: PartialEq + 'static, E: 'static> Router<K, E> {
+    /// Create a new router, using given closure to extract the key of each message
+    pub fn new<F: Fn(&E) -> K + 'static>(key: F) -> Router<K, E> {
+        Router {
+            inner: Rc::new(RouterInner {
+                key: Box::new(key),
+                routes: RefCell::new(Vec::new()),
+                fallback: RefCell::new(None),
+            }),
+        }
+    }
+
+    /// Send the messages with given key to given filter
+    ///
+    /// This replaces the previous route for this key, if any.
+    pub fn add_route(&self, key: K, filter: Filter<E>) {
+        let mut routes = self.inner.routes.borrow_mut();
+        match routes.iter_mut().find(|(k, _)| *k == key) {
+            Some(route) => route.1 = filter,
+            None => routes.push((key, filter)),
+        }
+    }
+
+    /// Remove the route for given key, returning its filter
+    pub fn remove_route(&self, key: &K) -> Option<Filter<E>> {
+        let mut routes = self.inner.routes.borrow_mut();
+        let index = routes.iter().position(|(k, _)| k == key)?;
+        Some(routes.remove(index).1)
+    }
+
+    /// Set the filter receiving the messages for which no route exists
+    pub fn set_fallback(&self, filter: Option<Filter<E>>) {
+        *self.inner.fallback.borrow_mut() = filter;
+    }
+
+    /// Create a filter sending its messages through this router
+    pub fn filter(&self) -> Filter<E> {
+        let inner = self.inner.clone();
+        Filter::new(move |evt, _, data| {
+            let key = (inner.key)(&evt);
+            // clone the target so that the routes can be modified from the filters
+            let target = inner
+                .routes
+                .borrow()
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, filter)| filter.clone())
+                .or_else(|| inner.fallback.borrow().clone());
+            if let Some(target) = target {
+                target.send(evt, data);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect<E: 'static>() -> (Filter<E>, Rc<RefCell<Vec<E>>>) {
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let received2 = received.clone();
+        (Filter::new(move |evt, _, _| received2.borrow_mut().push(evt)), received)
+    }
+
+    #[test]
+    fn map_filter_map() {
+        let (filter, received) = collect::<i64>();
+        let mapped = filter.map(|v: u32| i64::from(v) * 2);
+        let filtered = filter.filter_map(|v: i32| if v < 0 { Some(i64::from(v)) } else { None });
+
+        mapped.send(21, DispatchData::wrap(&mut ()));
+        filtered.send(12, DispatchData::wrap(&mut ()));
+        filtered.send(-1, DispatchData::wrap(&mut ()));
+
+        assert_eq!(*received.borrow(), vec![42, -1]);
+    }
+
+    #[test]
+    fn tee() {
+        let (first, first_received) = collect::<u32>();
+        let (second, second_received) = collect::<u32>();
+        let both = first.tee(&second);
+
+        both.send(1, DispatchData::wrap(&mut ()));
+        first.send(2, DispatchData::wrap(&mut ()));
+
+        assert_eq!(*first_received.borrow(), vec![1, 2]);
+        assert_eq!(*second_received.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn router() {
+        let (first, first_received) = collect::<(u32, &str)>();
+        let (second, second_received) = collect::<(u32, &str)>();
+        let (fallback, fallback_received) = collect::<(u32, &str)>();
+
+        let router = Router::new(|&(id, _): &(u32, &str)| id);
+        router.add_route(1, first);
+        router.add_route(2, second.clone());
+        let filter = router.filter();
+
+        filter.send((1, "a"), DispatchData::wrap(&mut ()));
+        filter.send((2, "b"), DispatchData::wrap(&mut ()));
+        // no fallback, discarded
+        filter.send((3, "c"), DispatchData::wrap(&mut ()));
+
+        router.set_fallback(Some(fallback));
+        assert!(router.remove_route(&2).is_some());
+        assert!(router.remove_route(&2).is_none());
+        filter.send((2, "d"), DispatchData::wrap(&mut ()));
+
+        // replace a route
+        router.add_route(1, second);
+        filter.send((1, "e"), DispatchData::wrap(&mut ()));
+
+        assert_eq!(*first_received.borrow(), vec![(1, "a")]);
+        assert_eq!(*second_received.borrow(), vec![(2, "b"), (1, "e")]);
+        assert_eq!(*fallback_received.borrow(), vec![(2, "d")]);
+    }
+
+    #[test]
+    fn router_reentrant() {
+        let router = Router::new(|&id: &u32| id);
+        let (collected, received) = collect::<u32>();
+        let router2 = router.clone();
+        // the first message for 1 replaces the route for 1
+        router.add_route(1, Filter::new(move |_, _, _| router2.add_route(1, collected.clone())));
+        let filter = router.filter();
+        filter.send(1, DispatchData::wrap(&mut ()));
+        filter.send(1, DispatchData::wrap(&mut ()));
+        assert_eq!(*received.borrow(), vec![1]);
+    }
+}
@@ -21,6 +21,7 @@ extern crate nix;
 use std::os::raw::c_void;
 use wayland_sys::common as syscom;
 
+pub mod capture;
 pub mod debug;
 pub mod filter;
 pub mod map;
@@ -58,9 +59,29 @@ pub trait MessageGroup: Sized {
         meta: &Meta,
     ) -> Option<crate::map::Object<Meta>>;
     /// Construct a message from its raw representation
-    fn from_raw(msg: wire::Message, map: &mut Self::Map) -> Result<Self, ()>;
+    fn from_raw(msg: wire::Message, map: &mut Self::Map) -> Result<Self, wire::FromRawError>;
+    /// Construct a message from its borrowed raw representation
+    ///
+    /// Contrary to `from_raw`, this does not require the arguments of the message
+    /// to be copied beforehand, and thus only allocates for the strings and arrays
+    /// of the constructed message.
+    ///
+    /// The file descriptors of `msg` are duplicated, they remain owned by the owner
+    /// of the buffers `msg` borrows.
+    ///
+    /// The default implementation copies the whole message with `MessageRef::to_owned()`
+    /// and forwards it to `from_raw`.
+    fn from_raw_ref(
+        msg: wire::MessageRef,
+        map: &mut Self::Map,
+    ) -> Result<Self, wire::FromRawError> {
+        Self::from_raw(msg.to_owned().map_err(wire::FromRawError::DupFdFailed)?, map)
+    }
     /// Turn this message into its raw representation
-    fn into_raw(self, send_id: u32) -> wire::Message;
+    ///
+    /// The file descriptors of the message are borrowed, the raw message holds
+    /// duplicates of them. This fails if they cannot be duplicated.
+    fn into_raw(self, send_id: u32) -> Result<wire::Message, wire::MessageWriteError>;
     /// Construct a message of this group from its C representation
     ///
     /// # Safety
@@ -71,7 +92,7 @@ pub trait MessageGroup: Sized {
         obj: *mut c_void,
         opcode: u32,
         args: *const syscom::wl_argument,
-    ) -> Result<Self, ()>;
+    ) -> Result<Self, wire::FromRawError>;
     /// Build a C representation of this message
     ///
     /// It can only be accessed from the provided closure, and this consumes
@@ -129,18 +150,18 @@ impl MessageGroup for NoMessage {
     fn child<M: self::map::ObjectMetadata>(_: u16, _: u32, _: &M) -> Option<crate::map::Object<M>> {
         None
     }
-    fn from_raw(_: wire::Message, _: &mut ()) -> Result<Self, ()> {
-        Err(())
+    fn from_raw(msg: wire::Message, _: &mut ()) -> Result<Self, wire::FromRawError> {
+        Err(wire::FromRawError::UnknownOpcode(msg.opcode))
     }
-    fn into_raw(self, _: u32) -> wire::Message {
+    fn into_raw(self, _: u32) -> Result<wire::Message, wire::MessageWriteError> {
         match self {}
     }
     unsafe fn from_raw_c(
         _obj: *mut c_void,
-        _opcode: u32,
+        opcode: u32,
         _args: *const syscom::wl_argument,
-    ) -> Result<Self, ()> {
-        Err(())
+    ) -> Result<Self, wire::FromRawError> {
+        Err(wire::FromRawError::UnknownOpcode(opcode as u16))
     }
     fn as_raw_c_in<F, T>(self, _f: F) -> T
     where
@@ -102,12 +102,13 @@ fn childs_from<M: MessageGroup, Meta: ObjectMetadata>(
 pub struct ObjectMap<Meta: ObjectMetadata> {
     client_objects: Vec<Option<Object<Meta>>>,
     server_objects: Vec<Option<Object<Meta>>>,
+    client_len: usize,
 }
 
 impl<Meta: ObjectMetadata> ObjectMap<Meta> {
     /// Create a new empty object map
     pub fn new() -> ObjectMap<Meta> {
-        ObjectMap { client_objects: Vec::new(), server_objects: Vec::new() }
+        ObjectMap { client_objects: Vec::new(), server_objects: Vec::new(), client_len: 0 }
     }
 
     /// Find an object in the store
@@ -132,7 +133,9 @@ impl<Meta: ObjectMetadata> ObjectMap<Meta> {
                 *place = None;
             }
         } else if let Some(place) = self.client_objects.get_mut((id - 1) as usize) {
-            *place = None;
+            if place.take().is_some() {
+                self.client_len -= 1;
+            }
         }
     }
 
@@ -146,12 +149,15 @@ impl<Meta: ObjectMetadata> ObjectMap<Meta> {
         } else if id >= SERVER_ID_LIMIT {
             insert_in_at(&mut self.server_objects, (id - SERVER_ID_LIMIT) as usize, object)
         } else {
-            insert_in_at(&mut self.client_objects, (id - 1) as usize, object)
+            insert_in_at(&mut self.client_objects, (id - 1) as usize, object)?;
+            self.client_len += 1;
+            Ok(())
         }
     }
 
     /// Allocate a new id for an object in the client namespace
     pub fn client_insert_new(&mut self, object: Object<Meta>) -> u32 {
+        self.client_len += 1;
         insert_in(&mut self.client_objects, object) + 1
     }
 
@@ -160,6 +166,13 @@ impl<Meta: ObjectMetadata> ObjectMap<Meta> {
         insert_in(&mut self.server_objects, object) + SERVER_ID_LIMIT
     }
 
+    /// Number of objects in the client namespace of the store
+    ///
+    /// These are the objects created by the client, including the display.
+    pub fn client_len(&self) -> usize {
+        self.client_len
+    }
+
     /// Mutably access an object of the map
     pub fn with<T, F: FnOnce(&mut Object<Meta>) -> T>(&mut self, id: u32, f: F) -> Result<T, ()> {
         if id == 0 {
@@ -6,8 +6,13 @@ use nix::{
     sys::{socket, uio},
     Result as NixResult,
 };
+use smallvec::SmallVec;
 
-use crate::wire::{ArgumentType, Message, MessageParseError, MessageWriteError};
+use crate::capture::MessageRecorder;
+use crate::debug::MessageDirection;
+use crate::wire::{
+    Argument, ArgumentType, Message, MessageParseError, MessageRef, MessageWriteError, INLINE_ARGS,
+};
 
 /// Maximum number of FD that can be sent in a single socket message
 pub const MAX_FDS_OUT: usize = 28;
@@ -28,18 +33,20 @@ impl Socket {
     ///
     /// A single socket message can contain several wayland messages
     ///
-    /// The `fds` slice should not be longer than `MAX_FDS_OUT`, and the `bytes`
-    /// slice should not be longer than `MAX_BYTES_OUT` otherwise the receiving
-    /// end may lose some data.
-    pub fn send_msg(&self, bytes: &[u8], fds: &[RawFd]) -> NixResult<()> {
+    /// The `fds` slice should not be longer than `MAX_FDS_OUT`, otherwise the
+    /// receiving end may lose some of them.
+    ///
+    /// Returns the number of bytes actually sent, which may be less than the
+    /// length of `bytes` if the socket is full. If any byte was sent, all the
+    /// fds were sent along with it.
+    pub fn send_msg(&self, bytes: &[u8], fds: &[RawFd]) -> NixResult<usize> {
         let iov = [uio::IoVec::from_slice(bytes)];
         if !fds.is_empty() {
             let cmsgs = [socket::ControlMessage::ScmRights(fds)];
-            socket::sendmsg(self.fd, &iov, &cmsgs, socket::MsgFlags::MSG_DONTWAIT, None)?;
+            socket::sendmsg(self.fd, &iov, &cmsgs, socket::MsgFlags::MSG_DONTWAIT, None)
         } else {
-            socket::sendmsg(self.fd, &iov, &[], socket::MsgFlags::MSG_DONTWAIT, None)?;
-        };
-        Ok(())
+            socket::sendmsg(self.fd, &iov, &[], socket::MsgFlags::MSG_DONTWAIT, None)
+        }
     }
 
     /// Receive a single message from the socket
@@ -101,28 +108,125 @@ impl Drop for Socket {
  * BufferedSocket
  */
 
+/// Size policy for the buffers of a `BufferedSocket`
+///
+/// The outgoing data buffer starts with `initial` bytes, and is allowed to
+/// grow up to `max` bytes when messages are sent faster than the other end
+/// reads them, or when a single message does not fit in it. The incoming data
+/// buffer starts twice as big as `initial`, and grows as needed to hold a
+/// full message.
+///
+/// The file descriptor buffers are not affected by this policy: at most
+/// `MAX_FDS_OUT` fds are buffered for sending, which are always sent along
+/// with the first bytes following them.
+///
+/// The default is `MAX_BYTES_OUT` for both values, which never grows the
+/// outgoing buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BufferCapacity {
+    /// Initial size in bytes of the outgoing data buffer
+    pub initial: usize,
+    /// Maximum size in bytes the outgoing data buffer can grow to
+    pub max: usize,
+}
+
+impl BufferCapacity {
+    /// A policy with an initial size and a maximum size, in bytes
+    ///
+    /// The sizes are rounded up to a multiple of 4, and `max` is raised to
+    /// `initial` if it is smaller.
+    pub fn new(initial: usize, max: usize) -> BufferCapacity {
+        let initial = ::std::cmp::max(round_up_words(initial), 4) * 4;
+        let max = ::std::cmp::max(round_up_words(max) * 4, initial);
+        BufferCapacity { initial, max }
+    }
+
+    /// A policy whose outgoing buffer has a fixed size, in bytes
+    pub fn fixed(size: usize) -> BufferCapacity {
+        BufferCapacity::new(size, size)
+    }
+}
+
+impl Default for BufferCapacity {
+    fn default() -> BufferCapacity {
+        BufferCapacity::fixed(MAX_BYTES_OUT)
+    }
+}
+
+// number of 32-bits words needed to store `bytes` bytes
+fn round_up_words(bytes: usize) -> usize {
+    (bytes + 3) / 4
+}
+
 /// An adapter around a raw Socket that directly handles buffering and
 /// conversion from/to wayland messages
 pub struct BufferedSocket {
     socket: Socket,
     in_data: Buffer<u32>,
-    in_fds: Buffer<RawFd>,
+    in_fds: FdBuffer,
     out_data: Buffer<u32>,
     out_fds: Buffer<RawFd>,
+    // number of bytes at the front of out_data that were already sent
+    out_sent: usize,
+    capacity: BufferCapacity,
+    recorder: Option<Box<dyn MessageRecorder>>,
 }
 
 impl BufferedSocket {
     /// Wrap a Socket into a Buffered Socket
     pub fn new(socket: Socket) -> BufferedSocket {
+        BufferedSocket::with_capacity(socket, BufferCapacity::default())
+    }
+
+    /// Wrap a Socket into a Buffered Socket, with given buffer sizes
+    pub fn with_capacity(socket: Socket, capacity: BufferCapacity) -> BufferedSocket {
+        let capacity = BufferCapacity::new(capacity.initial, capacity.max);
         BufferedSocket {
             socket,
-            in_data: Buffer::new(2 * MAX_BYTES_OUT / 4), // Incoming buffers are twice as big in order to be
-            in_fds: Buffer::new(2 * MAX_FDS_OUT),        // able to store leftover data if needed
-            out_data: Buffer::new(MAX_BYTES_OUT / 4),
+            in_data: Buffer::new(2 * capacity.initial / 4), // Incoming buffers are twice as big in order to be
+            in_fds: FdBuffer::new(2 * MAX_FDS_OUT),         // able to store leftover data if needed
+            out_data: Buffer::new(capacity.initial / 4),
             out_fds: Buffer::new(MAX_FDS_OUT),
+            out_sent: 0,
+            capacity,
+            recorder: None,
         }
     }
 
+    /// Change the buffer size policy of this socket
+    ///
+    /// Buffers that are smaller than the new initial size are grown
+    /// immediately, but buffers are never shrunk.
+    pub fn set_capacity(&mut self, capacity: BufferCapacity) {
+        let capacity = BufferCapacity::new(capacity.initial, capacity.max);
+        self.out_data.grow(capacity.initial / 4);
+        self.in_data.grow(2 * capacity.initial / 4);
+        self.capacity = capacity;
+    }
+
+    /// Get the current buffer size policy of this socket
+    pub fn capacity(&self) -> BufferCapacity {
+        self.capacity
+    }
+
+    /// Set the recorder of this socket
+    ///
+    /// Once set, the recorder is notified of every message written to or read
+    /// from this socket. Setting it to `None` stops the recording.
+    ///
+    /// Returns the previous recorder, if any.
+    pub fn set_recorder(
+        &mut self,
+        recorder: Option<Box<dyn MessageRecorder>>,
+    ) -> Option<Box<dyn MessageRecorder>> {
+        ::std::mem::replace(&mut self.recorder, recorder)
+    }
+
+    /// Number of bytes in the outgoing buffer that are not yet sent
+    pub fn pending_bytes(&self) -> usize {
+        self.out_data.get_contents().len() * 4 - self.out_sent
+    }
+
     /// Get direct access to the underlying socket
     pub fn get_socket(&mut self) -> &mut Socket {
         &mut self.socket
@@ -130,30 +234,67 @@ impl BufferedSocket {
 
     /// Retrieve ownership of the underlying Socket
     ///
-    /// Any leftover content in the internal buffers will be lost
+    /// Any leftover content in the internal buffers will be lost, and the file
+    /// descriptors that were received but not read yet are closed.
     pub fn into_socket(self) -> Socket {
         self.socket
     }
 
     /// Flush the contents of the outgoing buffer into the socket
+    ///
+    /// If the socket is full, the error `Error::Sys(EAGAIN)` is returned and
+    /// the data that could not be sent is kept for the next flush.
     pub fn flush(&mut self) -> NixResult<()> {
-        {
-            let words = self.out_data.get_contents();
-            let bytes = unsafe {
-                ::std::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * 4)
+        loop {
+            let remaining = {
+                let words = self.out_data.get_contents();
+                let bytes = unsafe {
+                    ::std::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * 4)
+                };
+                let bytes = &bytes[self.out_sent..];
+                let fds = self.out_fds.get_contents();
+                let sent = self.socket.send_msg(bytes, fds)?;
+                for &fd in fds {
+                    // once the fds are sent, we can close them
+                    let _ = ::nix::unistd::close(fd);
+                }
+                self.out_sent += sent;
+                bytes.len() - sent
             };
-            let fds = self.out_fds.get_contents();
-            self.socket.send_msg(bytes, fds)?;
-            for &fd in fds {
-                // once the fds are sent, we can close them
-                let _ = ::nix::unistd::close(fd);
+            self.out_fds.clear();
+            if remaining == 0 {
+                break;
             }
         }
         self.out_data.clear();
-        self.out_fds.clear();
+        self.out_sent = 0;
         Ok(())
     }
 
+    // internal method
+    //
+    // drops the data that was already sent from the front of the
+    // outgoing buffer
+    fn compact_outgoing_buffer(&mut self) {
+        self.out_data.offset += self.out_sent / 4;
+        self.out_sent %= 4;
+        self.out_data.move_to_front();
+    }
+
+    // internal method
+    //
+    // doubles the size of the outgoing buffer if the capacity policy allows it,
+    // returns true if it was grown
+    fn grow_outgoing_buffer(&mut self) -> bool {
+        let size = self.out_data.storage.len();
+        let max = self.capacity.max / 4;
+        if size >= max {
+            return false;
+        }
+        self.out_data.grow(::std::cmp::min(2 * size, max));
+        true
+    }
+
     // internal method
     //
     // attempts to write a message in the internal out buffers,
@@ -167,6 +308,20 @@ impl BufferedSocket {
             self.out_fds.get_writable_storage(),
         ) {
             Ok((bytes_out, fds_out)) => {
+                if let Some(ref mut recorder) = self.recorder {
+                    // record the message as it was written in the buffers, the fds it
+                    // contains remain owned by the socket
+                    let signature = msg
+                        .args
+                        .iter()
+                        .map(Argument::get_type)
+                        .collect::<SmallVec<[ArgumentType; INLINE_ARGS]>>();
+                    let data = &self.out_data.storage[self.out_data.occupied..][..bytes_out];
+                    let fds = &self.out_fds.storage[self.out_fds.occupied..][..fds_out];
+                    if let Ok((msg, _, _)) = MessageRef::from_raw(data, &signature, fds) {
+                        recorder.record(MessageDirection::Sent, msg);
+                    }
+                }
                 self.out_data.advance(bytes_out);
                 self.out_fds.advance(fds_out);
                 Ok(true)
@@ -179,18 +334,41 @@ impl BufferedSocket {
     /// Write a message to the outgoing buffer
     ///
     /// This method may flush the internal buffer if necessary (if it is full).
+    /// If the socket is full as well, the buffer is grown according to the
+    /// capacity policy of this socket.
     ///
     /// If the message is too big to fit in the buffer, the error `Error::Sys(E2BIG)`
-    /// will be returned.
+    /// will be returned. If the buffer is full and cannot grow anymore, the
+    /// error `Error::Sys(EAGAIN)` will be returned.
     pub fn write_message(&mut self, msg: &Message) -> NixResult<()> {
         if !self.attempt_write_message(msg)? {
             // the attempt failed, there is not enough space in the buffer
             // we need to flush it
-            self.flush()?;
-            if !self.attempt_write_message(msg)? {
-                // If this fails again, this means the message is too big
-                // to be transmitted at all
-                return Err(::nix::Error::Sys(::nix::errno::Errno::E2BIG));
+            let flushed = match self.flush() {
+                Ok(()) => Ok(()),
+                Err(e @ ::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => {
+                    // the other end is not reading fast enough, keep what
+                    // is left and try to make room for the message
+                    self.compact_outgoing_buffer();
+                    Err(e)
+                }
+                Err(e) => return Err(e),
+            };
+            let fd_count = msg.args.iter().filter(|a| a.get_type() == ArgumentType::Fd).count();
+            if fd_count > self.out_fds.get_writable_storage().len() {
+                // the fd buffer does not grow, the message can only be written
+                // once the pending fds are sent
+                return Err(flushed.err().unwrap_or(::nix::Error::Sys(::nix::errno::Errno::E2BIG)));
+            }
+            while !self.attempt_write_message(msg)? {
+                if !self.grow_outgoing_buffer() {
+                    // If this fails again and the buffer cannot grow, this means
+                    // either the message is too big to be transmitted at all, or the
+                    // other end needs to read some data first
+                    return Err(flushed
+                        .err()
+                        .unwrap_or(::nix::Error::Sys(::nix::errno::Errno::E2BIG)));
+                }
             }
         }
         Ok(())
@@ -199,12 +377,24 @@ impl BufferedSocket {
     /// Try to fill the incoming buffers of this socket, to prepare
     /// a new round of parsing.
     pub fn fill_incoming_buffers(&mut self) -> NixResult<()> {
+        self.in_fds.close_borrowed();
         // clear the buffers if they have no content
         if !self.in_data.has_content() {
             self.in_data.clear();
         }
-        if !self.in_fds.has_content() {
-            self.in_fds.clear();
+        if !self.in_fds.buffer.has_content() {
+            self.in_fds.buffer.clear();
+        }
+        // make room for the rest of a partially received message
+        self.in_data.move_to_front();
+        self.in_fds.buffer.move_to_front();
+        if let [_, header, ..] = *self.in_data.get_contents() {
+            let len = round_up_words((header >> 16) as usize);
+            self.in_data.grow(len);
+        }
+        if self.in_fds.buffer.get_writable_storage().len() < MAX_FDS_OUT {
+            let size = self.in_fds.buffer.occupied + MAX_FDS_OUT;
+            self.in_fds.buffer.grow(size);
         }
         // receive a message
         let (in_bytes, in_fds) = {
@@ -212,7 +402,7 @@ impl BufferedSocket {
             let bytes = unsafe {
                 ::std::slice::from_raw_parts_mut(words.as_ptr() as *mut u8, words.len() * 4)
             };
-            let fds = self.in_fds.get_writable_storage();
+            let fds = self.in_fds.buffer.get_writable_storage();
             self.socket.rcv_msg(bytes, fds)?
         };
         if in_bytes == 0 {
@@ -220,8 +410,8 @@ impl BufferedSocket {
             return Err(::nix::Error::Sys(::nix::errno::Errno::EPIPE));
         }
         // advance the storage
-        self.in_data.advance(in_bytes / 4 + if in_bytes % 4 > 0 { 1 } else { 0 });
-        self.in_fds.advance(in_fds);
+        self.in_data.advance(round_up_words(in_bytes));
+        self.in_fds.buffer.advance(in_fds);
         Ok(())
     }
 
@@ -240,35 +430,59 @@ impl BufferedSocket {
     /// - `Err(e)`: an I/O error occurred reading from the socked, details are in `e`
     ///   (this can be a "wouldblock" error, which just means that no message is available
     ///   to read)
-    pub fn read_one_message<F>(&mut self, mut signature: F) -> Result<Message, MessageParseError>
+    pub fn read_one_message<F>(&mut self, signature: F) -> Result<Message, MessageParseError>
     where
         F: FnMut(u32, u16) -> Option<&'static [ArgumentType]>,
     {
-        let (msg, read_data, read_fd) = {
-            let data = self.in_data.get_contents();
-            let fds = self.in_fds.get_contents();
-            if data.len() < 2 {
-                return Err(MessageParseError::MissingData);
-            }
-            let object_id = data[0];
-            let opcode = (data[1] & 0x0000_FFFF) as u16;
-            if let Some(sig) = signature(object_id, opcode) {
-                match Message::from_raw(data, sig, fds) {
-                    Ok((msg, rest_data, rest_fds)) => {
-                        (msg, data.len() - rest_data.len(), fds.len() - rest_fds.len())
-                    }
-                    // TODO: gracefully handle wayland messages split across unix messages ?
-                    Err(e) => return Err(e),
-                }
-            } else {
-                // no signature found ?
-                println!("Socket malformed signature? opcode {}, object_id {}", opcode, object_id);
-                return Err(MessageParseError::Malformed);
-            }
+        let msg = self.read_one_message_ref(signature)?;
+        // the socket owns the fds of the message, and gives them up to the returned message
+        let msg = unsafe { msg.take_owned() };
+        self.in_fds.borrowed = 0;
+        Ok(msg)
+    }
+
+    /// Read a single message from the incoming buffers socket, without copying it
+    ///
+    /// This behaves like `read_one_message()`, but the returned message borrows
+    /// the internal buffers of the socket rather than allocating its string and
+    /// array arguments.
+    ///
+    /// The file descriptors of the returned message remain owned by the socket,
+    /// which closes them once the next message is read. They can be kept by
+    /// duplicating them, for example with `MessageRef::to_owned()`.
+    pub fn read_one_message_ref<F>(
+        &mut self,
+        mut signature: F,
+    ) -> Result<MessageRef<'_>, MessageParseError>
+    where
+        F: FnMut(u32, u16) -> Option<&'static [ArgumentType]>,
+    {
+        self.in_fds.close_borrowed();
+        // borrow the fields separately, so that the read offsets can be updated
+        // while the message borrows the storage
+        let in_data = &mut self.in_data;
+        let in_fds = &mut self.in_fds;
+        let data = &in_data.storage[in_data.offset..in_data.occupied];
+        let fds = &in_fds.buffer.storage[in_fds.buffer.offset..in_fds.buffer.occupied];
+        if data.len() < 2 {
+            return Err(MessageParseError::MissingData);
+        }
+        let object_id = data[0];
+        let opcode = (data[1] & 0x0000_FFFF) as u16;
+        let (msg, rest_data, rest_fds) = if let Some(sig) = signature(object_id, opcode) {
+            MessageRef::from_raw(data, sig, fds)?
+        } else {
+            // no signature found ?
+            return Err(MessageParseError::Malformed);
         };
 
-        self.in_data.offset(read_data);
-        self.in_fds.offset(read_fd);
+        in_data.offset += data.len() - rest_data.len();
+        in_fds.buffer.offset += fds.len() - rest_fds.len();
+        in_fds.borrowed = fds.len() - rest_fds.len();
+
+        if let Some(ref mut recorder) = self.recorder {
+            recorder.record(MessageDirection::Received, msg);
+        }
 
         Ok(msg)
     }
@@ -300,12 +514,48 @@ impl BufferedSocket {
     ///   to read)
     pub fn read_messages<F1, F2>(
         &mut self,
-        mut signature: F1,
+        signature: F1,
         mut callback: F2,
     ) -> NixResult<Result<usize, MessageParseError>>
     where
         F1: FnMut(u32, u16) -> Option<&'static [ArgumentType]>,
         F2: FnMut(Message) -> bool,
+    {
+        self.read_messages_with(signature, |socket, signature| {
+            socket.read_one_message(signature).map(&mut callback)
+        })
+    }
+
+    /// Read and deserialize messages from the socket, without copying them
+    ///
+    /// This behaves like `read_messages()`, but the messages given to the callback
+    /// borrow the internal buffers of the socket, as with `read_one_message_ref()`.
+    /// Their file descriptors remain owned by the socket, which closes them once
+    /// the next message is read.
+    pub fn read_messages_ref<F1, F2>(
+        &mut self,
+        signature: F1,
+        mut callback: F2,
+    ) -> NixResult<Result<usize, MessageParseError>>
+    where
+        F1: FnMut(u32, u16) -> Option<&'static [ArgumentType]>,
+        F2: FnMut(MessageRef<'_>) -> bool,
+    {
+        self.read_messages_with(signature, |socket, signature| {
+            socket.read_one_message_ref(signature).map(&mut callback)
+        })
+    }
+
+    // `read_one` reads a single message and processes it, returning whether
+    // reading should continue
+    fn read_messages_with<F1, F2>(
+        &mut self,
+        mut signature: F1,
+        mut read_one: F2,
+    ) -> NixResult<Result<usize, MessageParseError>>
+    where
+        F1: FnMut(u32, u16) -> Option<&'static [ArgumentType]>,
+        F2: FnMut(&mut Self, &mut F1) -> Result<bool, MessageParseError>,
     {
         // message parsing
         let mut dispatched = 0;
@@ -314,9 +564,8 @@ impl BufferedSocket {
             let mut err = None;
             // first parse any leftover messages
             loop {
-                match self.read_one_message(&mut signature) {
-                    Ok(msg) => {
-                        let keep_going = callback(msg);
+                match read_one(self, &mut signature) {
+                    Ok(keep_going) => {
                         dispatched += 1;
                         if !keep_going {
                             break;
@@ -331,11 +580,11 @@ impl BufferedSocket {
 
             // copy back any leftover content to the front of the buffer
             self.in_data.move_to_front();
-            self.in_fds.move_to_front();
+            self.in_fds.close_borrowed();
+            self.in_fds.buffer.move_to_front();
 
             if let Some(MessageParseError::Malformed) = err {
                 // early stop here
-                println!("Socket other malformed");
                 return Ok(Err(MessageParseError::Malformed));
             }
 
@@ -390,11 +639,6 @@ impl<T: Copy + Default> Buffer<T> {
         self.occupied += bytes;
     }
 
-    /// Advance the read offset of current occupied space
-    fn offset(&mut self, bytes: usize) {
-        self.offset += bytes;
-    }
-
     /// Clears the contents of the buffer
     ///
     /// This only sets the counter of occupied space back to zero,
@@ -414,25 +658,65 @@ impl<T: Copy + Default> Buffer<T> {
         &mut self.storage[(self.occupied)..]
     }
 
+    /// Grow the storage of the buffer to at least `size` elements
+    fn grow(&mut self, size: usize) {
+        if self.storage.len() < size {
+            self.storage.resize(size, T::default());
+        }
+    }
+
     /// Move the unread contents of the buffer to the front, to ensure
     /// maximal write space availability
     fn move_to_front(&mut self) {
-        unsafe {
-            ::std::ptr::copy(
-                &self.storage[self.offset] as *const T,
-                &mut self.storage[0] as *mut T,
-                self.occupied - self.offset,
-            );
-        }
+        self.storage.copy_within(self.offset..self.occupied, 0);
         self.occupied -= self.offset;
         self.offset = 0;
     }
 }
 
+/*
+ * FdBuffer
+ */
+
+// The buffer of the received fds, which are owned by the socket
+//
+// The fds of the last message returned by `read_one_message_ref()` are kept open
+// until the next one is read, so that the message can borrow them. Any fd still
+// owned by the buffer is closed when it is dropped.
+struct FdBuffer {
+    buffer: Buffer<RawFd>,
+    // number of fds right before `buffer.offset` borrowed by the last returned message
+    borrowed: usize,
+}
+
+impl FdBuffer {
+    fn new(size: usize) -> FdBuffer {
+        FdBuffer { buffer: Buffer::new(size), borrowed: 0 }
+    }
+
+    /// Close the fds borrowed by the last returned message
+    fn close_borrowed(&mut self) {
+        let offset = self.buffer.offset;
+        for &fd in &self.buffer.storage[offset - self.borrowed..offset] {
+            let _ = ::nix::unistd::close(fd);
+        }
+        self.borrowed = 0;
+    }
+}
+
+impl Drop for FdBuffer {
+    fn drop(&mut self) {
+        self.close_borrowed();
+        for &fd in self.buffer.get_contents() {
+            let _ = ::nix::unistd::close(fd);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::wire::{Argument, ArgumentType, Message};
+    use crate::wire::{Argument, ArgumentType, Message, OwnedFd};
 
     use std::ffi::CString;
 
@@ -453,8 +737,8 @@ mod tests {
         assert_eq!(msg1.opcode, msg2.opcode);
         assert_eq!(msg1.args.len(), msg2.args.len());
         for (arg1, arg2) in msg1.args.iter().zip(msg2.args.iter()) {
-            if let (&Argument::Fd(fd1), &Argument::Fd(fd2)) = (arg1, arg2) {
-                assert!(same_file(fd1, fd2));
+            if let (Argument::Fd(fd1), Argument::Fd(fd2)) = (arg1, arg2) {
+                assert!(same_file(fd1.as_raw_fd(), fd2.as_raw_fd()));
             } else {
                 assert_eq!(arg1, arg2);
             }
@@ -520,8 +804,8 @@ mod tests {
             sender_id: 42,
             opcode: 7,
             args: smallvec![
-                Argument::Fd(1), // stdin
-                Argument::Fd(0), // stdout
+                Argument::Fd(OwnedFd::dup(1).unwrap()), // stdin
+                Argument::Fd(OwnedFd::dup(0).unwrap()), // stdout
             ],
         };
 
@@ -554,6 +838,81 @@ mod tests {
         assert_eq!(ret, 1);
     }
 
+    #[test]
+    fn read_message_ref_borrows_fds() {
+        let msg = Message {
+            sender_id: 42,
+            opcode: 7,
+            args: smallvec![Argument::Fd(OwnedFd::dup(1).unwrap())],
+        };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(unsafe { Socket::from_raw_fd(client.into_raw_fd()) });
+        let mut server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+
+        client.write_message(&msg).unwrap();
+        client.flush().unwrap();
+        server.fill_incoming_buffers().unwrap();
+
+        static SIGNATURE: &[ArgumentType] = &[ArgumentType::Fd];
+        let borrowed = server.read_one_message_ref(|_, _| Some(SIGNATURE)).unwrap();
+        let fd = borrowed.fds()[0];
+        let owned = borrowed.to_owned().unwrap();
+        // the copy is a new fd for the same file, the original is still owned by the socket
+        assert_eq_msgs(&owned, &msg);
+        match owned.args[0] {
+            Argument::Fd(ref copy) => assert_ne!(copy.as_raw_fd(), fd),
+            _ => panic!("Not an fd argument."),
+        }
+        assert!(same_file(fd, 1));
+    }
+
+    #[test]
+    fn read_messages_ref_cycle() {
+        let messages = [
+            Message {
+                sender_id: 42,
+                opcode: 0,
+                args: smallvec![Argument::Str(Box::new(
+                    CString::new(&b"I like trains"[..]).unwrap()
+                ))],
+            },
+            Message {
+                sender_id: 42,
+                opcode: 1,
+                args: smallvec![Argument::Fd(OwnedFd::dup(1).unwrap())],
+            },
+        ];
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(unsafe { Socket::from_raw_fd(client.into_raw_fd()) });
+        let mut server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+
+        for msg in &messages {
+            client.write_message(msg).unwrap();
+        }
+        client.flush().unwrap();
+
+        static SIGNATURES: &[&[ArgumentType]] = &[&[ArgumentType::Str], &[ArgumentType::Fd]];
+        let mut recv_msgs = Vec::new();
+        let ret = server
+            .read_messages_ref(
+                |_, opcode| SIGNATURES.get(opcode as usize).copied(),
+                |msg| {
+                    recv_msgs.push(msg.to_owned().unwrap());
+                    true
+                },
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(ret, 2);
+        assert_eq!(recv_msgs.len(), 2);
+        for (msg1, msg2) in messages.iter().zip(recv_msgs.iter()) {
+            assert_eq_msgs(msg1, msg2);
+        }
+    }
+
     #[test]
     fn write_read_cycle_multiple() {
         let messages = [
@@ -569,8 +928,8 @@ mod tests {
                 sender_id: 42,
                 opcode: 1,
                 args: smallvec![
-                    Argument::Fd(1), // stdin
-                    Argument::Fd(0), // stdout
+                    Argument::Fd(OwnedFd::dup(1).unwrap()), // stdin
+                    Argument::Fd(OwnedFd::dup(0).unwrap()), // stdout
                 ],
             },
             Message {
@@ -578,7 +937,7 @@ mod tests {
                 opcode: 2,
                 args: smallvec![
                     Argument::Uint(3),
-                    Argument::Fd(2), // stderr
+                    Argument::Fd(OwnedFd::dup(2).unwrap()), // stderr
                 ],
             },
         ];
@@ -664,4 +1023,133 @@ mod tests {
 
         assert_eq!(ret, 1);
     }
+
+    #[test]
+    fn read_borrowed_messages() {
+        let msg1 = Message {
+            sender_id: 2,
+            opcode: 0,
+            args: smallvec![
+                Argument::Uint(18),
+                Argument::Str(Box::new(CString::new(&b"wl_shell"[..]).unwrap())),
+                Argument::Uint(1),
+            ],
+        };
+        let msg2 = Message { sender_id: 3, opcode: 0, args: smallvec![Argument::Uint(42)] };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(unsafe { Socket::from_raw_fd(client.into_raw_fd()) });
+        let mut server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+
+        client.write_message(&msg1).unwrap();
+        client.write_message(&msg2).unwrap();
+        client.flush().unwrap();
+
+        static SIGNATURE_1: &[ArgumentType] =
+            &[ArgumentType::Uint, ArgumentType::Str, ArgumentType::Uint];
+        static SIGNATURE_2: &[ArgumentType] = &[ArgumentType::Uint];
+        let signature = |sender_id, _| match sender_id {
+            2 => Some(SIGNATURE_1),
+            3 => Some(SIGNATURE_2),
+            _ => None,
+        };
+
+        match server.read_one_message_ref(signature) {
+            Err(MessageParseError::MissingData) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        server.fill_incoming_buffers().unwrap();
+        assert_eq!(server.read_one_message_ref(signature).unwrap(), msg1);
+        assert_eq!(server.read_one_message_ref(signature).unwrap(), msg2);
+        match server.read_one_message_ref(signature) {
+            Err(MessageParseError::MissingData) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn write_read_large_message() {
+        let msg = Message {
+            sender_id: 2,
+            opcode: 0,
+            args: smallvec![Argument::Array(Box::new(vec![42; 12_000]))],
+        };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(unsafe { Socket::from_raw_fd(client.into_raw_fd()) });
+        let mut server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+
+        // the message does not fit in the default buffer
+        assert_eq!(client.write_message(&msg), Err(::nix::Error::Sys(::nix::errno::Errno::E2BIG)));
+
+        client.set_capacity(BufferCapacity::new(MAX_BYTES_OUT, 4 * MAX_BYTES_OUT));
+        client.write_message(&msg).unwrap();
+        client.flush().unwrap();
+
+        static SIGNATURE: &[ArgumentType] = &[ArgumentType::Array];
+
+        // the incoming buffer grows to receive the whole message
+        let ret = server
+            .read_messages(
+                |_, _| Some(SIGNATURE),
+                |message| {
+                    assert_eq_msgs(&message, &msg);
+                    true
+                },
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(ret, 1);
+    }
+
+    #[test]
+    fn outgoing_buffer_grows() {
+        let msg = Message {
+            sender_id: 2,
+            opcode: 0,
+            args: smallvec![Argument::Array(Box::new(vec![42; 1000]))],
+        };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::with_capacity(
+            unsafe { Socket::from_raw_fd(client.into_raw_fd()) },
+            BufferCapacity::new(MAX_BYTES_OUT, 1024 * 1024),
+        );
+        let mut server = BufferedSocket::new(unsafe { Socket::from_raw_fd(server.into_raw_fd()) });
+
+        // write until both the socket and the buffer are full
+        let mut written = 0;
+        let err = loop {
+            match client.write_message(&msg) {
+                Ok(()) => written += 1,
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(err, ::nix::Error::Sys(::nix::errno::Errno::EAGAIN));
+        assert!(written * 1000 > 1024 * 1024);
+        assert!(client.pending_bytes() > 0);
+
+        static SIGNATURE: &[ArgumentType] = &[ArgumentType::Array];
+
+        // nothing is lost once the other end starts reading
+        let mut read = 0;
+        while read < written {
+            let _ = client.flush();
+            match server.read_messages(
+                |_, _| Some(SIGNATURE),
+                |message| {
+                    assert_eq_msgs(&message, &msg);
+                    true
+                },
+            ) {
+                Ok(ret) => read += ret.unwrap(),
+                Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => {}
+                Err(e) => panic!("{:?}", e),
+            }
+        }
+        assert_eq!(read, written);
+        client.flush().unwrap();
+        assert_eq!(client.pending_bytes(), 0);
+    }
 }
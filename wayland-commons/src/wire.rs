@@ -1,7 +1,7 @@
 //! Types and routines used to manipulate arguments from the wire format
 
 use std::ffi::{CStr, CString};
-use std::os::unix::io::RawFd;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::ptr;
 
 use nix::errno::Errno;
@@ -15,7 +15,7 @@ use smallvec::SmallVec;
 //
 // This brings the size of Message to 11*usize (instead of 4*usize with a regular vec), but eliminates
 // almost all allocations that may occur during the processing of messages, both client-side and server-side.
-const INLINE_ARGS: usize = 4;
+pub(crate) const INLINE_ARGS: usize = 4;
 
 /// Wire metadata of a given message
 #[derive(Copy, Clone, Debug)]
@@ -52,7 +52,7 @@ pub enum ArgumentType {
 }
 
 /// Enum of possible argument as recognized by the wire, including values
-#[derive(Clone, PartialEq, Debug)]
+#[derive(PartialEq, Debug)]
 #[allow(clippy::box_vec)]
 pub enum Argument {
     /// i32
@@ -75,8 +75,8 @@ pub enum Argument {
     /// The value is boxed to reduce the stack size of Argument. The performance
     /// impact is negligible as `array` arguments are pretty rare in the protocol.
     Array(Box<Vec<u8>>),
-    /// RawFd
-    Fd(RawFd),
+    /// An owned file descriptor
+    Fd(OwnedFd),
 }
 
 impl Argument {
@@ -93,6 +93,20 @@ impl Argument {
             Argument::Fd(_) => ArgumentType::Fd,
         }
     }
+
+    /// Borrow the contents of this argument as an `ArgumentRef`
+    pub fn as_ref(&self) -> ArgumentRef<'_> {
+        match *self {
+            Argument::Int(i) => ArgumentRef::Int(i),
+            Argument::Uint(u) => ArgumentRef::Uint(u),
+            Argument::Fixed(f) => ArgumentRef::Fixed(f),
+            Argument::Str(ref s) => ArgumentRef::Str(s),
+            Argument::Object(o) => ArgumentRef::Object(o),
+            Argument::NewId(n) => ArgumentRef::NewId(n),
+            Argument::Array(ref a) => ArgumentRef::Array(a),
+            Argument::Fd(ref fd) => ArgumentRef::Fd(fd.as_raw_fd()),
+        }
+    }
 }
 
 impl std::fmt::Display for Argument {
@@ -105,13 +119,13 @@ impl std::fmt::Display for Argument {
             Argument::Object(value) => write!(f, "{}", value),
             Argument::NewId(value) => write!(f, "{}", value),
             Argument::Array(value) => write!(f, "{:?}", value),
-            Argument::Fd(value) => write!(f, "{}", value),
+            Argument::Fd(value) => write!(f, "{}", value.as_raw_fd()),
         }
     }
 }
 
 /// A wire message
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, PartialEq)]
 pub struct Message {
     /// ID of the object sending this message
     pub sender_id: u32,
@@ -154,6 +168,8 @@ pub enum MessageParseError {
     MissingData,
     /// The message is malformed and cannot be parsed
     Malformed,
+    /// The message contains a FD that could not be dup-ed
+    DupFdFailed(::nix::Error),
 }
 
 impl std::error::Error for MessageParseError {}
@@ -170,6 +186,72 @@ impl std::fmt::Display for MessageParseError {
             MessageParseError::Malformed => {
                 f.write_str("The message is malformed and cannot be parsed")
             }
+            MessageParseError::DupFdFailed(_) => {
+                f.write_str("The message contains a file descriptor that could not be dup()-ed.")
+            }
+        }
+    }
+}
+
+/// Error generated when converting a raw message into its typed representation
+///
+/// This is the error returned by the methods of `MessageGroup` constructing a message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FromRawError {
+    /// The opcode does not correspond to any message of the interface
+    UnknownOpcode(u16),
+    /// An argument of the message is invalid
+    InvalidArgument {
+        /// The index of the argument in the message
+        index: usize,
+        /// The name of the argument in the protocol
+        name: &'static str,
+        /// Why the argument is invalid
+        reason: ArgumentError,
+    },
+    /// A file descriptor of the message could not be duplicated
+    DupFdFailed(::nix::Error),
+}
+
+/// The reason why an argument of a message is invalid
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArgumentError {
+    /// The argument is missing, or is not of the type expected by the protocol
+    UnexpectedType,
+    /// The value is not part of the enum associated to the argument
+    UnknownEnumValue(u32),
+    /// The argument is a null object, while it is not nullable
+    NullObject,
+    /// The argument refers to an object that does not exist or is dead
+    InvalidObject(u32),
+    /// The argument is not a valid id for a new object
+    InvalidNewId(u32),
+}
+
+impl std::error::Error for FromRawError {}
+
+impl std::fmt::Display for FromRawError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        match *self {
+            FromRawError::UnknownOpcode(opcode) => write!(f, "unknown opcode {}", opcode),
+            FromRawError::InvalidArgument { index, name, reason } => {
+                write!(f, "argument {} ({}): {}", index, name, reason)
+            }
+            FromRawError::DupFdFailed(e) => {
+                write!(f, "a file descriptor could not be duplicated: {}", e)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        match *self {
+            ArgumentError::UnexpectedType => f.write_str("missing or of unexpected type"),
+            ArgumentError::UnknownEnumValue(value) => write!(f, "unknown enum value {}", value),
+            ArgumentError::NullObject => f.write_str("null object"),
+            ArgumentError::InvalidObject(id) => write!(f, "invalid object {}", id),
+            ArgumentError::InvalidNewId(id) => write!(f, "invalid new id {}", id),
         }
     }
 }
@@ -244,9 +326,10 @@ impl Message {
                 Argument::Array(ref a) => {
                     payload = write_array_to_payload(&a, old_payload)?;
                 }
-                Argument::Fd(fd) => {
+                Argument::Fd(ref fd) => {
                     let old_fds = fds;
-                    let dup_fd = dup_fd_cloexec(fd).map_err(MessageWriteError::DupFdFailed)?;
+                    let dup_fd =
+                        dup_fd_cloexec(fd.as_raw_fd()).map_err(MessageWriteError::DupFdFailed)?;
                     pending_fds.push(dup_fd);
                     fds = write_buf(dup_fd, old_fds)?;
                     payload = old_payload;
@@ -271,94 +354,359 @@ impl Message {
     /// the returned slices should thus be empty.
     ///
     /// Errors if the message is malformed.
+    ///
+    /// This allocates the contents of string and array arguments, see `MessageRef::from_raw`
+    /// for a borrowed alternative.
+    ///
+    /// The file descriptors of the message are duplicated, the ones in `fds` remain
+    /// owned by the caller.
     pub fn from_raw<'a, 'b>(
         raw: &'a [u32],
         signature: &[ArgumentType],
         fds: &'b [RawFd],
     ) -> Result<(Message, &'a [u32], &'b [RawFd]), MessageParseError> {
-        // helper function to read arrays
-        fn read_array_from_payload(
-            array_len: usize,
-            payload: &[u32],
-        ) -> Result<(&[u8], &[u32]), MessageParseError> {
-            let word_len = array_len / 4 + if array_len % 4 != 0 { 1 } else { 0 };
-            if word_len > payload.len() {
-                return Err(MessageParseError::MissingData);
-            }
-            let (array_contents, rest) = payload.split_at(word_len);
-            let array = unsafe {
-                ::std::slice::from_raw_parts(array_contents.as_ptr() as *const u8, array_len)
-            };
-            Ok((array, rest))
+        let (msg, rest, fd_count) = parse_raw(raw, signature, fds)?;
+        let (msg_fds, rest_fds) = fds.split_at(fd_count);
+        let msg = MessageRef {
+            sender_id: msg.sender_id,
+            opcode: msg.opcode,
+            signature,
+            raw: msg.raw,
+            fds: msg_fds,
+        };
+        let msg = msg.to_owned().map_err(MessageParseError::DupFdFailed)?;
+        Ok((msg, rest, rest_fds))
+    }
+}
+
+/// A borrowed argument, pointing into the buffer it was parsed from
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ArgumentRef<'a> {
+    /// i32
+    Int(i32),
+    /// u32
+    Uint(u32),
+    /// fixed point, 1/256 precision
+    Fixed(i32),
+    /// CStr
+    Str(&'a CStr),
+    /// id of a wayland object
+    Object(u32),
+    /// id of a newly created wayland object
+    NewId(u32),
+    /// &[u8]
+    Array(&'a [u8]),
+    /// RawFd
+    ///
+    /// The file descriptor is borrowed from the owner of the buffers the message
+    /// was parsed from.
+    Fd(RawFd),
+}
+
+impl<'a> ArgumentRef<'a> {
+    /// Retrieve the type of a given argument instance
+    pub fn get_type(self) -> ArgumentType {
+        match self {
+            ArgumentRef::Int(_) => ArgumentType::Int,
+            ArgumentRef::Uint(_) => ArgumentType::Uint,
+            ArgumentRef::Fixed(_) => ArgumentType::Fixed,
+            ArgumentRef::Str(_) => ArgumentType::Str,
+            ArgumentRef::Object(_) => ArgumentType::Object,
+            ArgumentRef::NewId(_) => ArgumentType::NewId,
+            ArgumentRef::Array(_) => ArgumentType::Array,
+            ArgumentRef::Fd(_) => ArgumentType::Fd,
         }
+    }
 
-        if raw.len() < 2 {
-            return Err(MessageParseError::MissingData);
+    /// Copy the contents of this argument into an owned `Argument`
+    ///
+    /// A file descriptor is duplicated, the original one remains owned by its
+    /// previous owner.
+    pub fn to_owned(self) -> NixResult<Argument> {
+        Ok(match self {
+            ArgumentRef::Int(i) => Argument::Int(i),
+            ArgumentRef::Uint(u) => Argument::Uint(u),
+            ArgumentRef::Fixed(f) => Argument::Fixed(f),
+            ArgumentRef::Str(s) => Argument::Str(Box::new(s.into())),
+            ArgumentRef::Object(o) => Argument::Object(o),
+            ArgumentRef::NewId(n) => Argument::NewId(n),
+            ArgumentRef::Array(a) => Argument::Array(Box::new(a.into())),
+            ArgumentRef::Fd(fd) => Argument::Fd(OwnedFd::dup(fd)?),
+        })
+    }
+
+    // Copy the contents of this argument, taking ownership of its file descriptor
+    //
+    // Safety: the caller must own the file descriptor of an `Fd` argument and
+    // give it up
+    unsafe fn take_owned(self) -> Argument {
+        match self {
+            ArgumentRef::Fd(fd) => Argument::Fd(OwnedFd::from_raw_fd(fd)),
+            // other arguments cannot fail to be copied
+            arg => arg.to_owned().unwrap(),
+        }
+    }
+}
+
+impl<'a> std::fmt::Display for ArgumentRef<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgumentRef::Int(value) => write!(f, "{}", value),
+            ArgumentRef::Uint(value) => write!(f, "{}", value),
+            ArgumentRef::Fixed(value) => write!(f, "{}", value),
+            ArgumentRef::Str(value) => write!(f, "{:?}", value),
+            ArgumentRef::Object(value) => write!(f, "{}", value),
+            ArgumentRef::NewId(value) => write!(f, "{}", value),
+            ArgumentRef::Array(value) => write!(f, "{:?}", value),
+            ArgumentRef::Fd(value) => write!(f, "{}", value),
         }
+    }
+}
+
+/// A borrowed wire message
+///
+/// This is a view into the buffers a message was parsed from, it does not
+/// allocate. Its arguments are decoded on the fly when iterating over
+/// `args()`.
+///
+/// Its file descriptors are borrowed as well: they remain owned by the owner
+/// of the buffers, and are duplicated when the message is copied with
+/// `to_owned()`.
+#[derive(Copy, Clone, Debug)]
+pub struct MessageRef<'a> {
+    /// ID of the object sending this message
+    pub sender_id: u32,
+    /// Opcode of the message
+    pub opcode: u16,
+    signature: &'a [ArgumentType],
+    raw: &'a [u32],
+    fds: &'a [RawFd],
+}
+
+impl<'a> MessageRef<'a> {
+    /// Attempts to parse a single wayland message with the given signature, without copying it.
+    ///
+    /// If the buffers contains several messages, only the first one will be parsed,
+    /// and the unused tail of the buffers is returned. If a single message was present,
+    /// the returned slices should thus be empty.
+    ///
+    /// The whole message is validated, so iterating over its arguments cannot fail.
+    ///
+    /// Errors if the message is malformed.
+    pub fn from_raw(
+        raw: &'a [u32],
+        signature: &'a [ArgumentType],
+        fds: &'a [RawFd],
+    ) -> Result<(MessageRef<'a>, &'a [u32], &'a [RawFd]), MessageParseError> {
+        let (msg, rest, fd_count) = parse_raw(raw, signature, fds)?;
+        let (msg_fds, rest_fds) = fds.split_at(fd_count);
+        let msg = MessageRef {
+            sender_id: msg.sender_id,
+            opcode: msg.opcode,
+            signature,
+            raw: msg.raw,
+            fds: msg_fds,
+        };
+        Ok((msg, rest, rest_fds))
+    }
+
+    /// Iterate over the arguments of this message
+    pub fn args(self) -> ArgumentRefIter<'a> {
+        ArgumentRefIter { signature: self.signature.iter(), payload: &self.raw[2..], fds: self.fds }
+    }
+
+    /// The signature this message was parsed with
+    pub fn signature(self) -> &'a [ArgumentType] {
+        self.signature
+    }
+
+    /// The raw contents of this message, header included
+    ///
+    /// Parsing them back with `MessageRef::from_raw()` and the same signature and
+    /// file descriptors gives an identical message.
+    pub fn raw(self) -> &'a [u32] {
+        self.raw
+    }
+
+    /// The file descriptors of this message
+    pub fn fds(self) -> &'a [RawFd] {
+        self.fds
+    }
 
-        let sender_id = raw[0];
-        let word_2 = raw[1];
-        let opcode = (word_2 & 0x0000_FFFF) as u16;
-        let len = (word_2 >> 16) as usize / 4;
+    /// Copy the contents of this message into an owned `Message`
+    ///
+    /// The file descriptors of the message are duplicated, see `ArgumentRef::to_owned()`.
+    pub fn to_owned(self) -> NixResult<Message> {
+        Ok(Message {
+            sender_id: self.sender_id,
+            opcode: self.opcode,
+            args: self.args().map(ArgumentRef::to_owned).collect::<NixResult<_>>()?,
+        })
+    }
 
-        if len < 2 || len > raw.len() {
-            println!("First ocurrence, len {}, rawlen {}", len, raw.len());
-            return Err(MessageParseError::Malformed);
+    /// Copy the contents of this message into an owned `Message`, taking ownership
+    /// of its file descriptors rather than duplicating them
+    ///
+    /// # Safety
+    ///
+    /// The file descriptors of this message must be owned by the caller, who gives
+    /// up their ownership: it must neither use nor close them afterwards, and must
+    /// not call this method twice for the same message.
+    pub(crate) unsafe fn take_owned(self) -> Message {
+        Message {
+            sender_id: self.sender_id,
+            opcode: self.opcode,
+            args: self.args().map(|arg| arg.take_owned()).collect(),
         }
+    }
+}
 
-        let (mut payload, rest) = raw.split_at(len);
-        payload = &payload[2..];
-        let mut fds = fds;
-
-        let arguments = signature
-            .iter()
-            .map(|argtype| {
-                if let ArgumentType::Fd = *argtype {
-                    // don't consume input but fd
-                    if let Some((&front, tail)) = fds.split_first() {
-                        fds = tail;
-                        Ok(Argument::Fd(front))
-                    } else {
-                        Err(MessageParseError::MissingFD)
-                    }
-                } else if let Some((&front, mut tail)) = payload.split_first() {
-                    let arg = match *argtype {
-                        ArgumentType::Int => Ok(Argument::Int(front as i32)),
-                        ArgumentType::Uint => Ok(Argument::Uint(front)),
-                        ArgumentType::Fixed => Ok(Argument::Fixed(front as i32)),
-                        ArgumentType::Str => read_array_from_payload(front as usize, tail)
-                            .and_then(|(v, rest)| {
-                                tail = rest;
-                                match CStr::from_bytes_with_nul(v) {
-                                    Ok(s) => Ok(Argument::Str(Box::new(s.into()))),
-                                    Err(e) => 
-                                    {
-                                        println!("Second occurrence, {}", e);
-                                        Err(MessageParseError::Malformed)
-                                    },
-                                }
-                            }),
-                        ArgumentType::Object => Ok(Argument::Object(front)),
-                        ArgumentType::NewId => Ok(Argument::NewId(front)),
-                        ArgumentType::Array => {
-                            read_array_from_payload(front as usize, tail).map(|(v, rest)| {
-                                tail = rest;
-                                Argument::Array(Box::new(v.into()))
-                            })
-                        }
-                        ArgumentType::Fd => unreachable!(),
-                    };
-                    payload = tail;
-                    arg
-                } else {
-                    Err(MessageParseError::MissingData)
-                }
+impl<'a> PartialEq<Message> for MessageRef<'a> {
+    fn eq(&self, other: &Message) -> bool {
+        self.sender_id == other.sender_id
+            && self.opcode == other.opcode
+            && self.args().len() == other.args.len()
+            && self.args().zip(other.args.iter()).all(|(a, b)| match (a, b) {
+                (ArgumentRef::Int(a), &Argument::Int(b)) => a == b,
+                (ArgumentRef::Uint(a), &Argument::Uint(b)) => a == b,
+                (ArgumentRef::Fixed(a), &Argument::Fixed(b)) => a == b,
+                (ArgumentRef::Str(a), Argument::Str(b)) => a == b.as_c_str(),
+                (ArgumentRef::Object(a), &Argument::Object(b)) => a == b,
+                (ArgumentRef::NewId(a), &Argument::NewId(b)) => a == b,
+                (ArgumentRef::Array(a), Argument::Array(b)) => a == &b[..],
+                (ArgumentRef::Fd(a), Argument::Fd(b)) => a == b.as_raw_fd(),
+                _ => false,
             })
-            .collect::<Result<SmallVec<_>, MessageParseError>>()?;
+    }
+}
+
+/// Iterator over the arguments of a `MessageRef`
+#[derive(Clone, Debug)]
+pub struct ArgumentRefIter<'a> {
+    signature: std::slice::Iter<'a, ArgumentType>,
+    payload: &'a [u32],
+    fds: &'a [RawFd],
+}
+
+impl<'a> Iterator for ArgumentRefIter<'a> {
+    type Item = ArgumentRef<'a>;
+
+    fn next(&mut self) -> Option<ArgumentRef<'a>> {
+        // the message has been validated by `MessageRef::from_raw`, so the
+        // buffers are guaranteed to contain all the arguments
+        let arg = match *self.signature.next()? {
+            ArgumentType::Fd => {
+                let (&front, tail) = self.fds.split_first()?;
+                self.fds = tail;
+                return Some(ArgumentRef::Fd(front));
+            }
+            ArgumentType::Int => ArgumentRef::Int(self.payload[0] as i32),
+            ArgumentType::Uint => ArgumentRef::Uint(self.payload[0]),
+            ArgumentType::Fixed => ArgumentRef::Fixed(self.payload[0] as i32),
+            ArgumentType::Object => ArgumentRef::Object(self.payload[0]),
+            ArgumentType::NewId => ArgumentRef::NewId(self.payload[0]),
+            ArgumentType::Str => {
+                let (s, rest) =
+                    read_array_from_payload(self.payload[0] as usize, &self.payload[1..]).ok()?;
+                self.payload = rest;
+                return Some(ArgumentRef::Str(unsafe { CStr::from_bytes_with_nul_unchecked(s) }));
+            }
+            ArgumentType::Array => {
+                let (a, rest) =
+                    read_array_from_payload(self.payload[0] as usize, &self.payload[1..]).ok()?;
+                self.payload = rest;
+                return Some(ArgumentRef::Array(a));
+            }
+        };
+        self.payload = &self.payload[1..];
+        Some(arg)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.signature.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for ArgumentRefIter<'a> {}
+
+// header and payload of a validated message
+struct RawMessage<'a> {
+    sender_id: u32,
+    opcode: u16,
+    raw: &'a [u32],
+}
 
-        let msg = Message { sender_id, opcode, args: arguments };
-        Ok((msg, rest, fds))
+// helper function to read arrays
+fn read_array_from_payload(
+    array_len: usize,
+    payload: &[u32],
+) -> Result<(&[u8], &[u32]), MessageParseError> {
+    let word_len = array_len / 4 + if array_len % 4 != 0 { 1 } else { 0 };
+    if word_len > payload.len() {
+        return Err(MessageParseError::MissingData);
     }
+    let (array_contents, rest) = payload.split_at(word_len);
+    let array =
+        unsafe { ::std::slice::from_raw_parts(array_contents.as_ptr() as *const u8, array_len) };
+    Ok((array, rest))
+}
+
+// Parse the header of the first message of the buffer and check that its contents
+// match the signature, returning the message, the unused tail of the buffer and
+// the number of fds used by the message
+fn parse_raw<'a>(
+    raw: &'a [u32],
+    signature: &[ArgumentType],
+    fds: &[RawFd],
+) -> Result<(RawMessage<'a>, &'a [u32], usize), MessageParseError> {
+    if raw.len() < 2 {
+        return Err(MessageParseError::MissingData);
+    }
+
+    let sender_id = raw[0];
+    let word_2 = raw[1];
+    let opcode = (word_2 & 0x0000_FFFF) as u16;
+    let len = (word_2 >> 16) as usize / 4;
+
+    if len < 2 {
+        return Err(MessageParseError::Malformed);
+    }
+
+    if len > raw.len() {
+        // the end of the message has not been received yet
+        return Err(MessageParseError::MissingData);
+    }
+
+    let (message, rest) = raw.split_at(len);
+    let payload = &message[2..];
+
+    let mut remaining = payload;
+    let mut fd_count = 0;
+    for argtype in signature {
+        if let ArgumentType::Fd = *argtype {
+            // don't consume input but fd
+            if fd_count >= fds.len() {
+                return Err(MessageParseError::MissingFD);
+            }
+            fd_count += 1;
+        } else if let Some((&front, tail)) = remaining.split_first() {
+            remaining = match *argtype {
+                ArgumentType::Str => {
+                    let (v, rest) = read_array_from_payload(front as usize, tail)?;
+                    if CStr::from_bytes_with_nul(v).is_err() {
+                        return Err(MessageParseError::Malformed);
+                    }
+                    rest
+                }
+                ArgumentType::Array => read_array_from_payload(front as usize, tail)?.1,
+                _ => tail,
+            };
+        } else {
+            return Err(MessageParseError::MissingData);
+        }
+    }
+
+    Ok((RawMessage { sender_id, opcode, raw: message }, rest, fd_count))
 }
 
 /// Duplicate a `RawFd` and set the CLOEXEC flag on the copy
@@ -391,6 +739,57 @@ pub fn dup_fd_cloexec(fd: RawFd) -> NixResult<RawFd> {
     }
 }
 
+/// An owned file descriptor
+///
+/// The file descriptor is closed when this value is dropped, unless ownership of
+/// it is explicitly taken back with `IntoRawFd::into_raw_fd()`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OwnedFd {
+    fd: RawFd,
+}
+
+impl OwnedFd {
+    /// Duplicate a `RawFd` into a new `OwnedFd`
+    ///
+    /// The original file descriptor is left untouched, and still needs to be
+    /// closed by its owner.
+    pub fn dup(fd: RawFd) -> NixResult<OwnedFd> {
+        dup_fd_cloexec(fd).map(|fd| OwnedFd { fd })
+    }
+
+    /// Duplicate this file descriptor
+    pub fn try_clone(&self) -> NixResult<OwnedFd> {
+        OwnedFd::dup(self.fd)
+    }
+}
+
+impl AsRawFd for OwnedFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl IntoRawFd for OwnedFd {
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.fd;
+        ::std::mem::forget(self);
+        fd
+    }
+}
+
+impl FromRawFd for OwnedFd {
+    unsafe fn from_raw_fd(fd: RawFd) -> OwnedFd {
+        OwnedFd { fd }
+    }
+}
+
+impl Drop for OwnedFd {
+    fn drop(&mut self) {
+        // not much can be done if we can't close that anyway...
+        let _ = ::nix::unistd::close(self.fd);
+    }
+}
+
 /*
  * utility struct that closes every FD it contains on drop
  */
@@ -463,4 +862,133 @@ mod tests {
         .unwrap();
         assert_eq!(rebuilt, msg);
     }
+
+    #[test]
+    fn borrowed_from_raw() {
+        let mut bytes_buffer = [0; 1024];
+        let mut fd_buffer = [0; 10];
+
+        let msg = Message {
+            sender_id: 42,
+            opcode: 7,
+            args: smallvec![
+                Argument::Uint(3),
+                Argument::Str(Box::new(CString::new(&b"I like trains!"[..]).unwrap())),
+                Argument::Array(vec![1, 2, 3, 4, 5].into()),
+                Argument::NewId(56),
+                Argument::Int(-25),
+            ],
+        };
+        let signature = [
+            ArgumentType::Uint,
+            ArgumentType::Str,
+            ArgumentType::Array,
+            ArgumentType::NewId,
+            ArgumentType::Int,
+        ];
+        let (len, _) = msg.write_to_buffers(&mut bytes_buffer[..], &mut fd_buffer[..]).unwrap();
+        let (borrowed, rest, _) =
+            MessageRef::from_raw(&bytes_buffer[..len], &signature, &fd_buffer[..0]).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(borrowed.sender_id, 42);
+        assert_eq!(borrowed.opcode, 7);
+        let args = borrowed.args().collect::<Vec<_>>();
+        assert_eq!(
+            args[1],
+            ArgumentRef::Str(CStr::from_bytes_with_nul(b"I like trains!\0").unwrap())
+        );
+        assert_eq!(args[2], ArgumentRef::Array(&[1, 2, 3, 4, 5]));
+        assert_eq!(borrowed, msg);
+        assert_eq!(borrowed.raw(), &bytes_buffer[..len]);
+        assert_eq!(borrowed.to_owned().unwrap(), msg);
+    }
+
+    #[test]
+    fn borrowed_from_raw_malformed() {
+        let mut bytes_buffer = [0; 1024];
+        let mut fd_buffer = [0; 10];
+
+        let msg = Message { sender_id: 1, opcode: 0, args: smallvec![Argument::Uint(3)] };
+        let (len, _) = msg.write_to_buffers(&mut bytes_buffer[..], &mut fd_buffer[..]).unwrap();
+        // the signature expects more arguments than the message contains
+        match MessageRef::from_raw(
+            &bytes_buffer[..len],
+            &[ArgumentType::Uint, ArgumentType::Int],
+            &fd_buffer[..0],
+        ) {
+            Err(MessageParseError::MissingData) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        match MessageRef::from_raw(
+            &bytes_buffer[..len],
+            &[ArgumentType::Uint, ArgumentType::Fd],
+            &fd_buffer[..0],
+        ) {
+            Err(MessageParseError::MissingFD) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        // the end of the message has not been received yet
+        match MessageRef::from_raw(&bytes_buffer[..len - 1], &[ArgumentType::Uint], &fd_buffer[..0])
+        {
+            Err(MessageParseError::MissingData) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn owned_fd_close() {
+        // the read end of a pipe reaches EOF once all its write ends are closed
+        let (read_end, write_end) = ::nix::unistd::pipe().unwrap();
+        let write_end = unsafe { OwnedFd::from_raw_fd(write_end) };
+        let dup = write_end.try_clone().unwrap();
+        drop(write_end);
+        let raw = dup.into_raw_fd();
+        // the write end is still open, owned by `raw`
+        assert_eq!(::nix::unistd::write(raw, b"a").unwrap(), 1);
+        drop(unsafe { OwnedFd::from_raw_fd(raw) });
+        let mut buf = [0u8; 2];
+        assert_eq!(::nix::unistd::read(read_end, &mut buf).unwrap(), 1);
+        assert_eq!(::nix::unistd::read(read_end, &mut buf).unwrap(), 0);
+        let _ = ::nix::unistd::close(read_end);
+    }
+
+    #[test]
+    fn borrowed_fd_to_owned() {
+        let (read_end, write_end) = ::nix::unistd::pipe().unwrap();
+        let mut bytes_buffer = [0; 16];
+        let msg = Message { sender_id: 3, opcode: 0, args: smallvec![Argument::Uint(0)] };
+        let (len, _) = msg.write_to_buffers(&mut bytes_buffer[..], &mut []).unwrap();
+        let fds = [write_end];
+        let (borrowed, _, _) = MessageRef::from_raw(
+            &bytes_buffer[..len],
+            &[ArgumentType::Uint, ArgumentType::Fd],
+            &fds,
+        )
+        .unwrap();
+        let owned = borrowed.to_owned().unwrap();
+        let fd = match owned.args[1] {
+            Argument::Fd(ref fd) => fd.as_raw_fd(),
+            _ => panic!("Not an fd argument."),
+        };
+        assert_ne!(fd, write_end);
+        // dropping the copy leaves the original open
+        drop(owned);
+        assert_eq!(::nix::unistd::write(write_end, b"a").unwrap(), 1);
+        let _ = ::nix::unistd::close(write_end);
+        let mut buf = [0u8; 2];
+        assert_eq!(::nix::unistd::read(read_end, &mut buf).unwrap(), 1);
+        assert_eq!(::nix::unistd::read(read_end, &mut buf).unwrap(), 0);
+        let _ = ::nix::unistd::close(read_end);
+    }
+
+    #[test]
+    fn from_raw_error_display() {
+        assert_eq!(FromRawError::UnknownOpcode(42).to_string(), "unknown opcode 42");
+        let error = FromRawError::InvalidArgument {
+            index: 1,
+            name: "format",
+            reason: ArgumentError::UnknownEnumValue(57005),
+        };
+        assert_eq!(error.to_string(), "argument 1 (format): unknown enum value 57005");
+    }
 }
@@ -18,7 +18,7 @@ macro_rules! wayland_protocol(
                 pub(crate) use wayland_client::{Main, Attached, Proxy, ProxyMap, AnonymousObject};
                 pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
                 pub(crate) use wayland_commons::{Interface, MessageGroup};
-                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, Message};
+                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, Message, ArgumentRef, MessageRef, OwnedFd, FromRawError, ArgumentError, MessageWriteError};
                 pub(crate) use wayland_commons::smallvec;
                 pub(crate) use wayland_client::protocol::{$($import),*};
                 pub(crate) use wayland_client::sys;
@@ -34,7 +34,7 @@ macro_rules! wayland_protocol(
                 pub(crate) use wayland_server::{Main, AnonymousObject, Resource, ResourceMap};
                 pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
                 pub(crate) use wayland_commons::{Interface, MessageGroup};
-                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, Message};
+                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, Message, ArgumentRef, MessageRef, OwnedFd, FromRawError, ArgumentError, MessageWriteError};
                 pub(crate) use wayland_commons::smallvec;
                 pub(crate) use wayland_server::protocol::{$($import),*};
                 pub(crate) use wayland_server::sys;
@@ -55,7 +55,9 @@ pub(crate) fn generate_protocol_client(protocol: Protocol) -> TokenStream {
                 use std::os::raw::c_char;
                 use super::{
                     Proxy, AnonymousObject, Interface, MessageGroup, MessageDesc, ArgumentType,
-                    Object, Message, Argument, ObjectMetadata, types_null, NULLPTR, Main, smallvec,
+                    Object, Message, Argument, MessageRef, ArgumentRef, OwnedFd, ObjectMetadata,
+                    FromRawError, ArgumentError, MessageWriteError, types_null, NULLPTR, Main,
+                    smallvec,
                 };
                 use super::sys::common::{wl_interface, wl_array, wl_argument, wl_message};
                 use super::sys::client::*;
@@ -143,7 +145,9 @@ pub(crate) fn generate_protocol_server(protocol: Protocol) -> TokenStream {
                     use std::os::raw::c_char;
                     use super::{
                         Resource, AnonymousObject, Interface, MessageGroup, MessageDesc, Main, smallvec,
-                        ArgumentType, Object, Message, Argument, ObjectMetadata, types_null, NULLPTR
+                        ArgumentType, Object, Message, Argument, MessageRef, ArgumentRef, OwnedFd,
+                        ObjectMetadata, FromRawError, ArgumentError, MessageWriteError, types_null,
+                        NULLPTR
                     };
                     use super::sys::common::{wl_argument, wl_interface, wl_array, wl_message};
                     use super::sys::server::*;
@@ -194,11 +198,20 @@ fn messagegroup_c_addon(
                 );
 
                         let idx = Literal::usize_unsuffixed(j);
+                        let arg_name = &arg.name;
+                        let enum_error = |value: TokenStream| {
+                            quote!(FromRawError::InvalidArgument {
+                                index: #idx,
+                                name: #arg_name,
+                                reason: ArgumentError::UnknownEnumValue(#value),
+                            })
+                        };
                         let field_value = match arg.typ {
                             Type::Uint => {
                                 if let Some(ref enu) = arg.enum_ {
                                     let enum_type = dotted_to_relname(enu);
-                                    quote!(#enum_type::from_raw(_args[#idx].u).ok_or(())?)
+                                    let error = enum_error(quote!(_args[#idx].u));
+                                    quote!(#enum_type::from_raw(_args[#idx].u).ok_or(#error)?)
                                 } else {
                                     quote!(_args[#idx].u)
                                 }
@@ -206,7 +219,8 @@ fn messagegroup_c_addon(
                             Type::Int => {
                                 if let Some(ref enu) = arg.enum_ {
                                     let enum_type = dotted_to_relname(enu);
-                                    quote!(#enum_type::from_raw(_args[#idx].i as u32).ok_or(())?)
+                                    let error = enum_error(quote!(_args[#idx].i as u32));
+                                    quote!(#enum_type::from_raw(_args[#idx].i as u32).ok_or(#error)?)
                                 } else {
                                     quote!(_args[#idx].i)
                                 }
@@ -242,7 +256,7 @@ fn messagegroup_c_addon(
                                     array_conversion
                                 }
                             }
-                            Type::Fd => quote!(_args[#idx].h),
+                            Type::Fd => quote!(::std::os::unix::io::FromRawFd::from_raw_fd(_args[#idx].h)),
                             Type::Object => {
                                 let object_name = side.object_name();
                                 let object_conversion = if let Some(ref iface) = arg.interface {
@@ -329,7 +343,7 @@ fn messagegroup_c_addon(
                     }
                 }
             })
-            .chain(iter::once(quote!(_ => return Err(()))));
+            .chain(iter::once(quote!(_ => return Err(FromRawError::UnknownOpcode(opcode as u16)))));
 
         quote! {
             match opcode {
@@ -530,7 +544,7 @@ fn messagegroup_c_addon(
             obj: *mut ::std::os::raw::c_void,
             opcode: u32,
             args: *const wl_argument,
-        ) -> Result<#name, ()> {
+        ) -> Result<#name, FromRawError> {
             #from_raw_c_body
         }
 
@@ -205,6 +205,8 @@ pub(crate) fn gen_messagegroup(
                         Type::Fixed => quote!(f64),
                         Type::String => quote!(String),
                         Type::Array => quote!(Vec<u8>),
+                        // received fds are owned by the message, sent ones are borrowed
+                        Type::Fd if receiver => quote!(OwnedFd),
                         Type::Fd => quote!(::std::os::unix::io::RawFd),
                         Type::Object => {
                             if let Some(ref iface) = arg.interface {
@@ -360,127 +362,170 @@ pub(crate) fn gen_messagegroup(
         })
         .chain(iter::once(quote!(_ => None)));
 
-    let from_raw_body = if receiver {
-        let match_arms = messages
-            .iter()
-            .enumerate()
-            .map(|(opcode, msg)| {
-                let pattern = Literal::u16_unsuffixed(opcode as u16);
-                let msg_type = Ident::new(&snake_to_camel(&msg.name), Span::call_site());
-                let msg_type_qualified = quote!(#name::#msg_type);
-
-                let block = if msg.args.is_empty() {
-                    quote!(Ok(#msg_type_qualified))
-                } else {
-                    let fields = msg.args.iter().map(|arg| {
-                        let field_name = Ident::new(
-                            &format!("{}{}", if is_keyword(&arg.name) { "_" } else { "" }, arg.name),
-                            Span::call_site(),
-                        );
-                        let some_code_path = match arg.typ {
-                            Type::Int => {
-                                if let Some(ref enu) = arg.enum_ {
-                                    let enum_ident = dotted_to_relname(enu);
-                                    quote!(#enum_ident::from_raw(val as u32).ok_or(())?)
-                                } else {
-                                    quote!(val)
+    // generate the body of `from_raw` or, if `borrowed` is true, of `from_raw_ref`
+    let gen_from_raw_body = |borrowed: bool| {
+        if receiver {
+            let match_arms = messages
+                .iter()
+                .enumerate()
+                .map(|(opcode, msg)| {
+                    let pattern = Literal::u16_unsuffixed(opcode as u16);
+                    let msg_type = Ident::new(&snake_to_camel(&msg.name), Span::call_site());
+                    let msg_type_qualified = quote!(#name::#msg_type);
+
+                    let block = if msg.args.is_empty() {
+                        quote!(Ok(#msg_type_qualified))
+                    } else {
+                        let fields = msg.args.iter().enumerate().map(|(i, arg)| {
+                            let field_name = Ident::new(
+                                &format!("{}{}", if is_keyword(&arg.name) { "_" } else { "" }, arg.name),
+                                Span::call_site(),
+                            );
+                            let index = Literal::usize_unsuffixed(i);
+                            let arg_name = &arg.name;
+                            let invalid_arg = |reason: TokenStream| {
+                                quote!(FromRawError::InvalidArgument {
+                                    index: #index,
+                                    name: #arg_name,
+                                    reason: ArgumentError::#reason,
+                                })
+                            };
+                            let some_code_path = match arg.typ {
+                                Type::Int => {
+                                    if let Some(ref enu) = arg.enum_ {
+                                        let enum_ident = dotted_to_relname(enu);
+                                        let error = invalid_arg(quote!(UnknownEnumValue(val as u32)));
+                                        quote!(#enum_ident::from_raw(val as u32).ok_or(#error)?)
+                                    } else {
+                                        quote!(val)
+                                    }
                                 }
-                            }
-                            Type::Uint => {
-                                if let Some(ref enu) = arg.enum_ {
-                                    let enum_ident = dotted_to_relname(enu);
-                                    quote!(#enum_ident::from_raw(val).ok_or(())?)
-                                } else {
-                                    quote!(val)
+                                Type::Uint => {
+                                    if let Some(ref enu) = arg.enum_ {
+                                        let enum_ident = dotted_to_relname(enu);
+                                        let error = invalid_arg(quote!(UnknownEnumValue(val)));
+                                        quote!(#enum_ident::from_raw(val).ok_or(#error)?)
+                                    } else {
+                                        quote!(val)
+                                    }
                                 }
-                            }
-                            Type::Fixed => quote!((val as f64) / 256.),
-                            Type::Array => {
-                                if arg.allow_null {
-                                    quote!(if val.len() == 0 { None } else { Some(*val) })
-                                } else {
-                                    quote!(*val)
+                                Type::Fixed => quote!((val as f64) / 256.),
+                                Type::Array => {
+                                    let array_conversion =
+                                        if borrowed { quote!(val.to_vec()) } else { quote!(*val) };
+                                    if arg.allow_null {
+                                        quote!(if val.len() == 0 { None } else { Some(#array_conversion) })
+                                    } else {
+                                        array_conversion
+                                    }
                                 }
-                            }
-                            Type::String => {
-                                let string_conversion = quote! {
-                                    let s = String::from_utf8(val.into_bytes())
-                                        .unwrap_or_else(|e| String::from_utf8_lossy(&e.into_bytes()).into());
-                                };
-
-                                if arg.allow_null {
-                                    quote! {
-                                        #string_conversion
-                                        if s.len() == 0 { None } else { Some(s) }
+                                Type::String => {
+                                    let string_conversion = if borrowed {
+                                        quote! {
+                                            let s = String::from_utf8_lossy(val.to_bytes()).into_owned();
+                                        }
+                                    } else {
+                                        quote! {
+                                            let s = String::from_utf8(val.into_bytes())
+                                                .unwrap_or_else(|e| String::from_utf8_lossy(&e.into_bytes()).into());
+                                        }
+                                    };
+
+                                    if arg.allow_null {
+                                        quote! {
+                                            #string_conversion
+                                            if s.len() == 0 { None } else { Some(s) }
+                                        }
+                                    } else {
+                                        quote! {
+                                            #string_conversion
+                                            s
+                                        }
                                     }
-                                } else {
-                                    quote! {
-                                        #string_conversion
-                                        s
+                                }
+                                Type::Fd => {
+                                    if borrowed {
+                                        // the handler takes ownership of a copy of the borrowed fd
+                                        quote!(OwnedFd::dup(val).map_err(FromRawError::DupFdFailed)?)
+                                    } else {
+                                        quote!(val)
                                     }
                                 }
-                            }
-                            Type::Fd => quote!(val),
-                            Type::Object => {
-                                let map_lookup = if side == Side::Client {
-                                    quote!(map.get_or_dead(val).into())
-                                } else {
-                                    quote!(map.get(val).ok_or(())?.into())
-                                };
-                                if arg.allow_null {
-                                    quote!(if val == 0 { None } else { Some(#map_lookup) })
-                                } else {
-                                    map_lookup
+                                Type::Object => {
+                                    let map_lookup = if side == Side::Client {
+                                        quote!(map.get_or_dead(val).into())
+                                    } else {
+                                        let null_error = invalid_arg(quote!(NullObject));
+                                        let invalid_error = invalid_arg(quote!(InvalidObject(val)));
+                                        let error =
+                                            quote!(if val == 0 { #null_error } else { #invalid_error });
+                                        quote!(map.get(val).ok_or(#error)?.into())
+                                    };
+                                    if arg.allow_null {
+                                        quote!(if val == 0 { None } else { Some(#map_lookup) })
+                                    } else {
+                                        map_lookup
+                                    }
                                 }
-                            }
-                            Type::NewId => {
-                                let map_lookup = quote!(map.get_new(val).ok_or(())?);
-                                if arg.allow_null {
-                                    quote!(if val == 0 { None } else { Some(#map_lookup) })
-                                } else {
-                                    map_lookup
+                                Type::NewId => {
+                                    let error = invalid_arg(quote!(InvalidNewId(val)));
+                                    let map_lookup = quote!(map.get_new(val).ok_or(#error)?);
+                                    if arg.allow_null {
+                                        quote!(if val == 0 { None } else { Some(#map_lookup) })
+                                    } else {
+                                        map_lookup
+                                    }
                                 }
-                            }
-                            Type::Destructor => panic!("An argument cannot have type destructor!"),
-                        };
+                                Type::Destructor => panic!("An argument cannot have type destructor!"),
+                            };
 
-                        let common_type = arg.typ.common_type();
+                            let type_error = invalid_arg(quote!(UnexpectedType));
+                            let common_type = arg.typ.common_type();
+                            let argument_type =
+                                if borrowed { quote!(ArgumentRef) } else { quote!(Argument) };
 
-                        quote! {
-                            #field_name: {
-                                if let Some(Argument::#common_type(val)) = args.next() {
-                                    #some_code_path
-                                } else {
-                                    return Err(());
+                            quote! {
+                                #field_name: {
+                                    if let Some(#argument_type::#common_type(val)) = args.next() {
+                                        #some_code_path
+                                    } else {
+                                        return Err(#type_error);
+                                    }
                                 }
                             }
-                        }
-                    });
+                        });
+
+                        let args_iter =
+                            if borrowed { quote!(msg.args()) } else { quote!(msg.args.into_iter()) };
 
-                    quote! {
-                        {
-                            let mut args = msg.args.into_iter();
+                        quote! {
+                            {
+                                let mut args = #args_iter;
 
-                            Ok(#msg_type_qualified {
-                                #(#fields,)*
-                            })
+                                Ok(#msg_type_qualified {
+                                    #(#fields,)*
+                                })
+                            }
                         }
-                    }
-                };
+                    };
 
-                quote!(#pattern => #block)
-            })
-            .chain(iter::once(quote!(_ => Err(()))));
+                    quote!(#pattern => #block)
+                })
+                .chain(iter::once(quote!(_ => Err(FromRawError::UnknownOpcode(msg.opcode)))));
 
-        quote! {
-            match msg.opcode {
-                #(#match_arms,)*
+            quote! {
+                match msg.opcode {
+                    #(#match_arms,)*
+                }
             }
+        } else {
+            let method = if borrowed { "from_raw_ref" } else { "from_raw" };
+            let panic_message = format!("{}::{} can not be used {:?}-side.", name, method, side);
+            quote!(panic!(#panic_message))
         }
-    } else {
-        let panic_message = format!("{}::from_raw can not be used {:?}-side.", name, side);
-        quote!(panic!(#panic_message))
     };
+    let from_raw_body = gen_from_raw_body(false);
+    let from_raw_ref_body = gen_from_raw_body(true);
 
     let into_raw_body = if receiver {
         let panic_message = format!("{}::into_raw can not be used {:?}-side.", name, side);
@@ -553,7 +598,9 @@ pub(crate) fn gen_messagegroup(
                             quote!(Argument::Array(Box::new(#arg_ident)))
                         }
                     }
-                    Type::Fd => quote!(Argument::Fd(#arg_ident)),
+                    Type::Fd => quote! {
+                        Argument::Fd(OwnedFd::dup(#arg_ident).map_err(MessageWriteError::DupFdFailed)?)
+                    },
                     Type::NewId => {
                         if arg.interface.is_some() {
                             let id = if side == Side::Client {
@@ -597,9 +644,13 @@ pub(crate) fn gen_messagegroup(
             })
         });
 
-        quote! {
-            match self {
-                #(#match_arms,)*
+        if messages.is_empty() {
+            quote!(match self {})
+        } else {
+            quote! {
+                Ok(match self {
+                    #(#match_arms,)*
+                })
             }
         }
     };
@@ -642,11 +693,15 @@ pub(crate) fn gen_messagegroup(
                 }
             }
 
-            fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, ()> {
+            fn from_raw(msg: Message, map: &mut Self::Map) -> Result<Self, FromRawError> {
                 #from_raw_body
             }
 
-            fn into_raw(self, sender_id: u32) -> Message {
+            fn from_raw_ref(msg: MessageRef, map: &mut Self::Map) -> Result<Self, FromRawError> {
+                #from_raw_ref_body
+            }
+
+            fn into_raw(self, sender_id: u32) -> Result<Message, MessageWriteError> {
                 #into_raw_body
             }
 
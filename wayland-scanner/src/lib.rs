@@ -72,6 +72,8 @@
 //!         pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
 //!         pub(crate) use wayland_commons::{Interface, MessageGroup};
 //!         pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, Message};
+//!         pub(crate) use wayland_commons::wire::{ArgumentRef, MessageRef, OwnedFd};
+//!         pub(crate) use wayland_commons::wire::{FromRawError, ArgumentError, MessageWriteError};
 //!         pub(crate) use wayland_commons::smallvec;
 //!         pub(crate) use wayland_client::protocol::{$($import),*};
 //!         pub(crate) use wayland_client::sys;
@@ -5,7 +5,41 @@ use wayland_sys::server::wl_client;
 
 use crate::imp::ClientInner;
 
-use crate::{Interface, Main, Resource, UserDataMap};
+use crate::{
+    AnonymousObject, BufferCapacity, ClientLimits, Interface, LimitsError, Main,
+    MessageRecorder, Resource, UserDataMap,
+};
+
+/// Information about a protocol object of a client
+///
+/// See `Client::objects()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectInfo {
+    /// The protocol id of the object
+    pub id: u32,
+    /// The interface of the object
+    ///
+    /// This is an owned string, as with the `use_system_lib` feature the resource
+    /// may have been created by C code, with an interface not known to this library.
+    pub interface: String,
+    /// The version of the object
+    pub version: u32,
+    /// Whether the object is still alive, and can be used to send events
+    pub alive: bool,
+}
+
+/// The credentials of the process of a client
+///
+/// See `Client::credentials()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Credentials {
+    /// The process id of the client
+    pub pid: libc::pid_t,
+    /// The user id of the client
+    pub uid: libc::uid_t,
+    /// The group id of the client
+    pub gid: libc::gid_t,
+}
 
 /// A handle to a client connected to your server
 ///
@@ -46,11 +80,84 @@ impl Client {
         self.inner.equals(&other.inner)
     }
 
+    /// Get the credentials of the process of this client
+    ///
+    /// They are the credentials of the process that connected the socket of the client, as
+    /// reported by the kernel when it connected. Returns `None` if the client is dead, or if
+    /// they are not available on this platform.
+    pub fn credentials(&self) -> Option<Credentials> {
+        self.inner.credentials()
+    }
+
     /// Flushes the pending events to this client
     pub fn flush(&self) {
         self.inner.flush()
     }
 
+    /// Get a snapshot of the protocol objects of this client
+    ///
+    /// This is meant for debugging purposes, the returned list is sorted by id and
+    /// is not updated as objects are created or destroyed. It is empty if the client
+    /// is dead.
+    pub fn objects(&self) -> Vec<ObjectInfo> {
+        self.inner.objects()
+    }
+
+    /// Get a handle to the resource of this client with given protocol id
+    ///
+    /// Returns `None` if the client is dead, if it has no live object with this id, or if
+    /// this object is not of interface `I`. This is useful for protocols referring to
+    /// objects by their id, see `get_object()` if the interface is not known.
+    pub fn get_resource<I: Interface + From<Resource<I>> + AsRef<Resource<I>>>(
+        &self,
+        id: u32,
+    ) -> Option<I> {
+        self.inner.get_resource::<I>(id).map(|inner| Resource::wrap(inner).into())
+    }
+
+    /// Get an anonymous handle to the resource of this client with given protocol id
+    ///
+    /// Returns `None` if the client is dead or if it has no live object with this id.
+    pub fn get_object(&self, id: u32) -> Option<AnonymousObject> {
+        self.inner.get_object(id).map(|inner| Resource::wrap(inner).into())
+    }
+
+    /// Set the buffer size policy of this client connection
+    ///
+    /// See `Display::set_default_buffer_capacity` for details.
+    pub fn set_buffer_capacity(&self, capacity: BufferCapacity) {
+        self.inner.set_buffer_capacity(capacity)
+    }
+
+    /// Set the recorder of this client connection
+    ///
+    /// Once set, the recorder is notified of every message sent to or received from this
+    /// client, for example to store the session with a `CaptureWriter` from the
+    /// `wayland_commons::capture` module. Pass `None` to remove the current recorder.
+    ///
+    /// Returns the previous recorder, if any.
+    ///
+    /// With the `use_system_lib` feature, the connection is managed by `libwayland-server`
+    /// and cannot be recorded: this method does nothing and returns `None`.
+    pub fn set_recorder(
+        &self,
+        recorder: Option<Box<dyn MessageRecorder>>,
+    ) -> Option<Box<dyn MessageRecorder>> {
+        self.inner.set_recorder(recorder)
+    }
+
+    /// Set the limits on the resources used by this client
+    ///
+    /// This replaces the limits the client was created with, see
+    /// `Display::set_default_client_limits` for details.
+    ///
+    /// This returns `LimitsError::DeferredObjects` if the objects limit uses the `Defer`
+    /// policy. With the `use_system_lib` feature, this returns `LimitsError::Unsupported` if any
+    /// limit is set.
+    pub fn set_limits(&self, limits: ClientLimits) -> Result<(), LimitsError> {
+        self.inner.set_limits(limits)
+    }
+
     /// Kills this client
     ///
     /// Does nothing if the client is already dead.
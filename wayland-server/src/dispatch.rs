@@ -0,0 +1,103 @@
+use crate::{Display, Filter, Global, Interface, Main, MessageGroup, Resource, ResourceMap};
+
+/// A trait for state types handling the binding of globals of interface `I`
+///
+/// Globals created with `Display::create_global_dispatch()` call the `bind()` method of the
+/// state type when a client instantiates them, with the mutable reference given as dispatch
+/// data to `Display::dispatch()`.
+pub trait GlobalDispatch<I>: Sized
+where
+    I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+{
+    /// The data associated with each global of this interface
+    type GlobalData: 'static;
+
+    /// Handle the instantiation of a global by a client
+    ///
+    /// The new resource is not assigned to anything, you need to assign it, for example with
+    /// `Main::assign_dispatch()` to set its data and handle its requests with this state type.
+    fn bind(&mut self, resource: Main<I>, global_data: &Self::GlobalData);
+}
+
+/// A trait for state types handling the requests of resources of interface `I`
+///
+/// This is an alternative to assigning a `Filter` to each resource: the requests of the
+/// resources assigned with `Main::assign_dispatch()` are given to the `request()` method of the
+/// state type, along with the data set when they were assigned. The state is the mutable
+/// reference given as dispatch data to `Display::dispatch()`, so the handlers of all
+/// resources can access it without downcasting their `DispatchData`.
+///
+/// A state type implements this trait once for each interface it handles.
+pub trait Dispatch<I>: Sized
+where
+    I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+{
+    /// The data associated with each resource of this interface
+    type UserData: 'static;
+
+    /// Handle a request of a resource of this interface
+    ///
+    /// The resources created by the request are not assigned to anything, and should be
+    /// assigned with `Main::assign_dispatch()` as well.
+    fn request(&mut self, resource: &Main<I>, request: I::Request, data: &Self::UserData);
+}
+
+impl Display {
+    /// Create a new global object handled by a state type
+    ///
+    /// This behaves like `create_global()`, but the instantiations of the global are given to
+    /// `<State as GlobalDispatch<I>>::bind()` along with `global_data`. The display must then
+    /// be dispatched with a `&mut State` as dispatch data, otherwise the instantiations of this
+    /// global are ignored.
+    pub fn create_global_dispatch<I, State>(
+        &mut self,
+        version: u32,
+        global_data: <State as GlobalDispatch<I>>::GlobalData,
+    ) -> Global<I>
+    where
+        I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+        State: GlobalDispatch<I> + 'static,
+    {
+        self.create_global(
+            version,
+            Filter::new(move |(resource, _): (Main<I>, u32), _, mut ddata| {
+                match ddata.get::<State>() {
+                    Some(state) => state.bind(resource, &global_data),
+                    None => eprintln!(
+                        "[wayland-server] Binding of {} ignored, as there is no state to handle it.",
+                        I::NAME
+                    ),
+                }
+            }),
+        )
+    }
+}
+
+impl<I> Main<I>
+where
+    I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+{
+    /// Assign this object to a state type
+    ///
+    /// All future requests received by this object will be delivered to
+    /// `<State as Dispatch<I>>::request()` along with `data`, which is fixed for the lifetime of
+    /// the object. This replaces any filter previously assigned to the object.
+    ///
+    /// The display must be dispatched with a `&mut State` as dispatch data, otherwise the
+    /// requests received by this object are ignored.
+    pub fn assign_dispatch<State>(&self, data: <State as Dispatch<I>>::UserData)
+    where
+        State: Dispatch<I> + 'static,
+        I::Request: MessageGroup<Map = ResourceMap>,
+    {
+        self.quick_assign(move |resource, request, mut ddata| match ddata.get::<State>() {
+            Some(state) => state.request(&resource, request, &data),
+            None => eprintln!(
+                "[wayland-server] Request {} for {}@{} ignored, as there is no state to handle it.",
+                I::Request::MESSAGES[request.opcode() as usize].name,
+                I::NAME,
+                resource.as_ref().id()
+            ),
+        });
+    }
+}
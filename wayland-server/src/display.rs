@@ -1,15 +1,22 @@
 use std::env;
 use std::ffi::{OsStr, OsString};
 use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::os::raw::c_int;
 use std::os::unix::io::{IntoRawFd, RawFd};
+use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 #[cfg(feature = "use_system_lib")]
 use wayland_sys::server::wl_display;
 
 use crate::imp::DisplayInner;
 
-use crate::{Client, Filter, Global, Interface, Main, Resource};
+use crate::{
+    BufferCapacity, Client, ClientLimits, FdSource, Filter, Global, IdleSource, Interest,
+    Interface, LimitsError, Main, MessageGroup, ProtocolLogger, Readiness, Resource,
+    SignalSource, TimerSource,
+};
 
 /// The wayland display
 ///
@@ -23,8 +30,8 @@ pub struct Display {
 impl Display {
     /// Create a new display
     ///
-    /// This method provides you a `Display` and inserts it into an existing
-    /// `calloop::EventLoop`.
+    /// This method provides you a `Display`. To drive it from a `calloop::EventLoop`,
+    /// you can wrap it in a `DisplaySource` if the `calloop` cargo feature is enabled.
     ///
     /// Note that at this point, your server is not yet ready to receive connections,
     /// your need to add listening sockets using the `add_socket*` methods.
@@ -111,7 +118,13 @@ impl Display {
     /// during this (destructors notably) via the [`DispatchData`](struct.DispatchData.html) mechanism.
     /// If you don't need global data, you can just provide a `&mut ()` there.
     pub fn flush_clients<T: std::any::Any>(&mut self, data: &mut T) {
-        let data = crate::DispatchData::wrap(data);
+        self.flush_clients_with_data(crate::DispatchData::wrap(data))
+    }
+
+    /// Same as `flush_clients()`, with the callbacks accessing the content of a `DispatchData`
+    ///
+    /// This allows giving several references of different types to the callbacks.
+    pub fn flush_clients_with_data(&mut self, data: crate::DispatchData) {
         self.inner.flush_clients(data)
     }
 
@@ -138,12 +151,34 @@ impl Display {
         timeout: std::time::Duration,
         data: &mut T,
     ) -> IoResult<()> {
-        let data = crate::DispatchData::wrap(data);
+        self.dispatch_with_data(timeout, crate::DispatchData::wrap(data))
+    }
+
+    /// Same as `dispatch()`, with the callbacks accessing the content of a `DispatchData`
+    ///
+    /// This allows giving several references of different types to the callbacks.
+    pub fn dispatch_with_data(
+        &mut self,
+        timeout: std::time::Duration,
+        data: crate::DispatchData,
+    ) -> IoResult<()> {
         let ms = timeout.as_millis();
         let clamped_timeout = if ms > std::i32::MAX as u128 { std::i32::MAX } else { ms as i32 };
         self.inner.dispatch(clamped_timeout, data)
     }
 
+    // Dispatch the pending messages and flush the clients through a shared reference, so that
+    // the callbacks of a `DisplaySource` can still borrow its display
+    #[cfg(feature = "calloop")]
+    pub(crate) fn dispatch_pending_shared(&self, data: crate::DispatchData) -> IoResult<()> {
+        self.inner.dispatch(0, data)
+    }
+
+    #[cfg(feature = "calloop")]
+    pub(crate) fn flush_clients_shared(&self, data: crate::DispatchData) {
+        self.inner.flush_clients(data)
+    }
+
     /// Retrieve the underlying file descriptor
     ///
     /// This file descriptor can be monitored for activity with a poll/epoll like mechanism.
@@ -154,6 +189,141 @@ impl Display {
     pub fn get_poll_fd(&self) -> RawFd {
         self.inner.get_poll_fd()
     }
+
+    /// Add an fd to the event loop of this display
+    ///
+    /// `callback` will be called during `dispatch()` whenever `fd` has one of the readiness of
+    /// `interest`, with the current readiness of the fd. The fd is not owned by the display, and
+    /// must be kept open until the source is removed.
+    pub fn add_fd_source<F>(
+        &mut self,
+        fd: RawFd,
+        interest: Interest,
+        callback: F,
+    ) -> IoResult<FdSource>
+    where
+        F: FnMut(Readiness, crate::DispatchData<'_>) + 'static,
+    {
+        self.inner.add_fd_source(fd, interest, callback).map(FdSource::make)
+    }
+
+    /// Add a timer to the event loop of this display
+    ///
+    /// The timer is initially disarmed, and is armed with `TimerSource::set_timeout()`.
+    /// `callback` is then called once during `dispatch()` after the delay has elapsed.
+    pub fn add_timer<F>(&mut self, callback: F) -> IoResult<TimerSource>
+    where
+        F: FnMut(crate::DispatchData<'_>) + 'static,
+    {
+        self.inner.add_timer(callback).map(TimerSource::make)
+    }
+
+    /// Add a signal handler to the event loop of this display
+    ///
+    /// `signal` is blocked for the current thread, and `callback` is called during `dispatch()`
+    /// whenever it is received. As with `libwayland-server`, the signal should also be blocked
+    /// for the other threads of your program, for example by adding this source before spawning
+    /// them, otherwise they may receive it instead.
+    pub fn add_signal<F>(&mut self, signal: c_int, callback: F) -> IoResult<SignalSource>
+    where
+        F: FnMut(crate::DispatchData<'_>) + 'static,
+    {
+        self.inner.add_signal(signal, callback).map(SignalSource::make)
+    }
+
+    /// Add an idle callback to the event loop of this display
+    ///
+    /// `callback` is called once, at the start of the next `dispatch()`, before waiting for new
+    /// events.
+    pub fn add_idle<F>(&mut self, callback: F) -> IoResult<IdleSource>
+    where
+        F: FnOnce(crate::DispatchData<'_>) + 'static,
+    {
+        self.inner.add_idle(callback).map(IdleSource::make)
+    }
+
+    /// Get a new serial
+    ///
+    /// Serials are increasing numbers, sent with the events notifying the clients of user actions
+    /// or state changes so that they can refer to them in their requests. Each call returns a new
+    /// serial, wrapping around after `u32::MAX`. See `SerialTracker` to check the serials provided
+    /// by the clients.
+    pub fn next_serial(&self) -> u32 {
+        self.inner.next_serial()
+    }
+
+    /// Get handles to the clients currently connected to this display
+    ///
+    /// This is a snapshot, it is not updated as clients connect or disconnect.
+    pub fn clients(&self) -> Vec<Client> {
+        self.inner.clients().into_iter().map(Client::make).collect()
+    }
+
+    /// Set the protocol logger of this display
+    ///
+    /// The logger will be invoked for every request dispatched and every event sent
+    /// to any client of this display, replacing any previously set logger. Pass `None`
+    /// to remove the current logger.
+    ///
+    /// If the `WAYLAND_DEBUG` env variable is set to `1` or `server`, messages are
+    /// initially printed to stderr.
+    ///
+    /// With the `use_system_lib` feature, the logger is installed with
+    /// `wl_display_add_protocol_logger` and sees the messages of all objects. The
+    /// `WAYLAND_DEBUG` output is then printed by `libwayland-server` in its own format, and is
+    /// not affected by this method.
+    pub fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.inner.set_protocol_logger(logger)
+    }
+
+    /// Set the default filter of an interface
+    ///
+    /// When an object of interface `I` that is not assigned to any filter receives a request,
+    /// it is assigned to this filter, which then receives the request. This replaces any default
+    /// filter previously set for this interface.
+    ///
+    /// This allows handling objects without assigning each of them, for example the objects
+    /// created by requests of other objects, which are otherwise given unassigned to the filter
+    /// of their parent.
+    pub fn set_default_filter<I, E>(&mut self, filter: Filter<E>)
+    where
+        I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+        E: From<(Main<I>, I::Request)> + 'static,
+        I::Request: MessageGroup<Map = crate::ResourceMap>,
+    {
+        self.inner.set_default_filter(filter)
+    }
+
+    /// Set the buffer size policy of the clients created from now on
+    ///
+    /// By default, the outgoing buffer of each client holds 4 KiB of events, and
+    /// sending more events while the client is not reading them fails. Allowing the
+    /// buffers to grow gives slow clients more leeway, and allows sending larger
+    /// events. See also `Client::set_buffer_capacity`.
+    ///
+    /// With the `use_system_lib` feature, the buffers are managed by `libwayland-server`
+    /// and this method does nothing.
+    pub fn set_default_buffer_capacity(&mut self, capacity: BufferCapacity) {
+        self.inner.set_default_buffer_capacity(capacity)
+    }
+
+    /// Set the limits on the resources used by the clients created from now on
+    ///
+    /// By default, clients have no limits. This applies to the clients connecting to the
+    /// listening sockets of this display as well as the ones created with `create_client()`,
+    /// and can be changed for each client with `Client::set_limits`. See `ClientLimits` for
+    /// details.
+    ///
+    /// This returns `LimitsError::DeferredObjects` if the objects limit uses the `Defer` policy,
+    /// and `LimitsError::Io` if the event loop cannot be set up to resume the deferred clients.
+    /// With the `use_system_lib` feature, the clients are managed by `libwayland-server` and
+    /// this returns `LimitsError::Unsupported` if any limit is set.
+    pub fn set_default_client_limits(
+        &mut self,
+        limits: ClientLimits,
+    ) -> Result<(), LimitsError> {
+        self.inner.set_default_client_limits(limits)
+    }
 }
 
 impl Display {
@@ -231,6 +401,39 @@ impl Display {
         let data = crate::DispatchData::wrap(data);
         Client::make(self.inner.create_client(fd, data))
     }
+
+    /// Create a new client to this display from an already connected unix socket
+    ///
+    /// This is the safe counterpart of `create_client()`, which takes ownership of the socket.
+    pub fn create_client_from_stream<T: std::any::Any>(
+        &mut self,
+        stream: UnixStream,
+        data: &mut T,
+    ) -> Client {
+        unsafe { self.create_client(stream.into_raw_fd(), data) }
+    }
+
+    /// Connect a client running in the same process to this display
+    ///
+    /// This creates a pair of connected unix sockets, inserts one end as a new client of this
+    /// display and connects a `wayland_client::Display` to the other end. It does not involve
+    /// any listening socket, and thus works without `XDG_RUNTIME_DIR`.
+    ///
+    /// The client `Display` uses its own backend, as selected by the features of the
+    /// `wayland-client` crate. This method is only available with the `wayland-client` cargo
+    /// feature.
+    #[cfg(feature = "wayland-client")]
+    pub fn connect_client<T: std::any::Any>(
+        &mut self,
+        data: &mut T,
+    ) -> IoResult<(Client, wayland_client::Display)> {
+        let (server_end, client_end) = UnixStream::pair()?;
+        // on a fresh socket, this can only fail if libwayland-client.so is missing or fails
+        let display = wayland_client::Display::from_stream(client_end)
+            .map_err(|e| IoError::new(ErrorKind::NotFound, e))?;
+        let client = self.create_client_from_stream(server_end, data);
+        Ok((client, display))
+    }
 }
 
 #[cfg(feature = "use_system_lib")]
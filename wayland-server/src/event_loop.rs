@@ -0,0 +1,133 @@
+use std::io::Result as IoResult;
+use std::time::Duration;
+
+use crate::imp::{FdSourceInner, IdleSourceInner, SignalSourceInner, TimerSourceInner};
+
+bitflags! {
+    /// The readiness an fd source is interested in
+    pub struct Interest: u32 {
+        /// The fd is readable
+        const READ = 1;
+        /// The fd is writable
+        const WRITE = 2;
+    }
+}
+
+bitflags! {
+    /// The readiness of the fd of an fd source
+    pub struct Readiness: u32 {
+        /// The fd is readable
+        const READABLE = 1;
+        /// The fd is writable
+        const WRITABLE = 2;
+        /// The other end of the fd hung up
+        const HANGUP = 4;
+        /// An error occurred on the fd
+        const ERROR = 8;
+    }
+}
+
+/// A handle to an fd source of a `Display`
+///
+/// See `Display::add_fd_source()`.
+///
+/// If you know you will never remove this source, you can let this handle go out of scope. It
+/// may outlive the display, whose destruction removes the source: its methods then do nothing.
+pub struct FdSource {
+    inner: FdSourceInner,
+}
+
+impl FdSource {
+    pub(crate) fn make(inner: FdSourceInner) -> FdSource {
+        FdSource { inner }
+    }
+
+    /// Change the readiness this source is interested in
+    pub fn update(&self, interest: Interest) -> IoResult<()> {
+        self.inner.update(interest)
+    }
+
+    /// Remove this source from the event loop of its display
+    ///
+    /// The fd is not closed.
+    pub fn remove(self) {
+        self.inner.remove()
+    }
+}
+
+/// A handle to a timer source of a `Display`
+///
+/// See `Display::add_timer()`.
+///
+/// If you know you will never remove this source, you can let this handle go out of scope. It
+/// may outlive the display, whose destruction removes the source: its methods then do nothing.
+pub struct TimerSource {
+    inner: TimerSourceInner,
+}
+
+impl TimerSource {
+    pub(crate) fn make(inner: TimerSourceInner) -> TimerSource {
+        TimerSource { inner }
+    }
+
+    /// Arm or disarm the timer
+    ///
+    /// With `Some(delay)`, the callback of the timer will be called once `delay` has elapsed,
+    /// replacing any previously set delay. With `None`, the timer is disarmed.
+    ///
+    /// The delay has a millisecond precision with the system library.
+    pub fn set_timeout(&self, delay: Option<Duration>) -> IoResult<()> {
+        self.inner.set_timeout(delay)
+    }
+
+    /// Remove this timer from the event loop of its display
+    pub fn remove(self) {
+        self.inner.remove()
+    }
+}
+
+/// A handle to a signal source of a `Display`
+///
+/// See `Display::add_signal()`.
+///
+/// If you know you will never remove this source, you can let this handle go out of scope. It
+/// may outlive the display, whose destruction removes the source: its methods then do nothing.
+pub struct SignalSource {
+    inner: SignalSourceInner,
+}
+
+impl SignalSource {
+    pub(crate) fn make(inner: SignalSourceInner) -> SignalSource {
+        SignalSource { inner }
+    }
+
+    /// Remove this source from the event loop of its display
+    ///
+    /// The signal stays blocked for the thread hosting the display.
+    pub fn remove(self) {
+        self.inner.remove()
+    }
+}
+
+/// A handle to an idle callback of a `Display`
+///
+/// See `Display::add_idle()`.
+///
+/// If you know you will never cancel this callback, you can let this handle go out of scope. It
+/// may outlive the display, whose destruction cancels the callback: its methods then do nothing.
+pub struct IdleSource {
+    inner: IdleSourceInner,
+}
+
+impl IdleSource {
+    pub(crate) fn make(inner: IdleSourceInner) -> IdleSource {
+        IdleSource { inner }
+    }
+
+    /// Cancel this idle callback
+    ///
+    /// Does nothing if it already ran.
+    pub fn remove(self) {
+        self.inner.remove()
+    }
+}
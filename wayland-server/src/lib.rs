@@ -55,16 +55,43 @@
 //! a request), unless the exact message received is a destructor (which is indicated in the API
 //! documentations).
 //!
+//! ### Typed dispatching
+//!
+//! As an alternative to filters, the requests of your objects can be handled by the `Dispatch<I>`
+//! implementations of your compositor state type, and the instantiations of your globals by its
+//! `GlobalDispatch<I>` implementations. Globals are created with `Display::create_global_dispatch()`
+//! and resources are assigned with `Main::assign_dispatch()`, which fixes their typed data. The
+//! `&mut State` given to `Display::dispatch()` is then provided directly to these methods.
+//!
 //! ## General structure
 //!
 //! The core of your server is the `Display` object. It represent the ability of your program to
 //! process Wayland messages. Once this object is created, you can configure it to listen on one
 //! or more sockets for incoming client connections (see the `Display` docs for details).
 //!
-//! `wayland-server` does not include an event loop, and you are expected to drive the wayland socket
+//! `wayland-server` does not run an event loop by itself, and you are expected to drive the wayland socket
 //! yourself using the `Display::flush_clients` and `Display::dispatch` methods. The `Display::get_poll_fd`
 //! methods provides you with a file descriptor that can be used in a polling structure to integrate
 //! the wayland socket in an event loop.
+//!
+//! The event loop of the `Display` can also monitor your own file descriptors, timers and signals,
+//! and run idle callbacks, see `Display::add_fd_source()`, `Display::add_timer()`,
+//! `Display::add_signal()` and `Display::add_idle()`. Small servers can thus run without a
+//! separate event loop, calling `Display::dispatch()` in a loop.
+//!
+//! Serials for the events sent to the clients are obtained with `Display::next_serial()`, and the
+//! `SerialTracker` helps validating the serials the clients give back in their requests.
+//!
+//! The `shm` module provides a ready-made `wl_shm` global, created with
+//! `Display::create_shm_global()`, and safe access to the contents of the buffers of its clients.
+//!
+//! Clients running in the same process can be connected without any listening socket using
+//! `Display::create_client_from_stream()`, or `Display::connect_client()` which also provides the
+//! connected `wayland_client::Display` if the `wayland-client` cargo feature is enabled.
+//!
+//! If you activate the `calloop` cargo feature, the `DisplaySource` type provides a ready-made
+//! event source for the [`calloop`](https://docs.rs/calloop) event loop, which dispatches the
+//! `Display` with the shared data of the loop.
 
 #![warn(missing_docs)]
 
@@ -75,19 +102,36 @@ extern crate bitflags;
 extern crate wayland_sys;
 
 mod client;
+mod dispatch;
 mod display;
+mod event_loop;
 mod globals;
+mod limits;
+#[cfg(feature = "calloop")]
+mod loop_source;
 mod resource;
+mod serial;
+pub mod shm;
 
-pub use client::Client;
+pub use client::{Client, Credentials, ObjectInfo};
+pub use dispatch::{Dispatch, GlobalDispatch};
 pub use display::Display;
+pub use event_loop::{FdSource, IdleSource, Interest, Readiness, SignalSource, TimerSource};
 pub use globals::Global;
+pub use limits::{ClientLimits, Limit, LimitPolicy, LimitsError};
+#[cfg(feature = "calloop")]
+pub use loop_source::DisplaySource;
 pub use resource::{Main, Resource};
+pub use serial::SerialTracker;
 
 pub use anonymous_object::AnonymousObject;
 pub use wayland_commons::user_data::UserDataMap;
 pub use wayland_commons::{
-    filter::{DispatchData, Filter},
+    capture::MessageRecorder,
+    debug::{LoggedMessage, MessageDirection, ProtocolLogger, StderrLogger},
+    filter::{DispatchData, Filter, Router},
+    socket::BufferCapacity,
+    wire::OwnedFd,
     Interface, MessageGroup, NoMessage,
 };
 
@@ -119,7 +163,10 @@ pub mod protocol {
     pub(crate) use crate::{AnonymousObject, Main, Resource, ResourceMap};
     pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
     pub(crate) use wayland_commons::smallvec;
-    pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+    pub(crate) use wayland_commons::wire::{
+        Argument, ArgumentError, ArgumentRef, ArgumentType, FromRawError, Message, MessageDesc,
+        MessageRef, MessageWriteError, OwnedFd,
+    };
     pub(crate) use wayland_commons::{Interface, MessageGroup};
     pub(crate) use wayland_sys as sys;
     include!(concat!(env!("OUT_DIR"), "/wayland_api.rs"));
@@ -0,0 +1,118 @@
+use std::{fmt, io};
+
+/// What happens when a client exceeds one of its limits
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LimitPolicy {
+    /// Stop processing the requests of the client until it is back under the limit
+    ///
+    /// The requests are processed again during a later dispatch. This policy cannot be used for
+    /// the objects limit, as the client would need its requests to be processed to destroy
+    /// objects: setting it there returns `LimitsError::DeferredObjects`.
+    Defer,
+    /// Kill the client with a `no_memory` protocol error
+    NoMemory,
+    /// Disconnect the client without sending any error
+    Disconnect,
+}
+
+/// A limit on a resource used by a client, and the policy applied when it is exceeded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Limit {
+    /// The maximum amount of the resource the client can use
+    pub max: usize,
+    /// What happens when the client exceeds `max`
+    pub policy: LimitPolicy,
+}
+
+impl Limit {
+    /// A limit of `max`, applying `policy` when it is exceeded
+    pub fn new(max: usize, policy: LimitPolicy) -> Limit {
+        Limit { max, policy }
+    }
+}
+
+/// The limits on the resources used by a client
+///
+/// These allow a server to stay responsive when a buggy or hostile client creates objects or
+/// sends requests without bounds. All limits are disabled by default. See
+/// `Display::set_default_client_limits()` and `Client::set_limits()`.
+///
+/// With the `use_system_lib` feature, the clients are managed by `libwayland-server` and these
+/// limits cannot be enforced: setting any of them returns `LimitsError::Unsupported`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientLimits {
+    /// Maximum number of live objects created by the client, including its `wl_display`
+    ///
+    /// This is checked when the client sends a request creating a new object, the objects
+    /// created by the server are not counted. The `Defer` policy cannot apply to this limit.
+    pub objects: Option<Limit>,
+    /// Maximum number of requests of the client processed during a single dispatch
+    ///
+    /// This ensures a client flooding the server with requests cannot prevent the others from
+    /// being served. With the `Defer` policy, the remaining requests are processed during the
+    /// next dispatch.
+    pub requests_per_dispatch: Option<Limit>,
+    /// Maximum number of bytes of events queued for the client and not yet sent
+    ///
+    /// This is checked when the client sends a request and when flushing the clients. With the
+    /// `Defer` policy, the requests of the client are not processed until it has read enough of
+    /// its events. This is only useful if the outgoing buffer of the client can grow larger than
+    /// this limit, see `BufferCapacity`.
+    pub queued_bytes: Option<Limit>,
+}
+
+/// The error returned when setting client limits
+#[derive(Debug)]
+pub enum LimitsError {
+    /// The limits cannot be enforced with the system library
+    ///
+    /// With the `use_system_lib` feature, the clients are managed by `libwayland-server`, which
+    /// has no limits on their resources. Only `ClientLimits::default()`, with all limits
+    /// disabled, can then be set.
+    Unsupported,
+    /// The `Defer` policy was set for the objects limit
+    DeferredObjects,
+    /// The event loop could not be set up to resume the deferred clients
+    Io(io::Error),
+}
+
+impl std::error::Error for LimitsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            LimitsError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LimitsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            LimitsError::Unsupported => {
+                f.write_str("Client limits are not supported with the system library.")
+            }
+            LimitsError::DeferredObjects => {
+                f.write_str("The objects limit cannot use the Defer policy.")
+            }
+            LimitsError::Io(ref err) => {
+                write!(f, "Could not set up the event loop for the deferred clients: {}", err)
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "use_system_lib"))]
+impl ClientLimits {
+    pub(crate) fn check(&self) -> Result<(), LimitsError> {
+        match self.objects {
+            Some(Limit { policy: LimitPolicy::Defer, .. }) => Err(LimitsError::DeferredObjects),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn uses_defer(&self) -> bool {
+        [self.objects, self.requests_per_dispatch, self.queued_bytes]
+            .iter()
+            .any(|limit| limit.map(|limit| limit.policy) == Some(LimitPolicy::Defer))
+    }
+}
@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::{Rc, Weak};
+
+use calloop::generic::{Fd, Generic};
+use calloop::{
+    EventSource, InsertError, Interest, LoopHandle, Mode, Poll, Readiness, Source, Token,
+};
+
+use crate::{DispatchData, Display};
+
+/// A `calloop` event source for a `Display`
+///
+/// This event source becomes ready when the display has pending messages, and provides a
+/// handle to the display to its callback, which can then dispatch them. The simplest way to use
+/// it is to insert it using the `insert()` method, whose callback dispatches the display with
+/// the shared data of the event loop, and which also flushes the clients at the end of each
+/// dispatch of the event loop.
+///
+/// Once inserted, the display can still be accessed through the handle given by the
+/// `display()` method. If you insert it with `LoopHandle::insert_source()` instead, nothing is
+/// flushed for you: you then need to flush the clients after each dispatch of the event loop,
+/// for example in the callback given to `EventLoop::run()`.
+///
+/// This type is only available with the `calloop` cargo feature.
+pub struct DisplaySource {
+    display: Rc<RefCell<Display>>,
+    fd: Generic<Fd>,
+    // dropped with the source, to stop flushing once it is removed from its event loop
+    alive: Rc<()>,
+}
+
+impl DisplaySource {
+    /// Create an event source for this display
+    pub fn new(display: Display) -> DisplaySource {
+        let fd = Generic::from_fd(display.get_poll_fd(), Interest::Readable, Mode::Level);
+        DisplaySource { display: Rc::new(RefCell::new(display)), fd, alive: Rc::new(()) }
+    }
+
+    /// Get a handle to the display of this source
+    pub fn display(&self) -> Rc<RefCell<Display>> {
+        self.display.clone()
+    }
+
+    /// Insert this source in an event loop
+    ///
+    /// Every time the display has pending messages, they are dispatched with the shared data
+    /// of the event loop. The display is only borrowed immutably while doing so, the callbacks
+    /// can thus borrow it as well through its handle, for example to get the next serial.
+    ///
+    /// This also inserts an idle callback, re-inserted every time it runs, which flushes the
+    /// clients at the end of each dispatch of the event loop, including the events sent from
+    /// the callbacks of other event sources. It stops once this source is removed from the
+    /// event loop, and skips flushing if the display is mutably borrowed at that time.
+    pub fn insert<Data: 'static>(
+        self,
+        handle: &LoopHandle<Data>,
+    ) -> Result<Source<DisplaySource>, InsertError<DisplaySource>> {
+        let display = self.display.clone();
+        let alive = Rc::downgrade(&self.alive);
+        let source = handle.insert_source(self, |(), display, data| {
+            display.borrow().dispatch_pending_shared(DispatchData::wrap(data))
+        })?;
+        flush_on_idle(handle.clone(), display, alive);
+        Ok(source)
+    }
+}
+
+fn flush_on_idle<Data: 'static>(
+    handle: LoopHandle<Data>,
+    display: Rc<RefCell<Display>>,
+    alive: Weak<()>,
+) {
+    handle.clone().insert_idle(move |data| {
+        if alive.upgrade().is_some() {
+            if let Ok(display) = display.try_borrow() {
+                display.flush_clients_shared(DispatchData::wrap(data));
+            }
+            flush_on_idle(handle, display, alive);
+        }
+    });
+}
+
+impl EventSource for DisplaySource {
+    type Event = ();
+    type Metadata = Rc<RefCell<Display>>;
+    type Ret = io::Result<()>;
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> io::Result<()>
+    where
+        F: FnMut((), &mut Rc<RefCell<Display>>) -> io::Result<()>,
+    {
+        let display = &mut self.display;
+        self.fd.process_events(readiness, token, |_, _| callback((), display))
+    }
+
+    fn register(&mut self, poll: &mut Poll, token: Token) -> io::Result<()> {
+        self.fd.register(poll, token)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token: Token) -> io::Result<()> {
+        self.fd.reregister(poll, token)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> io::Result<()> {
+        self.fd.unregister(poll)
+    }
+}
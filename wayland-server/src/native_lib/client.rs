@@ -8,7 +8,10 @@ use wayland_commons::ThreadGuard;
 use wayland_sys::server::*;
 
 use super::resource::ResourceInner;
-use crate::{DispatchData, Interface, Resource, UserDataMap};
+use crate::{
+    BufferCapacity, ClientLimits, Credentials, DispatchData, Interface, LimitsError,
+    MessageRecorder, ObjectInfo, Resource, UserDataMap,
+};
 
 type BoxedDest = Box<dyn FnMut(Arc<UserDataMap>, DispatchData<'_>) + 'static>;
 
@@ -89,6 +92,103 @@ impl ClientInner {
         }
     }
 
+    pub(crate) fn credentials(&self) -> Option<Credentials> {
+        if !self.alive() {
+            return None;
+        }
+        let (mut pid, mut uid, mut gid) = (0, 0, 0);
+        let _c_safety_guard = super::C_SAFETY.lock();
+        unsafe {
+            ffi_dispatch!(
+                WAYLAND_SERVER_HANDLE,
+                wl_client_get_credentials,
+                self.ptr,
+                &mut pid,
+                &mut uid,
+                &mut gid
+            );
+        }
+        Some(Credentials { pid, uid, gid })
+    }
+
+    pub(crate) fn objects(&self) -> Vec<ObjectInfo> {
+        let mut objects = Vec::new();
+        if !self.alive() {
+            return objects;
+        }
+        let _c_safety_guard = super::C_SAFETY.lock();
+        unsafe {
+            ffi_dispatch!(
+                WAYLAND_SERVER_HANDLE,
+                wl_client_for_each_resource,
+                self.ptr,
+                object_info_iterator,
+                &mut objects as *mut Vec<ObjectInfo> as *mut c_void
+            );
+        }
+        objects.sort_by_key(|object| object.id);
+        objects
+    }
+
+    pub(crate) fn get_resource<I: Interface + From<Resource<I>> + AsRef<Resource<I>>>(
+        &self,
+        id: u32,
+    ) -> Option<ResourceInner> {
+        let ptr = self.get_object_ptr(id)?;
+        let _c_safety_guard = super::C_SAFETY.lock();
+        unsafe {
+            let interface = ::std::ffi::CStr::from_ptr(ffi_dispatch!(
+                WAYLAND_SERVER_HANDLE,
+                wl_resource_get_class,
+                ptr
+            ));
+            if interface.to_bytes() != I::NAME.as_bytes() {
+                return None;
+            }
+            Some(ResourceInner::from_c_ptr::<I>(ptr))
+        }
+    }
+
+    pub(crate) fn get_object(&self, id: u32) -> Option<ResourceInner> {
+        let ptr = self.get_object_ptr(id)?;
+        unsafe { Some(ResourceInner::from_c_ptr_anonymous(ptr)) }
+    }
+
+    fn get_object_ptr(&self, id: u32) -> Option<*mut wl_resource> {
+        if !self.alive() {
+            return None;
+        }
+        let _c_safety_guard = super::C_SAFETY.lock();
+        let ptr =
+            unsafe { ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_client_get_object, self.ptr, id) };
+        if ptr.is_null() {
+            None
+        } else {
+            Some(ptr)
+        }
+    }
+
+    pub(crate) fn set_buffer_capacity(&self, _capacity: BufferCapacity) {
+        // libwayland-server manages the size of its buffers itself
+    }
+
+    pub(crate) fn set_recorder(
+        &self,
+        _recorder: Option<Box<dyn MessageRecorder>>,
+    ) -> Option<Box<dyn MessageRecorder>> {
+        // libwayland-server does not give access to the messages of its clients
+        None
+    }
+
+    pub(crate) fn set_limits(&self, limits: ClientLimits) -> Result<(), LimitsError> {
+        // libwayland-server has no limits on the resources of its clients
+        if limits == ClientLimits::default() {
+            Ok(())
+        } else {
+            Err(LimitsError::Unsupported)
+        }
+    }
+
     pub(crate) fn kill(&self) {
         if !self.alive() {
             return;
@@ -146,6 +246,25 @@ impl ClientInner {
     }
 }
 
+unsafe extern "C" fn object_info_iterator(
+    resource: *mut wl_resource,
+    data: *mut c_void,
+) -> wl_iterator_result {
+    let objects = &mut *(data as *mut Vec<ObjectInfo>);
+    let interface = ::std::ffi::CStr::from_ptr(ffi_dispatch!(
+        WAYLAND_SERVER_HANDLE,
+        wl_resource_get_class,
+        resource
+    ));
+    objects.push(ObjectInfo {
+        id: ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_id, resource),
+        interface: interface.to_string_lossy().into_owned(),
+        version: ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_version, resource) as u32,
+        alive: true,
+    });
+    wl_iterator_result::WL_ITERATOR_CONTINUE
+}
+
 unsafe extern "C" fn client_destroy(listener: *mut wl_listener, _data: *mut c_void) {
     let internal =
         Box::from_raw(signal::rust_listener_get_user_data(listener) as *mut Arc<ClientInternal>);
@@ -1,23 +1,39 @@
 use std::cell::RefCell;
 use std::ffi::{CStr, CString, OsStr, OsString};
 use std::io::{Error as IoError, ErrorKind, Result as IoResult};
-use std::os::raw::c_void;
+use std::os::raw::{c_int, c_void};
 use std::os::unix::ffi::{OsStrExt, OsStringExt};
 use std::os::unix::io::RawFd;
 use std::ptr;
 use std::rc::Rc;
+use std::sync::Arc;
 
+use wayland_commons::debug::{LoggedMessage, MessageDirection};
+use wayland_commons::wire::ArgumentRef;
+use wayland_sys::common::{wl_argument, wl_message};
 use wayland_sys::server::*;
 
 use super::globals::GlobalData;
-use super::{ClientInner, GlobalInner};
+use super::resource::DefaultHandler;
+use super::{
+    ClientInner, DefaultHandlers, EventLoopSources, FdSourceInner, GlobalInner, IdleSourceInner,
+    SignalSourceInner, TimerSourceInner,
+};
 
 use crate::display::get_runtime_dir;
-use crate::{Interface, Main, Resource};
+use crate::{
+    BufferCapacity, ClientLimits, Filter, Interest, Interface, LimitsError, Main,
+    ProtocolLogger, Readiness, Resource,
+};
+
+type BoxedLogger = Box<Arc<dyn ProtocolLogger>>;
 
 pub(crate) struct DisplayInner {
     pub(crate) ptr: *mut wl_display,
     rust_globals: Rc<RefCell<Vec<*mut wl_global>>>,
+    logger: RefCell<Option<(*mut wl_protocol_logger, BoxedLogger)>>,
+    default_handlers: DefaultHandlers,
+    sources: Rc<EventLoopSources>,
 }
 
 impl Drop for DisplayInner {
@@ -25,10 +41,18 @@ impl Drop for DisplayInner {
         {
             let _c_safety_guard = super::C_SAFETY.lock();
             unsafe {
+                if let Some((logger, _)) = self.logger.get_mut().take() {
+                    ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_protocol_logger_destroy, logger);
+                }
                 ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_destroy_clients, self.ptr);
-                ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_destroy, self.ptr);
             }
         }
+        // the handles of the sources may outlive the display, they do nothing once it is destroyed
+        unsafe { self.sources.destroy() };
+        {
+            let _c_safety_guard = super::C_SAFETY.lock();
+            unsafe { ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_destroy, self.ptr) };
+        }
     }
 }
 
@@ -55,7 +79,13 @@ impl DisplayInner {
                 &*rust_globals as *const RefCell<Vec<*mut wl_global>> as *mut _
             );
 
-            DisplayInner { ptr, rust_globals }
+            DisplayInner {
+                ptr,
+                rust_globals,
+                logger: RefCell::new(None),
+                default_handlers: Default::default(),
+                sources: Rc::new(EventLoopSources::new()),
+            }
         }
     }
 
@@ -93,25 +123,24 @@ impl DisplayInner {
         }
     }
 
-    pub(crate) fn flush_clients(&mut self, data: crate::DispatchData) {
+    pub(crate) fn flush_clients(&self, data: crate::DispatchData) {
         super::with_dispatch_data(data, || {
             let _c_safety_guard = super::C_SAFETY.lock();
             unsafe { ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_flush_clients, self.ptr) };
         })
     }
 
-    pub(crate) fn dispatch(
-        &mut self,
-        timeout: i32,
-        data: crate::DispatchData,
-    ) -> std::io::Result<()> {
-        let ret = super::with_dispatch_data(data, || {
-            let _c_safety_guard = super::C_SAFETY.lock();
-            unsafe {
-                let evl_ptr =
-                    ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_get_event_loop, self.ptr);
-                ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_loop_dispatch, evl_ptr, timeout)
-            }
+    pub(crate) fn dispatch(&self, timeout: i32, data: crate::DispatchData) -> std::io::Result<()> {
+        let ptr = self.ptr;
+        let ret = super::DEFAULT_HANDLERS.set(&self.default_handlers, || {
+            super::with_dispatch_data(data, || {
+                let _c_safety_guard = super::C_SAFETY.lock();
+                unsafe {
+                    let evl_ptr =
+                        ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_get_event_loop, ptr);
+                    ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_loop_dispatch, evl_ptr, timeout)
+                }
+            })
         });
 
         if ret < 0 {
@@ -121,6 +150,115 @@ impl DisplayInner {
         }
     }
 
+    fn event_loop(&self) -> *mut wl_event_loop {
+        let _c_safety_guard = super::C_SAFETY.lock();
+        unsafe { ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_get_event_loop, self.ptr) }
+    }
+
+    pub(crate) fn add_fd_source<F>(
+        &mut self,
+        fd: RawFd,
+        interest: Interest,
+        callback: F,
+    ) -> IoResult<FdSourceInner>
+    where
+        F: FnMut(Readiness, crate::DispatchData<'_>) + 'static,
+    {
+        unsafe { FdSourceInner::new(self.event_loop(), &self.sources, fd, interest, callback) }
+    }
+
+    pub(crate) fn add_timer<F>(&mut self, callback: F) -> IoResult<TimerSourceInner>
+    where
+        F: FnMut(crate::DispatchData<'_>) + 'static,
+    {
+        unsafe { TimerSourceInner::new(self.event_loop(), &self.sources, callback) }
+    }
+
+    pub(crate) fn add_signal<F>(
+        &mut self,
+        signal: c_int,
+        callback: F,
+    ) -> IoResult<SignalSourceInner>
+    where
+        F: FnMut(crate::DispatchData<'_>) + 'static,
+    {
+        unsafe { SignalSourceInner::new(self.event_loop(), &self.sources, signal, callback) }
+    }
+
+    pub(crate) fn add_idle<F>(&mut self, callback: F) -> IoResult<IdleSourceInner>
+    where
+        F: FnOnce(crate::DispatchData<'_>) + 'static,
+    {
+        unsafe { IdleSourceInner::new(self.event_loop(), &self.sources, callback) }
+    }
+
+    pub(crate) fn set_default_filter<I, E>(&mut self, filter: Filter<E>)
+    where
+        I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+        E: From<(Main<I>, I::Request)> + 'static,
+    {
+        let handler: DefaultHandler<I> =
+            Rc::new(move |request, resource, data| filter.send((resource, request).into(), data));
+        self.default_handlers.borrow_mut().insert(I::NAME, Box::new(handler));
+    }
+
+    pub(crate) fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        let _c_safety_guard = super::C_SAFETY.lock();
+        let mut current = self.logger.borrow_mut();
+        unsafe {
+            if let Some((old_logger, _)) = current.take() {
+                ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_protocol_logger_destroy, old_logger);
+            }
+            if let Some(logger) = logger {
+                let data: BoxedLogger = Box::new(logger);
+                let ptr = ffi_dispatch!(
+                    WAYLAND_SERVER_HANDLE,
+                    wl_display_add_protocol_logger,
+                    self.ptr,
+                    protocol_logger,
+                    &*data as *const Arc<dyn ProtocolLogger> as *mut _
+                );
+                *current = Some((ptr, data));
+            }
+        }
+    }
+
+    pub(crate) fn next_serial(&self) -> u32 {
+        let _c_safety_guard = super::C_SAFETY.lock();
+        unsafe { ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_next_serial, self.ptr) }
+    }
+
+    pub(crate) fn clients(&self) -> Vec<ClientInner> {
+        let mut clients = Vec::new();
+        let _c_safety_guard = super::C_SAFETY.lock();
+        unsafe {
+            let list = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_get_client_list, self.ptr);
+            let mut link = (*list).next;
+            while link != list {
+                let client = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_client_from_link, link);
+                clients.push(ClientInner::from_ptr(client));
+                link = (*link).next;
+            }
+        }
+        clients
+    }
+
+    pub(crate) fn set_default_buffer_capacity(&mut self, _capacity: BufferCapacity) {
+        // libwayland-server manages the size of its buffers itself
+    }
+
+    pub(crate) fn set_default_client_limits(
+        &mut self,
+        limits: ClientLimits,
+    ) -> Result<(), LimitsError> {
+        // libwayland-server has no limits on the resources of its clients
+        if limits == ClientLimits::default() {
+            Ok(())
+        } else {
+            Err(LimitsError::Unsupported)
+        }
+    }
+
     pub(crate) fn get_poll_fd(&self) -> RawFd {
         unsafe {
             let evl_ptr = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_get_event_loop, self.ptr);
@@ -211,3 +349,76 @@ unsafe extern "C" fn client_created(_listener: *mut wl_listener, data: *mut c_vo
     // init the client
     let _client = ClientInner::from_ptr(data as *mut wl_client);
 }
+
+unsafe extern "C" fn protocol_logger(
+    user_data: *mut c_void,
+    direction: wl_protocol_logger_type,
+    message: *const wl_protocol_logger_message,
+) {
+    // safety of this function is the same as dispatch_func
+    let ret = ::std::panic::catch_unwind(move || {
+        let logger = &*(user_data as *const Arc<dyn ProtocolLogger>);
+        let message = &*message;
+        let direction = match direction {
+            wl_protocol_logger_type::WL_PROTOCOL_LOGGER_REQUEST => MessageDirection::Received,
+            wl_protocol_logger_type::WL_PROTOCOL_LOGGER_EVENT => MessageDirection::Sent,
+        };
+        let interface = CStr::from_ptr(ffi_dispatch!(
+            WAYLAND_SERVER_HANDLE,
+            wl_resource_get_class,
+            message.resource
+        ))
+        .to_string_lossy();
+        let id = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_id, message.resource);
+        let name = CStr::from_ptr((*message.message).name).to_string_lossy();
+        let args = parse_c_args(message.message, message.arguments);
+        logger.log(&LoggedMessage::now(direction, &interface, id, true, &name, &args));
+    });
+    if ret.is_err() {
+        // a panic occurred
+        eprintln!("[wayland-server error] A protocol logger panicked, aborting.");
+        ::libc::abort();
+    }
+}
+
+/// Parse the arguments of a message from their C representation
+///
+/// The returned arguments borrow the contents of `args`, and their file descriptors
+/// remain owned by libwayland.
+unsafe fn parse_c_args<'a>(
+    message: *const wl_message,
+    args: *const wl_argument,
+) -> Vec<ArgumentRef<'a>> {
+    let signature = CStr::from_ptr((*message).signature).to_bytes();
+    // skip the version and nullability markers of the signature
+    let types = signature.iter().filter(|&&c| c != b'?' && !c.is_ascii_digit());
+    types
+        .enumerate()
+        .map(|(i, &c)| {
+            let arg = &*args.add(i);
+            match c {
+                b'i' => ArgumentRef::Int(arg.i),
+                b'u' => ArgumentRef::Uint(arg.u),
+                b'f' => ArgumentRef::Fixed(arg.f),
+                b's' if arg.s.is_null() => ArgumentRef::Str(Default::default()),
+                b's' => ArgumentRef::Str(CStr::from_ptr(arg.s)),
+                b'o' if arg.o.is_null() => ArgumentRef::Object(0),
+                b'o' => ArgumentRef::Object(ffi_dispatch!(
+                    WAYLAND_SERVER_HANDLE,
+                    wl_resource_get_id,
+                    arg.o as *mut wl_resource
+                )),
+                b'n' => ArgumentRef::NewId(arg.n),
+                b'a' if arg.a.is_null() => ArgumentRef::Array(&[]),
+                b'a' => {
+                    let array = &*arg.a;
+                    ArgumentRef::Array(std::slice::from_raw_parts(
+                        array.data as *const u8,
+                        array.size,
+                    ))
+                }
+                _ => ArgumentRef::Fd(arg.h),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,400 @@
+use std::cell::{Cell, RefCell};
+use std::io::{Error as IoError, Result as IoResult};
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::RawFd;
+use std::rc::Rc;
+use std::time::Duration;
+
+use wayland_sys::server::*;
+
+use crate::{DispatchData, Interest, Readiness};
+
+type FdCallback = SourceData<dyn FnMut(Readiness, DispatchData<'_>)>;
+type TimerCallback = SourceData<dyn FnMut(DispatchData<'_>)>;
+type IdleCallback = Box<dyn FnOnce(DispatchData<'_>)>;
+
+/// The data of a source, which may be removed by its own callback
+struct SourceData<C: ?Sized> {
+    running: Cell<bool>,
+    removed: Cell<bool>,
+    callback: Box<C>,
+}
+
+impl<C: ?Sized> SourceData<C> {
+    fn new(callback: Box<C>) -> *mut SourceData<C> {
+        Box::into_raw(Box::new(SourceData {
+            running: Cell::new(false),
+            removed: Cell::new(false),
+            callback,
+        }))
+    }
+
+    /// Run the callback, freeing the data if it removed its source
+    unsafe fn run<F: FnOnce(&mut C)>(data: *mut SourceData<C>, f: F) {
+        (*data).running.set(true);
+        f(&mut *(*data).callback);
+        (*data).running.set(false);
+        if (*data).removed.get() {
+            drop(Box::from_raw(data));
+        }
+    }
+
+    /// Remove the source and free the data, or let `run()` free it if the callback is running
+    unsafe fn remove(data: *mut SourceData<C>, source: *mut wl_event_source) {
+        remove_source(source);
+        if (*data).running.get() {
+            (*data).removed.set(true);
+        } else {
+            drop(Box::from_raw(data));
+        }
+    }
+}
+
+struct IdleData {
+    callback: Option<IdleCallback>,
+    sources: Rc<EventLoopSources>,
+    id: usize,
+}
+
+unsafe fn free_data<T>(data: *mut c_void) {
+    drop(Box::from_raw(data as *mut T));
+}
+
+struct SourceEntry {
+    id: usize,
+    ptr: *mut wl_event_source,
+    data: *mut c_void,
+    free: unsafe fn(*mut c_void),
+}
+
+/// The sources added to the event loop of a display
+///
+/// Their handles may outlive the display: when it is destroyed, the sources still in its event
+/// loop are removed and their data is freed, after which the handles do nothing. The sources
+/// are identified by an id rather than by their data, whose address may be reused.
+pub(crate) struct EventLoopSources {
+    next_id: Cell<usize>,
+    entries: RefCell<Vec<SourceEntry>>,
+}
+
+impl EventLoopSources {
+    pub(crate) fn new() -> EventLoopSources {
+        EventLoopSources { next_id: Cell::new(0), entries: RefCell::new(Vec::new()) }
+    }
+
+    fn new_id(&self) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+        id
+    }
+
+    fn insert(&self, entry: SourceEntry) {
+        self.entries.borrow_mut().push(entry);
+    }
+
+    /// The source with this id, if it is still in the event loop
+    fn get(&self, id: usize) -> Option<*mut wl_event_source> {
+        self.entries.borrow().iter().find(|entry| entry.id == id).map(|entry| entry.ptr)
+    }
+
+    /// Forget the source with this id, returning it if it was still in the event loop
+    fn forget(&self, id: usize) -> Option<*mut wl_event_source> {
+        let mut entries = self.entries.borrow_mut();
+        let index = entries.iter().position(|entry| entry.id == id)?;
+        Some(entries.swap_remove(index).ptr)
+    }
+
+    /// Remove all the sources and free their data, before the display is destroyed
+    pub(crate) unsafe fn destroy(&self) {
+        let entries = ::std::mem::take(&mut *self.entries.borrow_mut());
+        for entry in entries {
+            remove_source(entry.ptr);
+            (entry.free)(entry.data);
+        }
+    }
+}
+
+/// Call `f` with the dispatch data of the current dispatch
+///
+/// The sources are only dispatched by `DisplayInner::dispatch()`, unless the event loop is
+/// dispatched by C code, in which case there is no dispatch data.
+fn with_current_data<F: FnOnce(DispatchData<'_>)>(f: F) {
+    if super::DISPATCH_DATA.is_set() {
+        super::DISPATCH_DATA.with(|data| f(data.borrow_mut().reborrow()))
+    } else {
+        f(DispatchData::wrap(&mut ()))
+    }
+}
+
+fn abort_on_panic<F: FnOnce()>(kind: &str, f: F) {
+    // safety of this function is the same as dispatch_func
+    if ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(f)).is_err() {
+        eprintln!("[wayland-server error] A {} callback panicked, aborting.", kind);
+        unsafe { ::libc::abort() };
+    }
+}
+
+unsafe fn remove_source(source: *mut wl_event_source) {
+    let _c_safety_guard = super::C_SAFETY.lock();
+    ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_source_remove, source);
+}
+
+pub(crate) struct FdSourceInner {
+    sources: Rc<EventLoopSources>,
+    id: usize,
+    data: *mut FdCallback,
+}
+
+impl FdSourceInner {
+    pub(crate) unsafe fn new<F: FnMut(Readiness, DispatchData<'_>) + 'static>(
+        event_loop: *mut wl_event_loop,
+        sources: &Rc<EventLoopSources>,
+        fd: RawFd,
+        interest: Interest,
+        callback: F,
+    ) -> IoResult<FdSourceInner> {
+        let data: *mut FdCallback = SourceData::new(Box::new(callback));
+        let _c_safety_guard = super::C_SAFETY.lock();
+        let ptr = ffi_dispatch!(
+            WAYLAND_SERVER_HANDLE,
+            wl_event_loop_add_fd,
+            event_loop,
+            fd,
+            interest.bits(),
+            fd_source_dispatch,
+            data as *mut c_void
+        );
+        if ptr.is_null() {
+            let err = IoError::last_os_error();
+            drop(Box::from_raw(data));
+            return Err(err);
+        }
+        let id = sources.new_id();
+        sources.insert(SourceEntry {
+            id,
+            ptr,
+            data: data as *mut c_void,
+            free: free_data::<FdCallback>,
+        });
+        Ok(FdSourceInner { sources: sources.clone(), id, data })
+    }
+
+    pub(crate) fn update(&self, interest: Interest) -> IoResult<()> {
+        let ptr = match self.sources.get(self.id) {
+            Some(ptr) => ptr,
+            None => return Ok(()),
+        };
+        let _c_safety_guard = super::C_SAFETY.lock();
+        let ret = unsafe {
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_source_fd_update, ptr, interest.bits())
+        };
+        if ret < 0 {
+            Err(IoError::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn remove(self) {
+        if let Some(ptr) = self.sources.forget(self.id) {
+            unsafe { SourceData::remove(self.data, ptr) }
+        }
+    }
+}
+
+unsafe extern "C" fn fd_source_dispatch(_fd: c_int, mask: u32, data: *mut c_void) -> c_int {
+    abort_on_panic("fd source", || {
+        SourceData::run(data as *mut FdCallback, |callback| {
+            with_current_data(|data| callback(Readiness::from_bits_truncate(mask), data))
+        })
+    });
+    0
+}
+
+pub(crate) struct TimerSourceInner {
+    sources: Rc<EventLoopSources>,
+    id: usize,
+    data: *mut TimerCallback,
+}
+
+impl TimerSourceInner {
+    pub(crate) unsafe fn new<F: FnMut(DispatchData<'_>) + 'static>(
+        event_loop: *mut wl_event_loop,
+        sources: &Rc<EventLoopSources>,
+        callback: F,
+    ) -> IoResult<TimerSourceInner> {
+        let data: *mut TimerCallback = SourceData::new(Box::new(callback));
+        let _c_safety_guard = super::C_SAFETY.lock();
+        let ptr = ffi_dispatch!(
+            WAYLAND_SERVER_HANDLE,
+            wl_event_loop_add_timer,
+            event_loop,
+            timer_source_dispatch,
+            data as *mut c_void
+        );
+        if ptr.is_null() {
+            let err = IoError::last_os_error();
+            drop(Box::from_raw(data));
+            return Err(err);
+        }
+        let id = sources.new_id();
+        sources.insert(SourceEntry {
+            id,
+            ptr,
+            data: data as *mut c_void,
+            free: free_data::<TimerCallback>,
+        });
+        Ok(TimerSourceInner { sources: sources.clone(), id, data })
+    }
+
+    pub(crate) fn set_timeout(&self, delay: Option<Duration>) -> IoResult<()> {
+        let ptr = match self.sources.get(self.id) {
+            Some(ptr) => ptr,
+            None => return Ok(()),
+        };
+        // libwayland uses a delay of 0 to disarm the timer, round up to a millisecond
+        let ms = match delay {
+            Some(delay) => {
+                let ms = (delay.as_nanos() + 999_999) / 1_000_000;
+                if ms > std::i32::MAX as u128 {
+                    std::i32::MAX
+                } else {
+                    std::cmp::max(ms as i32, 1)
+                }
+            }
+            None => 0,
+        };
+        let _c_safety_guard = super::C_SAFETY.lock();
+        let ret =
+            unsafe { ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_source_timer_update, ptr, ms) };
+        if ret < 0 {
+            Err(IoError::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn remove(self) {
+        if let Some(ptr) = self.sources.forget(self.id) {
+            unsafe { SourceData::remove(self.data, ptr) }
+        }
+    }
+}
+
+unsafe extern "C" fn timer_source_dispatch(data: *mut c_void) -> c_int {
+    abort_on_panic("timer", || {
+        SourceData::run(data as *mut TimerCallback, |callback| with_current_data(callback))
+    });
+    0
+}
+
+pub(crate) struct SignalSourceInner {
+    sources: Rc<EventLoopSources>,
+    id: usize,
+    data: *mut TimerCallback,
+}
+
+impl SignalSourceInner {
+    pub(crate) unsafe fn new<F: FnMut(DispatchData<'_>) + 'static>(
+        event_loop: *mut wl_event_loop,
+        sources: &Rc<EventLoopSources>,
+        signal: c_int,
+        callback: F,
+    ) -> IoResult<SignalSourceInner> {
+        let data: *mut TimerCallback = SourceData::new(Box::new(callback));
+        let _c_safety_guard = super::C_SAFETY.lock();
+        let ptr = ffi_dispatch!(
+            WAYLAND_SERVER_HANDLE,
+            wl_event_loop_add_signal,
+            event_loop,
+            signal,
+            signal_source_dispatch,
+            data as *mut c_void
+        );
+        if ptr.is_null() {
+            let err = IoError::last_os_error();
+            drop(Box::from_raw(data));
+            return Err(err);
+        }
+        let id = sources.new_id();
+        sources.insert(SourceEntry {
+            id,
+            ptr,
+            data: data as *mut c_void,
+            free: free_data::<TimerCallback>,
+        });
+        Ok(SignalSourceInner { sources: sources.clone(), id, data })
+    }
+
+    pub(crate) fn remove(self) {
+        if let Some(ptr) = self.sources.forget(self.id) {
+            unsafe { SourceData::remove(self.data, ptr) }
+        }
+    }
+}
+
+unsafe extern "C" fn signal_source_dispatch(_signal: c_int, data: *mut c_void) -> c_int {
+    abort_on_panic("signal", || {
+        SourceData::run(data as *mut TimerCallback, |callback| with_current_data(callback))
+    });
+    0
+}
+
+pub(crate) struct IdleSourceInner {
+    sources: Rc<EventLoopSources>,
+    id: usize,
+    data: *mut IdleData,
+}
+
+impl IdleSourceInner {
+    pub(crate) unsafe fn new<F: FnOnce(DispatchData<'_>) + 'static>(
+        event_loop: *mut wl_event_loop,
+        sources: &Rc<EventLoopSources>,
+        callback: F,
+    ) -> IoResult<IdleSourceInner> {
+        let id = sources.new_id();
+        let data = Box::into_raw(Box::new(IdleData {
+            callback: Some(Box::new(callback)),
+            sources: sources.clone(),
+            id,
+        }));
+        let _c_safety_guard = super::C_SAFETY.lock();
+        let ptr = ffi_dispatch!(
+            WAYLAND_SERVER_HANDLE,
+            wl_event_loop_add_idle,
+            event_loop,
+            idle_source_dispatch,
+            data as *mut c_void
+        );
+        if ptr.is_null() {
+            let err = IoError::last_os_error();
+            drop(Box::from_raw(data));
+            return Err(err);
+        }
+        sources.insert(SourceEntry {
+            id,
+            ptr,
+            data: data as *mut c_void,
+            free: free_data::<IdleData>,
+        });
+        Ok(IdleSourceInner { sources: sources.clone(), id, data })
+    }
+
+    pub(crate) fn remove(self) {
+        // once run, libwayland removes the source and the data is freed
+        if let Some(ptr) = self.sources.forget(self.id) {
+            unsafe {
+                remove_source(ptr);
+                drop(Box::from_raw(self.data));
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn idle_source_dispatch(data: *mut c_void) {
+    let data = Box::from_raw(data as *mut IdleData);
+    data.sources.forget(data.id);
+    if let Some(callback) = data.callback {
+        abort_on_panic("idle", || with_current_data(callback));
+    }
+}
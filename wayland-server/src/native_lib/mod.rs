@@ -1,4 +1,6 @@
+use std::any::Any;
 use std::cell::RefCell;
+use std::collections::HashMap;
 
 use parking_lot::ReentrantMutex;
 
@@ -6,11 +8,15 @@ use crate::{Interface, Main, Resource};
 
 mod client;
 mod display;
+mod event_loop;
 mod globals;
 mod resource;
 
 pub(crate) use self::client::ClientInner;
 pub(crate) use self::display::DisplayInner;
+pub(crate) use self::event_loop::{
+    EventLoopSources, FdSourceInner, IdleSourceInner, SignalSourceInner, TimerSourceInner,
+};
 pub(crate) use self::globals::GlobalInner;
 pub(crate) use self::resource::ResourceInner;
 
@@ -44,6 +50,15 @@ scoped_tls::scoped_thread_local! {
     pub(crate) static DISPATCH_DATA: RefCell<crate::DispatchData>
 }
 
+/// The default handlers of a display, by interface name
+///
+/// Each handler is a `resource::DefaultHandler<I>` for the interface it is associated to.
+pub(crate) type DefaultHandlers = RefCell<HashMap<&'static str, Box<dyn Any>>>;
+
+scoped_tls::scoped_thread_local! {
+    pub(crate) static DEFAULT_HANDLERS: DefaultHandlers
+}
+
 fn with_dispatch_data<T, F>(data: crate::DispatchData, f: F) -> T
 where
     F: FnOnce() -> T,
@@ -1,5 +1,6 @@
 use std::cell::RefCell;
-use std::os::raw::{c_int, c_void};
+use std::os::raw::{c_char, c_int, c_void};
+use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
@@ -7,6 +8,7 @@ use wayland_sys::common::*;
 use wayland_sys::server::*;
 
 use wayland_commons::user_data::UserData;
+use wayland_commons::wire::FromRawError;
 
 use crate::{DispatchData, Interface, Main, MessageGroup, Resource};
 
@@ -209,6 +211,35 @@ impl ResourceInner {
         ResourceInner { internal, ptr }
     }
 
+    pub(crate) unsafe fn from_c_ptr_anonymous(ptr: *mut wl_resource) -> Self {
+        let _c_safety_guard = super::C_SAFETY.lock();
+        // libwayland-server compares interfaces by name, so a stand-in with the name of the
+        // resource is enough to check whether it is managed by us
+        let interface = wl_interface {
+            name: ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_class, ptr),
+            version: 0,
+            request_count: 0,
+            requests: ::std::ptr::null(),
+            event_count: 0,
+            events: ::std::ptr::null(),
+        };
+        let is_managed = ffi_dispatch!(
+            WAYLAND_SERVER_HANDLE,
+            wl_resource_instance_of,
+            ptr,
+            &interface,
+            &::wayland_sys::RUST_MANAGED as *const u8 as *const _
+        ) != 0;
+        let internal = if is_managed {
+            let user_data = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_user_data, ptr)
+                as *mut ResourceUserData<crate::AnonymousObject>;
+            Some((*user_data).internal.clone())
+        } else {
+            None
+        };
+        ResourceInner { internal, ptr }
+    }
+
     pub unsafe fn make_child_for<J: Interface + From<Resource<J>> + AsRef<Resource<J>>>(
         &self,
         id: u32,
@@ -286,6 +317,8 @@ impl ResourceInner {
 }
 
 type BoxedHandler<I> = Box<dyn Fn(<I as Interface>::Request, Main<I>, DispatchData<'_>)>;
+pub(crate) type DefaultHandler<I> =
+    Rc<dyn Fn(<I as Interface>::Request, Main<I>, DispatchData<'_>)>;
 type BoxedDest<I> = Box<dyn Fn(Resource<I>, DispatchData<'_>)>;
 
 pub(crate) struct ResourceUserData<I: Interface + From<Resource<I>> + AsRef<Resource<I>>> {
@@ -306,6 +339,18 @@ impl<I: Interface + From<Resource<I>> + AsRef<Resource<I>>> ResourceUserData<I>
     }
 }
 
+fn default_handler<I>() -> Option<DefaultHandler<I>>
+where
+    I: Interface + From<Resource<I>> + AsRef<Resource<I>>,
+{
+    if !super::DEFAULT_HANDLERS.is_set() {
+        return None;
+    }
+    super::DEFAULT_HANDLERS.with(|handlers| {
+        handlers.borrow().get(I::NAME).and_then(|h| h.downcast_ref::<DefaultHandler<I>>()).cloned()
+    })
+}
+
 pub(crate) unsafe extern "C" fn resource_dispatcher<I: Interface>(
     _implem: *const c_void,
     resource: *mut c_void,
@@ -330,6 +375,13 @@ where
         let user_data = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_user_data, resource);
         {
             let user_data = &mut *(user_data as *mut ResourceUserData<I>);
+            if user_data.implem.borrow().is_none() {
+                // the object is not assigned, try the default handler of its interface
+                if let Some(handler) = default_handler::<I>() {
+                    *user_data.implem.borrow_mut() =
+                        Some(Box::new(move |req, obj, data| handler(req, obj, data)));
+                }
+            }
             let implem = user_data.implem.borrow();
 
             if must_destroy {
@@ -358,21 +410,37 @@ where
             // final cleanup
             ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_destroy, resource);
         }
-        Ok(())
+        Ok::<(), FromRawError>(())
     });
     // check the return status
     match ret {
         Ok(Ok(())) => 0,
-        Ok(Err(())) => {
-            eprintln!(
-                "[wayland-client error] Attempted to dispatch unknown opcode {} for {}, aborting.",
-                opcode,
-                I::NAME
+        Ok(Err(e)) => {
+            // libwayland already validated the message against the protocol, only enum
+            // values can be invalid here: this is a protocol error of the client
+            let name = I::Request::MESSAGES.get(opcode as usize).map(|m| m.name).unwrap_or("?");
+            let msg = format!(
+                "invalid arguments for {}@{}.{}: {}",
+                I::NAME,
+                ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_id, resource),
+                name,
+                e
             );
-            ::libc::abort();
+            let client = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_client, resource);
+            let display = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_client_get_object, client, 1);
+            let cstring = ::std::ffi::CString::from_vec_unchecked(msg.into());
+            ffi_dispatch!(
+                WAYLAND_SERVER_HANDLE,
+                wl_resource_post_error,
+                display,
+                1, // wl_display.error.invalid_method
+                b"%s\0".as_ptr() as *const c_char,
+                cstring.as_ptr()
+            );
+            0
         }
         Err(_) => {
-            eprintln!("[wayland-client error] A handler for {} panicked.", I::NAME);
+            eprintln!("[wayland-server error] A handler for {} panicked.", I::NAME);
             ::libc::abort()
         }
     }
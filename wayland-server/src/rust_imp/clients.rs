@@ -1,29 +1,39 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::ffi::CString;
+use std::io;
 use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 use std::thread::{self, ThreadId};
 
+use nix::sys::epoll::EpollFlags;
 use nix::Result as NixResult;
 
-use wayland_commons::debug;
+use wayland_commons::debug::{LoggedMessage, MessageDirection};
 use wayland_commons::map::{Object, ObjectMap, ObjectMetadata, SERVER_ID_LIMIT};
-use wayland_commons::socket::{BufferedSocket, Socket};
-use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc, MessageParseError};
+use wayland_commons::socket::{BufferCapacity, BufferedSocket, Socket};
+use wayland_commons::wire::{
+    Argument, ArgumentError, ArgumentRef, ArgumentType, FromRawError, Message, MessageDesc,
+    MessageParseError, MessageRef,
+};
 use wayland_commons::{smallvec, ThreadGuard};
 
-use crate::{DispatchData, Interface, UserDataMap};
+use crate::{
+    ClientLimits, Credentials, DispatchData, Interface, LimitPolicy, LimitsError, MessageRecorder,
+    ObjectInfo, UserDataMap,
+};
 
-use super::event_loop_glue::{FdManager, Token};
+use super::event_loop_glue::{to_io_error, FdManager, Token};
 use super::globals::GlobalManager;
 use super::resources::{ObjectMeta, ResourceDestructor, ResourceInner};
-use super::{Dispatched, WAYLAND_DEBUG};
+use super::{DefaultDispatchers, Dispatched, LoggerSlot, SerialCounter};
 
 #[derive(Clone, Debug)]
 pub(crate) enum Error {
     Protocol,
+    // the request would create an object past the objects limit of the client
+    TooManyObjects(LimitPolicy),
     Parse(MessageParseError),
     Nix(::nix::Error),
 }
@@ -38,6 +48,9 @@ pub(crate) struct ClientConnection {
     last_error: Option<Error>,
     pending_destructors: Vec<ResourceInner>,
     zombie_clients: Arc<Mutex<Vec<ClientConnection>>>,
+    pub(crate) logger: LoggerSlot,
+    credentials: Option<Credentials>,
+    limits: ClientLimits,
 }
 
 impl ClientConnection {
@@ -45,8 +58,12 @@ impl ClientConnection {
         fd: RawFd,
         display_object: Object<ObjectMeta>,
         zombies: Arc<Mutex<Vec<ClientConnection>>>,
+        logger: LoggerSlot,
+        capacity: BufferCapacity,
+        limits: ClientLimits,
     ) -> ClientConnection {
-        let socket = BufferedSocket::new(Socket::from_raw_fd(fd));
+        let credentials = peer_credentials(fd);
+        let socket = BufferedSocket::with_capacity(Socket::from_raw_fd(fd), capacity);
 
         let mut map = ObjectMap::new();
         // Insert first pre-existing object
@@ -60,6 +77,9 @@ impl ClientConnection {
             last_error: None,
             pending_destructors: Vec::new(),
             zombie_clients: zombies,
+            logger,
+            credentials,
+            limits,
         }
     }
 
@@ -76,6 +96,19 @@ impl ClientConnection {
     }
 
     pub(crate) fn write_message(&mut self, msg: &Message) -> NixResult<()> {
+        if let Some(ref logger) = *self.logger.lock().unwrap() {
+            if let Some(object) = self.map.lock().unwrap().find(msg.sender_id) {
+                let args = msg.args.iter().map(Argument::as_ref).collect::<Vec<_>>();
+                logger.log(&LoggedMessage::now(
+                    MessageDirection::Sent,
+                    object.interface,
+                    msg.sender_id,
+                    true,
+                    object.events[msg.opcode as usize].name,
+                    &args,
+                ));
+            }
+        }
         self.socket.write_message(msg)
     }
 
@@ -97,7 +130,7 @@ impl ClientConnection {
         }
     }
 
-    pub(crate) fn read_request(&mut self) -> Result<Option<Message>, Error> {
+    pub(crate) fn read_request(&mut self, request: &mut RequestBuffer) -> Result<bool, Error> {
         if let Some(ref err) = self.last_error {
             return Err(err.clone());
         }
@@ -105,20 +138,16 @@ impl ClientConnection {
         // are reading requests
         let mut map = self.map.lock().unwrap();
         // read messages
-        let ret = self.socket.read_one_message(|id, opcode| {
+        let ret = self.socket.read_one_message_ref(|id, opcode| {
             map.find(id).and_then(|o| o.requests.get(opcode as usize)).map(|desc| desc.signature)
         });
         let msg = match ret {
             Ok(msg) => msg,
-            Err(MessageParseError::Malformed) => {
-                self.last_error = Some(Error::Parse(MessageParseError::Malformed));
-                return Err(Error::Parse(MessageParseError::Malformed));
-            }
             Err(MessageParseError::MissingData) | Err(MessageParseError::MissingFD) => {
                 // missing data, read sockets and try again
                 self.socket.fill_incoming_buffers().map_err(Error::Nix)?;
 
-                let msg = self.socket.read_one_message(|id, opcode| {
+                let msg = self.socket.read_one_message_ref(|id, opcode| {
                     map.find(id)
                         .and_then(|o| o.requests.get(opcode as usize))
                         .map(|desc| desc.signature)
@@ -126,16 +155,20 @@ impl ClientConnection {
 
                 match msg {
                     Ok(msg) => msg,
-                    Err(MessageParseError::Malformed) => {
-                        self.last_error = Some(Error::Parse(MessageParseError::Malformed));
-                        return Err(Error::Parse(MessageParseError::Malformed));
-                    }
                     Err(MessageParseError::MissingData) | Err(MessageParseError::MissingFD) => {
                         // still nothing, there is nothing to read
-                        return Ok(None);
+                        return Ok(false);
+                    }
+                    Err(e) => {
+                        self.last_error = Some(Error::Parse(e.clone()));
+                        return Err(Error::Parse(e));
                     }
                 }
             }
+            Err(e) => {
+                self.last_error = Some(Error::Parse(e.clone()));
+                return Err(Error::Parse(e));
+            }
         };
 
         // we reach here, there is now a message to process in msg
@@ -145,24 +178,32 @@ impl ClientConnection {
             Some(obj) => obj,
             None => {
                 // this is a message sent to a destroyed object
-                // to avoid dying because of races, we just consume it into void
-                // closing any associated FDs
-                for a in msg.args {
-                    if let Argument::Fd(fd) = a {
-                        let _ = ::nix::unistd::close(fd);
-                    }
-                }
-
-                return Ok(None);
+                // to avoid dying because of races, we just consume it into void,
+                // its FDs are closed by the socket on the next read
+                request.clear();
+                return Ok(false);
             }
         };
 
+        // copy it to the request buffer, so that it can be dispatched once the
+        // connection is unlocked
+        request.fill(msg, object.requests[msg.opcode as usize].signature);
+
+        // the objects limit is checked before the request creates a new object, so that a
+        // client at the limit can still destroy its objects
+        if let Some(limit) = self.limits.objects {
+            if map.client_len() >= limit.max
+                && msg.args().any(|a| a.get_type() == ArgumentType::NewId)
+            {
+                return Err(Error::TooManyObjects(limit.policy));
+            }
+        }
+
         // create a new object if applicable
         if let Some(child) = object.request_child(msg.opcode) {
             let new_id = msg
-                .args
-                .iter()
-                .flat_map(|a| if let Argument::NewId(nid) = *a { Some(nid) } else { None })
+                .args()
+                .flat_map(|a| if let ArgumentRef::NewId(nid) = a { Some(nid) } else { None })
                 .next()
                 .unwrap();
 
@@ -183,11 +224,11 @@ impl ClientConnection {
             // NewId argument, unless we are the registry
             debug_assert!(
                 object.interface == "wl_registry"
-                    || !msg.args.iter().any(|a| a.get_type() == ArgumentType::NewId)
+                    || !msg.args().any(|a| a.get_type() == ArgumentType::NewId)
             );
         }
 
-        Ok(Some(msg))
+        Ok(true)
     }
 
     fn cleanup(mut self, mut data: crate::DispatchData) {
@@ -210,6 +251,22 @@ impl ClientConnection {
     }
 }
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_credentials(fd: RawFd) -> Option<Credentials> {
+    use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+    getsockopt(fd, PeerCredentials).ok().map(|cred| Credentials {
+        pid: cred.pid(),
+        uid: cred.uid(),
+        gid: cred.gid(),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_credentials(_fd: RawFd) -> Option<Credentials> {
+    // SO_PEERCRED is specific to linux
+    None
+}
+
 #[derive(Clone)]
 pub(crate) struct ClientInner {
     pub(crate) data: Arc<Mutex<Option<ClientConnection>>>,
@@ -232,6 +289,69 @@ impl ClientInner {
         }
     }
 
+    pub(crate) fn credentials(&self) -> Option<Credentials> {
+        self.data.lock().unwrap().as_ref().and_then(|cx| cx.credentials)
+    }
+
+    pub(crate) fn objects(&self) -> Vec<ObjectInfo> {
+        let mut objects = Vec::new();
+        if let Some(ref cx) = *self.data.lock().unwrap() {
+            cx.map.lock().unwrap().with_all(|id, object| {
+                if object.interface.is_empty() {
+                    // placeholder, not yet a real object
+                    return;
+                }
+                objects.push(ObjectInfo {
+                    id,
+                    interface: object.interface.to_owned(),
+                    version: object.version,
+                    alive: object.meta.alive.load(Ordering::Acquire),
+                });
+            });
+        }
+        objects
+    }
+
+    pub(crate) fn get_object(&self, id: u32) -> Option<ResourceInner> {
+        let map = match *self.data.lock().unwrap() {
+            Some(ref cx) => cx.map.clone(),
+            None => return None,
+        };
+        ResourceInner::from_id(id, map, self.clone()).filter(|resource| {
+            // placeholders are not yet real objects
+            !resource.object.interface.is_empty()
+                && resource.object.meta.alive.load(Ordering::Acquire)
+        })
+    }
+
+    pub(crate) fn get_resource<I: Interface>(&self, id: u32) -> Option<ResourceInner> {
+        self.get_object(id).filter(ResourceInner::is_interface::<I>)
+    }
+
+    pub(crate) fn set_buffer_capacity(&self, capacity: BufferCapacity) {
+        if let Some(ref mut cx) = *self.data.lock().unwrap() {
+            cx.socket.set_capacity(capacity);
+        }
+    }
+
+    pub(crate) fn set_recorder(
+        &self,
+        recorder: Option<Box<dyn MessageRecorder>>,
+    ) -> Option<Box<dyn MessageRecorder>> {
+        match *self.data.lock().unwrap() {
+            Some(ref mut cx) => cx.socket.set_recorder(recorder),
+            None => None,
+        }
+    }
+
+    pub(crate) fn set_limits(&self, limits: ClientLimits) -> Result<(), LimitsError> {
+        limits.check()?;
+        if let Some(ref mut cx) = *self.data.lock().unwrap() {
+            cx.limits = limits;
+        }
+        Ok(())
+    }
+
     pub(crate) fn kill(&self) {
         if let Some(mut clientconn) = self.data.lock().unwrap().take() {
             let _ = clientconn.socket.flush();
@@ -332,19 +452,52 @@ pub(crate) struct ClientManager {
     clients: Vec<(RefCell<Option<Token>>, ClientInner)>,
     zombie_clients: Arc<Mutex<Vec<ClientConnection>>>,
     global_mgr: Rc<RefCell<GlobalManager>>,
+    logger: LoggerSlot,
+    default_dispatchers: DefaultDispatchers,
+    serial: SerialCounter,
+    throttle: Rc<Throttle>,
+    pub(crate) buffer_capacity: BufferCapacity,
+    pub(crate) limits: ClientLimits,
 }
 
 impl ClientManager {
     pub(crate) fn new(
         epoll_mgr: Rc<FdManager>,
         global_mgr: Rc<RefCell<GlobalManager>>,
+        logger: LoggerSlot,
+        default_dispatchers: DefaultDispatchers,
+        serial: SerialCounter,
     ) -> ClientManager {
+        let throttle = Rc::new(Throttle::new(&epoll_mgr));
         ClientManager {
             epoll_mgr,
             clients: Vec::new(),
             zombie_clients: Arc::new(Mutex::new(Vec::new())),
             global_mgr,
+            logger,
+            default_dispatchers,
+            serial,
+            throttle,
+            buffer_capacity: BufferCapacity::default(),
+            limits: ClientLimits::default(),
+        }
+    }
+
+    pub(crate) fn clients(&self) -> Vec<ClientInner> {
+        self.clients.iter().map(|(_, client)| client.clone()).filter(ClientInner::alive).collect()
+    }
+
+    pub(crate) fn set_default_limits(&mut self, limits: ClientLimits) -> Result<(), LimitsError> {
+        limits.check()?;
+        if limits.uses_defer() {
+            Throttle::enable(&self.throttle).map_err(LimitsError::Io)?;
         }
+        self.limits = limits;
+        Ok(())
+    }
+
+    pub(crate) fn start_dispatch(&self) {
+        self.throttle.dispatch.set(self.throttle.dispatch.get().wrapping_add(1));
     }
 
     pub(crate) unsafe fn init_client(
@@ -359,12 +512,20 @@ impl ClientManager {
             events: DISPLAY_EVENTS,
             meta: ObjectMeta::with_dispatcher(DisplayDispatcher {
                 global_mgr: self.global_mgr.clone(),
+                serial: self.serial.clone(),
             }),
             childs_from_events: no_child,
             childs_from_requests: display_req_child,
         };
 
-        let cx = ClientConnection::new(fd, display_object, self.zombie_clients.clone());
+        let cx = ClientConnection::new(
+            fd,
+            display_object,
+            self.zombie_clients.clone(),
+            self.logger.clone(),
+            self.buffer_capacity,
+            self.limits,
+        );
         let map = cx.map.clone();
         let user_data_map = cx.user_data_map.clone();
 
@@ -374,10 +535,21 @@ impl ClientManager {
             loop_thread: thread::current().id(), // init_client is only called by the display, which does not change threads
         };
 
-        let implementation = ClientImplementation { inner: client.clone(), map };
+        let implementation = Rc::new(ClientImplementation {
+            inner: client.clone(),
+            map,
+            logger: self.logger.clone(),
+            default_dispatchers: self.default_dispatchers.clone(),
+            throttle: self.throttle.clone(),
+            processed: Cell::new((0, 0)),
+            request: RefCell::new(RequestBuffer::default()),
+            stashed: Cell::new(false),
+            deferred: Cell::new(false),
+            source: Cell::new(None),
+        });
 
         // process any pending messages before inserting it into the event loop
-        implementation.process_messages(data);
+        ClientImplementation::process_messages(&implementation, data);
 
         if !client.alive() {
             // client already made a protocol error and we killed it, there is no point
@@ -385,15 +557,26 @@ impl ClientManager {
             return client;
         }
 
-        let source =
-            match self.epoll_mgr.register(fd, move |data| implementation.process_messages(data)) {
-                Ok(source) => Some(source),
-                Err(e) => {
-                    eprintln!("[wayland-server] Failed to insert client into event loop: {:?}", e);
-                    client.kill();
-                    None
+        let interest = if implementation.deferred.get() { PAUSED } else { EpollFlags::EPOLLIN };
+        let registered = {
+            let implementation = implementation.clone();
+            self.epoll_mgr.register_with_interest(fd, interest, move |_, data| {
+                // a paused client can still report a hangup, it is processed once resumed
+                if !implementation.deferred.get() {
+                    ClientImplementation::process_messages(&implementation, data)
                 }
-            };
+            })
+        };
+        implementation.source.set(registered.as_ref().ok().copied());
+
+        let source = match registered {
+            Ok(source) => Some(source),
+            Err(e) => {
+                eprintln!("[wayland-server] Failed to insert client into event loop: {:?}", e);
+                client.kill();
+                None
+            }
+        };
 
         if source.is_some() {
             self.clients.push((RefCell::new(source), client.clone()));
@@ -405,10 +588,23 @@ impl ClientManager {
     pub(crate) fn flush_all(&mut self, mut disp_data: crate::DispatchData) {
         // flush all clients and cleanup dead ones
         let epoll_mgr = self.epoll_mgr.clone();
+        let mut exceeding = Vec::new();
         self.clients.retain(|&(ref s, ref c)| {
             if let Some(ref mut data) = *c.data.lock().unwrap() {
                 data.call_destructors(disp_data.reborrow());
-                data.flush().is_ok()
+                let ret = match data.flush() {
+                    // the client is not reading fast enough, the rest of the
+                    // data will be sent on the next flush
+                    Ok(()) | Err(::nix::Error::Sys(::nix::errno::Errno::EAGAIN)) => true,
+                    Err(_) => false,
+                };
+                if let Some(limit) = data.limits.queued_bytes {
+                    if limit.policy != LimitPolicy::Defer && data.socket.pending_bytes() > limit.max
+                    {
+                        exceeding.push((c.clone(), limit.policy));
+                    }
+                }
+                ret
             } else {
                 // This is a dead client, clean it up
                 if let Some(token) = s.borrow_mut().take() {
@@ -418,6 +614,13 @@ impl ClientManager {
             }
         });
 
+        for (client, policy) in exceeding {
+            exceed_limit(&client, policy, "too many queued events");
+        }
+
+        // the clients waiting for their events to be read may now be resumed
+        self.throttle.wake_if_resumable();
+
         let mut guard = self.zombie_clients.lock().unwrap();
         for zombie in guard.drain(..) {
             zombie.cleanup(disp_data.reborrow());
@@ -429,6 +632,7 @@ impl ClientManager {
         for &(_, ref client) in &self.clients {
             client.kill();
         }
+        self.throttle.deferred.borrow_mut().clear();
         self.flush_all(crate::DispatchData::wrap(&mut ()));
     }
 }
@@ -505,45 +709,266 @@ fn no_child(_: u16, _: u32, _: &ObjectMeta) -> Option<Object<ObjectMeta>> {
     None
 }
 
+// The interest of the socket of a deferred client: it is no longer read from, so that it does
+// not wake up the event loop, and a hangup is only reported once
+const PAUSED: EpollFlags = EpollFlags::EPOLLONESHOT;
+
+/// The clients whose requests are deferred because they reached one of their limits
+struct Throttle {
+    // an eventfd in the event loop, signaled to resume the deferred clients
+    //
+    // it is only created once a limit uses the `Defer` policy
+    eventfd: Cell<Option<RawFd>>,
+    // incremented at the start of each dispatch
+    dispatch: Cell<u64>,
+    deferred: RefCell<Vec<Rc<ClientImplementation>>>,
+    // weak, as the event loop owns this throttle through the callback of the eventfd
+    epoll_mgr: Weak<FdManager>,
+}
+
+impl Throttle {
+    fn new(epoll_mgr: &Rc<FdManager>) -> Throttle {
+        Throttle {
+            eventfd: Cell::new(None),
+            dispatch: Cell::new(0),
+            deferred: RefCell::new(Vec::new()),
+            epoll_mgr: Rc::downgrade(epoll_mgr),
+        }
+    }
+
+    /// Insert the eventfd resuming the deferred clients in the event loop, if not already done
+    fn enable(this: &Rc<Throttle>) -> io::Result<()> {
+        use nix::sys::eventfd::{eventfd, EfdFlags};
+        let epoll_mgr = match (this.eventfd.get(), this.epoll_mgr.upgrade()) {
+            (None, Some(epoll_mgr)) => epoll_mgr,
+            // already enabled, or the event loop is being dropped
+            _ => return Ok(()),
+        };
+        let fd = eventfd(0, EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).map_err(to_io_error)?;
+        let throttle = this.clone();
+        if let Err(e) = epoll_mgr.register(fd, move |data| throttle.resume(data)) {
+            let _ = nix::unistd::close(fd);
+            return Err(to_io_error(e));
+        }
+        this.eventfd.set(Some(fd));
+        Ok(())
+    }
+
+    /// Defer the requests of a client, resuming it during the next dispatch if `wake` is true
+    ///
+    /// The socket of the client is not read from until it is resumed. If the event loop cannot
+    /// be woken up to resume it, the client is killed with a `no_memory` error instead.
+    fn defer(this: &Rc<Throttle>, client: &Rc<ClientImplementation>, wake: bool, msg: &str) {
+        // the limits of the client may have been set with `Client::set_limits()`, which cannot
+        // enable the throttle
+        if let Err(e) = Throttle::enable(this) {
+            eprintln!("[wayland-server] Failed to defer the requests of a client: {}", e);
+            exceed_limit(&client.inner, LimitPolicy::NoMemory, msg);
+            return;
+        }
+        if !client.deferred.replace(true) {
+            this.deferred.borrow_mut().push(client.clone());
+            this.set_interest(client, PAUSED);
+        }
+        if wake {
+            this.wake();
+        }
+    }
+
+    fn set_interest(&self, client: &ClientImplementation, interest: EpollFlags) {
+        // the token of a dead client is deregistered, and may be reused by an other one
+        if !client.inner.alive() {
+            return;
+        }
+        if let (Some(epoll_mgr), Some(token)) = (self.epoll_mgr.upgrade(), client.source.get()) {
+            let _ = epoll_mgr.update(token, interest);
+        }
+    }
+
+    fn wake(&self) {
+        if let Some(fd) = self.eventfd.get() {
+            let _ = nix::unistd::write(fd, &1u64.to_ne_bytes());
+        }
+    }
+
+    /// Wake the event loop if a deferred client is no longer waiting for its events to be read
+    fn wake_if_resumable(&self) {
+        if self.deferred.borrow().iter().any(|client| client.can_resume()) {
+            self.wake();
+        }
+    }
+
+    fn resume(&self, mut data: crate::DispatchData) {
+        if let Some(fd) = self.eventfd.get() {
+            let mut buf = [0u8; 8];
+            let _ = nix::unistd::read(fd, &mut buf);
+        }
+        let clients = ::std::mem::take(&mut *self.deferred.borrow_mut());
+        for client in clients {
+            // the client is deferred again if it is still over its limits
+            client.deferred.set(false);
+            self.set_interest(&client, EpollFlags::EPOLLIN);
+            ClientImplementation::process_messages(&client, data.reborrow());
+        }
+    }
+}
+
+impl Drop for Throttle {
+    fn drop(&mut self) {
+        if let Some(fd) = self.eventfd.get() {
+            let _ = nix::unistd::close(fd);
+        }
+    }
+}
+
+fn exceed_limit(client: &ClientInner, policy: LimitPolicy, msg: &str) {
+    match policy {
+        // the deferred limits are handled by the callers, and the objects limit cannot be deferred
+        LimitPolicy::NoMemory | LimitPolicy::Defer => {
+            client.post_error(1, super::display::DISPLAY_ERROR_NO_MEMORY, msg.into())
+        }
+        LimitPolicy::Disconnect => client.kill(),
+    }
+}
+
 struct ClientImplementation {
     inner: ClientInner,
     map: Arc<Mutex<ObjectMap<ObjectMeta>>>,
+    logger: LoggerSlot,
+    default_dispatchers: DefaultDispatchers,
+    throttle: Rc<Throttle>,
+    // the last dispatch during which requests were processed, and their number
+    processed: Cell<(u64, usize)>,
+    // the request being dispatched, or deferred to the next dispatch if `stashed` is set
+    request: RefCell<RequestBuffer>,
+    stashed: Cell<bool>,
+    deferred: Cell<bool>,
+    // the token of the socket in the event loop, once inserted
+    source: Cell<Option<Token>>,
+}
+
+// A request read from the socket of a client, so that it can be dispatched without holding
+// the lock on the connection. Its contents are copied without allocating once the buffer has
+// grown, while its fds remain owned by the socket, which only closes them when reading the
+// next request: they are only duplicated when a handler takes ownership of them.
+#[derive(Default)]
+pub(crate) struct RequestBuffer {
+    signature: &'static [ArgumentType],
+    raw: Vec<u32>,
+    fds: Vec<RawFd>,
+}
+
+impl RequestBuffer {
+    fn fill(&mut self, msg: MessageRef, signature: &'static [ArgumentType]) {
+        self.clear();
+        self.signature = signature;
+        self.raw.extend_from_slice(msg.raw());
+        self.fds.extend_from_slice(msg.fds());
+    }
+
+    fn message(&self) -> MessageRef<'_> {
+        // the contents were copied from a valid message, so parsing them cannot fail
+        MessageRef::from_raw(&self.raw, self.signature, &self.fds)
+            .expect("Invalid request in the buffer.")
+            .0
+    }
+
+    fn clear(&mut self) {
+        self.signature = &[];
+        self.raw.clear();
+        self.fds.clear();
+    }
 }
 
 impl ClientImplementation {
-    fn process_messages(&self, mut data: crate::DispatchData) {
+    fn can_resume(&self) -> bool {
+        match *self.inner.data.lock().unwrap() {
+            Some(ref cx) => match cx.limits.queued_bytes {
+                Some(limit) => cx.socket.pending_bytes() <= limit.max,
+                None => true,
+            },
+            None => true,
+        }
+    }
+
+    fn process_messages(this: &Rc<ClientImplementation>, mut data: crate::DispatchData) {
         loop {
-            // we must process the messages one by one, because message parsing depends
-            // on the contents of the object map, which each message can change...
-            let ret = if let Some(ref mut data) = *self.inner.data.lock().unwrap() {
-                data.read_request()
-            } else {
+            let limits = match *this.inner.data.lock().unwrap() {
+                Some(ref cx) => cx.limits,
                 // client is now dead, abort
-                return;
+                None => return,
             };
 
-            let msg = match ret {
-                Ok(None) | Err(Error::Nix(::nix::Error::Sys(::nix::errno::Errno::EAGAIN))) => {
-                    // Nothing more to read.
+            if let Some(limit) = limits.queued_bytes {
+                if !this.can_resume() {
+                    if limit.policy == LimitPolicy::Defer {
+                        // wait for the client to read its events, flush_all() resumes it
+                        Throttle::defer(&this.throttle, this, false, "too many queued events");
+                    } else {
+                        exceed_limit(&this.inner, limit.policy, "too many queued events");
+                    }
+                    return;
+                }
+            }
+
+            // we must process the messages one by one, because message parsing depends
+            // on the contents of the object map, which each message can change...
+            let mut request = this.request.borrow_mut();
+            if !this.stashed.replace(false) {
+                let ret = if let Some(ref mut data) = *this.inner.data.lock().unwrap() {
+                    data.read_request(&mut request)
+                } else {
+                    // client is now dead, abort
                     return;
+                };
+
+                match ret {
+                    Ok(false) | Err(Error::Nix(::nix::Error::Sys(::nix::errno::Errno::EAGAIN))) => {
+                        // Nothing more to read.
+                        return;
+                    }
+                    Ok(true) => {}
+                    Err(Error::TooManyObjects(policy)) => {
+                        exceed_limit(&this.inner, policy, "too many objects");
+                        return;
+                    }
+                    Err(_) => {
+                        // On error, kill the client.
+                        this.inner.kill();
+                        return;
+                    }
                 }
-                Ok(Some(msg)) => msg,
-                Err(_) => {
-                    // On error, kill the client.
-                    self.inner.kill();
+            }
+
+            if let Some(limit) = limits.requests_per_dispatch {
+                let dispatch = this.throttle.dispatch.get();
+                let count = match this.processed.get() {
+                    (last, count) if last == dispatch => count,
+                    _ => 0,
+                };
+                if count >= limit.max {
+                    if limit.policy == LimitPolicy::Defer {
+                        // process it during the next dispatch
+                        this.stashed.set(true);
+                        Throttle::defer(&this.throttle, this, true, "too many requests");
+                    } else {
+                        exceed_limit(&this.inner, limit.policy, "too many requests");
+                    }
                     return;
                 }
-            };
+                this.processed.set((dispatch, count + 1));
+            }
 
             // There is a message to dispatch.
-            let mut resourcemap = super::ResourceMap::make(self.map.clone(), self.inner.clone());
+            let msg = request.message();
+            let mut resourcemap = super::ResourceMap::make(this.map.clone(), this.inner.clone());
             let id = msg.sender_id;
             let opcode = msg.opcode;
 
-            let res = match ResourceInner::from_id(id, self.map.clone(), self.inner.clone()) {
+            let res = match ResourceInner::from_id(id, this.map.clone(), this.inner.clone()) {
                 Some(res) => res,
                 None => {
-                    self.inner.post_error(
+                    this.inner.post_error(
                         1,
                         super::display::DISPLAY_ERROR_INVALID_OBJECT,
                         format!("invalid object {}", id),
@@ -553,24 +978,70 @@ impl ClientImplementation {
             };
 
             let object = res.object.clone();
+
+            if let Some(ref logger) = *this.logger.lock().unwrap() {
+                let args = msg.args().collect::<Vec<_>>();
+                logger.log(&LoggedMessage::now(
+                    MessageDirection::Received,
+                    object.interface,
+                    id,
+                    true,
+                    object.requests[opcode as usize].name,
+                    &args,
+                ));
+            }
+
             let mut dispatcher = object.meta.dispatcher.get().borrow_mut();
 
-            match dispatcher.dispatch(msg, res, &mut resourcemap, data.reborrow()) {
+            let mut ret = dispatcher.dispatch(msg, res, &mut resourcemap, data.reborrow());
+            if let Dispatched::NoDispatch(res) = ret {
+                // the object is not assigned, try the default dispatcher of its interface
+                let default = this.default_dispatchers.borrow().get(object.interface).cloned();
+                ret = match default {
+                    Some(default) => {
+                        let _ = this.map.lock().unwrap().with(id, |obj| {
+                            obj.meta.dispatcher = default.clone();
+                        });
+                        let mut default = default.get().borrow_mut();
+                        default.dispatch(msg, res, &mut resourcemap, data.reborrow())
+                    }
+                    None => Dispatched::NoDispatch(res),
+                };
+            }
+
+            match ret {
                 Dispatched::Yes => (),
-                Dispatched::NoDispatch(_msg, _res) => {
+                Dispatched::NoDispatch(_res) => {
                     eprintln!(
                         "[wayland-server] Request received for an object \
                                     not associated to any filter: {}@{}",
                         object.interface, id
                     );
-                    self.inner.post_error(
+                    this.inner.post_error(
                         1,
                         super::display::DISPLAY_ERROR_NO_MEMORY,
                         "Server-side bug, sorry.".into(),
                     );
                 }
-                Dispatched::BadMsg => {
-                    self.inner.post_error(
+                Dispatched::BadMsg(Some(e @ FromRawError::InvalidArgument { .. })) => {
+                    this.inner.post_error(
+                        1,
+                        super::display::DISPLAY_ERROR_INVALID_METHOD,
+                        format!(
+                            "invalid arguments for {}@{}.{}: {}",
+                            object.interface, id, object.requests[opcode as usize].name, e
+                        ),
+                    );
+                }
+                Dispatched::BadMsg(Some(FromRawError::DupFdFailed(e))) => {
+                    this.inner.post_error(
+                        1,
+                        super::display::DISPLAY_ERROR_NO_MEMORY,
+                        format!("could not duplicate a file descriptor: {}", e),
+                    );
+                }
+                Dispatched::BadMsg(_) => {
+                    this.inner.post_error(
                         1,
                         super::display::DISPLAY_ERROR_INVALID_METHOD,
                         format!("invalid method {}, object {}@{}", opcode, object.interface, id),
@@ -583,48 +1054,42 @@ impl ClientImplementation {
 
 struct DisplayDispatcher {
     global_mgr: Rc<RefCell<GlobalManager>>,
+    serial: SerialCounter,
 }
 
 impl super::Dispatcher for DisplayDispatcher {
     fn dispatch(
         &mut self,
-        msg: Message,
+        msg: MessageRef,
         _resource: ResourceInner,
         map: &mut super::ResourceMap,
         _data: crate::DispatchData,
     ) -> Dispatched {
         use crate::protocol::wl_callback;
 
-        if WAYLAND_DEBUG.load(Ordering::Relaxed) {
-            debug::print_dispatched_message(
-                "wl_display",
-                1,
-                DISPLAY_REQUESTS[msg.opcode as usize].name,
-                &msg.args,
-            );
-        }
-
         match msg.opcode {
             // sync
             0 => {
-                let new_id = match msg.args.first() {
-                    Some(&Argument::NewId(new_id)) => new_id,
-                    _ => return Dispatched::BadMsg,
+                let new_id = match msg.args().next() {
+                    Some(ArgumentRef::NewId(new_id)) => new_id,
+                    _ => return bad_argument(0, "callback", ArgumentError::UnexpectedType),
                 };
 
                 let cb = match map.get_new::<wl_callback::WlCallback>(new_id) {
                     Some(cb) => cb,
-                    None => return Dispatched::BadMsg,
+                    None => {
+                        return bad_argument(0, "callback", ArgumentError::InvalidNewId(new_id))
+                    }
                 };
 
-                // TODO: send a more meaningful serial?
-                cb.as_ref().send(wl_callback::Event::Done { callback_data: 0 });
+                // like libwayland-server, send the current serial of the display
+                cb.as_ref().send(wl_callback::Event::Done { callback_data: self.serial.get() });
             }
             // get_registry
             1 => {
-                let new_id = match msg.args.first() {
-                    Some(&Argument::NewId(new_id)) => new_id,
-                    _ => return Dispatched::BadMsg,
+                let new_id = match msg.args().next() {
+                    Some(ArgumentRef::NewId(new_id)) => new_id,
+                    _ => return bad_argument(0, "registry", ArgumentError::UnexpectedType),
                 };
 
                 // We don't have a regular object for the registry, rather we insert the
@@ -635,12 +1100,12 @@ impl super::Dispatcher for DisplayDispatcher {
                             global_mgr: self.global_mgr.clone(),
                         })));
                 }) {
-                    return Dispatched::BadMsg;
+                    return bad_argument(0, "registry", ArgumentError::InvalidNewId(new_id));
                 }
 
                 self.global_mgr.borrow_mut().new_registry(new_id, map.client.clone());
             }
-            _ => return Dispatched::BadMsg,
+            opcode => return Dispatched::BadMsg(Some(FromRawError::UnknownOpcode(opcode))),
         }
 
         Dispatched::Yes
@@ -654,36 +1119,27 @@ struct RegistryDispatcher {
 impl super::Dispatcher for RegistryDispatcher {
     fn dispatch(
         &mut self,
-        msg: Message,
+        msg: MessageRef,
         resource: ResourceInner,
         map: &mut super::ResourceMap,
         data: crate::DispatchData,
     ) -> Dispatched {
-        if WAYLAND_DEBUG.load(Ordering::Relaxed) {
-            debug::print_dispatched_message(
-                "wl_registry",
-                resource.id,
-                REGISTRY_REQUESTS[msg.opcode as usize].name,
-                &msg.args,
-            );
-        }
-
-        let mut iter = msg.args.into_iter();
+        let mut iter = msg.args();
         let global_id = match iter.next() {
-            Some(Argument::Uint(u)) => u,
-            _ => return Dispatched::BadMsg,
+            Some(ArgumentRef::Uint(u)) => u,
+            _ => return bad_argument(0, "name", ArgumentError::UnexpectedType),
         };
         let interface = match iter.next() {
-            Some(Argument::Str(s)) => s,
-            _ => return Dispatched::BadMsg,
+            Some(ArgumentRef::Str(s)) => s,
+            _ => return bad_argument(1, "interface", ArgumentError::UnexpectedType),
         };
         let version = match iter.next() {
-            Some(Argument::Uint(u)) => u,
-            _ => return Dispatched::BadMsg,
+            Some(ArgumentRef::Uint(u)) => u,
+            _ => return bad_argument(2, "version", ArgumentError::UnexpectedType),
         };
         let new_id = match iter.next() {
-            Some(Argument::NewId(id)) => id,
-            _ => return Dispatched::BadMsg,
+            Some(ArgumentRef::NewId(id)) => id,
+            _ => return bad_argument(3, "id", ArgumentError::UnexpectedType),
         };
         match self.global_mgr.borrow().bind(
             resource.id,
@@ -695,7 +1151,11 @@ impl super::Dispatcher for RegistryDispatcher {
             data,
         ) {
             Ok(()) => Dispatched::Yes,
-            Err(()) => Dispatched::BadMsg,
+            Err(()) => bad_argument(3, "id", ArgumentError::InvalidNewId(new_id)),
         }
     }
 }
+
+fn bad_argument(index: usize, name: &'static str, reason: ArgumentError) -> Dispatched {
+    Dispatched::BadMsg(Some(FromRawError::InvalidArgument { index, name, reason }))
+}
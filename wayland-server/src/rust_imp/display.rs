@@ -2,19 +2,27 @@ use std::cell::RefCell;
 use std::env;
 use std::ffi::{OsStr, OsString};
 use std::io;
+use std::os::raw::c_int;
 use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::os::unix::net::UnixListener;
 use std::path::Path;
 use std::rc::Rc;
-use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+
+use wayland_commons::debug::{self, ProtocolLogger, StderrLogger};
 
 use crate::display::get_runtime_dir;
-use crate::{Interface, Main, Resource};
+use crate::{
+    BufferCapacity, ClientLimits, Filter, Interest, Interface, LimitsError, Main, MessageGroup,
+    Readiness, Resource,
+};
 
 use super::clients::ClientManager;
-use super::event_loop_glue::{FdManager, Token};
+use super::event_loop_glue::{
+    FdManager, FdSourceInner, IdleSourceInner, SignalSourceInner, TimerSourceInner, Token,
+};
 use super::globals::GlobalManager;
-use super::{ClientInner, GlobalInner, WAYLAND_DEBUG};
+use super::{ClientInner, DefaultDispatchers, GlobalInner, LoggerSlot, SerialCounter};
 
 pub(crate) const DISPLAY_ERROR_INVALID_OBJECT: u32 = 0;
 pub(crate) const DISPLAY_ERROR_INVALID_METHOD: u32 = 1;
@@ -26,25 +34,77 @@ pub(crate) struct DisplayInner {
     pub(crate) clients_mgr: Rc<RefCell<ClientManager>>,
     global_mgr: Rc<RefCell<GlobalManager>>,
     listeners: Vec<Token>,
+    logger: LoggerSlot,
+    default_dispatchers: DefaultDispatchers,
+    serial: SerialCounter,
 }
 
 impl DisplayInner {
     pub(crate) fn new() -> DisplayInner {
-        if let Some(value) = std::env::var_os("WAYLAND_DEBUG") {
-            // Follow libwayland-client and enable debug log only on `1` and `server` values.
-            if value == "1" || value == "server" {
-                // Toggle debug log.
-                WAYLAND_DEBUG.store(true, Ordering::Relaxed);
-            }
-        }
+        // Follow libwayland-server and enable debug log only on `1` and `server` values.
+        let logger: LoggerSlot = if debug::debug_enabled("server") {
+            Arc::new(Mutex::new(Some(Arc::new(StderrLogger))))
+        } else {
+            Arc::new(Mutex::new(None))
+        };
 
         let global_mgr = Rc::new(RefCell::new(GlobalManager::new()));
         let epoll_mgr = Rc::new(FdManager::new().unwrap());
 
-        let clients_mgr =
-            Rc::new(RefCell::new(ClientManager::new(epoll_mgr.clone(), global_mgr.clone())));
+        let default_dispatchers: DefaultDispatchers = Default::default();
+        let serial: SerialCounter = Default::default();
+
+        let clients_mgr = Rc::new(RefCell::new(ClientManager::new(
+            epoll_mgr.clone(),
+            global_mgr.clone(),
+            logger.clone(),
+            default_dispatchers.clone(),
+            serial.clone(),
+        )));
+
+        DisplayInner {
+            epoll_mgr,
+            clients_mgr,
+            global_mgr,
+            listeners: Vec::new(),
+            logger,
+            default_dispatchers,
+            serial,
+        }
+    }
+
+    pub(crate) fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        *self.logger.lock().unwrap() = logger;
+    }
+
+    pub(crate) fn set_default_filter<I, E>(&mut self, filter: Filter<E>)
+    where
+        I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+        E: From<(Main<I>, I::Request)> + 'static,
+        I::Request: MessageGroup<Map = super::ResourceMap>,
+    {
+        self.default_dispatchers.borrow_mut().insert(I::NAME, super::make_dispatcher(filter));
+    }
+
+    pub(crate) fn next_serial(&self) -> u32 {
+        let serial = self.serial.get().wrapping_add(1);
+        self.serial.set(serial);
+        serial
+    }
 
-        DisplayInner { epoll_mgr, clients_mgr, global_mgr, listeners: Vec::new() }
+    pub(crate) fn clients(&self) -> Vec<ClientInner> {
+        self.clients_mgr.borrow().clients()
+    }
+
+    pub(crate) fn set_default_buffer_capacity(&mut self, capacity: BufferCapacity) {
+        self.clients_mgr.borrow_mut().buffer_capacity = capacity;
+    }
+
+    pub(crate) fn set_default_client_limits(
+        &mut self,
+        limits: ClientLimits,
+    ) -> Result<(), LimitsError> {
+        self.clients_mgr.borrow_mut().set_default_limits(limits)
     }
 
     pub(crate) fn create_global<I, F1, F2>(
@@ -61,7 +121,7 @@ impl DisplayInner {
         self.global_mgr.borrow_mut().add_global(version, implementation, filter)
     }
 
-    pub(crate) fn flush_clients(&mut self, data: crate::DispatchData) {
+    pub(crate) fn flush_clients(&self, data: crate::DispatchData) {
         self.clients_mgr.borrow_mut().flush_all(data)
     }
 
@@ -151,11 +211,8 @@ impl DisplayInner {
         self.clients_mgr.borrow_mut().init_client(fd, data)
     }
 
-    pub(crate) fn dispatch(
-        &mut self,
-        timeout: i32,
-        data: crate::DispatchData,
-    ) -> std::io::Result<()> {
+    pub(crate) fn dispatch(&self, timeout: i32, data: crate::DispatchData) -> std::io::Result<()> {
+        self.clients_mgr.borrow().start_dispatch();
         self.epoll_mgr
             .poll(timeout, data)
             .map_err(|e| From::from(e.as_errno().unwrap_or(nix::errno::Errno::EINVAL)))
@@ -164,6 +221,43 @@ impl DisplayInner {
     pub(crate) fn get_poll_fd(&self) -> RawFd {
         self.epoll_mgr.get_poll_fd()
     }
+
+    pub(crate) fn add_fd_source<F>(
+        &mut self,
+        fd: RawFd,
+        interest: Interest,
+        callback: F,
+    ) -> io::Result<FdSourceInner>
+    where
+        F: FnMut(Readiness, crate::DispatchData<'_>) + 'static,
+    {
+        FdSourceInner::new(&self.epoll_mgr, fd, interest, callback)
+    }
+
+    pub(crate) fn add_timer<F>(&mut self, callback: F) -> io::Result<TimerSourceInner>
+    where
+        F: FnMut(crate::DispatchData<'_>) + 'static,
+    {
+        TimerSourceInner::new(&self.epoll_mgr, callback)
+    }
+
+    pub(crate) fn add_signal<F>(
+        &mut self,
+        signal: c_int,
+        callback: F,
+    ) -> io::Result<SignalSourceInner>
+    where
+        F: FnMut(crate::DispatchData<'_>) + 'static,
+    {
+        SignalSourceInner::new(&self.epoll_mgr, signal, callback)
+    }
+
+    pub(crate) fn add_idle<F>(&mut self, callback: F) -> io::Result<IdleSourceInner>
+    where
+        F: FnOnce(crate::DispatchData<'_>) + 'static,
+    {
+        Ok(IdleSourceInner::new(&self.epoll_mgr, callback))
+    }
 }
 
 impl Drop for DisplayInner {
@@ -1,36 +1,66 @@
-use std::cell::RefCell;
-use std::os::unix::io::RawFd;
+use std::cell::{Cell, RefCell};
+use std::cmp::max;
+use std::convert::TryFrom;
+use std::io;
+use std::os::raw::c_int;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::rc::Rc;
+use std::time::Duration;
 
 use nix::sys::epoll::*;
+use nix::sys::signal::{SigSet, Signal};
+use nix::sys::signalfd::{SfdFlags, SignalFd};
+use nix::sys::time::TimeSpec;
+use nix::sys::timerfd::{ClockId, Expiration, TimerFd, TimerFlags, TimerSetTimeFlags};
 
-use crate::DispatchData;
+use crate::{DispatchData, Interest, OwnedFd, Readiness};
 
-type FdData = (RawFd, Option<Box<dyn FnMut(crate::DispatchData<'_>)>>);
+type FdData = (RawFd, Option<Box<dyn FnMut(EpollFlags, crate::DispatchData<'_>)>>);
+type IdleData = (usize, Box<dyn FnOnce(crate::DispatchData<'_>)>);
 
 #[derive(Copy, Clone)]
 pub(crate) struct Token(usize);
 
+#[derive(Copy, Clone)]
+pub(crate) struct IdleToken(usize);
+
 pub(crate) struct FdManager {
     epoll_fd: RawFd,
     callbacks: RefCell<Vec<Option<FdData>>>,
+    idles: RefCell<Vec<IdleData>>,
+    next_idle: Cell<usize>,
 }
 
 impl FdManager {
     pub(crate) fn new() -> nix::Result<FdManager> {
         let fd = epoll_create1(EpollCreateFlags::EPOLL_CLOEXEC)?;
 
-        Ok(FdManager { epoll_fd: fd, callbacks: RefCell::new(Vec::new()) })
+        Ok(FdManager {
+            epoll_fd: fd,
+            callbacks: RefCell::new(Vec::new()),
+            idles: RefCell::new(Vec::new()),
+            next_idle: Cell::new(0),
+        })
     }
 
     pub(crate) fn register<F: FnMut(DispatchData<'_>) + 'static>(
         &self,
         fd: RawFd,
+        mut cb: F,
+    ) -> nix::Result<Token> {
+        self.register_with_interest(fd, EpollFlags::EPOLLIN, move |_, data| cb(data))
+    }
+
+    pub(crate) fn register_with_interest<F: FnMut(EpollFlags, DispatchData<'_>) + 'static>(
+        &self,
+        fd: RawFd,
+        interest: EpollFlags,
         cb: F,
     ) -> nix::Result<Token> {
         let mut callbacks = self.callbacks.borrow_mut();
         // find the first free id
         let free_id = callbacks.iter().position(|c| c.is_none());
-        let cb = Some(Box::new(cb) as Box<dyn FnMut(DispatchData<'_>)>);
+        let cb = Some(Box::new(cb) as Box<dyn FnMut(EpollFlags, DispatchData<'_>)>);
         let id = match free_id {
             Some(i) => {
                 callbacks[i] = Some((fd, cb));
@@ -41,7 +71,7 @@ impl FdManager {
                 callbacks.len() - 1
             }
         };
-        let mut evt = EpollEvent::new(EpollFlags::EPOLLIN, id as u64);
+        let mut evt = EpollEvent::new(interest, id as u64);
         let ret = epoll_ctl(self.epoll_fd, EpollOp::EpollCtlAdd, fd, &mut evt);
         match ret {
             Ok(()) => Ok(Token(id)),
@@ -52,13 +82,50 @@ impl FdManager {
         }
     }
 
+    pub(crate) fn update(&self, token: Token, interest: EpollFlags) -> nix::Result<()> {
+        if let Some((fd, _)) = self.callbacks.borrow()[token.0] {
+            let mut evt = EpollEvent::new(interest, token.0 as u64);
+            epoll_ctl(self.epoll_fd, EpollOp::EpollCtlMod, fd, &mut evt)
+        } else {
+            Ok(())
+        }
+    }
+
     pub(crate) fn deregister(&self, token: Token) {
         if let Some((fd, _)) = self.callbacks.borrow_mut()[token.0].take() {
             let _ = epoll_ctl(self.epoll_fd, EpollOp::EpollCtlDel, fd, None);
         }
     }
 
+    pub(crate) fn add_idle<F: FnOnce(DispatchData<'_>) + 'static>(&self, cb: F) -> IdleToken {
+        let id = self.next_idle.get();
+        self.next_idle.set(id.wrapping_add(1));
+        self.idles.borrow_mut().push((id, Box::new(cb)));
+        IdleToken(id)
+    }
+
+    pub(crate) fn remove_idle(&self, token: IdleToken) {
+        self.idles.borrow_mut().retain(|&(id, _)| id != token.0);
+    }
+
+    fn dispatch_idles(&self, mut data: crate::DispatchData) {
+        // the idle callbacks may add new ones, which are run as well
+        loop {
+            let idle = {
+                let mut idles = self.idles.borrow_mut();
+                if idles.is_empty() {
+                    break;
+                }
+                idles.remove(0)
+            };
+            (idle.1)(data.reborrow());
+        }
+    }
+
     pub(crate) fn poll(&self, timeout: i32, mut data: crate::DispatchData) -> nix::Result<()> {
+        // like libwayland, run the idle callbacks before waiting
+        self.dispatch_idles(data.reborrow());
+
         let mut events = [EpollEvent::empty(); 32];
         let n = epoll_wait(self.epoll_fd, &mut events, timeout as isize)?;
 
@@ -67,7 +134,7 @@ impl FdManager {
             // remove the cb while we call it, to gracefully handle reentrancy
             let cb = self.callbacks.borrow_mut()[id].as_mut().and_then(|(_, ref mut cb)| cb.take());
             if let Some(mut cb) = cb {
-                cb(data.reborrow());
+                cb(event.events(), data.reborrow());
                 // now, put it back in place
                 if let Some(ref mut place) = self.callbacks.borrow_mut()[id] {
                     if place.1.is_none() {
@@ -77,11 +144,11 @@ impl FdManager {
                     // deleted and replaced by a new one while `cb` was running, in which case we should
                     // not put it back in place
                 }
-            // If self.callbacks[id] is None, this means that our callback has been deleted while running,
-            // in which case we should not put it back in place
-            } else {
-                panic!("Received a readiness for a deregistered fd?!");
+                // If self.callbacks[id] is None, this means that our callback has been deleted while running,
+                // in which case we should not put it back in place
             }
+            // If there is no callback, the source has been removed by a previous callback of this
+            // batch, and its readiness is ignored
         }
 
         Ok(())
@@ -91,3 +158,168 @@ impl FdManager {
         self.epoll_fd
     }
 }
+
+fn epoll_interest(interest: Interest) -> EpollFlags {
+    let mut flags = EpollFlags::empty();
+    if interest.contains(Interest::READ) {
+        flags |= EpollFlags::EPOLLIN;
+    }
+    if interest.contains(Interest::WRITE) {
+        flags |= EpollFlags::EPOLLOUT;
+    }
+    flags
+}
+
+fn readiness(flags: EpollFlags) -> Readiness {
+    let mut readiness = Readiness::empty();
+    if flags.contains(EpollFlags::EPOLLIN) {
+        readiness |= Readiness::READABLE;
+    }
+    if flags.contains(EpollFlags::EPOLLOUT) {
+        readiness |= Readiness::WRITABLE;
+    }
+    if flags.contains(EpollFlags::EPOLLHUP) {
+        readiness |= Readiness::HANGUP;
+    }
+    if flags.contains(EpollFlags::EPOLLERR) {
+        readiness |= Readiness::ERROR;
+    }
+    readiness
+}
+
+pub(crate) fn to_io_error(e: nix::Error) -> io::Error {
+    io::Error::from(e.as_errno().unwrap_or(nix::errno::Errno::EINVAL))
+}
+
+pub(crate) struct FdSourceInner {
+    mgr: Rc<FdManager>,
+    token: Token,
+}
+
+impl FdSourceInner {
+    pub(crate) fn new<F: FnMut(Readiness, DispatchData<'_>) + 'static>(
+        mgr: &Rc<FdManager>,
+        fd: RawFd,
+        interest: Interest,
+        mut cb: F,
+    ) -> io::Result<FdSourceInner> {
+        let token = mgr
+            .register_with_interest(fd, epoll_interest(interest), move |flags, data| {
+                cb(readiness(flags), data)
+            })
+            .map_err(to_io_error)?;
+        Ok(FdSourceInner { mgr: mgr.clone(), token })
+    }
+
+    pub(crate) fn update(&self, interest: Interest) -> io::Result<()> {
+        self.mgr.update(self.token, epoll_interest(interest)).map_err(to_io_error)
+    }
+
+    pub(crate) fn remove(self) {
+        self.mgr.deregister(self.token);
+    }
+}
+
+pub(crate) struct TimerSourceInner {
+    mgr: Rc<FdManager>,
+    token: Token,
+    timer: TimerFd,
+}
+
+impl TimerSourceInner {
+    pub(crate) fn new<F: FnMut(DispatchData<'_>) + 'static>(
+        mgr: &Rc<FdManager>,
+        mut cb: F,
+    ) -> io::Result<TimerSourceInner> {
+        let timer = TimerFd::new(
+            ClockId::CLOCK_MONOTONIC,
+            TimerFlags::TFD_CLOEXEC | TimerFlags::TFD_NONBLOCK,
+        )
+        .map_err(to_io_error)?;
+        // the timerfd is owned by the callback, and closed when it is dropped, either when the
+        // source is removed or with the event loop
+        let owned_fd = unsafe { OwnedFd::from_raw_fd(timer.as_raw_fd()) };
+        let token = mgr
+            .register(timer.as_raw_fd(), move |data| {
+                // clear the expiration, the timer is non-blocking
+                let _ = timer.wait();
+                let _ = &owned_fd;
+                cb(data)
+            })
+            .map_err(to_io_error)?;
+        Ok(TimerSourceInner { mgr: mgr.clone(), token, timer })
+    }
+
+    pub(crate) fn set_timeout(&self, delay: Option<Duration>) -> io::Result<()> {
+        match delay {
+            Some(delay) => {
+                // a zero expiration would disarm the timer
+                let delay = max(delay, Duration::from_nanos(1));
+                let spec = TimeSpec::from(libc::timespec {
+                    tv_sec: delay.as_secs() as libc::time_t,
+                    tv_nsec: delay.subsec_nanos() as libc::c_long,
+                });
+                self.timer.set(Expiration::OneShot(spec), TimerSetTimeFlags::empty())
+            }
+            None => self.timer.unset(),
+        }
+        .map_err(to_io_error)
+    }
+
+    pub(crate) fn remove(self) {
+        self.mgr.deregister(self.token);
+    }
+}
+
+pub(crate) struct SignalSourceInner {
+    mgr: Rc<FdManager>,
+    token: Token,
+}
+
+impl SignalSourceInner {
+    pub(crate) fn new<F: FnMut(DispatchData<'_>) + 'static>(
+        mgr: &Rc<FdManager>,
+        signal: c_int,
+        mut cb: F,
+    ) -> io::Result<SignalSourceInner> {
+        let mut mask = SigSet::empty();
+        mask.add(Signal::try_from(signal).map_err(to_io_error)?);
+        let mut signal_fd =
+            SignalFd::with_flags(&mask, SfdFlags::SFD_CLOEXEC | SfdFlags::SFD_NONBLOCK)
+                .map_err(to_io_error)?;
+        // like libwayland, block the signal so that it is only received through the signalfd
+        mask.thread_block().map_err(to_io_error)?;
+        let fd = signal_fd.as_raw_fd();
+        // the signalfd is owned by the callback, and closed when it is dropped
+        let token = mgr
+            .register(fd, move |mut data| {
+                while let Ok(Some(_)) = signal_fd.read_signal() {
+                    cb(data.reborrow());
+                }
+            })
+            .map_err(to_io_error)?;
+        Ok(SignalSourceInner { mgr: mgr.clone(), token })
+    }
+
+    pub(crate) fn remove(self) {
+        self.mgr.deregister(self.token);
+    }
+}
+
+pub(crate) struct IdleSourceInner {
+    mgr: Rc<FdManager>,
+    token: IdleToken,
+}
+
+impl IdleSourceInner {
+    pub(crate) fn new<F: FnOnce(DispatchData<'_>) + 'static>(
+        mgr: &Rc<FdManager>,
+        cb: F,
+    ) -> IdleSourceInner {
+        IdleSourceInner { mgr: mgr.clone(), token: mgr.add_idle(cb) }
+    }
+
+    pub(crate) fn remove(self) {
+        self.mgr.remove_idle(self.token);
+    }
+}
@@ -1,12 +1,14 @@
-use std::cell::RefCell;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 
 use downcast_rs::Downcast;
 
-use wayland_commons::debug;
+use wayland_commons::debug::ProtocolLogger;
 use wayland_commons::map::ObjectMap;
-use wayland_commons::wire::Message;
+use wayland_commons::wire::{FromRawError, MessageRef};
 use wayland_commons::{MessageGroup, ThreadGuard};
 
 use crate::{DispatchData, Filter, Interface, Main, Resource};
@@ -19,13 +21,23 @@ mod resources;
 
 pub(crate) use self::clients::ClientInner;
 pub(crate) use self::display::DisplayInner;
+pub(crate) use self::event_loop_glue::{
+    FdSourceInner, IdleSourceInner, SignalSourceInner, TimerSourceInner,
+};
 pub(crate) use self::globals::GlobalInner;
 pub(crate) use self::resources::ResourceInner;
 
 use self::resources::ResourceDestructor;
 
-/// Flag to toggle debug output.
-static WAYLAND_DEBUG: AtomicBool = AtomicBool::new(false);
+/// The protocol logger of a display, shared with all its clients
+pub(crate) type LoggerSlot = Arc<Mutex<Option<Arc<dyn ProtocolLogger>>>>;
+
+/// The serial counter of a display, shared with all its clients
+pub(crate) type SerialCounter = Rc<Cell<u32>>;
+
+/// The default dispatchers of a display, by interface name, shared with all its clients
+pub(crate) type DefaultDispatchers =
+    Rc<RefCell<HashMap<&'static str, Arc<ThreadGuard<RefCell<dyn Dispatcher>>>>>>;
 
 /// A handle to the object map internal to the library state
 ///
@@ -77,14 +89,16 @@ impl ResourceMap {
 #[allow(clippy::large_enum_variant)]
 pub(crate) enum Dispatched {
     Yes,
-    NoDispatch(Message, ResourceInner),
-    BadMsg,
+    NoDispatch(ResourceInner),
+    BadMsg(Option<FromRawError>),
 }
 
+// The fds of the message given to a dispatcher remain owned by the caller, a dispatcher
+// keeping them must duplicate them.
 pub(crate) trait Dispatcher: Downcast {
     fn dispatch(
         &mut self,
-        msg: Message,
+        msg: MessageRef,
         resource: ResourceInner,
         map: &mut ResourceMap,
         data: DispatchData,
@@ -114,25 +128,16 @@ where
 {
     fn dispatch(
         &mut self,
-        msg: Message,
+        msg: MessageRef,
         resource: ResourceInner,
         map: &mut ResourceMap,
         data: DispatchData,
     ) -> Dispatched {
         let opcode = msg.opcode as usize;
 
-        if WAYLAND_DEBUG.load(Ordering::Relaxed) {
-            debug::print_dispatched_message(
-                resource.object.interface,
-                resource.id,
-                resource.object.requests[opcode].name,
-                &msg.args,
-            );
-        }
-
-        let message = match I::Request::from_raw(msg, map) {
+        let message = match I::Request::from_raw_ref(msg, map) {
             Ok(msg) => msg,
-            Err(_) => return Dispatched::BadMsg,
+            Err(e) => return Dispatched::BadMsg(Some(e)),
         };
 
         if message.since() > resource.version() {
@@ -144,7 +149,7 @@ where
                 resource.id,
                 resource.version()
             );
-            return Dispatched::BadMsg;
+            return Dispatched::BadMsg(None);
         }
 
         if message.is_destructor() {
@@ -182,12 +187,12 @@ pub(crate) fn default_dispatcher() -> Arc<ThreadGuard<RefCell<dyn Dispatcher>>>
     impl Dispatcher for DefaultDisp {
         fn dispatch(
             &mut self,
-            msg: Message,
+            _msg: MessageRef,
             resource: ResourceInner,
             _map: &mut ResourceMap,
             _data: DispatchData,
         ) -> Dispatched {
-            Dispatched::NoDispatch(msg, resource)
+            Dispatched::NoDispatch(resource)
         }
     }
 
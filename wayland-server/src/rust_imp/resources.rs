@@ -4,12 +4,13 @@ use std::sync::{Arc, Mutex};
 
 use crate::{Interface, Main, Resource};
 
-use wayland_commons::debug;
+use wayland_commons::debug::{LoggedMessage, MessageDirection};
 use wayland_commons::map::{Object, ObjectMap, ObjectMetadata};
 use wayland_commons::user_data::UserData;
+use wayland_commons::wire::Argument;
 use wayland_commons::{MessageGroup, ThreadGuard};
 
-use super::{ClientInner, Dispatcher, WAYLAND_DEBUG};
+use super::{ClientInner, Dispatcher};
 
 pub(crate) type ResourceDestructor = RefCell<dyn FnMut(ResourceInner, crate::DispatchData<'_>)>;
 
@@ -69,29 +70,45 @@ impl ResourceInner {
     }
 
     pub(crate) fn send<I: Interface>(&self, msg: I::Event) {
+        let destructor = msg.is_destructor();
+        let msg = match msg.into_raw(self.id) {
+            Ok(msg) => msg,
+            Err(_) => {
+                // the fds of the message could not be duplicated, the client is disconnected
+                // like when the message cannot be written
+                if self.is_alive() {
+                    self.client.kill();
+                }
+                return;
+            }
+        };
+
+        let mut failed = false;
         if let Some(ref mut conn_lock) = *self.client.data.lock().unwrap() {
             let is_alive = self.is_alive();
 
-            let destructor = msg.is_destructor();
-            let msg = msg.into_raw(self.id);
-
-            if WAYLAND_DEBUG.load(Ordering::Relaxed) {
-                debug::print_send_message(
-                    I::NAME,
-                    self.id,
-                    is_alive,
-                    self.object.events[msg.opcode as usize].name,
-                    &msg.args,
-                );
-            }
-
             if !is_alive {
+                // the message is not actually sent, report it as such
+                if let Some(ref logger) = *conn_lock.logger.lock().unwrap() {
+                    let args = msg.args.iter().map(Argument::as_ref).collect::<Vec<_>>();
+                    logger.log(&LoggedMessage::now(
+                        MessageDirection::Sent,
+                        I::NAME,
+                        self.id,
+                        false,
+                        self.object.events[msg.opcode as usize].name,
+                        &args,
+                    ));
+                }
                 return;
             }
 
-            // TODO: figure our if this can fail and still be recoverable ?
-            conn_lock.write_message(&msg).expect("Sending a message failed.");
-            if destructor {
+            if conn_lock.write_message(&msg).is_err() {
+                // the message could not be sent (the client does not read its socket
+                // or an fd could not be duplicated), the client is disconnected like
+                // libwayland-server does
+                failed = true;
+            } else if destructor {
                 self.object.meta.alive.store(false, Ordering::Release);
                 // schedule a destructor
                 conn_lock.schedule_destructor(self.clone());
@@ -99,6 +116,9 @@ impl ResourceInner {
                 let _ = conn_lock.delete_id(self.id);
             }
         }
+        if failed {
+            self.client.kill();
+        }
     }
 
     pub(crate) fn is_alive(&self) -> bool {
@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+
+use crate::Client;
+
+/// A record of the serials recently sent to the clients
+///
+/// Some requests, like starting an interactive move or opening a popup with a grab, must only be
+/// honored if they come as a response to a specific user action, which the client proves by
+/// giving the serial of the event that notified it of this action. Record the serials you send
+/// with `record()`, associating them with a description `E` of the event (typically an enum of
+/// the input events of a seat), and check the serials provided by the clients with `check()`.
+///
+/// Only the last `capacity` serials are remembered, so older ones are no longer considered
+/// valid. You typically keep a tracker per seat.
+///
+/// ```no_run
+/// # extern crate wayland_server;
+/// use wayland_server::SerialTracker;
+///
+/// #[derive(PartialEq)]
+/// enum SeatEvent {
+///     ButtonPress,
+///     ButtonRelease,
+///     KeyPress,
+/// }
+///
+/// # let display = wayland_server::Display::new();
+/// # let client: wayland_server::Client = unimplemented!();
+/// let mut tracker = SerialTracker::new(16);
+///
+/// // when sending a button press to a client
+/// let serial = display.next_serial();
+/// // pointer.button(serial, time, button, ButtonState::Pressed);
+/// tracker.record(serial, &client, SeatEvent::ButtonPress);
+///
+/// // when this client requests an interactive move with this serial
+/// if tracker.check(serial, &client, |event| *event == SeatEvent::ButtonPress) {
+///     // start the move
+/// }
+/// ```
+pub struct SerialTracker<E> {
+    entries: VecDeque<SerialEntry<E>>,
+    capacity: usize,
+}
+
+struct SerialEntry<E> {
+    serial: u32,
+    client: Client,
+    event: E,
+}
+
+impl<E> SerialTracker<E> {
+    /// Create a tracker remembering the last `capacity` serials
+    pub fn new(capacity: usize) -> SerialTracker<E> {
+        SerialTracker { entries: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// Record that `serial` was sent to `client` with `event`
+    ///
+    /// The oldest serial is forgotten if the tracker is full, as well as the serials sent to the
+    /// clients that are no longer alive.
+    pub fn record(&mut self, serial: u32, client: &Client, event: E) {
+        self.entries.retain(|entry| entry.client.alive());
+        while !self.entries.is_empty() && self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        if self.capacity > 0 {
+            self.entries.push_back(SerialEntry { serial, client: client.clone(), event });
+        }
+    }
+
+    /// Get the event that was sent with `serial` to `client`
+    ///
+    /// Returns `None` if this serial was not sent to this client, or is no longer remembered.
+    pub fn get(&self, serial: u32, client: &Client) -> Option<&E> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.serial == serial && entry.client.equals(client))
+            .map(|entry| &entry.event)
+    }
+
+    /// Check that `serial` was recently sent to `client` with an event matching `filter`
+    pub fn check<F>(&self, serial: u32, client: &Client, filter: F) -> bool
+    where
+        F: FnOnce(&E) -> bool,
+    {
+        self.get(serial, client).map(filter).unwrap_or(false)
+    }
+
+    /// Get the last serial sent to `client` with an event matching `filter`
+    ///
+    /// This allows checking that a serial is not only valid but also the most recent of its kind,
+    /// for example the last button press of a seat.
+    pub fn last<F>(&self, client: &Client, mut filter: F) -> Option<u32>
+    where
+        F: FnMut(&E) -> bool,
+    {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.client.equals(client) && filter(&entry.event))
+            .map(|entry| entry.serial)
+    }
+
+    /// Forget all the recorded serials
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,221 @@
+//! Shared memory buffers
+//!
+//! This module provides a ready-made implementation of the `wl_shm` global, which clients use
+//! to share buffers with the server through memory pools. It handles the `wl_shm`,
+//! `wl_shm_pool` and the shm `wl_buffer` objects, validating the requests of the clients and
+//! mapping their pools in memory.
+//!
+//! The global is created with `Display::create_shm_global()`, and the contents of the buffers
+//! it created are then accessed with `with_buffer_contents()`:
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use wayland_server::protocol::wl_shm::Format;
+//! use wayland_server::shm::with_buffer_contents;
+//!
+//! let mut display = wayland_server::Display::new();
+//! // advertise the RGB565 format in addition to the mandatory ARGB8888 and XRGB8888
+//! display.create_shm_global(&[Format::Rgb565]).expect("Failed to place the SIGBUS handler.");
+//!
+//! # let buffer: wayland_server::protocol::wl_buffer::WlBuffer = unimplemented!();
+//! // when a client attaches a buffer to a surface
+//! let result = with_buffer_contents(&buffer, |pixels, data| {
+//!     // upload the `data.height` rows of `data.stride` bytes of `pixels` to the GPU...
+//! });
+//! ```
+//!
+//! A client can truncate the file backing a pool at any time, which makes the server receive a
+//! `SIGBUS` signal when accessing it. `create_shm_global()` installs a handler for this signal
+//! which recovers from it, so that `with_buffer_contents()` returns an error instead. The
+//! previous handler is still called for the `SIGBUS` signals that do not concern the pools.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+use crate::protocol::wl_buffer::WlBuffer;
+use crate::protocol::wl_shm::{self, Format, WlShm};
+use crate::protocol::wl_shm_pool;
+use crate::{Display, Filter, Global, Main};
+
+mod pool;
+
+use self::pool::Pool;
+
+/// Information about a shm buffer
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BufferData {
+    /// Offset of the buffer in its pool, in bytes
+    pub offset: i32,
+    /// Width of the buffer, in pixels
+    pub width: i32,
+    /// Height of the buffer, in pixels
+    pub height: i32,
+    /// Number of bytes between the starts of two consecutive rows
+    pub stride: i32,
+    /// Pixel format of the buffer
+    pub format: Format,
+}
+
+/// An error accessing the contents of a shm buffer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BufferAccessError {
+    /// The buffer was not created by a `wl_shm` global of `Display::create_shm_global()`
+    ///
+    /// It may be a buffer of an other kind, or it is accessed from an other thread than the one
+    /// hosting the display.
+    NotManaged,
+    /// The client truncated the file backing the pool of the buffer
+    ///
+    /// The client should be killed with a protocol error, as its buffers can no longer be used.
+    BadMap,
+    /// The buffer does not fit in the memory of its pool
+    ///
+    /// Buffers are checked against the size of their pool when they are created and pools can
+    /// only grow, so this indicates a bug in this library rather than in the client.
+    OutOfBounds,
+}
+
+impl std::error::Error for BufferAccessError {}
+
+impl fmt::Display for BufferAccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            BufferAccessError::NotManaged => f.write_str("The buffer is not a managed shm buffer."),
+            BufferAccessError::BadMap => {
+                f.write_str("The pool of the buffer was truncated by the client.")
+            }
+            BufferAccessError::OutOfBounds => {
+                f.write_str("The buffer does not fit in the memory of its pool.")
+            }
+        }
+    }
+}
+
+struct ShmBuffer {
+    pool: Rc<Pool>,
+    data: BufferData,
+}
+
+/// Access the contents of a shm buffer
+///
+/// `f` is given the bytes of the buffer, which are `data.height` rows of `data.stride` bytes,
+/// and its description.
+///
+/// If the client truncated the file backing the pool of the buffer, this returns
+/// `BufferAccessError::BadMap`. If this happens while `f` runs, the contents it was given are
+/// replaced by zeros so that it can complete, and its result is discarded.
+pub fn with_buffer_contents<F, T>(buffer: &WlBuffer, f: F) -> Result<T, BufferAccessError>
+where
+    F: FnOnce(&[u8], BufferData) -> T,
+{
+    let buffer =
+        buffer.as_ref().user_data().get::<ShmBuffer>().ok_or(BufferAccessError::NotManaged)?;
+    let data = buffer.data;
+    let len = data.stride as usize * data.height as usize;
+    buffer.pool.with_data(data.offset as usize, len, |pixels| f(pixels, data))
+}
+
+impl Display {
+    /// Create a `wl_shm` global
+    ///
+    /// It advertises the `Argb8888` and `Xrgb8888` formats, which all servers must support, and
+    /// the additional `formats`. The buffers created by its clients are accessed with
+    /// `shm::with_buffer_contents()`, see the `shm` module for details.
+    ///
+    /// The requests of the clients are validated like `libwayland-server` does, and invalid
+    /// ones are answered with the appropriate protocol error.
+    ///
+    /// This places the `SIGBUS` handler used to access the pools, and returns an error if that
+    /// fails. The global must be created on the thread accessing the buffers.
+    pub fn create_shm_global(&mut self, formats: &[Format]) -> io::Result<Global<WlShm>> {
+        pool::place_sigbus_handler()?;
+
+        let mut all_formats = vec![Format::Argb8888, Format::Xrgb8888];
+        for &format in formats {
+            if !all_formats.contains(&format) {
+                all_formats.push(format);
+            }
+        }
+        let formats: Rc<[Format]> = all_formats.into();
+
+        Ok(self.create_global(
+            1,
+            Filter::new(move |(shm, _): (Main<WlShm>, u32), _, _| {
+                let shm_formats = formats.clone();
+                shm.quick_assign(move |shm, request, _| match request {
+                    wl_shm::Request::CreatePool { id, fd, size } => {
+                        if size <= 0 {
+                            shm.as_ref().post_error(
+                                wl_shm::Error::InvalidStride as u32,
+                                format!("invalid size ({})", size),
+                            );
+                            return;
+                        }
+                        match Pool::new(fd, size as usize) {
+                            Ok(pool) => assign_pool(id, Rc::new(pool), shm_formats.clone()),
+                            Err(e) => shm.as_ref().post_error(
+                                wl_shm::Error::InvalidFd as u32,
+                                format!("failed mmap of the pool: {}", e),
+                            ),
+                        }
+                    }
+                });
+                for &format in formats.iter() {
+                    shm.format(format);
+                }
+            }),
+        ))
+    }
+}
+
+fn assign_pool(pool: Main<wl_shm_pool::WlShmPool>, data: Rc<Pool>, formats: Rc<[Format]>) {
+    pool.quick_assign(move |pool, request, _| match request {
+        wl_shm_pool::Request::CreateBuffer { id, offset, width, height, stride, format } => {
+            if !formats.contains(&format) {
+                pool.as_ref().post_error(
+                    wl_shm::Error::InvalidFormat as u32,
+                    format!("invalid format {:?}", format),
+                );
+                return;
+            }
+            let buffer_size = i64::from(stride) * i64::from(height);
+            if offset < 0
+                || width <= 0
+                || height <= 0
+                || stride < width
+                || i32::try_from(buffer_size).is_err()
+                || i64::from(offset) + buffer_size > data.size() as i64
+            {
+                pool.as_ref().post_error(
+                    wl_shm::Error::InvalidStride as u32,
+                    format!("invalid width, height or stride ({}x{}, {})", width, height, stride),
+                );
+                return;
+            }
+            let shm_buffer = ShmBuffer {
+                pool: data.clone(),
+                data: BufferData { offset, width, height, stride, format },
+            };
+            id.quick_assign(|_, _, _| {});
+            id.as_ref().user_data().set(move || shm_buffer);
+        }
+        wl_shm_pool::Request::Resize { size } => {
+            if size <= 0 || (size as usize) < data.size() {
+                pool.as_ref().post_error(
+                    wl_shm::Error::InvalidStride as u32,
+                    "shrinking pool invalid".into(),
+                );
+                return;
+            }
+            if let Err(e) = data.resize(size as usize) {
+                pool.as_ref().post_error(
+                    wl_shm::Error::InvalidFd as u32,
+                    format!("failed mmap of the pool: {}", e),
+                );
+            }
+        }
+        wl_shm_pool::Request::Destroy => {}
+    });
+}
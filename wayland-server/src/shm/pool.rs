@@ -0,0 +1,233 @@
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+use std::rc::Rc;
+use std::slice;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, Ordering};
+use std::sync::Once;
+
+use nix::sys::mman;
+use nix::sys::signal::{self, SigAction, SigHandler, Signal};
+
+use super::BufferAccessError;
+use crate::OwnedFd;
+
+thread_local! {
+    // the map currently accessed by this thread, initialised by `place_sigbus_handler()` for the
+    // thread of the display so that the signal handler does not have to do it
+    static SIGBUS_GUARD: Cell<*const MemMap> = Cell::new(ptr::null());
+}
+
+static SIGBUS_INIT: Once = Once::new();
+// the errno of the failure to place the handler, 0 if it was placed
+static SIGBUS_INIT_ERROR: AtomicI32 = AtomicI32::new(0);
+// the handler replaced by ours, set once by `place_sigbus_handler()` and never freed
+static OLD_SIGBUS_HANDLER: AtomicPtr<SigAction> = AtomicPtr::new(ptr::null_mut());
+
+/// A memory pool shared by a client
+///
+/// The contents of the pool are only accessed through `with_data()`, which recovers from the
+/// SIGBUS raised if the client truncates the file backing the pool.
+pub(crate) struct Pool {
+    fd: OwnedFd,
+    // shared with the accesses in progress, so that the pool can be resized while its contents
+    // are accessed, the previous mapping being released once they complete
+    map: RefCell<Rc<MemMap>>,
+}
+
+impl Pool {
+    pub(crate) fn new(fd: OwnedFd, size: usize) -> nix::Result<Pool> {
+        let map = MemMap::new(fd.as_raw_fd(), size)?;
+        Ok(Pool { fd, map: RefCell::new(Rc::new(map)) })
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.map.borrow().size
+    }
+
+    /// Grow the pool to `new_size`
+    ///
+    /// Does nothing if the pool is already larger. On error, the previous mapping is kept.
+    pub(crate) fn resize(&self, new_size: usize) -> nix::Result<()> {
+        if new_size > self.size() {
+            let map = MemMap::new(self.fd.as_raw_fd(), new_size)?;
+            *self.map.borrow_mut() = Rc::new(map);
+        }
+        Ok(())
+    }
+
+    /// Access `len` bytes of the pool, starting at `offset`
+    ///
+    /// Returns `BufferAccessError::BadMap` if the client truncated the file backing the pool. If
+    /// this happens while `f` runs, the slice it was given is replaced by zeros so that it can
+    /// complete. If the pool is resized while `f` runs, `f` keeps accessing the previous mapping.
+    pub(crate) fn with_data<T, F: FnOnce(&[u8]) -> T>(
+        &self,
+        offset: usize,
+        len: usize,
+        f: F,
+    ) -> Result<T, BufferAccessError> {
+        let map = self.map.borrow().clone();
+        match offset.checked_add(len) {
+            Some(end) if end <= map.size => {}
+            _ => return Err(BufferAccessError::OutOfBounds),
+        }
+        if map.truncated.load(Ordering::SeqCst) {
+            return Err(BufferAccessError::BadMap);
+        }
+
+        let t = {
+            let _guard = SigbusGuard::enter(&map);
+            f(unsafe { slice::from_raw_parts(map.ptr.add(offset), len) })
+        };
+
+        if map.truncated.load(Ordering::SeqCst) {
+            Err(BufferAccessError::BadMap)
+        } else {
+            Ok(t)
+        }
+    }
+}
+
+/// Marks a map as accessed by this thread, until it is dropped
+///
+/// The previous value is restored on drop, including when unwinding from a panic of the
+/// accessor, so that the signal handler never sees a map that is no longer accessed.
+struct SigbusGuard {
+    previous: *const MemMap,
+}
+
+impl SigbusGuard {
+    fn enter(map: &MemMap) -> SigbusGuard {
+        let previous = SIGBUS_GUARD.with(|guard| guard.replace(map as *const MemMap));
+        SigbusGuard { previous }
+    }
+}
+
+impl Drop for SigbusGuard {
+    fn drop(&mut self) {
+        SIGBUS_GUARD.with(|guard| guard.set(self.previous));
+    }
+}
+
+struct MemMap {
+    ptr: *mut u8,
+    size: usize,
+    // set when the mapping was replaced by zeros after the file was truncated, this is an atomic
+    // so that the compiler does not assume it is unchanged by the signal handler
+    truncated: AtomicBool,
+}
+
+impl MemMap {
+    fn new(fd: RawFd, size: usize) -> nix::Result<MemMap> {
+        let ptr = unsafe { map(fd, size) }?;
+        Ok(MemMap { ptr, size, truncated: AtomicBool::new(false) })
+    }
+
+    fn contains(&self, address: usize) -> bool {
+        let start = self.ptr as usize;
+        address >= start && address < start + self.size
+    }
+
+    /// Replace the mapping by anonymous memory, filled with zeros
+    unsafe fn nullify(&self) -> nix::Result<()> {
+        mman::mmap(
+            self.ptr as *mut c_void,
+            self.size,
+            mman::ProtFlags::PROT_READ,
+            mman::MapFlags::MAP_PRIVATE | mman::MapFlags::MAP_ANONYMOUS | mman::MapFlags::MAP_FIXED,
+            -1,
+            0,
+        )
+        .map(|_| ())
+    }
+}
+
+impl Drop for MemMap {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = mman::munmap(self.ptr as *mut c_void, self.size);
+        }
+    }
+}
+
+unsafe fn map(fd: RawFd, size: usize) -> nix::Result<*mut u8> {
+    let ptr = mman::mmap(
+        ptr::null_mut(),
+        size,
+        mman::ProtFlags::PROT_READ,
+        mman::MapFlags::MAP_SHARED,
+        fd,
+        0,
+    )?;
+    Ok(ptr as *mut u8)
+}
+
+/// Place the SIGBUS handler recovering from the truncation of the pools
+///
+/// The handler is placed once for the whole process, this also initialises the guard of the
+/// current thread, which must be the one accessing the pools.
+pub(crate) fn place_sigbus_handler() -> io::Result<()> {
+    SIGBUS_INIT.call_once(|| unsafe {
+        let action = SigAction::new(
+            SigHandler::SigAction(sigbus_handler),
+            signal::SaFlags::SA_NODEFER,
+            signal::SigSet::empty(),
+        );
+        match signal::sigaction(Signal::SIGBUS, &action) {
+            Ok(old_signal) => {
+                OLD_SIGBUS_HANDLER.store(Box::into_raw(Box::new(old_signal)), Ordering::SeqCst);
+            }
+            Err(e) => {
+                let errno = e.as_errno().map(|errno| errno as i32).unwrap_or(libc::EINVAL);
+                SIGBUS_INIT_ERROR.store(errno, Ordering::SeqCst);
+            }
+        }
+    });
+    match SIGBUS_INIT_ERROR.load(Ordering::SeqCst) {
+        0 => {
+            SIGBUS_GUARD.with(|_| {});
+            Ok(())
+        }
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+/// Forward a SIGBUS that is not caused by a pool to the previous handler
+unsafe fn reraise_sigbus(signum: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let old = OLD_SIGBUS_HANDLER.load(Ordering::SeqCst);
+    let handler = if old.is_null() { SigHandler::SigDfl } else { (*old).handler() };
+    match handler {
+        SigHandler::Handler(handler) => handler(signum),
+        SigHandler::SigAction(handler) => handler(signum, info, context),
+        SigHandler::SigDfl | SigHandler::SigIgn => {
+            // a SIGBUS caused by a fault cannot be ignored: restore the default action, which
+            // terminates the process when the faulting instruction triggers it again once this
+            // handler returns
+            let action = SigAction::new(
+                SigHandler::SigDfl,
+                signal::SaFlags::empty(),
+                signal::SigSet::empty(),
+            );
+            let _ = signal::sigaction(Signal::SIGBUS, &action);
+        }
+    }
+}
+
+extern "C" fn sigbus_handler(signum: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let faulty_address = unsafe { (*info).si_addr() } as usize;
+    SIGBUS_GUARD.with(|guard| match unsafe { guard.get().as_ref() } {
+        Some(map) if map.contains(faulty_address) => {
+            // the client truncated the file of the pool, replace it by zeros so that the
+            // access can complete, and notify the accessor
+            if unsafe { map.nullify() }.is_ok() {
+                map.truncated.store(true, Ordering::SeqCst);
+            } else {
+                unsafe { reraise_sigbus(signum, info, context) };
+            }
+        }
+        _ => unsafe { reraise_sigbus(signum, info, context) },
+    });
+}
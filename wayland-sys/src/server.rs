@@ -16,6 +16,7 @@ pub enum wl_display {}
 pub enum wl_event_loop {}
 pub enum wl_event_source {}
 pub enum wl_global {}
+pub enum wl_protocol_logger {}
 pub enum wl_resource {}
 pub enum wl_shm_buffer {}
 
@@ -28,6 +29,33 @@ pub type wl_notify_func_t = unsafe extern "C" fn(*mut wl_listener, *mut c_void)
 pub type wl_resource_destroy_func_t = unsafe extern "C" fn(*mut wl_resource) -> ();
 pub type wl_display_global_filter_func_t =
     unsafe extern "C" fn(*const wl_client, *const wl_global, *mut c_void) -> bool;
+pub type wl_protocol_logger_func_t =
+    unsafe extern "C" fn(*mut c_void, wl_protocol_logger_type, *const wl_protocol_logger_message) -> ();
+pub type wl_client_for_each_resource_iterator_func_t =
+    unsafe extern "C" fn(*mut wl_resource, *mut c_void) -> wl_iterator_result;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum wl_iterator_result {
+    WL_ITERATOR_STOP,
+    WL_ITERATOR_CONTINUE,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum wl_protocol_logger_type {
+    WL_PROTOCOL_LOGGER_REQUEST,
+    WL_PROTOCOL_LOGGER_EVENT,
+}
+
+#[repr(C)]
+pub struct wl_protocol_logger_message {
+    pub resource: *mut wl_resource,
+    pub message_opcode: c_int,
+    pub message: *const wl_message,
+    pub arguments_count: c_int,
+    pub arguments: *const wl_argument,
+}
 
 #[repr(C)]
 pub struct wl_listener {
@@ -49,15 +77,19 @@ external_library!(WaylandServer, "wayland-server",
         fn wl_client_get_display(*mut wl_client) -> *mut wl_display,
         fn wl_client_get_credentials(*mut wl_client, *mut pid_t, *mut uid_t, *mut gid_t) -> (),
         fn wl_client_get_object(*mut wl_client, u32) -> *mut wl_resource,
+        fn wl_client_get_link(*mut wl_client) -> *mut wl_list,
+        fn wl_client_from_link(*mut wl_list) -> *mut wl_client,
         fn wl_client_add_destroy_listener(*mut wl_client, *mut wl_listener) -> (),
         fn wl_client_get_destroy_listener(*mut wl_client, wl_notify_func_t) -> *mut wl_listener,
         fn wl_client_post_no_memory(*mut wl_client) -> (),
+        fn wl_client_for_each_resource(*mut wl_client, wl_client_for_each_resource_iterator_func_t, *mut c_void) -> (),
         fn wl_resource_create(*mut wl_client, *const wl_interface, c_int, u32) -> *mut wl_resource,
     // wl_display
         fn wl_client_create(*mut wl_display, c_int) -> *mut wl_client,
         fn wl_display_create() -> *mut wl_display,
         fn wl_display_destroy(*mut wl_display) -> (),
         fn wl_display_destroy_clients(*mut wl_display) -> (),
+        fn wl_display_get_client_list(*mut wl_display) -> *mut wl_list,
         fn wl_display_get_serial(*mut wl_display) -> u32,
         fn wl_display_next_serial(*mut wl_display) -> u32,
         fn wl_display_add_socket(*mut wl_display, *const c_char) -> c_int,
@@ -74,6 +106,7 @@ external_library!(WaylandServer, "wayland-server",
         fn wl_display_init_shm(*mut wl_display) -> c_int,
         fn wl_display_add_client_created_listener(*mut wl_display, *mut wl_listener) -> (),
         fn wl_display_set_global_filter(*mut wl_display, wl_display_global_filter_func_t, *mut c_void) -> (),
+        fn wl_display_add_protocol_logger(*mut wl_display, wl_protocol_logger_func_t, *mut c_void) -> *mut wl_protocol_logger,
     // wl_event_loop
         fn wl_event_loop_create() -> *mut wl_event_loop,
         fn wl_event_loop_destroy(*mut wl_event_loop) -> (),
@@ -94,6 +127,8 @@ external_library!(WaylandServer, "wayland-server",
     // wl_global
         fn wl_global_destroy(*mut wl_global) -> (),
         fn wl_global_get_user_data(*const wl_global) -> *mut c_void,
+    // wl_protocol_logger
+        fn wl_protocol_logger_destroy(*mut wl_protocol_logger) -> (),
     // wl_resource
         fn wl_resource_post_event_array(*mut wl_resource, u32, *mut wl_argument) -> (),
         fn wl_resource_queue_event_array(*mut wl_resource, u32, *mut wl_argument) -> (),
@@ -109,6 +144,7 @@ external_library!(WaylandServer, "wayland-server",
         fn wl_resource_set_user_data(*mut wl_resource, *mut c_void) -> (),
         fn wl_resource_get_user_data(*mut wl_resource) -> *mut c_void,
         fn wl_resource_get_version(*mut wl_resource) -> c_int,
+        fn wl_resource_get_class(*mut wl_resource) -> *const c_char,
         fn wl_resource_set_destructor(*mut wl_resource, Option<wl_resource_destroy_func_t>) -> (),
         fn wl_resource_instance_of(*mut wl_resource, *const wl_interface, *const c_void) -> c_int,
         fn wl_resource_add_destroy_listener(*mut wl_resource, wl_notify_func_t) -> (),
@@ -0,0 +1,240 @@
+//! Helpers to test wayland clients and servers
+//!
+//! This crate connects a `wayland-client` connection to a `wayland-server` display running in
+//! the same process, and provides helpers to exchange messages between them, so that the protocol
+//! handlers of both sides can be tested together.
+//!
+//! ## Deterministic roundtrips
+//!
+//! The connection between the `TestClient` and the `TestServer` is a socketpair, so a message
+//! sent by one side is immediately readable by the other. The `roundtrip()` function uses this
+//! to alternate non-blocking dispatches of both sides until the server has answered a
+//! `wl_display.sync` request, without sleeping. It gives up with a `TimedOut` error if the
+//! answer never comes, rather than hanging.
+//!
+//! ```no_run
+//! use wayland_testing::{roundtrip, TestServer};
+//!
+//! let mut server = TestServer::new();
+//! // create the globals of the server...
+//! let (_client, mut client) = server.add_client();
+//! let manager = wayland_testing::client::GlobalManager::new(&client.display_proxy);
+//! roundtrip(&mut client, &mut server).unwrap();
+//! // the globals of the server are now listed by the manager
+//! ```
+//!
+//! ## Assertions
+//!
+//! `expect_protocol_error()` checks that the server kills the client with a given protocol error,
+//! and `EventRecorder` records the events received by a proxy, to check them after a roundtrip.
+//!
+//! ## Mock server
+//!
+//! `MockServer` is a scriptable server to test clients: it advertises chosen globals, and
+//! answers the requests of its clients according to expectations matching their interface,
+//! opcode and arguments. It also records all the requests it receives, to check them later.
+//!
+//! ## Backends
+//!
+//! The `client_native`, `server_native` and `both_native` cargo features enable the `dlopen`
+//! features of `wayland-client` and `wayland-server`, to use the system libraries instead of
+//! the rust implementation. Running your tests with each of these features thus runs them against
+//! each combination of backends.
+
+#![warn(missing_docs)]
+
+pub extern crate wayland_client as client;
+pub extern crate wayland_server as server;
+
+pub use wayland_commons::wire::Argument;
+
+mod mock;
+
+pub use mock::{ExpectationBuilder, ExpectationHandle, MockGlobal, MockServer, ReceivedRequest};
+
+use std::cell::{Cell, RefCell};
+use std::fmt::Debug;
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+
+use client::protocol::wl_display::WlDisplay;
+use client::{Attached, EventQueue, Interface, Main, MessageGroup, ProtocolError, Proxy, ProxyMap};
+
+/// Number of exchanges between the client and the server before a roundtrip gives up
+const MAX_ITERATIONS: u32 = 100;
+
+/// A server for testing
+///
+/// It does not listen on any socket, clients are added with `add_client()`.
+pub struct TestServer {
+    /// The display of the server
+    pub display: server::Display,
+}
+
+impl TestServer {
+    /// Create a new test server
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> TestServer {
+        TestServer { display: server::Display::new() }
+    }
+
+    /// Add a new client to this server
+    ///
+    /// This returns the `Client` as seen by the server, and the `TestClient` connected to it.
+    pub fn add_client(&mut self) -> (server::Client, TestClient) {
+        let (client, display) =
+            self.display.connect_client(&mut ()).expect("Failed to connect a test client.");
+        (client, TestClient::new(display))
+    }
+
+    /// Dispatch the pending requests of the clients without blocking, and flush the answers
+    pub fn answer<SD: 'static>(&mut self, ddata: &mut SD) -> io::Result<()> {
+        self.display.dispatch(Duration::from_millis(0), &mut *ddata)?;
+        self.display.flush_clients(&mut *ddata);
+        Ok(())
+    }
+}
+
+/// A client for testing
+pub struct TestClient {
+    /// The display of the client
+    pub display: client::Display,
+    /// The `wl_display` of the client, attached to `event_queue`
+    pub display_proxy: Attached<WlDisplay>,
+    /// The event queue of the client
+    pub event_queue: EventQueue,
+}
+
+impl TestClient {
+    /// Create a test client from a connected display
+    ///
+    /// The `wl_display` is attached to a new event queue, which is dispatched by `roundtrip()`.
+    pub fn new(display: client::Display) -> TestClient {
+        let event_queue = display.create_event_queue();
+        let display_proxy = (*display).clone().attach(event_queue.token());
+        TestClient { display, display_proxy, event_queue }
+    }
+
+    /// Read the available events and dispatch the pending ones without blocking
+    ///
+    /// The events of unassigned objects are ignored.
+    pub fn dispatch_pending<CD: 'static>(&mut self, ddata: &mut CD) -> io::Result<()> {
+        self.event_queue.dispatch_pending(&mut *ddata, |_, _, _| {})?;
+        let read = match self.event_queue.prepare_read() {
+            Some(guard) => match guard.read_events() {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+                ret => ret,
+            },
+            None => Ok(()),
+        };
+        // even if read_events returns an error, some messages may need dispatching
+        self.event_queue.dispatch_pending(&mut *ddata, |_, _, _| {})?;
+        read
+    }
+}
+
+/// Make a roundtrip between a client and the server
+///
+/// Once this returns, the server has processed all the requests previously sent by the client,
+/// and the client has dispatched the events sent in response.
+pub fn roundtrip(client: &mut TestClient, server: &mut TestServer) -> io::Result<()> {
+    roundtrip_with_ddata(client, server, &mut (), &mut ())
+}
+
+/// Make a roundtrip between a client and the server with some dispatch data
+///
+/// This behaves like `roundtrip()`, dispatching the client with `client_ddata` and the server
+/// with `server_ddata`.
+pub fn roundtrip_with_ddata<CD: 'static, SD: 'static>(
+    client: &mut TestClient,
+    server: &mut TestServer,
+    client_ddata: &mut CD,
+    server_ddata: &mut SD,
+) -> io::Result<()> {
+    let done = Rc::new(Cell::new(false));
+    let done2 = done.clone();
+    client.display_proxy.sync().quick_assign(move |_, _, _| done2.set(true));
+    for _ in 0..MAX_ITERATIONS {
+        match client.display.flush() {
+            Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+            ret => ret?,
+        }
+        server.answer(&mut *server_ddata)?;
+        client.dispatch_pending(&mut *client_ddata)?;
+        if done.get() {
+            return Ok(());
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::TimedOut, "The server did not answer the roundtrip."))
+}
+
+/// Check that the server kills a client with a protocol error
+///
+/// This makes a roundtrip and panics if it succeeds, or if the protocol error that killed the
+/// client is not on an object of interface `interface` with code `code`. The error is returned
+/// to allow checking its other fields.
+pub fn expect_protocol_error(
+    client: &mut TestClient,
+    server: &mut TestServer,
+    interface: &str,
+    code: u32,
+) -> ProtocolError {
+    if roundtrip(client, server).is_ok() {
+        panic!("Expected a protocol error on {} with code {}, but none occurred.", interface, code);
+    }
+    let error =
+        client.display.protocol_error().expect("The connection failed without a protocol error.");
+    assert!(
+        error.object_interface == interface && error.code == code,
+        "Expected a protocol error on {} with code {}, got {}.",
+        interface,
+        code,
+        error
+    );
+    error
+}
+
+/// A recorder of the events received by a proxy
+///
+/// It assigns the proxy to a filter storing its events, which are then checked with `take()`
+/// or `assert_received()` once they have been dispatched.
+pub struct EventRecorder<I: Interface> {
+    events: Rc<RefCell<Vec<I::Event>>>,
+}
+
+impl<I: Interface> EventRecorder<I>
+where
+    I::Event: Debug,
+{
+    /// Record the events of this proxy
+    ///
+    /// This replaces the filter previously assigned to the proxy.
+    pub fn new(proxy: &Main<I>) -> EventRecorder<I>
+    where
+        I: AsRef<Proxy<I>> + From<Proxy<I>> + Sync,
+        I::Event: MessageGroup<Map = ProxyMap>,
+    {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let filter_events = events.clone();
+        proxy.quick_assign(move |_, event, _| filter_events.borrow_mut().push(event));
+        EventRecorder { events }
+    }
+
+    /// Take all the events recorded so far
+    pub fn take(&self) -> Vec<I::Event> {
+        std::mem::take(&mut *self.events.borrow_mut())
+    }
+
+    /// Check that an event matching `predicate` was recorded
+    ///
+    /// The first matching event is removed from the recorder and returned. If there is none, this
+    /// panics, listing the recorded events.
+    pub fn assert_received<F: FnMut(&I::Event) -> bool>(&self, predicate: F) -> I::Event {
+        let mut events = self.events.borrow_mut();
+        match events.iter().position(predicate) {
+            Some(i) => events.remove(i),
+            None => panic!("No matching event received by {}, got {:?}.", I::NAME, *events),
+        }
+    }
+}
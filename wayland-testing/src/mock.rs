@@ -0,0 +1,381 @@
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use wayland_commons::wire::Argument;
+
+use crate::server::{
+    Filter, Interface, LoggedMessage, Main, MessageDirection, MessageGroup, Resource, ResourceMap,
+};
+use crate::{TestClient, TestServer};
+
+type Responder<I> = Rc<dyn Fn(&Main<I>, &<I as Interface>::Request)>;
+type Matcher<I> = Rc<dyn Fn(&<I as Interface>::Request) -> bool>;
+
+/// A request received by a `MockServer`
+#[derive(Debug, PartialEq)]
+pub struct ReceivedRequest {
+    /// Interface of the object the request was sent to
+    pub interface: String,
+    /// Id of the object the request was sent to
+    pub object_id: u32,
+    /// Name of the request
+    pub name: String,
+    /// Arguments of the request
+    ///
+    /// The file descriptors are duplicated, and stay open as long as the request is kept.
+    pub args: Vec<Argument>,
+}
+
+impl<'a> From<&LoggedMessage<'a>> for ReceivedRequest {
+    fn from(message: &LoggedMessage<'a>) -> ReceivedRequest {
+        ReceivedRequest {
+            interface: message.interface.into(),
+            object_id: message.id,
+            name: message.name.into(),
+            args: message
+                .args
+                .iter()
+                .map(|&arg| arg.to_owned().expect("Failed to duplicate a received file descriptor."))
+                .collect(),
+        }
+    }
+}
+
+/// A global of a `MockServer`
+///
+/// It is used to remove the global with `MockServer::remove_global()` or with an expectation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MockGlobal(u32);
+
+enum Action<I: Interface + AsRef<Resource<I>> + From<Resource<I>>> {
+    Reply(Responder<I>),
+    PostError(u32, String),
+    RemoveGlobal(MockGlobal),
+}
+
+impl<I: Interface + AsRef<Resource<I>> + From<Resource<I>>> Clone for Action<I> {
+    fn clone(&self) -> Action<I> {
+        match *self {
+            Action::Reply(ref responder) => Action::Reply(responder.clone()),
+            Action::PostError(code, ref msg) => Action::PostError(code, msg.clone()),
+            Action::RemoveGlobal(global) => Action::RemoveGlobal(global),
+        }
+    }
+}
+
+struct Expectation<I: Interface + AsRef<Resource<I>> + From<Resource<I>>> {
+    opcode: Option<u16>,
+    matcher: Option<Matcher<I>>,
+    times: Option<u32>,
+    action: Action<I>,
+    count: Rc<Cell<u32>>,
+}
+
+impl<I: Interface + AsRef<Resource<I>> + From<Resource<I>>> Expectation<I> {
+    fn accepts(&self, request: &I::Request) -> bool {
+        self.times.map(|times| self.count.get() < times).unwrap_or(true)
+            && self.opcode.map(|opcode| request.opcode() == opcode).unwrap_or(true)
+            && self.matcher.as_ref().map(|matcher| matcher(request)).unwrap_or(true)
+    }
+}
+
+type Globals = Rc<RefCell<HashMap<MockGlobal, Box<dyn FnOnce()>>>>;
+
+/// A scriptable server to test clients
+///
+/// It advertises the globals added with `add_global()`, and answers the requests of its clients
+/// according to the expectations registered with `on()`. The requests of an object that no
+/// expectation accepts are ignored. All the requests received by the server are recorded, and
+/// can be checked with `take_requests()` or `assert_received()`.
+///
+/// The objects of the interfaces given to `add_global()`, `on()` or `handle()` are handled by the
+/// mock. Receiving a request for an object of another interface is a protocol error, as the
+/// server has no way to handle it.
+///
+/// This works with any interface generated by `wayland-scanner`:
+///
+/// ```no_run
+/// use wayland_testing::server::protocol::{wl_compositor, wl_seat, wl_surface};
+/// use wayland_testing::{roundtrip, MockServer};
+///
+/// let mut mock = MockServer::new();
+/// mock.add_global::<wl_compositor::WlCompositor>(4);
+/// let seat = mock.add_global_with_bind::<wl_seat::WlSeat, _>(5, |seat| {
+///     seat.capabilities(wl_seat::Capability::Pointer)
+/// });
+/// let commits = mock.on::<wl_surface::WlSurface>().opcode(6).ignore();
+/// mock.on::<wl_surface::WlSurface>().opcode(3).reply(|_, request| {
+///     if let wl_surface::Request::Frame { callback } = request {
+///         callback.done(0);
+///     }
+/// });
+/// mock.on::<wl_surface::WlSurface>().opcode(1).post_error(0, "no attaching".into());
+///
+/// let (_client, mut client) = mock.add_client();
+/// // run the client code...
+/// roundtrip(&mut client, &mut mock.server).unwrap();
+/// mock.remove_global(seat);
+/// roundtrip(&mut client, &mut mock.server).unwrap();
+/// mock.assert_received(|req| req.interface == "wl_surface" && req.name == "commit");
+/// assert_eq!(commits.count(), 1);
+/// ```
+pub struct MockServer {
+    /// The underlying test server
+    pub server: TestServer,
+    expectations: Rc<RefCell<Vec<Rc<dyn Any>>>>,
+    globals: Globals,
+    next_global: u32,
+    handled: HashSet<&'static str>,
+    requests: Arc<Mutex<Vec<ReceivedRequest>>>,
+}
+
+impl MockServer {
+    /// Create a new mock server, without any global
+    ///
+    /// It records the requests it receives with the protocol logger of its display, which must
+    /// thus not be replaced.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> MockServer {
+        let server = TestServer::new();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        server.display.set_protocol_logger(Some(Arc::new(move |message: &LoggedMessage<'_>| {
+            if message.direction == MessageDirection::Received {
+                log.lock().unwrap().push(message.into());
+            }
+        })));
+        MockServer {
+            server,
+            expectations: Rc::new(RefCell::new(Vec::new())),
+            globals: Rc::new(RefCell::new(HashMap::new())),
+            next_global: 0,
+            handled: HashSet::new(),
+            requests,
+        }
+    }
+
+    /// Add a new client to this server
+    ///
+    /// See `TestServer::add_client()`.
+    pub fn add_client(&mut self) -> (crate::server::Client, TestClient) {
+        self.server.add_client()
+    }
+
+    /// Handle the objects of interface `I` with this mock
+    ///
+    /// Their requests are recorded and given to the expectations registered for `I`. This is
+    /// done automatically by `add_global()` and `on()`, calling it is only needed for objects
+    /// whose requests are never expected.
+    pub fn handle<I>(&mut self)
+    where
+        I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+        I::Request: MessageGroup<Map = ResourceMap>,
+    {
+        if !self.handled.insert(I::NAME) {
+            return;
+        }
+        let expectations = self.expectations.clone();
+        let globals = self.globals.clone();
+        self.server.display.set_default_filter::<I, _>(Filter::new(
+            move |(resource, request): (Main<I>, I::Request), _, _| {
+                let found = expectations
+                    .borrow()
+                    .iter()
+                    .filter_map(|e| e.downcast_ref::<Expectation<I>>())
+                    .find(|e| e.accepts(&request))
+                    .map(|e| (e.action.clone(), e.count.clone()));
+                // the expectations are no longer borrowed, the responders may thus create
+                // new objects and send events freely
+                if let Some((action, count)) = found {
+                    count.set(count.get() + 1);
+                    match action {
+                        Action::Reply(responder) => responder(&resource, &request),
+                        Action::PostError(code, msg) => resource.as_ref().post_error(code, msg),
+                        Action::RemoveGlobal(global) => {
+                            let destroy = globals.borrow_mut().remove(&global);
+                            if let Some(destroy) = destroy {
+                                destroy();
+                            }
+                        }
+                    }
+                }
+            },
+        ));
+    }
+
+    /// Advertise a global of interface `I` with version `version`
+    pub fn add_global<I>(&mut self, version: u32) -> MockGlobal
+    where
+        I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+        I::Request: MessageGroup<Map = ResourceMap>,
+    {
+        self.add_global_with_bind::<I, _>(version, |_| {})
+    }
+
+    /// Advertise a global of interface `I` with version `version`, calling `bind` when a client
+    /// instantiates it
+    ///
+    /// This allows sending the events expected right after the binding, for example the formats
+    /// of a `wl_shm` or the capabilities of a `wl_seat`.
+    pub fn add_global_with_bind<I, F>(&mut self, version: u32, mut bind: F) -> MockGlobal
+    where
+        I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+        I::Request: MessageGroup<Map = ResourceMap>,
+        F: FnMut(&Main<I>) + 'static,
+    {
+        self.handle::<I>();
+        // the resource is left unassigned, its requests are given to the default filter
+        let global = self.server.display.create_global::<I, _>(
+            version,
+            Filter::new(move |(resource, _): (Main<I>, u32), _, _| bind(&resource)),
+        );
+        let id = MockGlobal(self.next_global);
+        self.next_global += 1;
+        self.globals.borrow_mut().insert(id, Box::new(move || global.destroy()));
+        id
+    }
+
+    /// Remove a global
+    ///
+    /// Does nothing if the global was already removed.
+    pub fn remove_global(&mut self, global: MockGlobal) {
+        let destroy = self.globals.borrow_mut().remove(&global);
+        if let Some(destroy) = destroy {
+            destroy();
+        }
+    }
+
+    /// Register an expectation on the requests of objects of interface `I`
+    ///
+    /// When a request is received, the first registered expectation accepting it is triggered.
+    pub fn on<I>(&mut self) -> ExpectationBuilder<'_, I>
+    where
+        I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+        I::Request: MessageGroup<Map = ResourceMap>,
+    {
+        self.handle::<I>();
+        ExpectationBuilder { mock: self, opcode: None, matcher: None, times: None }
+    }
+
+    /// Take all the requests received so far
+    pub fn take_requests(&self) -> Vec<ReceivedRequest> {
+        std::mem::take(&mut *self.requests.lock().unwrap())
+    }
+
+    /// Check that a request matching `predicate` was received
+    ///
+    /// The first matching request is removed from the record and returned. If there is none,
+    /// this panics, listing the received requests.
+    pub fn assert_received<F: FnMut(&ReceivedRequest) -> bool>(
+        &self,
+        predicate: F,
+    ) -> ReceivedRequest {
+        let mut requests = self.requests.lock().unwrap();
+        match requests.iter().position(predicate) {
+            Some(i) => requests.remove(i),
+            None => panic!("No matching request received, got {:?}.", *requests),
+        }
+    }
+}
+
+impl fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockServer")
+            .field("globals", &self.globals.borrow().len())
+            .field("handled", &self.handled)
+            .finish()
+    }
+}
+
+/// A builder for an expectation of a `MockServer`
+///
+/// The filters `opcode()`, `matching()` and `times()` restrict the requests accepted by the
+/// expectation, and it is registered by giving the action to do when triggered.
+pub struct ExpectationBuilder<'a, I: Interface + AsRef<Resource<I>> + From<Resource<I>>> {
+    mock: &'a mut MockServer,
+    opcode: Option<u16>,
+    matcher: Option<Matcher<I>>,
+    times: Option<u32>,
+}
+
+impl<'a, I> ExpectationBuilder<'a, I>
+where
+    I: Interface + AsRef<Resource<I>> + From<Resource<I>>,
+{
+    /// Only accept the requests with this opcode
+    pub fn opcode(mut self, opcode: u16) -> Self {
+        self.opcode = Some(opcode);
+        self
+    }
+
+    /// Only accept the requests for which `matcher` returns `true`
+    ///
+    /// This allows matching the arguments of the requests.
+    pub fn matching<F: Fn(&I::Request) -> bool + 'static>(mut self, matcher: F) -> Self {
+        self.matcher = Some(Rc::new(matcher));
+        self
+    }
+
+    /// Only trigger this expectation `times` times
+    ///
+    /// Once exhausted, the expectation lets the following ones handle the requests.
+    pub fn times(mut self, times: u32) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    /// Answer the accepted requests with `responder`
+    ///
+    /// It is given the object and the request, and can send events and assign the objects
+    /// created by the request.
+    pub fn reply<F: Fn(&Main<I>, &I::Request) + 'static>(self, responder: F) -> ExpectationHandle {
+        self.register(Action::Reply(Rc::new(responder)))
+    }
+
+    /// Answer the accepted requests with a protocol error on their object
+    pub fn post_error(self, code: u32, msg: String) -> ExpectationHandle {
+        self.register(Action::PostError(code, msg))
+    }
+
+    /// Remove a global when a request is accepted
+    pub fn remove_global(self, global: MockGlobal) -> ExpectationHandle {
+        self.register(Action::RemoveGlobal(global))
+    }
+
+    /// Accept the requests without doing anything
+    ///
+    /// This allows counting them, or preventing the following expectations from handling them.
+    pub fn ignore(self) -> ExpectationHandle {
+        self.register(Action::Reply(Rc::new(|_, _| {})))
+    }
+
+    fn register(self, action: Action<I>) -> ExpectationHandle {
+        let count = Rc::new(Cell::new(0));
+        let expectation = Expectation {
+            opcode: self.opcode,
+            matcher: self.matcher,
+            times: self.times,
+            action,
+            count: count.clone(),
+        };
+        self.mock.expectations.borrow_mut().push(Rc::new(expectation));
+        ExpectationHandle { count }
+    }
+}
+
+/// A handle to a registered expectation
+#[derive(Clone, Debug)]
+pub struct ExpectationHandle {
+    count: Rc<Cell<u32>>,
+}
+
+impl ExpectationHandle {
+    /// Number of requests that triggered this expectation so far
+    pub fn count(&self) -> u32 {
+        self.count.get()
+    }
+}